@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use guff::GaloisField;
+use guff::good::{new_gf16_0x1002b, new_gf8_0x11b};
+use guff_ssss::{combine_decoder, split, Decoder, Share};
+
+// The combiner's inner loops are dominated by field multiplies (pass
+// 2) and divides (pass 1), so keep an eye on the cost of both.
+
+fn gf8_mul(c: &mut Criterion) {
+    let f = new_gf8_0x11b();
+    c.bench_function("gf8 mul", |b| b.iter(|| {
+	f.mul(black_box(0x53), black_box(0xca))
+    }));
+}
+
+fn gf8_div(c: &mut Criterion) {
+    let f = new_gf8_0x11b();
+    c.bench_function("gf8 div", |b| b.iter(|| {
+	f.div(black_box(0x53), black_box(0xca))
+    }));
+}
+
+// A whole combine of a large secret, which is mostly pass 2 (and goes
+// through the vectorised kernels with the `simd` feature)
+fn gf8_combine_1m(c: &mut Criterion) {
+    let secret = vec![0x5au8; 1 << 20];
+    let shares = split(&secret, 3, 5, 8).unwrap();
+    c.bench_function("gf8 combine 1MiB k=3", |b| b.iter(|| {
+	let mut decoder = Decoder::new();
+	for share in &shares[..3] { decoder.add(share.clone()).unwrap(); }
+	combine_decoder(black_box(&mut decoder), false).unwrap()
+    }));
+}
+
+// Shares are hex text, so a large one spends a while in the hex codec
+// (vectorised with the `simd` feature)
+fn share_text_1m(c: &mut Criterion) {
+    let share = split(&vec![0x5au8; 1 << 20], 2, 2, 8).unwrap().remove(0).to_string();
+    c.bench_function("share text 1MiB", |b| b.iter(|| {
+	black_box(&share).parse::<Share>().unwrap().to_string()
+    }));
+}
+
+// Setting up a field is paid on every split and combine; GF(2^16)
+// builds 64K-entry tables
+fn gf16_tables(c: &mut Criterion) {
+    c.bench_function("gf16 tables", |b| b.iter(new_gf16_0x1002b));
+}
+
+criterion_group!(benches, gf8_mul, gf8_div, gf8_combine_1m, share_text_1m,
+		 gf16_tables);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+// Fuzz the solver: `cargo fuzz run combine`
+//
+// Shares with any quorum, width, number and data at all, and a
+// decoder whose public fields have been tampered with, must give an
+// error or a secret from add, combine and derive_share, never a
+// panic.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use guff_ssss_core::width::{Width, U8};
+use guff_ssss_core::{combine_decoder, derive_share, pass_1, pass_2, Decoder, Share};
+
+type Fields = (u16, u16, u64, Vec<u8>);
+
+fuzz_target!(|input : (Vec<Fields>, u64, bool, Option<(u16, usize, Vec<u8>, Vec<u8>)>)| {
+    let (fields, x, hardened, tamper) = input;
+    let shares : Vec<Share> = fields.into_iter().map(|(quorum, width, index, values)| Share {
+	set_id : None, quorum, width, index, values, padded : false,
+    }).collect();
+    let mut decoder = Decoder::new();
+    for share in shares.iter() { let _ = decoder.add(share.clone()); }
+    let _ = decoder.all_shares();
+    if let Some((quorum, hex_length, x_values, coefficients)) = tamper {
+	decoder.quorum = quorum;
+	decoder.hex_length = hex_length;
+	decoder.x_values = x_values;
+	decoder.coefficients = coefficients;
+	let field = U8::field();
+	let _ = pass_1::<U8>(&field, &mut decoder.clone(), hardened);
+	let _ = pass_2::<U8>(&field, &mut decoder.clone(), &mut |_, _| {});
+	let _ = decoder.all_shares();
+    }
+    let _ = combine_decoder(&mut decoder, hardened);
+    let _ = derive_share(&shares, x);
+});
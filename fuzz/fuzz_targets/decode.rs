@@ -0,0 +1,19 @@
+// Fuzz the share decoders: `cargo fuzz run decode`
+//
+// Every codec, the stream readers and the binary format must give an
+// error or a share for any input, never a panic.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use guff_ssss_core::binary::decode_binary;
+use guff_ssss_core::codec::{decode_armor_stream, decode_kubernetes_stream, Registry};
+
+fuzz_target!(|data : &[u8]| {
+    let _ = decode_binary(data);
+    if let Ok(text) = std::str::from_utf8(data) {
+	let registry = Registry::new();
+	let _ = registry.decode(text);
+	let _ = decode_armor_stream(text);
+	let _ = decode_kubernetes_stream(text);
+    }
+});
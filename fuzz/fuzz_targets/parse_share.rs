@@ -0,0 +1,17 @@
+// Fuzz the share-line parser: `cargo fuzz run parse_share`
+//
+// parse_share must never panic, and anything it accepts must print
+// back out as a line that parses to the same share.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use guff_ssss_core::{parse_share, Decoder};
+
+fuzz_target!(|data : &[u8]| {
+    if let Ok(line) = std::str::from_utf8(data) {
+	if let Ok(share) = parse_share(line) {
+	    assert_eq!(parse_share(&share.to_string()), Ok(share.clone()));
+	    let _ = Decoder::new().add(share);
+	}
+    }
+});
@@ -0,0 +1,155 @@
+//! Buffers for bulk share data
+//!
+//! Share and secret data live in plain `Vec<u8>`s, which only promise
+//! byte alignment (and a large one, in practice, starts 16 bytes into
+//! a page). Rather than copy into and out of specially aligned
+//! storage, the large buffers are set up so that the alignment that
+//! matters comes out right:
+//!
+//! * [reserve] and [zeroed] allocate a large buffer in one go, and
+//!   ask Linux (`madvise`) to back every whole 2MiB stretch of it
+//!   with transparent huge pages, so a combine of a large secret
+//!   takes a TLB miss per 2MiB rather than per 4KiB
+//! * [aligned_chunks] splits work on a buffer at cache-line
+//!   boundaries in memory, so the [simd](crate::simd) kernels store
+//!   whole, aligned cache lines everywhere but the first chunk
+//!
+//! Small things are kept off the heap instead: [small_slice] holds
+//! the per-share values of a combine on the stack for quorums up to
+//! [SMALL_K].
+//!
+//! How big those chunks are is a [ChunkSize]. The best size depends
+//! on the machine's caches and on the quorum, so `Auto` times a few
+//! on the start of a large secret and keeps the fastest.
+
+use crate::{Error, Result, PROGRESS_INTERVAL};
+use arrayvec::ArrayVec;
+use std::ops::Range;
+
+/// Chunks from [aligned_chunks] start on this boundary
+pub const CACHE_LINE : usize = 64;
+
+/// Size and alignment of a huge page
+pub const HUGE_PAGE : usize = 2 << 20;
+
+/// Make room for `additional` more bytes in `buf`, all at once
+pub fn reserve(buf : &mut Vec<u8>, additional : usize) {
+    buf.reserve_exact(additional);
+    advise_huge(buf);
+}
+
+/// A buffer of `len` zero bytes
+pub fn zeroed(len : usize) -> Vec<u8> {
+    // Zeroed allocations come straight from the OS when they're
+    // large, so nothing has been touched yet when the advice is given
+    let buf = vec![0u8; len];
+    advise_huge(&buf);
+    buf
+}
+
+// Huge pages are only advice: without transparent huge page support
+// it's refused, and the buffer works all the same
+fn advise_huge(buf : &Vec<u8>) {
+    #[cfg(target_os = "linux")]
+    {
+	let start = buf.as_ptr() as usize;
+	let first = (start + HUGE_PAGE - 1) & !(HUGE_PAGE - 1);
+	let end = (start + buf.capacity()) & !(HUGE_PAGE - 1);
+	if end > first {
+	    unsafe { libc::madvise(first as *mut libc::c_void, end - first, libc::MADV_HUGEPAGE) };
+	}
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = buf;
+}
+
+/// Ranges covering `buf`, of `size` bytes (a multiple of
+/// [CACHE_LINE]) but for the first and last, with every one after the
+/// first starting on a cache line
+pub fn aligned_chunks(buf : &[u8], size : usize) -> impl Iterator<Item = Range<usize>> {
+    debug_assert!(size > 0 && size.is_multiple_of(CACHE_LINE));
+    let len = buf.len();
+    let lead = buf.as_ptr().align_offset(CACHE_LINE).min(len);
+    let firsts = (lead > 0).then_some(0..lead);
+    firsts.into_iter().chain((lead..len).step_by(size).map(move |s| s..len.min(s + size)))
+}
+
+/// Quorums up to this size are combined without the heap
+pub const SMALL_K : usize = 16;
+
+/// Call `f` with `items` collected into a slice, which is on the
+/// stack if there are no more than [SMALL_K] of them
+pub fn small_slice<T, R>(items : impl ExactSizeIterator<Item = T>, f : impl FnOnce(&[T]) -> R) -> R {
+    if items.len() <= SMALL_K {
+	f(&items.collect::<ArrayVec<T, SMALL_K>>())
+    } else {
+	f(&items.collect::<Vec<T>>())
+    }
+}
+
+/// How much of the secret the vectorised pass 2 of
+/// [combine](crate::combine) works on at a time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkSize {
+    /// This many bytes
+    Fixed(usize),
+    /// Time a few sizes on the first megabytes of the secret, and use
+    /// the fastest for the rest
+    Auto,
+}
+
+impl Default for ChunkSize {
+    fn default() -> Self { ChunkSize::Fixed(PROGRESS_INTERVAL) }
+}
+
+impl std::str::FromStr for ChunkSize {
+    type Err = Error;
+
+    /// "auto", or a number of bytes with an optional K or M suffix
+    fn from_str(size : &str) -> Result<ChunkSize> {
+	if size == "auto" { return Ok(ChunkSize::Auto) }
+	let (digits, unit) = match size.char_indices().last() {
+	    Some((i, 'k')) | Some((i, 'K')) => (&size[..i], 1 << 10),
+	    Some((i, 'm')) | Some((i, 'M')) => (&size[..i], 1 << 20),
+	    _ => (size, 1),
+	};
+	match digits.parse::<usize>().ok().and_then(|n| n.checked_mul(unit)) {
+	    Some(bytes) if bytes > 0 && bytes.is_multiple_of(CACHE_LINE) => Ok(ChunkSize::Fixed(bytes)),
+	    _ => Err(Error::FieldError(format!(
+		"chunk size '{}' isn't 'auto' or a multiple of {} bytes", size, CACHE_LINE))),
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers() {
+	let buf = zeroed(3 * HUGE_PAGE);
+	assert!(buf.len() == 3 * HUGE_PAGE && buf.iter().all(|&b| b == 0));
+	let mut grown = Vec::new();
+	reserve(&mut grown, 100);
+	assert!(grown.capacity() >= 100);
+
+	for &(offset, len) in &[(0, 0), (0, 10), (3, 10), (1, 5000), (17, 10000), (0, 8192)] {
+	    let chunks : Vec<Range<usize>> = aligned_chunks(&buf[offset..offset + len], 4096)
+		.collect();
+	    // Contiguous, covering everything, aligned after the first
+	    assert_eq!(chunks.first().map_or(0, |c| c.start), 0);
+	    assert_eq!(chunks.last().map_or(0, |c| c.end), len);
+	    for pair in chunks.windows(2) { assert_eq!(pair[0].end, pair[1].start) }
+	    for c in chunks.iter().skip(1) {
+		assert_eq!(buf[offset + c.start..].as_ptr() as usize % CACHE_LINE, 0);
+		assert!(c.len() <= 4096 && !c.is_empty());
+	    }
+	}
+
+	assert_eq!("auto".parse::<ChunkSize>().unwrap(), ChunkSize::Auto);
+	assert_eq!("64K".parse::<ChunkSize>().unwrap(), ChunkSize::Fixed(65536));
+	assert_eq!("2m".parse::<ChunkSize>().unwrap(), ChunkSize::Fixed(2 << 20));
+	assert_eq!("4096".parse::<ChunkSize>().unwrap(), ChunkSize::default());
+	for bad in &["", "0", "100", "K", "-64", "64KB"] { assert!(bad.parse::<ChunkSize>().is_err()) }
+    }
+}
@@ -0,0 +1,1201 @@
+//! Share encodings
+//!
+//! A [ShareCodec] turns a [Share] into text and back. The plain
+//! `K=W=S=Values=` format is [TextCodec], and [V2Codec] is the
+//! versioned line format that adds the field polynomial and a
+//! checksum to it; other encodings (JSON, armored, mnemonic word
+//! lists, or something specific to one organisation) implement the
+//! same trait and are added to a [Registry], which can then pick the
+//! right codec by name or recognise which one a given piece of text
+//! is in.
+//!
+//! ```
+//! use guff_ssss_core::codec::Registry;
+//!
+//! let registry = Registry::new();
+//! let share = registry.decode("3=8=1=876ec0b40cf66736af2848=").unwrap();
+//! assert_eq!(share.index, 1);
+//! assert_eq!(registry.get("text").unwrap().encode(&share),
+//!            "3=8=1=876ec0b40cf66736af2848=");
+//! ```
+
+use crate::hash::Algorithm;
+use crate::{encode_hex, parse_share, Error, Result, Share};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::write::EncoderWriter;
+use base64::Engine;
+use std::io::{self, Write};
+use rfc1751::{FromRfc1751, FromRfc1751Error, ToRfc1751};
+
+/// An encoding of single shares as text
+pub trait ShareCodec : Send + Sync {
+    /// Short name used to select the codec, eg `text` or `json`
+    fn name(&self) -> &str;
+
+    /// Encode a share
+    fn encode(&self, share : &Share) -> String;
+
+    /// Write a share to `out` as a line: the encoding, without any
+    /// trailing whitespace, then a newline. Codecs whose output grows
+    /// with the share write it a piece at a time; the default makes
+    /// the whole encoding first.
+    fn write_line(&self, share : &Share, out : &mut dyn Write) -> io::Result<()> {
+	writeln!(out, "{}", self.encode(share).trim_end())
+    }
+
+    /// Decode a share. Only the encoding is checked here; whether
+    /// the share makes sense for its field is up to the
+    /// [Decoder](crate::Decoder).
+    fn decode(&self, text : &str) -> Result<Share>;
+
+    /// Does `text` look like it's in this encoding? Used to pick a
+    /// codec when the format isn't known in advance. The default
+    /// tries a full decode.
+    fn detect(&self, text : &str) -> bool {
+	self.decode(text).is_ok()
+    }
+}
+
+/// The plain `K=W=S=Values=` (or `I=K=W=S=Values=`) line format
+pub struct TextCodec;
+
+impl ShareCodec for TextCodec {
+    fn name(&self) -> &str { "text" }
+
+    fn encode(&self, share : &Share) -> String { share.to_string() }
+
+    fn write_line(&self, share : &Share, out : &mut dyn Write) -> io::Result<()> {
+	share.write_to(out)?;
+	out.write_all(b"\n")
+    }
+
+    fn decode(&self, text : &str) -> Result<Share> {
+	parse_share(text).map_err(|error| Error::ParseError { line : 1, error })
+    }
+
+    // Anything with the right number of '=' separators (and nothing
+    // that can't appear in a share, like quotes) is meant to be a text
+    // share, so report its parse errors rather than looking elsewhere
+    fn detect(&self, text : &str) -> bool {
+	let text = text.trim();
+	let fields = text.split('=').count();
+	(fields == 5 || fields == 6)
+	    && text.chars().all(|c| c.is_ascii_alphanumeric() || "=-_".contains(c))
+    }
+}
+
+// Version 2 lines
+//
+// The text format says nothing about which version of it a line is,
+// which field polynomial the words are in, or whether the line has
+// been mistyped, and there's no room in it to say more later. A
+// version 2 line starts with a magic word and version number, and
+// puts the parameters in a header of `key=value` fields:
+//
+// ssss2:k=3,w=8,s=2,p=11b,id=ops,ck=24910412:a02282b29bd85113fd46ad
+//
+// k, w and s are as in the text format, id is the set ID (if any),
+// p the field polynomial in hex, h the hash algorithm (if not
+// SHA-256), pad=7816 if the secret was padded to whole words (see
+// Share::padded) and ck the start of the hash of the share in the
+// text format (with `pad=7816` after it for a padded share), as in an
+// armored block. The fields can come in any order.
+// Readers skip fields they don't know, so later writers can add ones
+// that older readers can do without; a change that they can't do
+// without gets a new version number instead.
+
+const V2_MAGIC : &str = "ssss";
+const V2_VERSION : u32 = 2;
+
+// The version number after the magic word, if `text` has one
+fn v2_version(text : &str) -> Option<&str> {
+    let text = text.trim();
+    let head = text.get(..V2_MAGIC.len())?;
+    if !head.eq_ignore_ascii_case(V2_MAGIC) { return None }
+    let (version, _) = text[V2_MAGIC.len()..].split_once(':')?;
+    if version.is_empty() || !version.bytes().all(|b| b.is_ascii_digit()) { return None }
+    Some(version)
+}
+
+/// Version 2 lines, `ssss2:k=K,w=W,s=S,p=POLY,ck=CHECK:Values`, which
+/// carry a version number, the field polynomial and a checksum as
+/// well as the fields of the text format (see the comment in the
+/// source for the details)
+pub struct V2Codec;
+
+/// [V2Codec], checked with another hash algorithm. Either one reads
+/// both kinds of line.
+pub struct HashedV2Codec(pub Algorithm);
+
+/// The hash a v2 line's checksum is made with (SHA-256 unless it names
+/// another), which shamir-split also makes its fingerprint with. None
+/// if `text` isn't a v2 line, or names a hash this build doesn't have.
+pub fn v2_hash(text : &str) -> Option<Algorithm> {
+    let version = v2_version(text)?;
+    let (header, _) = text.trim()[V2_MAGIC.len() + version.len() + 1..].split_once(':')?;
+    let named = header.split(',').filter_map(|field| field.split_once('='))
+	.find(|(k, _)| k.trim().eq_ignore_ascii_case("h"));
+    match named {
+	Some((_, name)) => name.trim().parse().ok(),
+	None => Some(Algorithm::Sha256),
+    }
+}
+
+fn v2_head(share : &Share, hash : Algorithm) -> String {
+    let mut head = format!("{}{}:k={},w={},s={}", V2_MAGIC, V2_VERSION,
+			   share.quorum, share.width, share.index);
+    if let Some(poly) = crate::width::polynomial(share.width) { head += &format!(",p={:x}", poly) }
+    if let Some(id) = &share.set_id { head += &format!(",id={}", id) }
+    if hash != Algorithm::Sha256 { head += &format!(",h={}", hash) }
+    if share.padded { head += &format!(",pad={}", V2_PADDING) }
+    head + &format!(",ck={}:", v2_checksum(share, hash))
+}
+
+// The only padding there is so far: ISO/IEC 7816-4, to whole words
+const V2_PADDING : &str = "7816";
+
+// As for an armored block, but covering the pad field too, since the
+// text format can't
+fn v2_checksum(share : &Share, hash : Algorithm) -> String {
+    match share.padded {
+	false => armor_checksum(share, hash),
+	true => hex::encode(&hash.digest(format!("{}pad={}", share, V2_PADDING).as_bytes())[..4]),
+    }
+}
+
+impl ShareCodec for HashedV2Codec {
+    fn name(&self) -> &str { "v2" }
+
+    fn encode(&self, share : &Share) -> String {
+	v2_head(share, self.0) + &encode_hex(&share.values)
+    }
+
+    fn write_line(&self, share : &Share, out : &mut dyn Write) -> io::Result<()> {
+	out.write_all(v2_head(share, self.0).as_bytes())?;
+	crate::hex_pieces(&share.values, |hex| out.write_all(hex.as_bytes()))?;
+	out.write_all(b"\n")
+    }
+
+    fn decode(&self, text : &str) -> Result<Share> { V2Codec.decode(text) }
+
+    fn detect(&self, text : &str) -> bool { V2Codec.detect(text) }
+}
+
+impl ShareCodec for V2Codec {
+    fn name(&self) -> &str { "v2" }
+
+    fn encode(&self, share : &Share) -> String { HashedV2Codec(Algorithm::Sha256).encode(share) }
+
+    fn write_line(&self, share : &Share, out : &mut dyn Write) -> io::Result<()> {
+	HashedV2Codec(Algorithm::Sha256).write_line(share, out)
+    }
+
+    fn decode(&self, text : &str) -> Result<Share> {
+	let bad = |what : &str| Error::UnknownFormat(format!("v2 ({})", what));
+	let version = v2_version(text).ok_or_else(|| bad("no ssss2: prefix"))?;
+	if version.parse::<u32>().ok() != Some(V2_VERSION) {
+	    return Err(Error::UnknownFormat(format!("{}{} (this build reads versions 1 and 2)",
+						    V2_MAGIC, version)))
+	}
+	let text = text.trim();
+	let rest = &text[V2_MAGIC.len() + version.len() + 1..];
+	let (header, payload) = rest.split_once(':').ok_or_else(|| bad("no header"))?;
+	// an '=' anywhere else would make extra fields in the text line
+	if payload.contains('=') { return Err(bad("'=' in the share data")) }
+	let fields = header.split(',').map(|field| field.split_once('=')
+	    .filter(|(_, v)| !v.contains('='))
+	    .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim()))
+	    .ok_or_else(|| bad(&format!("header field \"{}\" isn't key=value", field))))
+	    .collect::<Result<Vec<_>>>()?;
+	let field = |name : &str| fields.iter().find(|(k, _)| k == name).map(|(_, v)| *v);
+	let need = |name : &str| field(name).ok_or_else(|| bad(&format!("no {} field", name)));
+	let line = match field("id") {
+	    Some(id) => format!("{}={}={}={}={}=", id, need("k")?, need("w")?, need("s")?, payload),
+	    None => format!("{}={}={}={}=", need("k")?, need("w")?, need("s")?, payload),
+	};
+	let share = TextCodec.decode(&line)?;
+	if let Some(poly) = field("p") {
+	    let expected = crate::width::polynomial(share.width);
+	    if u64::from_str_radix(poly, 16).ok() != expected {
+		return Err(Error::FieldError(match expected {
+		    Some(expected) => format!("share is in GF(2^{}) with polynomial {}, but this \
+					       program's field has polynomial {:x}",
+					      share.width, poly, expected),
+		    None => format!("no GF(2^{}) field with polynomial {}", share.width, poly),
+		}))
+	    }
+	}
+	let hash = match field("h") {
+	    Some(name) => name.parse().map_err(|_| bad(&format!("unknown hash {}", name)))?,
+	    None => Algorithm::Sha256,
+	};
+	// Padding that this build doesn't know how to take off would be
+	// left on the secret, so that's an error rather than skipped
+	let share = match field("pad") {
+	    Some(V2_PADDING) => Share { padded : true, ..share },
+	    Some(other) => return Err(bad(&format!("unknown padding {}", other))),
+	    None => share,
+	};
+	match need("ck")?.to_ascii_lowercase() {
+	    check if check == v2_checksum(&share, hash) => Ok(share),
+	    _ => Err(Error::BadChecksum("v2".into())),
+	}
+    }
+
+    // Any version, so that a newer one gets a clear error
+    fn detect(&self, text : &str) -> bool {
+	v2_version(text).is_some()
+    }
+}
+
+// z-base-32
+//
+// Zooko's base-32 alphabet leaves out the characters most easily
+// confused with each other (0/o, 1/l, 2/z, v/u), and is read without
+// regard to case, so it suits shares that are read aloud or written
+// down by hand. The payload is marked with `z:` and broken into groups
+// of four with '-', which are ignored (as is other whitespace) when
+// reading. The marker is case-insensitive too.
+
+const ZBASE32 : &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+fn zbase32_encode(bytes : &[u8]) -> String {
+    let mut out = Vec::with_capacity(bytes.len() * 8 / 5 + 1);
+    let (mut acc, mut bits) = (0u32, 0);
+    for &b in bytes {
+	acc = (acc << 8) | b as u32;
+	bits += 8;
+	while bits >= 5 {
+	    bits -= 5;
+	    out.push(ZBASE32[(acc >> bits) as usize & 31]);
+	}
+    }
+    if bits > 0 { out.push(ZBASE32[(acc << (5 - bits)) as usize & 31]) }
+    out.chunks(4).map(|c| String::from_utf8(c.to_vec()).unwrap())
+	.collect::<Vec<_>>().join("-")
+}
+
+fn zbase32_decode(text : &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.chars().filter(|c| *c != '-' && !c.is_whitespace()) {
+	let c = c.to_ascii_lowercase() as u8;
+	let v = ZBASE32.iter().position(|&z| z == c)? as u32;
+	acc = (acc << 5) | v;
+	bits += 5;
+	if bits >= 8 {
+	    bits -= 8;
+	    out.push((acc >> bits) as u8);
+	}
+	acc &= (1 << bits) - 1;
+    }
+    // Leftover bits are padding, and must be zero
+    if acc != 0 { return None }
+    Some(out)
+}
+
+/// Text shares with a z-base-32 payload, `K=W=S=z:DATA=`
+pub struct ZBase32Codec;
+
+impl ShareCodec for ZBase32Codec {
+    fn name(&self) -> &str { "zbase32" }
+
+    fn encode(&self, share : &Share) -> String {
+	let head = match &share.set_id {
+	    Some(id) => format!("{}={}={}={}", id, share.quorum, share.width, share.index),
+	    None => format!("{}={}={}", share.quorum, share.width, share.index),
+	};
+	format!("{}=z:{}=", head, zbase32_encode(&share.values))
+    }
+
+    fn decode(&self, text : &str) -> Result<Share> {
+	let bad = || Error::UnknownFormat("zbase32 (bad payload)".into());
+	let text = text.trim();
+	let marker = text.to_ascii_lowercase().find("=z:").ok_or_else(bad)?;
+	let (head, rest) = text.split_at(marker);
+	let data = rest[3..].strip_suffix('=').ok_or_else(bad)?;
+	let values = zbase32_decode(data).ok_or_else(bad)?;
+	TextCodec.decode(&format!("{}={}=", head, hex::encode(values)))
+    }
+
+    fn detect(&self, text : &str) -> bool {
+	text.to_ascii_lowercase().contains("=z:")
+    }
+}
+
+/// Text shares with the payload as RFC 1751 (S/KEY) English words,
+/// `K=W=S=wN:WORD WORD ...=`. The words encode 64 bits at a time, so
+/// the payload is zero-padded to a multiple of eight bytes, and `N`
+/// records its real length. Words are read without regard to case,
+/// and the two parity bits in every sixth word catch most misspellings.
+pub struct Rfc1751Codec;
+
+// Where the `=wN:` payload starts, with the length and the offset of
+// the words
+fn rfc1751_payload(text : &str) -> Option<(usize, usize, usize)> {
+    let lower = text.to_ascii_lowercase();
+    lower.match_indices("=w").find_map(|(at, _)| {
+	let rest = &lower[at + 2..];
+	let digits = rest.find(|c : char| !c.is_ascii_digit())?;
+	if digits == 0 || !rest[digits..].starts_with(':') { return None }
+	Some((at, rest[..digits].parse().ok()?, at + 2 + digits + 1))
+    })
+}
+
+impl ShareCodec for Rfc1751Codec {
+    fn name(&self) -> &str { "rfc1751" }
+
+    fn encode(&self, share : &Share) -> String {
+	let head = match &share.set_id {
+	    Some(id) => format!("{}={}={}={}", id, share.quorum, share.width, share.index),
+	    None => format!("{}={}={}", share.quorum, share.width, share.index),
+	};
+	let mut padded = share.values.clone();
+	padded.resize(padded.len().div_ceil(8) * 8, 0);
+	let words = padded.to_rfc1751().expect("padded to a multiple of 8 bytes");
+	format!("{}=w{}:{}=", head, share.values.len(), words)
+    }
+
+    fn decode(&self, text : &str) -> Result<Share> {
+	let bad = |what : &str| Error::UnknownFormat(format!("rfc1751 ({})", what));
+	let text = text.trim();
+	let (at, len, start) = rfc1751_payload(text).ok_or_else(|| bad("no =wN: payload"))?;
+	let words = text[start..].strip_suffix('=').ok_or_else(|| bad("missing final '='"))?;
+	let mut values = match words.to_ascii_uppercase().from_rfc1751() {
+	    Ok(values) => values,
+	    Err(FromRfc1751Error::InvalidWord(w)) => return Err(bad(&format!("unknown word {}", w))),
+	    Err(FromRfc1751Error::IncorrectParity(_)) => return Err(bad("parity check failed")),
+	    Err(FromRfc1751Error::NotMultipleOfSixWords) => return Err(bad("words come in sixes")),
+	};
+	if len > values.len() || values.len() - len >= 8 || values[len..].iter().any(|&b| b != 0) {
+	    return Err(bad("length doesn't match the words"))
+	}
+	values.truncate(len);
+	TextCodec.decode(&format!("{}={}=", &text[..at], hex::encode(values)))
+    }
+
+    fn detect(&self, text : &str) -> bool {
+	rfc1751_payload(text).is_some()
+    }
+}
+
+/// Shell variable assignments, `SHARE_3='3=8=3=4f29...='`, for
+/// sourcing into provisioning environments. With a set ID, the
+/// variable is `SHARE_<SET ID>_3`. A leading `export`, double quotes
+/// or no quotes at all are accepted when reading, though an unquoted
+/// assignment is also a valid text share (with the variable name as
+/// its set ID), and a [Registry] will read it as one.
+pub struct DotenvCodec;
+
+impl ShareCodec for DotenvCodec {
+    fn name(&self) -> &str { "dotenv" }
+
+    fn encode(&self, share : &Share) -> String {
+	match &share.set_id {
+	    Some(id) => format!("SHARE_{}_{}='{}'", id.to_uppercase().replace('-', "_"),
+				share.index, share),
+	    None => format!("SHARE_{}='{}'", share.index, share),
+	}
+    }
+
+    fn decode(&self, text : &str) -> Result<Share> {
+	let bad = || Error::UnknownFormat("dotenv (expected SHARE_N='...')".into());
+	let text = text.trim();
+	let text = text.strip_prefix("export ").map(str::trim_start).unwrap_or(text);
+	let (name, value) = text.split_at(text.find('=').ok_or_else(bad)?);
+	if !name.starts_with("SHARE_")
+	    || !name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_') {
+	    return Err(bad())
+	}
+	let value = &value[1..];
+	let value = ['\'', '"'].iter()
+	    .find_map(|&q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+	    .unwrap_or(value);
+	TextCodec.decode(value)
+    }
+
+    fn detect(&self, text : &str) -> bool {
+	let text = text.trim();
+	let text = text.strip_prefix("export ").map(str::trim_start).unwrap_or(text);
+	text.starts_with("SHARE_") && text.contains('=')
+    }
+}
+
+/// Shares as Kubernetes Secret manifests, ready for `kubectl apply`.
+/// The text share is the Secret's `share` data (base64, as Kubernetes
+/// wants), and labels carry the set ID, share number and quorum so
+/// that shares can be found with a selector. Each manifest starts with
+/// `---`, so several make one stream.
+pub struct KubernetesCodec;
+
+// Secret names are DNS subdomains: lower case letters, digits and '-'
+fn kubernetes_name(share : &Share) -> String {
+    match &share.set_id {
+	Some(id) => format!("guff-ssss-{}-share-{}",
+			    id.to_lowercase().replace('_', "-").trim_matches('-'), share.index),
+	None => format!("guff-ssss-share-{}", share.index),
+    }
+}
+
+// Everything up to the share data
+fn kubernetes_head(share : &Share) -> String {
+    let mut labels = String::new();
+    if let Some(id) = &share.set_id {
+	labels += &format!("    guff-ssss/set-id: \"{}\"\n", id);
+    }
+    labels += &format!("    guff-ssss/index: \"{}\"\n    guff-ssss/quorum: \"{}\"\n",
+		       share.index, share.quorum);
+    format!("---\napiVersion: v1\nkind: Secret\nmetadata:\n  name: {}\n  labels:\n{}\
+	     type: Opaque\ndata:\n  share: ", kubernetes_name(share), labels)
+}
+
+impl ShareCodec for KubernetesCodec {
+    fn name(&self) -> &str { "kubernetes" }
+
+    fn encode(&self, share : &Share) -> String {
+	format!("{}{}\n", kubernetes_head(share), BASE64.encode(share.to_string()))
+    }
+
+    fn write_line(&self, share : &Share, out : &mut dyn Write) -> io::Result<()> {
+	out.write_all(kubernetes_head(share).as_bytes())?;
+	let mut data = EncoderWriter::new(&mut *out, &BASE64);
+	share.write_to(&mut data)?;
+	data.finish()?.write_all(b"\n")
+    }
+
+    // Only the share data is read; the labels are just for finding it
+    fn decode(&self, text : &str) -> Result<Share> {
+	let bad = |what : &str| Error::UnknownFormat(format!("kubernetes ({})", what));
+	let mut in_data = false;
+	for line in text.lines() {
+	    if !line.starts_with(' ') {
+		in_data = line.trim_end() == "data:";
+	    } else if let (true, Some(value)) = (in_data, line.trim().strip_prefix("share:")) {
+		let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+		let bytes = BASE64.decode(value).map_err(|_| bad("share data isn't base64"))?;
+		let share = String::from_utf8(bytes).map_err(|_| bad("share data isn't text"))?;
+		return TextCodec.decode(&share)
+	    }
+	}
+	Err(bad("no share in the Secret's data"))
+    }
+
+    fn detect(&self, text : &str) -> bool {
+	text.lines().any(|line| line.trim_end() == "kind: Secret")
+    }
+}
+
+/// Decode a stream of Secret manifests separated by `---`, as written
+/// by [KubernetesCodec] one share after another
+pub fn decode_kubernetes_stream(text : &str) -> Result<Vec<Share>> {
+    let mut docs = vec![String::new()];
+    for line in text.lines() {
+	if line.trim_end() == "---" {
+	    docs.push(String::new())
+	} else {
+	    let doc = docs.last_mut().unwrap();
+	    *doc += line;
+	    doc.push('\n');
+	}
+    }
+    docs.iter().filter(|doc| KubernetesCodec.detect(doc))
+	.map(|doc| KubernetesCodec.decode(doc)).collect()
+}
+
+// Armored blocks
+//
+// For pasting into email and tickets. Everything in the block is
+// letters, digits, spaces and ':', and no line is longer than 64
+// characters, so there's nothing for a mail client to wrap, quote-
+// print, linkify or turn into typographic quotes. Reading is
+// forgiving of what replies and forwards do to text: `>` and `|`
+// quoting, non-breaking and zero-width spaces, dashes turned into en
+// or em dashes (in set IDs), quoted-printable soft line breaks and
+// re-wrapped payload lines. A checksum catches anything else.
+
+const ARMOR_BEGIN : &str = "BEGIN GUFF SSSS SHARE";
+const ARMOR_END : &str = "END GUFF SSSS SHARE";
+const ARMOR_COLS : usize = 64;
+
+fn armor_checksum(share : &Share, hash : Algorithm) -> String {
+    hex::encode(&hash.digest(share.to_string().as_bytes())[..4])
+}
+
+// Undo what mail clients do to a line
+fn unmangle(line : &str) -> String {
+    let line : String = line.chars().filter_map(|c| match c {
+	'\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{2060}' | '\u{feff}' | '\u{ad}' => None,
+	'\u{a0}' | '\u{202f}' => Some(' '),
+	'\u{2010}'..='\u{2015}' | '\u{2212}' => Some('-'),
+	c => Some(c),
+    }).collect();
+    let line = line.trim_start_matches(|c : char| c == '>' || c == '|' || c.is_whitespace());
+    let line = line.trim_end();
+    line.strip_suffix('=').unwrap_or(line).trim_end().to_string()
+}
+
+/// Shares as armored text blocks that survive email and ticketing
+/// systems:
+///
+/// ```text
+/// BEGIN GUFF SSSS SHARE
+/// Set: ops
+/// Quorum: 3
+/// Width: 8
+/// Share: 2
+///
+/// a02282b29bd85113fd46ad
+/// Check: 24910412
+/// END GUFF SSSS SHARE
+/// ```
+///
+/// The payload is hex, 64 digits to a line, and the check is the
+/// start of the SHA-256 of the share in the text format (or of
+/// another [hash](crate::hash), named in a `Hash:` header, with
+/// [HashedArmorCodec]).
+pub struct ArmorCodec;
+
+/// [ArmorCodec], checked with another hash algorithm. Either one
+/// reads both kinds of block.
+pub struct HashedArmorCodec(pub Algorithm);
+
+fn encode_armor(share : &Share, hash : Algorithm) -> String {
+    let mut text = format!("{}\n", ARMOR_BEGIN);
+    if let Some(id) = &share.set_id { text += &format!("Set: {}\n", id) }
+    text += &format!("Quorum: {}\nWidth: {}\nShare: {}\n", share.quorum, share.width, share.index);
+    if hash != Algorithm::Sha256 { text += &format!("Hash: {}\n", hash) }
+    text.push('\n');
+    for chunk in encode_hex(&share.values).as_bytes().chunks(ARMOR_COLS) {
+	text += std::str::from_utf8(chunk).unwrap();
+	text.push('\n');
+    }
+    text + &format!("Check: {}\n{}\n", armor_checksum(share, hash), ARMOR_END)
+}
+
+impl ShareCodec for HashedArmorCodec {
+    fn name(&self) -> &str { "armor" }
+
+    fn encode(&self, share : &Share) -> String { encode_armor(share, self.0) }
+
+    fn decode(&self, text : &str) -> Result<Share> { ArmorCodec.decode(text) }
+
+    fn detect(&self, text : &str) -> bool { ArmorCodec.detect(text) }
+}
+
+impl ShareCodec for ArmorCodec {
+    fn name(&self) -> &str { "armor" }
+
+    fn encode(&self, share : &Share) -> String { encode_armor(share, Algorithm::Sha256) }
+
+    fn decode(&self, text : &str) -> Result<Share> {
+	let bad = |what : &str| Error::UnknownFormat(format!("armor ({})", what));
+	let lines : Vec<String> = text.lines().map(unmangle).collect();
+	let begin = lines.iter().position(|l| l.eq_ignore_ascii_case(ARMOR_BEGIN))
+	    .ok_or_else(|| bad("no BEGIN line"))?;
+	let end = lines[begin..].iter().position(|l| l.eq_ignore_ascii_case(ARMOR_END))
+	    .ok_or_else(|| bad("no END line"))? + begin;
+	let (mut set_id, mut fields, mut payload, mut check) = (None, Vec::new(), String::new(), None);
+	for line in lines[begin + 1..end].iter().filter(|l| !l.is_empty()) {
+	    match line.split_once(':').map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim())) {
+		Some((key, value)) if key == "set" => set_id = Some(value.to_string()),
+		Some((key, value)) if key == "check" => check = Some(value.to_ascii_lowercase()),
+		Some((key, value)) => fields.push((key, value.to_string())),
+		None => payload.extend(line.chars().filter(|c| !c.is_whitespace())),
+	    }
+	}
+	let field = |name : &str| fields.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+	    .ok_or_else(|| bad(&format!("no {} line", name)));
+	let line = match &set_id {
+	    Some(id) => format!("{}={}={}={}={}=", id, field("quorum")?, field("width")?,
+				field("share")?, payload),
+	    None => format!("{}={}={}={}=", field("quorum")?, field("width")?,
+			    field("share")?, payload),
+	};
+	let share = TextCodec.decode(&line)?;
+	let hash = match fields.iter().find(|(k, _)| k == "hash") {
+	    Some((_, name)) => name.parse().map_err(|_| bad(&format!("unknown hash {}", name)))?,
+	    None => Algorithm::Sha256,
+	};
+	match check {
+	    Some(check) if check == armor_checksum(&share, hash) => Ok(share),
+	    Some(_) => Err(bad("checksum doesn't match; the block has been altered")),
+	    None => Err(bad("no Check line")),
+	}
+    }
+
+    fn detect(&self, text : &str) -> bool {
+	text.lines().any(|line| unmangle(line).eq_ignore_ascii_case(ARMOR_BEGIN))
+    }
+}
+
+/// Decode every armored block in `text`, ignoring whatever is around
+/// them (such as the rest of an email)
+pub fn decode_armor_stream(text : &str) -> Result<Vec<Share>> {
+    let mut shares = Vec::new();
+    let mut block : Option<String> = None;
+    for line in text.lines() {
+	let clean = unmangle(line);
+	if clean.eq_ignore_ascii_case(ARMOR_BEGIN) {
+	    block = Some(String::new());
+	}
+	if let Some(b) = block.as_mut() {
+	    *b += line;
+	    b.push('\n');
+	    if clean.eq_ignore_ascii_case(ARMOR_END) {
+		shares.push(ArmorCodec.decode(b)?);
+		block = None;
+	    }
+	}
+    }
+    Ok(shares)
+}
+
+/// Shares as YAML documents, one mapping per share with the same
+/// fields as the `serde` representation (with the `yaml` feature)
+#[cfg(feature = "yaml")]
+pub struct YamlCodec;
+
+#[cfg(feature = "yaml")]
+impl ShareCodec for YamlCodec {
+    fn name(&self) -> &str { "yaml" }
+
+    fn encode(&self, share : &Share) -> String {
+	format!("---\n{}", serde_yaml::to_string(share).expect("shares always serialize"))
+    }
+
+    fn decode(&self, text : &str) -> Result<Share> {
+	serde_yaml::from_str(text).map_err(|e| Error::UnknownFormat(format!("yaml ({})", e)))
+    }
+
+    fn detect(&self, text : &str) -> bool {
+	text.lines().any(|line| line.trim_start().starts_with("quorum:"))
+    }
+}
+
+/// Decode a stream of YAML share documents separated by `---`, as
+/// written by [YamlCodec] one share after another
+#[cfg(feature = "yaml")]
+pub fn decode_yaml_stream(text : &str) -> Result<Vec<Share>> {
+    use serde::Deserialize;
+    serde_yaml::Deserializer::from_str(text)
+	.map(|doc| Share::deserialize(doc)
+	     .map_err(|e| Error::UnknownFormat(format!("yaml ({})", e))))
+	.collect()
+}
+
+/// Shares as single-line JSON objects, with the same fields as the
+/// `serde` representation (with the `json` feature)
+#[cfg(feature = "json")]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl ShareCodec for JsonCodec {
+    fn name(&self) -> &str { "json" }
+
+    fn encode(&self, share : &Share) -> String {
+	serde_json::to_string(share).expect("shares always serialize")
+    }
+
+    fn decode(&self, text : &str) -> Result<Share> {
+	serde_json::from_str(text).map_err(|e| Error::UnknownFormat(format!("json ({})", e)))
+    }
+
+    fn detect(&self, text : &str) -> bool {
+	text.trim_start().starts_with('{')
+    }
+}
+
+// Templates
+//
+// Organisations often have a house style for documenting key material
+// ("ACME-ROOT-3: ..."), so shares can be written through a template
+// with `{{field}}` placeholders. Values are substituted once and never
+// re-expanded, and none of them can contain braces, so a share can't
+// inject anything into the template. Reading shares back needs some
+// literal text between each pair of placeholders to know where one
+// value stops and the next starts.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field { Label, Quorum, Width, Index, Data, Share }
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment { Literal(String), Field(Field) }
+
+/// Shares written through a `{{field}}` template
+///
+/// The fields are `label` (the set ID, or nothing), `quorum`, `width`,
+/// `index`, `data` (the hex values) and `share` (the whole share in
+/// the text format). To be read back, a template needs either `share`
+/// or all of `quorum`, `width`, `index` and `data`.
+pub struct TemplateCodec {
+    segments : Vec<Segment>,
+}
+
+impl TemplateCodec {
+    pub fn new(template : &str) -> Result<TemplateCodec> {
+	let bad = |what : String| Error::UnknownFormat(format!("template: {}", what));
+	let mut segments = Vec::new();
+	let mut rest = template;
+	while let Some(start) = rest.find("{{") {
+	    let end = rest[start..].find("}}")
+		.ok_or_else(|| bad("unclosed '{{'".into()))? + start;
+	    let field = match rest[start + 2..end].trim() {
+		"label" => Field::Label, "quorum" => Field::Quorum,
+		"width" => Field::Width, "index" => Field::Index,
+		"data" => Field::Data, "share" => Field::Share,
+		other => return Err(bad(format!("unknown field '{}'", other))),
+	    };
+	    if start > 0 { segments.push(Segment::Literal(rest[..start].to_string())) }
+	    if let Some(Segment::Field(_)) = segments.last() {
+		return Err(bad("fields must be separated by some text".into()))
+	    }
+	    segments.push(Segment::Field(field));
+	    rest = &rest[end + 2..];
+	}
+	if !rest.is_empty() { segments.push(Segment::Literal(rest.to_string())) }
+	Ok(TemplateCodec { segments })
+    }
+
+    /// Does the template keep enough to read the shares back?
+    pub fn can_decode(&self) -> bool {
+	let has = |field| self.segments.contains(&Segment::Field(field));
+	has(Field::Share)
+	    || [Field::Quorum, Field::Width, Field::Index, Field::Data].iter().all(|&f| has(f))
+    }
+}
+
+impl ShareCodec for TemplateCodec {
+    fn name(&self) -> &str { "template" }
+
+    fn encode(&self, share : &Share) -> String {
+	self.segments.iter().map(|segment| match segment {
+	    Segment::Literal(text) => text.clone(),
+	    Segment::Field(Field::Label) => share.set_id.clone().unwrap_or_default(),
+	    Segment::Field(Field::Quorum) => share.quorum.to_string(),
+	    Segment::Field(Field::Width) => share.width.to_string(),
+	    Segment::Field(Field::Index) => share.index.to_string(),
+	    Segment::Field(Field::Data) => encode_hex(&share.values),
+	    Segment::Field(Field::Share) => share.to_string(),
+	}).collect()
+    }
+
+    fn decode(&self, text : &str) -> Result<Share> {
+	let mismatch = || Error::UnknownFormat("(doesn't match the template)".into());
+	let mut rest = text.trim();
+	let mut values : Vec<(Field, &str)> = Vec::new();
+	for (i, segment) in self.segments.iter().enumerate() {
+	    match segment {
+		Segment::Literal(lit) => {
+		    // Outer whitespace was trimmed from the text, so trim
+		    // it from the template too
+		    let lit = if i == 0 { lit.trim_start() } else { lit.as_str() };
+		    let lit = if i + 1 == self.segments.len() { lit.trim_end() } else { lit };
+		    rest = rest.strip_prefix(lit).ok_or_else(mismatch)?;
+		},
+		Segment::Field(field) => {
+		    let end = match self.segments.get(i + 1) {
+			Some(Segment::Literal(lit)) => rest.find(lit.as_str()).ok_or_else(mismatch)?,
+			_ => rest.len(),
+		    };
+		    values.push((*field, &rest[..end]));
+		    rest = &rest[end..];
+		},
+	    }
+	}
+	if !rest.is_empty() { return Err(mismatch()) }
+	let get = |field| values.iter().find(|(f, _)| *f == field).map(|(_, v)| *v);
+	let line = match get(Field::Share) {
+	    Some(share) => share.to_string(),
+	    None => match (get(Field::Quorum), get(Field::Width), get(Field::Index), get(Field::Data)) {
+		(Some(k), Some(w), Some(s), Some(data)) => match get(Field::Label) {
+		    Some(id) if !id.is_empty() => format!("{}={}={}={}={}=", id, k, w, s, data),
+		    _ => format!("{}={}={}={}=", k, w, s, data),
+		},
+		_ => return Err(Error::UnknownFormat(
+		    "template: can't read shares back without {{share}}, or all of \
+		     {{quorum}}, {{width}}, {{index}} and {{data}}".into())),
+	    },
+	};
+	TextCodec.decode(&line)
+    }
+
+    fn detect(&self, text : &str) -> bool {
+	self.decode(text).is_ok()
+    }
+}
+
+/// A set of codecs, looked up by name or by recognising their output
+pub struct Registry {
+    codecs : Vec<Box<dyn ShareCodec>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self { Self::new() }
+}
+
+impl Registry {
+    /// A registry holding all the codecs built into this crate
+    pub fn new() -> Registry {
+	let mut registry = Registry::empty();
+	registry.register(Box::new(TextCodec));
+	registry.register(Box::new(V2Codec));
+	registry.register(Box::new(DotenvCodec));
+	registry.register(Box::new(ZBase32Codec));
+	registry.register(Box::new(Rfc1751Codec));
+	registry.register(Box::new(KubernetesCodec));
+	registry.register(Box::new(ArmorCodec));
+	#[cfg(feature = "yaml")]
+	registry.register(Box::new(YamlCodec));
+	#[cfg(feature = "json")]
+	registry.register(Box::new(JsonCodec));
+	registry
+    }
+
+    /// A registry with no codecs at all
+    pub fn empty() -> Registry {
+	Registry { codecs : Vec::new() }
+    }
+
+    /// Add a codec, replacing any existing one with the same name
+    pub fn register(&mut self, codec : Box<dyn ShareCodec>) {
+	self.codecs.retain(|c| c.name() != codec.name());
+	self.codecs.push(codec);
+    }
+
+    /// Look up a codec by name
+    pub fn get(&self, name : &str) -> Option<&dyn ShareCodec> {
+	self.codecs.iter().find(|c| c.name() == name).map(|c| c.as_ref())
+    }
+
+    /// Names of all registered codecs, in the order they were added
+    pub fn names(&self) -> Vec<&str> {
+	self.codecs.iter().map(|c| c.name()).collect()
+    }
+
+    /// Encode a share with the named codec
+    pub fn encode(&self, name : &str, share : &Share) -> Result<String> {
+	match self.get(name) {
+	    Some(codec) => Ok(codec.encode(share)),
+	    None => Err(Error::UnknownFormat(name.to_string())),
+	}
+    }
+
+    /// Write a share as a line with the named codec
+    pub fn write_line(&self, name : &str, share : &Share, out : &mut dyn Write) -> Result<()> {
+	match self.get(name) {
+	    Some(codec) => Ok(codec.write_line(share, out)?),
+	    None => Err(Error::UnknownFormat(name.to_string())),
+	}
+    }
+
+    /// Decode a share in whichever format it's in. Codecs are asked
+    /// in the order they were registered, and the first one that
+    /// recognises the text decodes it.
+    pub fn decode(&self, text : &str) -> Result<Share> {
+	match self.codecs.iter().find(|c| c.detect(text)) {
+	    Some(codec) => codec.decode(text),
+	    None => Err(Error::UnknownFormat("(not recognised)".to_string())),
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A made-up organisation-specific format: "ACME:" then the text
+    // share with '=' replaced by ':'
+    struct Acme;
+
+    impl ShareCodec for Acme {
+	fn name(&self) -> &str { "acme" }
+	fn encode(&self, share : &Share) -> String {
+	    format!("ACME:{}", share.to_string().replace('=', ":"))
+	}
+	fn decode(&self, text : &str) -> Result<Share> {
+	    let inner = text.trim().strip_prefix("ACME:")
+		.ok_or_else(|| Error::UnknownFormat("acme".into()))?;
+	    TextCodec.decode(&inner.replace(':', "="))
+	}
+	fn detect(&self, text : &str) -> bool { text.trim().starts_with("ACME:") }
+    }
+
+    #[test]
+    fn plug_in_codec() {
+	// Just the text codec, whatever other features are on
+	let mut registry = Registry::empty();
+	registry.register(Box::new(TextCodec));
+	registry.register(Box::new(Acme));
+	assert_eq!(registry.names(), ["text", "acme"]);
+
+	let share = registry.decode("x1=2=8=5=00ff=").unwrap();
+	let acme = registry.encode("acme", &share).unwrap();
+	assert_eq!(acme, "ACME:x1:2:8:5:00ff:");
+	assert_eq!(registry.decode(&acme).unwrap(), share);
+
+	assert!(matches!(registry.encode("json", &share), Err(Error::UnknownFormat(_))));
+	assert!(matches!(registry.decode("hello"), Err(Error::UnknownFormat(_))));
+	assert!(matches!(registry.decode("2=8=5=0g="), Err(Error::ParseError { .. })));
+    }
+
+    #[test]
+    fn templates() {
+	let share : Share = "ops=3=8=2=a02282b29bd85113fd46ad=".parse().unwrap();
+	let house = TemplateCodec::new("{{label}}-{{index}}: {{data}} (k={{quorum}}, w={{width}})")
+	    .unwrap();
+	let text = house.encode(&share);
+	assert_eq!(text, "ops-2: a02282b29bd85113fd46ad (k=3, w=8)");
+	assert_eq!(house.decode(&format!("  {}\n", text)).unwrap(), share);
+	assert!(house.decode("ops-2: a022 (k=3)").is_err());
+
+	let wrapped = TemplateCodec::new("Share {{index}} of set '{{label}}': {{share}}").unwrap();
+	assert_eq!(wrapped.decode(&wrapped.encode(&share)).unwrap(), share);
+
+	// Can be written but not read back
+	let lossy = TemplateCodec::new("{{label}}-{{index}}: {{data}}").unwrap();
+	assert_eq!(lossy.encode(&share), "ops-2: a02282b29bd85113fd46ad");
+	assert!(lossy.decode("ops-2: a02282b29bd85113fd46ad").is_err());
+	assert!(!lossy.can_decode() && house.can_decode() && wrapped.can_decode());
+
+	assert!(TemplateCodec::new("{{index}}{{data}}").is_err());
+	assert!(TemplateCodec::new("{{colour}}").is_err());
+	assert!(TemplateCodec::new("{{index").is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_documents() {
+	let shares : Vec<Share> = ["ops=3=8=1=876ec0b40cf66736af2848=",
+				   "3=8=2=a02282b29bd85113fd46ad="]
+	    .iter().map(|s| s.parse().unwrap()).collect();
+	let stream : String = shares.iter().map(|s| YamlCodec.encode(s)).collect();
+	assert_eq!(stream, "---\nset_id: ops\nquorum: 3\nwidth: 8\nindex: 1\n\
+			    values: 876ec0b40cf66736af2848\n\
+			    ---\nquorum: 3\nwidth: 8\nindex: 2\nvalues: a02282b29bd85113fd46ad\n");
+	assert_eq!(decode_yaml_stream(&stream).unwrap(), shares);
+	let registry = Registry::new();
+	assert_eq!(registry.decode(&YamlCodec.encode(&shares[0])).unwrap(), shares[0]);
+	// hand-written, with a comment and quoting
+	assert_eq!(YamlCodec.decode("# custodian: A\nquorum: 3\nwidth: 8\nindex: 2\n\
+				     values: 'a02282b29bd85113fd46ad'").unwrap(), shares[1]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_objects() {
+	let share : Share = "ops=3=8=1=876ec0b40cf66736af2848=".parse().unwrap();
+	let json = r#"{"set_id":"ops","quorum":3,"width":8,"index":1,"values":"876ec0b40cf66736af2848"}"#;
+	assert_eq!(JsonCodec.encode(&share), json);
+	let registry = Registry::new();
+	assert_eq!(registry.decode(json).unwrap(), share);
+	assert_eq!(registry.decode("  {\"quorum\": 3, \"width\": 8, \"index\": 1,\n\
+				    \"values\": \"876ec0b40cf66736af2848\"}").unwrap(),
+		   Share { set_id : None, ..share });
+	assert!(registry.decode(r#"{"quorum":3,"width":8,"index":1}"#).is_err());
+    }
+
+    #[test]
+    fn dotenv() {
+	let plain : Share = "3=8=2=a02282b29bd85113fd46ad=".parse().unwrap();
+	let labelled : Share = "ops-1=3=8=2=a02282b29bd85113fd46ad=".parse().unwrap();
+	assert_eq!(DotenvCodec.encode(&plain), "SHARE_2='3=8=2=a02282b29bd85113fd46ad='");
+	assert_eq!(DotenvCodec.encode(&labelled),
+		   "SHARE_OPS_1_2='ops-1=3=8=2=a02282b29bd85113fd46ad='");
+
+	let registry = Registry::new();
+	for text in ["SHARE_2='3=8=2=a02282b29bd85113fd46ad='",
+		     "export SHARE_2=\"3=8=2=a02282b29bd85113fd46ad=\""] {
+	    assert_eq!(registry.decode(text).unwrap(), plain);
+	}
+	// Unquoted, it's also a text share with a set ID, which wins
+	let bare = "SHARE_X=3=8=2=a02282b29bd85113fd46ad=";
+	assert_eq!(DotenvCodec.decode(bare).unwrap(), plain);
+	assert_eq!(registry.decode(bare).unwrap().set_id.as_deref(), Some("SHARE_X"));
+	assert_eq!(registry.decode(&DotenvCodec.encode(&labelled)).unwrap(), labelled);
+	assert!(DotenvCodec.decode("PATH=/bin").is_err());
+	assert!(registry.decode("SHARE_2='3=8=2=a0228'").is_err());
+    }
+
+    #[test]
+    fn zbase32() {
+	// From the z-base-32 specification
+	assert_eq!(zbase32_encode(&[0xf0, 0xbf, 0xc7]), "6n9h-q");
+	assert_eq!(zbase32_decode("6n9hq").unwrap(), [0xf0, 0xbf, 0xc7]);
+	assert_eq!(zbase32_encode(&[0xd4, 0x7a, 0x04]), "4t7y-e");
+	assert_eq!(zbase32_decode(""), Some(vec![]));
+
+	let share : Share = "ops=3=8=2=a02282b29bd85113fd46ad=".parse().unwrap();
+	let text = ZBase32Codec.encode(&share);
+	assert_eq!(text, "ops=3=8=2=z:wyte-fcw5-5bet-89kg-iw=");
+	let registry = Registry::new();
+	assert_eq!(registry.decode(&text).unwrap(), share);
+	assert_eq!(registry.decode(&text.to_uppercase().replace("OPS", "ops")).unwrap(), share);
+	assert_eq!(registry.decode("ops=3=8=2=z:wytefcw5 5bet89kg iw=").unwrap(), share);
+	assert!(registry.decode("ops=3=8=2=z:wyte-0cw5=").is_err());
+    }
+
+    #[test]
+    fn rfc1751() {
+	// From RFC 1751 itself
+	let share : Share = "3=8=2=eb33f77ee73d4053=".parse().unwrap();
+	assert_eq!(Rfc1751Codec.encode(&share), "3=8=2=w8:TIDE ITCH SLOW REIN RULE MOT=");
+
+	let share : Share = "ops=3=8=2=a02282b29bd85113fd46ad=".parse().unwrap();
+	let text = Rfc1751Codec.encode(&share);
+	assert!(text.starts_with("ops=3=8=2=w11:"));
+	assert_eq!(text.split(' ').count(), 12);
+	let registry = Registry::new();
+	assert_eq!(registry.decode(&text).unwrap(), share);
+	assert_eq!(registry.decode(&text.to_lowercase().replace("  ", " ")).unwrap(), share);
+	// A wrong length, a misspelt word, or a word missing
+	assert!(registry.decode(&text.replace("w11:", "w17:")).is_err());
+	assert!(registry.decode(&text.replace("w11:", "w3:")).is_err());
+	let words : Vec<&str> = text.split(' ').collect();
+	assert!(registry.decode(&words[1..].join(" ")).is_err());
+	assert!(registry.decode("3=8=2=w8:TIDE ITCH SLOW REIN RULE MOM=").is_err());
+    }
+
+    #[test]
+    fn kubernetes() {
+	let shares : Vec<Share> = ["ops_1=3=8=2=a02282b29bd85113fd46ad=", "3=8=1=876e="]
+	    .iter().map(|s| s.parse().unwrap()).collect();
+	let text = KubernetesCodec.encode(&shares[0]);
+	assert_eq!(text, "---\napiVersion: v1\nkind: Secret\nmetadata:\n  \
+			  name: guff-ssss-ops-1-share-2\n  labels:\n    \
+			  guff-ssss/set-id: \"ops_1\"\n    guff-ssss/index: \"2\"\n    \
+			  guff-ssss/quorum: \"3\"\ntype: Opaque\ndata:\n  \
+			  share: b3BzXzE9Mz04PTI9YTAyMjgyYjI5YmQ4NTExM2ZkNDZhZD0=\n");
+	let registry = Registry::new();
+	assert_eq!(registry.decode(&text).unwrap(), shares[0]);
+
+	let stream : String = shares.iter().map(|s| KubernetesCodec.encode(s)).collect();
+	assert_eq!(decode_kubernetes_stream(&stream).unwrap(), shares);
+	assert!(KubernetesCodec.decode("kind: Secret\ndata:\n  share: !!!\n").is_err());
+	assert!(KubernetesCodec.decode("kind: Secret\ndata:\n  other: eA==\n").is_err());
+    }
+
+    // Share files saved on Windows (or mailed through a client that
+    // uses CRLF) read the same as ones written here
+    #[test]
+    fn crlf_line_endings() {
+	let shares : Vec<Share> = ["ops=3=8=1=876ec0b40cf66736af2848=", "ops=3=8=2=a02282b29bd85113fd46ad="]
+	    .iter().map(|s| s.parse().unwrap()).collect();
+	let crlf = |text : &str| text.replace('\n', "\r\n");
+	let registry = Registry::new();
+	for name in registry.names() {
+	    let text = crlf(&format!("{}\n", registry.encode(name, &shares[0]).unwrap().trim_end()));
+	    assert_eq!(registry.decode(&text).unwrap(), shares[0], "{}", name);
+	}
+	let stream = |codec : &dyn ShareCodec| crlf(&shares.iter().map(|s| codec.encode(s)).collect::<String>());
+	assert_eq!(decode_armor_stream(&stream(&ArmorCodec)).unwrap(), shares);
+	assert_eq!(decode_kubernetes_stream(&stream(&KubernetesCodec)).unwrap(), shares);
+	#[cfg(feature = "yaml")]
+	assert_eq!(decode_yaml_stream(&stream(&YamlCodec)).unwrap(), shares);
+	let wrapped = crlf(&crate::wrap_share(&shares[1].to_string(), 12));
+	assert_eq!(crate::join_continuations(wrapped.lines()), [shares[1].to_string()]);
+    }
+
+    #[test]
+    fn write_line() {
+	// Long enough to be written in several pieces
+	let long = Share { set_id : Some("big".into()), quorum : 2, width : 8, index : 1,
+			   values : (0..10000).map(|i| (i % 256) as u8).collect(), padded : false };
+	let registry = Registry::new();
+	for share in [&long, &"3=8=1=876ec0b40cf66736af2848=".parse().unwrap()] {
+	    for name in registry.names() {
+		let mut out = Vec::new();
+		registry.write_line(name, share, &mut out).unwrap();
+		let line = format!("{}\n", registry.encode(name, share).unwrap().trim_end());
+		assert!(out == line.as_bytes(), "{}", name);
+	    }
+	}
+	let mut out = Vec::new();
+	long.write_to(&mut out).unwrap();
+	assert_eq!(out, long.to_string().as_bytes());
+    }
+
+    #[test]
+    fn armor() {
+	let share : Share = "ops-1=3=8=2=a02282b29bd85113fd46ad=".parse().unwrap();
+	let text = ArmorCodec.encode(&share);
+	assert!(text.starts_with("BEGIN GUFF SSSS SHARE\nSet: ops-1\nQuorum: 3\nWidth: 8\n\
+				  Share: 2\n\na02282b29bd85113fd46ad\nCheck: "));
+	assert!(text.chars().all(|c| c.is_ascii_alphanumeric() || " :-\n".contains(c)));
+	let registry = Registry::new();
+	assert_eq!(registry.decode(&text).unwrap(), share);
+
+	let long = Share { values : vec![0xab; 40], ..share.clone() };
+	let text = ArmorCodec.encode(&long);
+	assert!(text.lines().all(|l| l.len() <= 64));
+	assert_eq!(ArmorCodec.decode(&text).unwrap(), long);
+
+	// Quoted twice in a reply, with the usual damage
+	let mangled : String = ArmorCodec.encode(&long).lines().map(|line| {
+	    let soft_break = if line.starts_with("abab") { "=" } else { "" };
+	    format!("> >\u{a0}{}{}  \r\n", line.replace("ops-1", "ops\u{2013}1")
+		    .replace("abababab", "abab\u{200b}abab"), soft_break)
+	}).collect();
+	let email = format!("Here's my share:\n\n{}\n-- \nAlice\n", mangled);
+	assert_eq!(decode_armor_stream(&email).unwrap(), [long]);
+
+	let tampered = ArmorCodec.encode(&share).replace("Share: 2", "Share: 3");
+	assert!(ArmorCodec.decode(&tampered).is_err());
+	assert!(ArmorCodec.decode("BEGIN GUFF SSSS SHARE\nQuorum: 3\n").is_err());
+
+	// A block checked with SHA-512 says so, and reads the same way
+	let text = HashedArmorCodec(Algorithm::Sha512).encode(&share);
+	assert!(text.contains("Share: 2\nHash: sha-512\n\n"));
+	assert_ne!(text, ArmorCodec.encode(&share));
+	assert_eq!(HashedArmorCodec(Algorithm::Sha256).encode(&share), ArmorCodec.encode(&share));
+	assert_eq!(registry.decode(&text).unwrap(), share);
+	assert!(ArmorCodec.decode(&text.replace("Hash: sha-512\n", "")).is_err());
+	assert!(ArmorCodec.decode(&text.replace("sha-512", "md5")).is_err());
+    }
+
+    #[test]
+    fn v2_lines() {
+	let share : Share = "ops=3=8=2=a02282b29bd85113fd46ad=".parse().unwrap();
+	let line = "ssss2:k=3,w=8,s=2,p=11b,id=ops,ck=24910412:a02282b29bd85113fd46ad";
+	assert_eq!(V2Codec.encode(&share), line);
+	let registry = Registry::new();
+	assert_eq!(registry.decode(line).unwrap(), share);
+	// Fields in any order, in any case, and ones this version
+	// doesn't know about are skipped
+	assert_eq!(registry.decode("SSSS2:ck=24910412,ID=ops,s=2,w=8,k=3,later=x:A02282B29BD85113FD46AD")
+		   .unwrap(), share);
+	let plain : Share = "3=8=2=a02282b29bd85113fd46ad=".parse().unwrap();
+	assert_eq!(registry.decode(&V2Codec.encode(&plain)).unwrap(), plain);
+
+	assert!(matches!(registry.decode(&line.replace("s=2", "s=3")), Err(Error::BadChecksum(_))));
+	assert!(matches!(registry.decode(&line.replace("ad", "ae")), Err(Error::BadChecksum(_))));
+	assert!(registry.decode(&line.replace(",ck=24910412", "")).is_err());
+	assert!(registry.decode(&line.replace("id=ops", "id=ops=3")).is_err());
+	assert!(matches!(registry.decode(&line.replace("p=11b", "p=11d")), Err(Error::FieldError(_))));
+	let newer = registry.decode(&line.replace("ssss2", "ssss3")).unwrap_err();
+	assert!(newer.to_string().contains("ssss3 (this build reads"));
+
+	let text = HashedV2Codec(Algorithm::Sha512).encode(&share);
+	assert!(text.contains(",h=sha-512,ck="));
+	assert_eq!((v2_hash(&text), v2_hash(line)), (Some(Algorithm::Sha512), Some(Algorithm::Sha256)));
+	assert_eq!(v2_hash("ops=3=8=2=a02282b29bd85113fd46ad="), None);
+	assert_eq!(registry.decode(&text).unwrap(), share);
+	assert!(registry.decode(&text.replace(",h=sha-512", "")).is_err());
+
+	#[cfg(feature = "width-16")]
+	{
+	    let wide = Share { width : 16, values : vec![1, 2, 3, 4], ..share.clone() };
+	    assert!(V2Codec.encode(&wide).contains(",w=16,s=2,p=1002b,"));
+	    assert_eq!(registry.decode(&V2Codec.encode(&wide)).unwrap(), wide);
+
+	    // The pad field is checked, and can't be added or taken away
+	    let padded = Share { padded : true, ..wide.clone() };
+	    let line = V2Codec.encode(&padded);
+	    assert!(line.contains(",pad=7816,"));
+	    assert_eq!(registry.decode(&line).unwrap(), padded);
+	    assert!(matches!(registry.decode(&line.replace(",pad=7816", "")), Err(Error::BadChecksum(_))));
+	    assert!(registry.decode(&line.replace("pad=7816", "pad=pkcs7")).is_err());
+	    assert!(matches!(registry.decode(&V2Codec.encode(&wide).replace(",ck=", ",pad=7816,ck=")),
+			     Err(Error::BadChecksum(_))));
+	}
+    }
+}
@@ -0,0 +1,240 @@
+//! Error-correcting reconstruction
+//!
+//! The shares of a split are the values of one polynomial of degree
+//! k - 1 at their share numbers, which makes them a Reed-Solomon code
+//! word. Given m shares with different numbers, the polynomial can
+//! still be found when up to (m - k) / 2 of them are wrong, and the
+//! wrong ones picked out by not lying on it. [combine_correcting] does
+//! that a word at a time with the Berlekamp-Welch algorithm. Where
+//! [pass 3](crate::pass_3) can only say that the shares don't agree,
+//! and [combine_majority](crate::combine_majority) has to try subsets
+//! of them, this says which shares are wrong and recovers the secret
+//! anyway.
+//!
+//! Each word takes a small linear system solved with ordinary field
+//! inversions, so this is slower than [combine](crate::combine), and
+//! there is no hardened version of it.
+
+use crate::width::{self, check_width, Elem, Width, U8};
+#[cfg(feature = "width-4")]
+use crate::width::U4;
+#[cfg(feature = "width-16")]
+use crate::width::U16;
+#[cfg(feature = "width-32")]
+use crate::width::U32;
+use crate::{Decoder, Error, Result, Share};
+use guff::GaloisField;
+use num_traits::{One, Zero};
+
+/// Outcome of [combine_correcting]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Correction {
+    pub secret : Vec<u8>,
+    /// Share numbers of the shares that were wrong, in the order they
+    /// were added (a number appears twice if both shares with it were)
+    pub corrupted : Vec<u64>,
+    /// The most wrong shares that could have been corrected
+    pub capacity : usize,
+}
+
+/// Reconstruct the secret from all the shares in `decoder`
+/// (including its spares), correcting up to (m - k) / 2 wrong ones
+/// among the m with different share numbers.
+///
+/// A spare that repeats the number of another share takes no part in
+/// the decoding, but it's checked against the answer like the rest.
+/// It's an error if too many shares are wrong to correct.
+pub fn combine_correcting(decoder : &Decoder) -> Result<Correction> {
+    if !decoder.is_complete() {
+	return Err(Error::InsufficientShares {
+	    have : decoder.have() as usize, need : decoder.quorum as usize
+	})
+    }
+    check_width(decoder.width)?;
+    let shares = decoder.all_shares();
+    let k = decoder.quorum as usize;
+    match decoder.width {
+	#[cfg(feature = "width-4")]
+	4  => correct::<U4>(&shares, k),
+	8  => correct::<U8>(&shares, k),
+	#[cfg(feature = "width-16")]
+	16 => correct::<U16>(&shares, k),
+	#[cfg(feature = "width-32")]
+	32 => correct::<U32>(&shares, k),
+	w => Err(width::unsupported(w)),
+    }
+}
+
+fn correct<U : Width>(shares : &[Share], k : usize) -> Result<Correction> {
+    let field = U::field();
+    // The first share with each number is a point to decode from
+    let mut points : Vec<usize> = Vec::new();
+    for (j, share) in shares.iter().enumerate() {
+	if !points.iter().any(|&p| shares[p].index == share.index) { points.push(j) }
+    }
+    if points.len() < k {
+	return Err(Error::InsufficientShares { have : points.len(), need : k })
+    }
+    let capacity = (points.len() - k) / 2;
+    let too_many = || Error::InconsistentShares(format!(
+	"more than {} of the {} shares are wrong, which is too many to correct",
+	capacity, points.len()));
+
+    let xs : Vec<Elem<U>> = shares.iter().map(|share| U::from_index(share.index)).collect();
+    let point_xs : Vec<Elem<U>> = points.iter().map(|&p| xs[p]).collect();
+    let bytes = shares[0].values.len();
+    let mut wrong = vec![false; shares.len()];
+    let mut secret = Vec::with_capacity(bytes);
+    let mut ys = Vec::with_capacity(points.len());
+    for i in 0..U::words(bytes) {
+	ys.clear();
+	ys.extend(points.iter().map(|&p| U::get(&shares[p].values, i)));
+	let poly = berlekamp_welch::<U>(&field, k, capacity, &point_xs, &ys).ok_or_else(too_many)?;
+	let mut errors = 0;
+	for (j, share) in shares.iter().enumerate() {
+	    if evaluate::<U>(&field, &poly, xs[j]) != U::get(&share.values, i) {
+		wrong[j] = true;
+		if points.contains(&j) { errors += 1 }
+	    }
+	}
+	if errors > capacity { return Err(too_many()) }
+	U::put(&mut secret, i, poly[0]);
+    }
+    let corrupted = shares.iter().zip(wrong).filter(|(_, wrong)| *wrong)
+	.map(|(share, _)| share.index).collect();
+    Ok(Correction { secret, corrupted, capacity })
+}
+
+// P(x), with P's coefficients lowest first
+fn evaluate<U : Width>(field : &U::Field, poly : &[Elem<U>], x : Elem<U>) -> Elem<U> {
+    poly.iter().rev().fold(Elem::<U>::zero(), |sum, &c| field.mul(sum, x) ^ c)
+}
+
+// Berlekamp-Welch for one word: find Q of degree < k + e and monic E
+// of degree e with
+//
+// Q(x_j) = y_j E(x_j)
+//
+// at every point, as a linear system in their coefficients (Q's
+// first, then all of E's but the leading 1), and return P = Q / E.
+// Any solution gives the same P if no more than e points are off it.
+// None means there's no solution, or that E doesn't divide Q, either
+// of which means more than e are.
+fn berlekamp_welch<U : Width>(field : &U::Field, k : usize, e : usize, xs : &[Elem<U>],
+			      ys : &[Elem<U>]) -> Option<Vec<Elem<U>>> {
+    let zero = Elem::<U>::zero();
+    let n = k + 2 * e;
+    // each row is the n coefficients, then the right hand side
+    let mut rows : Vec<Vec<Elem<U>>> = xs.iter().zip(ys).map(|(&x, &y)| {
+	let mut row = Vec::with_capacity(n + 1);
+	let mut power = Elem::<U>::one();
+	for _ in 0..k + e {
+	    row.push(power);
+	    power = field.mul(power, x);
+	}
+	let mut power = Elem::<U>::one();
+	for _ in 0..e {
+	    row.push(field.mul(y, power));
+	    power = field.mul(power, x);
+	}
+	// subtraction is XOR, so y E(x) moves across unchanged
+	row.push(field.mul(y, power));
+	row
+    }).collect();
+
+    // Gauss-Jordan elimination, leaving any free unknowns at zero
+    let mut pivots = Vec::with_capacity(n);
+    for col in 0..n {
+	let r = pivots.len();
+	let found = match (r..rows.len()).find(|&i| rows[i][col] != zero) {
+	    Some(found) => found,
+	    None => continue,
+	};
+	rows.swap(r, found);
+	let inv = field.inv(rows[r][col]);
+	for v in rows[r].iter_mut() { *v = field.mul(*v, inv) }
+	let pivot = rows[r].clone();
+	for (i, row) in rows.iter_mut().enumerate() {
+	    let factor = row[col];
+	    if i == r || factor == zero { continue }
+	    for (v, &p) in row.iter_mut().zip(pivot.iter()) { *v = *v ^ field.mul(factor, p) }
+	}
+	pivots.push(col);
+    }
+    if rows[pivots.len()..].iter().any(|row| row[n] != zero) { return None }
+    let mut solution = vec![zero; n];
+    for (row, &col) in rows.iter().zip(pivots.iter()) { solution[col] = row[n] }
+
+    // Long division of Q by E, which being monic needs no inversions
+    let (mut rem, e_low) = (solution[..k + e].to_vec(), &solution[k + e..]);
+    let mut quotient = vec![zero; k];
+    for d in (0..k).rev() {
+	let c = rem[d + e];
+	quotient[d] = c;
+	for (t, &ec) in e_low.iter().enumerate() { rem[d + t] = rem[d + t] ^ field.mul(c, ec) }
+	rem[d + e] = zero;
+    }
+    if rem.iter().any(|&r| r != zero) { None } else { Some(quotient) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{split_shares, Params};
+
+    fn decoder(shares : &[Share]) -> Decoder {
+	let mut decoder = Decoder::new();
+	for share in shares { decoder.add(share.clone()).unwrap(); }
+	decoder
+    }
+
+    #[test]
+    fn corrupted_shares_found() {
+	let secret = b"attack at dawn!!";
+	let mut widths = vec![8];
+	if cfg!(feature = "width-4") { widths.push(4) }
+	if cfg!(feature = "width-16") { widths.push(16) }
+	if cfg!(feature = "width-32") { widths.push(32) }
+	for w in widths {
+	    let shares = split_shares(secret, &Params::new(3, 7, w), |_, _| {}).unwrap();
+	    let clean = combine_correcting(&decoder(&shares)).unwrap();
+	    assert_eq!(clean, Correction { secret : secret.to_vec(), corrupted : vec![], capacity : 2 });
+
+	    // two wrong shares out of seven, one only in its last word
+	    let mut bad = shares.clone();
+	    for b in bad[1].values.iter_mut() { *b ^= 0x5a }
+	    *bad[5].values.last_mut().unwrap() ^= 1;
+	    let fixed = combine_correcting(&decoder(&bad)).unwrap();
+	    assert_eq!(fixed.secret, secret, "width {}", w);
+	    assert_eq!(fixed.corrupted, [2, 6]);
+
+	    // three is too many: never the right secret, whatever else
+	    for b in bad[3].values.iter_mut() { *b ^= 0xa5 }
+	    assert!(!matches!(combine_correcting(&decoder(&bad)), Ok(c) if c.secret == secret));
+	}
+    }
+
+    #[test]
+    fn repeated_numbers_and_small_quorums() {
+	let shares = split_shares(b"plain", &Params::new(1, 3, 8), |_, _| {}).unwrap();
+	let mut bad = shares.clone();
+	bad[0].values[0] ^= 1;
+	let fixed = combine_correcting(&decoder(&bad)).unwrap();
+	assert_eq!((fixed.secret.as_slice(), fixed.corrupted, fixed.capacity), (&b"plain"[..], vec![1], 1));
+
+	// a damaged copy of share 1 is found, wherever it is
+	let shares = split_shares(b"plain", &Params::new(2, 4, 8), |_, _| {}).unwrap();
+	let mut damaged = shares[0].clone();
+	damaged.values[2] ^= 0x10;
+	let mut added = shares.clone();
+	added.push(damaged.clone());
+	assert_eq!(combine_correcting(&decoder(&added)).unwrap().corrupted, [1]);
+	added.insert(0, damaged);
+	assert_eq!(combine_correcting(&decoder(&added)).unwrap().corrupted, [1]);
+
+	// with just a quorum there's nothing to correct with
+	assert_eq!(combine_correcting(&decoder(&shares[..2])).unwrap().capacity, 0);
+	assert!(matches!(combine_correcting(&decoder(&shares[..1])),
+			 Err(Error::InsufficientShares { .. })));
+    }
+}
@@ -0,0 +1,140 @@
+//! Who holds a share
+//!
+//! A share found in a safe years after the split doesn't say whose it
+//! is, or who has the others. A dealer can put a block of comment
+//! lines above each share to say so:
+//!
+//! ```text
+//! # Custodian: Alice Jones <alice@example.com>
+//! # Note: any 3 of the 5 shares recover the backup key
+//! # Note: share 40 is with Bob Smith <+44 20 7946 0000>
+//! 3=8=2=a02282b29bd85113fd46ad=
+//! ```
+//!
+//! Everything that reads shares skips comment lines, so the block
+//! takes no part in combining, and the share's fingerprint, checksum
+//! and manifest hash are the same with or without it. By the same
+//! token nothing protects it: anyone who can change the file can
+//! change the block, so it's a note on the envelope, not a record to
+//! rely on.
+
+use crate::error::{Error, Result};
+use std::fmt;
+
+/// A share's holder, with anything else whoever finds it should know
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Custodian {
+    pub name : String,
+    /// Email address, phone number or the like
+    pub contact : Option<String>,
+    pub notes : Vec<String>,
+}
+
+// A value has to fit on one comment line
+fn check_text(what : &str, text : &str) -> Result<()> {
+    if text.chars().any(char::is_control) {
+	return Err(Error::FieldError(format!("{} has a line break or control character in it", what)))
+    }
+    Ok(())
+}
+
+impl Custodian {
+    /// Parse `NAME` or `NAME <CONTACT>`, the way git writes authors
+    pub fn parse(spec : &str) -> Result<Custodian> {
+	let spec = spec.trim();
+	let (name, contact) = match spec.strip_suffix('>').and_then(|s| s.split_once('<')) {
+	    Some((name, contact)) => (name.trim(), Some(contact.trim())),
+	    None => (spec, None),
+	};
+	if name.is_empty() {
+	    return Err(Error::FieldError(format!("no custodian's name in \"{}\"", spec)))
+	}
+	check_text("a custodian's name", name)?;
+	check_text("a custodian's contact", contact.unwrap_or(""))?;
+	Ok(Custodian {
+	    name : name.to_string(),
+	    contact : contact.filter(|c| !c.is_empty()).map(str::to_string),
+	    notes : Vec::new(),
+	})
+    }
+
+    /// Add a note (a blank one is ignored)
+    pub fn with_note(mut self, note : &str) -> Result<Custodian> {
+	check_text("a note", note)?;
+	if !note.trim().is_empty() { self.notes.push(note.trim().to_string()) }
+	Ok(self)
+    }
+
+    /// The block of comment lines, each ending in a newline
+    pub fn comments(&self) -> String {
+	let mut block = format!("# Custodian: {}\n", self);
+	for note in self.notes.iter() {
+	    block += &format!("# Note: {}\n", note);
+	}
+	block
+    }
+
+    /// The block directly above the first share in `text`, if there
+    /// is one. Blank lines and other comments may come between the
+    /// block's lines, and a `---` (from a stream of YAML documents)
+    /// between the block and the share.
+    pub fn read(text : &str) -> Option<Custodian> {
+	let mut custodian : Option<Custodian> = None;
+	for line in text.lines().map(str::trim) {
+	    let comment = match line.strip_prefix('#') {
+		Some(comment) => comment,
+		None if line.is_empty() || line == "---" => continue,
+		None => break,
+	    };
+	    let (key, value) = match comment.split_once(':') {
+		Some((key, value)) => (key.trim().to_ascii_lowercase(), value.trim()),
+		None => continue,
+	    };
+	    match (key.as_str(), custodian.as_mut()) {
+		("custodian", _) => custodian = Custodian::parse(value).ok(),
+		("note", Some(custodian)) if !value.is_empty() => custodian.notes.push(value.to_string()),
+		_ => {},
+	    }
+	}
+	custodian
+    }
+}
+
+impl fmt::Display for Custodian {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match &self.contact {
+	    Some(contact) => write!(f, "{} <{}>", self.name, contact),
+	    None => write!(f, "{}", self.name),
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::Registry;
+
+    #[test]
+    fn comment_block() {
+	let alice = Custodian::parse(" Alice Jones <alice@example.com> ").unwrap()
+	    .with_note("any 3 of 5").unwrap()
+	    .with_note(" ").unwrap();
+	assert_eq!(alice.name, "Alice Jones");
+	assert_eq!(alice.contact.as_deref(), Some("alice@example.com"));
+	assert_eq!(alice.comments(),
+		   "# Custodian: Alice Jones <alice@example.com>\n# Note: any 3 of 5\n");
+	assert_eq!(Custodian::parse("Bob").unwrap().to_string(), "Bob");
+	assert_eq!(Custodian::parse("Bob <>").unwrap().contact, None);
+	assert!(Custodian::parse(" <bob@example.com>").is_err());
+	assert!(Custodian::parse("Bob\nSmith").is_err());
+	assert!(alice.clone().with_note("two\nlines").is_err());
+
+	// The block is read back, and doesn't get in the way of the share
+	let text = format!("# shares of the backup key\n{}---\n3=8=2=a02282b29bd85113fd46ad=\n\
+			    # Custodian: Bob\n", alice.comments());
+	assert_eq!(Custodian::read(&text), Some(alice));
+	assert_eq!(Custodian::read("3=8=2=a02282b29bd85113fd46ad=\n# Custodian: Bob\n"), None);
+	let share = text.lines().find(|l| !l.starts_with('#') && *l != "---").unwrap();
+	assert_eq!(Registry::new().decode(share).unwrap().index, 2);
+    }
+}
@@ -0,0 +1,166 @@
+//! Dealer-less generation
+//!
+//! For ceremonies where nobody should ever hold the whole secret, not
+//! even for the moment it takes to split it. Each of the n parties
+//! picks a random contribution of its own, splits it k-of-n and sends
+//! share j to party j ([deal]). Once a party has a [Deal] from every
+//! party (its own included) it adds them up ([finish]). Addition in
+//! GF(2<sup>w</sup>) is XOR, and the sum of everyone's share j is
+//! share j of the sum of the contributions, which is the secret.
+//!
+//! Nobody ever knew that secret: fewer than k parties learn nothing
+//! about it so long as any one of the others picked its contribution
+//! at random and kept it to itself.
+//!
+//! There's no verification. A party that sends shares which don't lie
+//! on one polynomial goes unnoticed until shares are combined, so
+//! it's worth combining with more than a quorum of shares
+//! (`shamir-combine --majority`) before putting the secret to use.
+//!
+//! Deals travel as one line of text, addressed by the share number:
+//!
+//! ```text
+//! dkg from F: I=K=W=S=Values=
+//! ```
+
+use crate::error::{Error, Result};
+use crate::{split_with_params, Params, Share};
+use rand::RngCore;
+use std::collections::HashSet;
+
+const PREFIX : &str = "dkg from ";
+
+/// One party's share for another party (share number `share.index`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Deal {
+    /// The dealing party
+    pub from  : u16,
+    pub share : Share,
+}
+
+impl std::fmt::Display for Deal {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	write!(f, "{}{}: {}", PREFIX, self.from, self.share)
+    }
+}
+
+impl std::str::FromStr for Deal {
+    type Err = Error;
+
+    fn from_str(line : &str) -> Result<Deal> {
+	let bad = || Error::UnknownFormat(format!("dkg (not a deal: {})", line.trim()));
+	let (from, share) = line.trim().strip_prefix(PREFIX)
+	    .and_then(|rest| rest.split_once(": ")).ok_or_else(bad)?;
+	Ok(Deal {
+	    from : from.parse().map_err(|_| bad())?,
+	    share : share.parse().map_err(|error| Error::ParseError { line : 1, error })?,
+	})
+    }
+}
+
+/// Deal party `from`'s random contribution of `length` bytes, one
+/// [Deal] for each party in order of share number. Every party must
+/// use the same `params` (set ID included) and `length`.
+pub fn deal(params : &Params, from : u16, length : usize) -> Result<Vec<Deal>> {
+    params.check()?;
+    if from < 1 || from > params.shares {
+	return Err(Error::FieldError(format!("party {} isn't one of 1 to {}", from,
+					     params.shares)))
+    }
+    let mut contribution = vec![0u8; length];
+    rand::thread_rng().fill_bytes(&mut contribution);
+    let deals = deal_secret(params, from, &contribution);
+    contribution.iter_mut().for_each(|b| *b = 0);
+    deals
+}
+
+// Deal a given contribution (zero, for a refresh)
+pub(crate) fn deal_secret(params : &Params, from : u16, secret : &[u8]) -> Result<Vec<Deal>> {
+    split_with_params(secret, params, |_, _| {})?.iter().map(|line| Ok(Deal {
+	from,
+	share : line.parse().map_err(|error| Error::ParseError { line : 1, error })?,
+    })).collect()
+}
+
+/// Add up the deals sent to one party by all `parties` parties, giving
+/// that party's share of the jointly generated secret
+pub fn finish(deals : &[Deal], parties : u16) -> Result<Share> {
+    let first = match deals.first() {
+	Some(deal) => &deal.share,
+	None => return Err(Error::InsufficientShares { have : 0, need : parties as usize }),
+    };
+    let inconsistent = |what : &str| Err(Error::InconsistentShares(what.to_string()));
+    let mut seen = HashSet::new();
+    let mut values = vec![0u8; first.values.len()];
+    for deal in deals {
+	let share = &deal.share;
+	if deal.from < 1 || deal.from > parties {
+	    return Err(Error::FieldError(format!("deal from party {}, who isn't one of 1 to {}",
+						 deal.from, parties)))
+	}
+	if !seen.insert(deal.from) {
+	    return inconsistent(&format!("two deals from party {}", deal.from))
+	}
+	if share.index != first.index {
+	    return inconsistent("deals are for different parties")
+	}
+	if share.quorum != first.quorum || share.width != first.width
+	    || share.set_id != first.set_id || share.values.len() != values.len() {
+	    return inconsistent("deals made with different parameters")
+	}
+	values.iter_mut().zip(share.values.iter()).for_each(|(v, s)| *v ^= s);
+    }
+    if first.index < 1 || first.index > parties as u64 {
+	return inconsistent(&format!("deals are for share {}, but there are {} parties",
+				     first.index, parties))
+    }
+    if seen.len() < parties as usize {
+	return Err(Error::InsufficientShares { have : seen.len(), need : parties as usize })
+    }
+    Ok(Share { values, ..first.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{combine_decoder, Decoder};
+
+    #[test]
+    fn dealerless() {
+	let params = Params::new(2, 3, 8).with_set_id("dkg1");
+	let deals : Vec<Vec<Deal>> = (1..=3).map(|from| deal(&params, from, 16).unwrap())
+	    .collect();
+	let deal_line = deals[1][2].to_string();
+	assert!(deal_line.starts_with("dkg from 2: dkg1=2=8=3="));
+	assert_eq!(deal_line.parse::<Deal>().unwrap(), deals[1][2]);
+
+	// Party j gets deal j from everyone
+	let shares : Vec<Share> = (0..3).map(|j| {
+	    let mine : Vec<Deal> = deals.iter().map(|d| d[j].clone()).collect();
+	    finish(&mine, 3).unwrap()
+	}).collect();
+
+	// Any quorum gives the XOR of the contributions
+	let mut expected = vec![0u8; 16];
+	for from in deals.iter() {
+	    let mut decoder = Decoder::new();
+	    for d in from[..2].iter() { decoder.add(d.share.clone()).unwrap(); }
+	    let contribution = combine_decoder(&mut decoder, false).unwrap();
+	    expected.iter_mut().zip(contribution).for_each(|(e, c)| *e ^= c);
+	}
+	for pair in [[0, 1], [1, 2], [0, 2]] {
+	    let mut decoder = Decoder::new();
+	    for &i in pair.iter() { decoder.add(shares[i].clone()).unwrap(); }
+	    assert_eq!(combine_decoder(&mut decoder, false).unwrap(), expected);
+	}
+
+	// Missing, repeated or misaddressed deals
+	let mine : Vec<Deal> = deals.iter().map(|d| d[0].clone()).collect();
+	assert!(finish(&mine[..2], 3).is_err());
+	assert!(finish(&[mine[0].clone(), mine[0].clone(), mine[1].clone()], 3).is_err());
+	assert!(finish(&[mine[0].clone(), mine[1].clone(), deals[2][1].clone()], 3).is_err());
+	assert!(deal(&params, 4, 16).is_err());
+	assert!("dkg from x: 2=8=1=00=".parse::<Deal>().is_err());
+    }
+}
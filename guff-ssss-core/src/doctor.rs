@@ -0,0 +1,346 @@
+//! Working out why a recovery failed
+//!
+//! When shares won't combine, the error from the [Decoder] names the
+//! first thing that went wrong, which is rarely all that's wrong, and
+//! not always the real cause. [diagnose] looks at every share offered
+//! at once and lists each problem it can find, with what to do about
+//! it:
+//!
+//! * lines that aren't shares, and whether a likely copying mistake
+//!   (a letter O for a zero, a space, a missing `=`) would fix them
+//! * shares from more than one split, or that disagree on the field
+//!   width, quorum or length (one odd length out usually means a
+//!   dropped or doubled digit)
+//! * two different shares with the same number
+//! * too few shares for the quorum
+//! * with spares, shares that the rest outvote (see
+//!   [combine_majority])
+//! * with a [Manifest], shares the dealer didn't hand out
+
+use crate::codec::Registry;
+use crate::manifest::Manifest;
+use crate::{combine_majority, parse_share, Decoder, Share};
+
+// Subsets to try when looking for a share the others outvote
+const VOTE_BUDGET : usize = 100;
+
+/// One problem found by [diagnose]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// Input `line` (counting from 1) isn't a share in any known
+    /// format. `repair`, if there is one, is the line with likely
+    /// copying mistakes fixed, and what was changed.
+    Unreadable { line : usize, text : String, error : String, repair : Option<(String, String)> },
+    /// Shares from more than one split, with the share numbers from
+    /// each
+    MixedSets { sets : Vec<(Option<String>, Vec<u64>)> },
+    /// Shares of one set that don't agree on `what` (width, quorum or
+    /// length), with the share numbers having each value
+    Mismatched { set : Option<String>, what : &'static str, values : Vec<(String, Vec<u64>)> },
+    /// Two or more shares numbered `index`. If they're `identical`,
+    /// that's harmless; if not, at most one of them is right.
+    Duplicate { set : Option<String>, index : u64, identical : bool },
+    /// A share the decoder won't take at all (eg, an unsupported
+    /// width, or a share number out of range)
+    Rejected { set : Option<String>, index : u64, error : String },
+    /// Fewer distinct shares than the quorum
+    TooFew { set : Option<String>, have : usize, need : u16 },
+    /// Subsets of the shares give different secrets. `suspects` are
+    /// the shares in every subset that disagreed with the majority
+    /// (empty if there's no majority to go by).
+    Disagreeing { set : Option<String>, suspects : Vec<u64> },
+    /// A share that doesn't match the manifest's record of it
+    Unlisted { set : Option<String>, index : u64 },
+}
+
+fn set_name(set : &Option<String>) -> String {
+    match set {
+	Some(id) => format!("set {}", id),
+	None => "the shares without a set ID".to_string(),
+    }
+}
+
+fn list(indices : &[u64]) -> String {
+    indices.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    Finding::Unreadable { line, text, error, .. } =>
+		write!(f, "Line {} isn't a share ({}): {}", line, error, text),
+	    Finding::MixedSets { sets } => {
+		let sets : Vec<String> = sets.iter()
+		    .map(|(set, indices)| format!("{} (share {})", set_name(set), list(indices))).collect();
+		write!(f, "The shares are from {} different splits: {}", sets.len(), sets.join("; "))
+	    },
+	    Finding::Mismatched { set, what, values } => {
+		let values : Vec<String> = values.iter()
+		    .map(|(value, indices)| format!("{} (share {})", value, list(indices))).collect();
+		write!(f, "Shares in {} have different {}s: {}", set_name(set), what, values.join("; "))
+	    },
+	    Finding::Duplicate { set, index, identical : true } =>
+		write!(f, "Share {} of {} is given more than once", index, set_name(set)),
+	    Finding::Duplicate { set, index, identical : false } =>
+		write!(f, "There are different shares numbered {} in {}", index, set_name(set)),
+	    Finding::Rejected { set, index, error } =>
+		write!(f, "Share {} of {} can't be used: {}", index, set_name(set), error),
+	    Finding::TooFew { set, have, need } =>
+		write!(f, "Only {} of the {} shares needed for {}", have, need, set_name(set)),
+	    Finding::Disagreeing { set, suspects } if suspects.is_empty() =>
+		write!(f, "Shares in {} give different secrets, with no majority", set_name(set)),
+	    Finding::Disagreeing { set, suspects } =>
+		write!(f, "Shares in {} give different secrets; the odd one out is share {}",
+		       set_name(set), list(suspects)),
+	    Finding::Unlisted { set, index } =>
+		write!(f, "Share {} of {} doesn't match the manifest", index, set_name(set)),
+	}
+    }
+}
+
+impl Finding {
+    /// What to try next
+    pub fn advice(&self) -> String {
+	match self {
+	    Finding::Unreadable { repair : Some((fixed, what)), .. } =>
+		format!("It reads as a share if {}: {}", what, fixed),
+	    Finding::Unreadable { .. } =>
+		"Check it against the original; if it isn't meant to be a share, leave it out".into(),
+	    Finding::MixedSets { .. } =>
+		"Combine each set on its own; a share only helps recover its own set's secret".into(),
+	    Finding::Mismatched { what : "length", .. } =>
+		"The share with the odd length out was probably mis-copied (a digit dropped or \
+		 doubled); check it against its original".into(),
+	    Finding::Mismatched { .. } =>
+		"These can't all be from the same split; check where each came from, and leave \
+		 out the odd ones".into(),
+	    Finding::Duplicate { identical : true, .. } =>
+		"Nothing to do: the copies are the same, and only one is used".into(),
+	    Finding::Duplicate { index, .. } =>
+		format!("At most one share {} is right; check each against its backup", index),
+	    Finding::Rejected { .. } =>
+		"Check the share's numbers against its original, or rebuild with the field width \
+		 it needs".into(),
+	    Finding::TooFew { have, need, .. } =>
+		format!("Find {} more share(s) of the set, with numbers not already here",
+			*need as usize - have),
+	    Finding::Disagreeing { suspects, .. } if suspects.is_empty() =>
+		"Add more shares, or check each one against its backup".into(),
+	    Finding::Disagreeing { .. } =>
+		"Check the suspect against its backup, or leave it out".into(),
+	    Finding::Unlisted { .. } =>
+		"Either it was mis-copied or it's from another split; check it against its \
+		 original".into(),
+	}
+    }
+}
+
+// Characters easily mistaken for digits when copying
+const LOOK_ALIKES : [(char, char); 8] =
+    [('O', '0'), ('o', '0'), ('I', '1'), ('l', '1'), ('i', '1'), ('S', '5'), ('s', '5'), ('G', '6')];
+
+// The line with common copying mistakes undone, if that makes it a
+// share, and what was changed
+fn repair(line : &str) -> Option<(String, String)> {
+    let mut fixed = line.trim().to_string();
+    let mut what = Vec::new();
+    if fixed.chars().any(char::is_whitespace) {
+	fixed.retain(|c| !c.is_whitespace());
+	what.push("the spaces are taken out".to_string());
+    }
+    // Only the numbers and values: a set ID can be any letters
+    let fields : Vec<&str> = fixed.split('=').collect();
+    let skip = fields.len().saturating_sub(if fixed.ends_with('=') { 5 } else { 4 });
+    let mut changed = Vec::new();
+    let numbers : Vec<String> = fields.iter().enumerate().map(|(i, field)| {
+	if i < skip { return field.to_string() }
+	field.chars().map(|c| match LOOK_ALIKES.iter().find(|(from, _)| *from == c) {
+	    Some(&(from, to)) => {
+		if !changed.contains(&(from, to)) { changed.push((from, to)) }
+		to
+	    },
+	    None => c,
+	}).collect()
+    }).collect();
+    fixed = numbers.join("=");
+    for (from, to) in changed {
+	what.push(format!("'{}' is read as '{}'", from, to));
+    }
+    if !fixed.ends_with('=') {
+	fixed.push('=');
+	what.push("a final '=' is added".to_string());
+    }
+    match (what.is_empty(), parse_share(&fixed)) {
+	(false, Ok(_)) => Some((fixed, what.join(" and "))),
+	_ => None,
+    }
+}
+
+// A property shares of one set should agree on, as text
+type Key = fn(&Share) -> String;
+
+// Group share numbers by some property, in the order first seen
+fn tally(shares : &[Share], key : Key) -> Vec<(String, Vec<u64>)> {
+    let mut values : Vec<(String, Vec<u64>)> = Vec::new();
+    for share in shares.iter() {
+	let value = key(share);
+	match values.iter_mut().find(|(v, _)| *v == value) {
+	    Some((_, indices)) => indices.push(share.index),
+	    None => values.push((value, vec![share.index])),
+	}
+    }
+    values
+}
+
+// Everything wrong with the shares of one set
+fn diagnose_set(set : &Option<String>, shares : &[Share], manifest : Option<&Manifest>,
+		findings : &mut Vec<Finding>) {
+    let mut mismatched = false;
+    let keys : [(&'static str, Key); 3] = [
+	("width", |s| s.width.to_string()),
+	("quorum", |s| s.quorum.to_string()),
+	("length", |s| format!("{} hex digits", 2 * s.values.len())),
+    ];
+    for (what, key) in keys.iter() {
+	let values = tally(shares, *key);
+	if values.len() > 1 {
+	    findings.push(Finding::Mismatched { set : set.clone(), what, values });
+	    mismatched = true;
+	}
+    }
+
+    // One of each share number, noting any repeats
+    let mut unique : Vec<Share> = Vec::new();
+    for share in shares.iter() {
+	match unique.iter().find(|s| s.index == share.index) {
+	    None => unique.push(share.clone()),
+	    Some(first) => {
+		let identical = first == share;
+		let finding = Finding::Duplicate { set : set.clone(), index : share.index, identical };
+		if !findings.contains(&finding) { findings.push(finding) }
+	    },
+	}
+    }
+
+    if let Some(manifest) = manifest {
+	for share in shares.iter().filter(|s| manifest.check_share(s).is_err()) {
+	    findings.push(Finding::Unlisted { set : set.clone(), index : share.index });
+	}
+    }
+    if mismatched { return }
+
+    let mut decoder = Decoder::new();
+    for share in unique.iter() {
+	if let Err(e) = decoder.add(share.clone()) {
+	    findings.push(Finding::Rejected { set : set.clone(), index : share.index,
+					      error : e.to_string() });
+	}
+    }
+    let need = manifest.map_or(shares[0].quorum, |m| m.quorum);
+    let have = decoder.all_shares().len();
+    if decoder.have() == 0 { return }
+    if have < need as usize {
+	findings.push(Finding::TooFew { set : set.clone(), have, need });
+    } else if have > need as usize {
+	match combine_majority(&decoder, false, VOTE_BUDGET) {
+	    Ok(vote) if vote.is_degraded() =>
+		findings.push(Finding::Disagreeing { set : set.clone(), suspects : vote.suspects() }),
+	    Ok(_) => {},
+	    Err(_) => findings.push(Finding::Disagreeing { set : set.clone(), suspects : Vec::new() }),
+	}
+    }
+}
+
+/// Everything wrong with `lines`, a pile of shares that wouldn't
+/// combine. Each line is a share in any format the [Registry] knows.
+/// With the `manifest` the shares were split with, each share is also
+/// checked against it. Nothing found means the shares look fine: if
+/// the secret still comes out wrong, the likely cause is exactly a
+/// quorum of shares with one wrong, which only a manifest (or more
+/// shares) can show.
+pub fn diagnose(lines : &[&str], manifest : Option<&Manifest>) -> Vec<Finding> {
+    let registry = Registry::new();
+    let mut findings = Vec::new();
+    let mut shares : Vec<Share> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+	match parse_share(line) {
+	    Ok(share) => shares.push(share),
+	    Err(error) => match registry.decode(line) {
+		Ok(share) => shares.push(share),
+		Err(_) => findings.push(Finding::Unreadable {
+		    line : i + 1, text : line.trim().to_string(), error : error.to_string(),
+		    repair : repair(line),
+		}),
+	    },
+	}
+    }
+
+    let mut sets : Vec<(Option<String>, Vec<Share>)> = Vec::new();
+    for share in shares.into_iter() {
+	match sets.iter_mut().find(|(id, _)| *id == share.set_id) {
+	    Some((_, members)) => members.push(share),
+	    None => sets.push((share.set_id.clone(), vec![share])),
+	}
+    }
+    if sets.len() > 1 {
+	findings.push(Finding::MixedSets {
+	    sets : sets.iter().map(|(id, members)| {
+		(id.clone(), members.iter().map(|s| s.index).collect())
+	    }).collect()
+	});
+    }
+    for (set, members) in sets.iter() {
+	diagnose_set(set, members, manifest, &mut findings);
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO : [&str; 5] = [
+	"3=8=1=876ec0b40cf66736af2848=",
+	"3=8=2=a02282b29bd85113fd46ad=",
+	"3=8=3=4f292e6af80e414a200281=",
+	"3=8=4=d7b4290454ec2382cf869f=",
+	"3=8=5=38bf85dc373a33db12c2b3=",
+    ];
+
+    #[test]
+    fn diagnoses() {
+	assert!(diagnose(&HELLO, None).is_empty());
+
+	// Copying mistakes: a letter O, a space and no final '='
+	let findings = diagnose(&["3=8=1=876ec0b4Ocf66736af2848=", "3=8=2=a02282b29bd8 5113fd46ad",
+				  "3=8=3=4f292e6af80e414a200281="], None);
+	assert!(matches!(&findings[0], Finding::Unreadable { line : 1, repair : Some((fixed, _)), .. }
+			 if fixed == HELLO[0]));
+	assert!(matches!(&findings[1], Finding::Unreadable { line : 2, repair : Some((fixed, what)), .. }
+			 if fixed == HELLO[1] && what.contains("spaces") && what.contains("'='")));
+	assert_eq!(findings[2], Finding::TooFew { set : None, have : 1, need : 3 });
+	assert!(findings[2].advice().contains("Find 2 more"));
+
+	// A dropped digit, a share of another split, and a repeat
+	let findings = diagnose(&[HELLO[0], "3=8=2=a02282b29bd8113fd46ad=", "x=3=8=3=4f29=",
+				  HELLO[0]], None);
+	assert!(matches!(&findings[0], Finding::Unreadable { line : 2, repair : None, .. }));
+	assert!(matches!(&findings[1], Finding::MixedSets { sets } if sets.len() == 2));
+	assert_eq!(findings[2], Finding::Duplicate { set : None, index : 1, identical : true });
+
+	let mut long = HELLO.to_vec();
+	long[3] = "3=8=4=d7b4290454ec2382cf869f00=";
+	let findings = diagnose(&long, None);
+	assert!(matches!(&findings[0], Finding::Mismatched { what : "length", values, .. }
+			 if values[1].1 == [4]));
+
+	// One damaged share among spares is outvoted
+	let mut damaged = HELLO.to_vec();
+	damaged[1] = "3=8=2=a02282b29bd85113fd46ae=";
+	assert_eq!(diagnose(&damaged, None),
+		   [Finding::Disagreeing { set : None, suspects : vec![2] }]);
+	damaged.push("3=8=2=a02282b29bd85113fd46ad=");
+	assert!(diagnose(&damaged, None).contains(
+	    &Finding::Duplicate { set : None, index : 2, identical : false }));
+    }
+}
@@ -0,0 +1,114 @@
+//! Error types
+//!
+//! Everything fallible in the library returns [enum@Error], so callers can
+//! tell malformed input apart from shares that simply don't go
+//! together, or from there not being enough of them.
+
+use thiserror::Error;
+
+/// Problems with the text of a single share line
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("wrong number of fields")]
+    FieldCount,
+    #[error("bad {0} value")]
+    BadNumber(&'static str),
+    #[error("bad set ID {0}")]
+    BadSetId(String),
+    #[error("trailing data after final '='")]
+    TrailingData,
+    #[error("hex data {0} is not a multiple of field width")]
+    BadLength(String),
+    #[error("hex data {0} missing final (padding) nibble")]
+    MissingPadding(String),
+    #[error("problem with hex conversion for {0}")]
+    BadHex(String),
+}
+
+/// The crate-level error type
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A share line couldn't be parsed. `line` counts the shares
+    /// offered to the decoder so far, starting from 1.
+    #[error("Line {line}: {error}")]
+    ParseError { line : usize, #[source] error : ParseError },
+
+    /// A parameter is out of range for the field: unsupported width,
+    /// quorum or share index too large, and so on
+    #[error("{0}")]
+    FieldError(String),
+
+    /// Fewer shares than the quorum
+    #[error("not enough shares: have {have} of {need}")]
+    InsufficientShares { have : usize, need : usize },
+
+    /// Shares that parse individually but don't belong together
+    /// (different quorum, width, length or set ID), or that can't
+    /// be solved for
+    #[error("{0}")]
+    InconsistentShares(String),
+
+    /// No share codec by that name, or none that recognises the
+    /// text it was given
+    #[error("unknown share format {0}")]
+    UnknownFormat(String),
+
+    /// A share in a format that was recognised, but whose checksum
+    /// doesn't match: it was mistyped or damaged
+    #[error("bad {0} share: checksum doesn't match; the share has been altered or mistyped")]
+    BadChecksum(String),
+
+    /// A public verification record that can't be read
+    #[error("bad manifest: {0}")]
+    BadManifest(String),
+
+    /// Shares, a secret or a signature that don't match their
+    /// verification record
+    #[error("verification failed: {0}")]
+    Verification(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// The exit status a program should stop with for this error, so
+    /// that scripts can tell the classes of failure apart. 1 is left
+    /// for failures that aren't library errors.
+    ///
+    /// * 2: malformed input ([ParseError](Error::ParseError),
+    ///   [UnknownFormat](Error::UnknownFormat),
+    ///   [BadChecksum](Error::BadChecksum))
+    /// * 3: parameters out of range ([FieldError](Error::FieldError))
+    /// * 4: not enough shares
+    /// * 5: shares that don't go together
+    /// * 6: a bad or unmatched verification record
+    /// * 7: I/O
+    pub fn exit_code(&self) -> i32 {
+	match self {
+	    Error::ParseError { .. } | Error::UnknownFormat(_) | Error::BadChecksum(_) => 2,
+	    Error::FieldError(_) => 3,
+	    Error::InsufficientShares { .. } => 4,
+	    Error::InconsistentShares(_) => 5,
+	    Error::BadManifest(_) | Error::Verification(_) => 6,
+	    Error::Io(_) => 7,
+	}
+    }
+}
+
+/// Shorthand used throughout the crate
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+	let parse = Error::ParseError { line : 2, error : ParseError::FieldCount };
+	assert_eq!(parse.to_string(), "Line 2: wrong number of fields");
+	assert_eq!(parse.exit_code(), 2);
+	assert_eq!(Error::InsufficientShares { have : 1, need : 3 }.exit_code(), 4);
+	assert_eq!(Error::Io(std::io::ErrorKind::NotFound.into()).exit_code(), 7);
+    }
+}
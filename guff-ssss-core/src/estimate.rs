@@ -0,0 +1,158 @@
+//! How big shares will be, before splitting
+//!
+//! Planning a split means knowing how much paper, how many QR codes
+//! or how much storage the shares will take. A share's length depends
+//! only on the parameters, its number and the secret's length, not on
+//! what the secret is, so [estimate] can work it out exactly without
+//! splitting anything. The exceptions are the `rfc1751` encoding,
+//! whose words are one to four letters long, and `yaml`, which quotes
+//! values that happen to look like numbers: for those the figure is
+//! the most a share can take.
+//!
+//! The QR version is the smallest that holds the share's text form
+//! (which is what `shamir-reencode --qr` draws) as bytes, at error
+//! correction level M. A QR encoder can sometimes fit the digits in a
+//! share into fewer bits, so this is a version that is sure to be
+//! enough.
+
+use crate::codec::ShareCodec;
+use crate::error::Result;
+use crate::{Params, Share};
+use std::io::{self, Write};
+
+/// The estimate for one share
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShareSize {
+    pub index : u64,
+    /// Bytes written in the chosen encoding, including the newline
+    pub bytes : usize,
+    /// Version (1 to 40) of the QR code needed, or `None` if the
+    /// share won't fit in a QR code at all
+    pub qr_version : Option<u8>,
+}
+
+/// Sizes of every share of a split
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Estimate {
+    pub shares : Vec<ShareSize>,
+    /// False if the sizes are upper bounds
+    pub exact : bool,
+}
+
+impl Estimate {
+    /// Total bytes of all the shares
+    pub fn total(&self) -> usize {
+	self.shares.iter().map(|share| share.bytes).sum()
+    }
+}
+
+// Bytes a QR code of each version holds in byte mode, at error
+// correction level M (ISO/IEC 18004 table 7)
+const QR_BYTES_M : [usize; 40] = [
+    14, 26, 42, 62, 84, 106, 122, 152, 180, 213,
+    251, 287, 331, 362, 412, 450, 504, 560, 624, 666,
+    711, 779, 857, 911, 997, 1059, 1125, 1190, 1264, 1370,
+    1452, 1538, 1628, 1722, 1809, 1911, 1989, 2099, 2213, 2331,
+];
+
+/// The smallest QR code version that holds `bytes` bytes at error
+/// correction level M, if any does
+pub fn qr_version(bytes : usize) -> Option<u8> {
+    QR_BYTES_M.iter().position(|&capacity| capacity >= bytes).map(|v| v as u8 + 1)
+}
+
+// Counts what's written to it, so that long shares are never held
+// in memory
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write(&mut self, buf : &[u8]) -> io::Result<usize> {
+	self.0 += buf.len();
+	Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+// The most an rfc1751 share can take, given one encoding of it:
+// every word could have been four letters
+fn rfc1751_most(encoded : &str) -> usize {
+    let words = encoded.rsplit(':').next().unwrap_or("").trim_end().trim_end_matches('=');
+    let letters : usize = words.split(' ').map(str::len).sum();
+    encoded.trim_end().len() + 1 - letters + 4 * words.split(' ').count()
+}
+
+/// Sizes of the shares of a secret `secret_len` bytes long (after
+/// any padding) split with `params` and written with `codec`
+pub fn estimate(params : &Params, secret_len : usize, codec : &dyn ShareCodec) -> Result<Estimate> {
+    params.check()?;
+    let exact = !matches!(codec.name(), "rfc1751" | "yaml");
+    let secret = vec![0; secret_len];
+    let padded = match params.word_padding {
+	true => crate::pad_words(&secret, params.width),
+	false => None,
+    };
+    let shares = (1..=params.shares as u64).map(|index| {
+	let share = Share {
+	    set_id : params.set_id.clone(), quorum : params.quorum, width : params.width,
+	    index, values : padded.clone().unwrap_or_else(|| secret.clone()),
+	    padded : padded.is_some(),
+	};
+	let bytes = if exact {
+	    let mut counter = Counter(0);
+	    codec.write_line(&share, &mut counter)?;
+	    counter.0
+	} else if codec.name() == "rfc1751" {
+	    rfc1751_most(&codec.encode(&share))
+	} else {
+	    // Values of all zeros look like a number, so they're quoted
+	    codec.encode(&share).trim_end().len() + 1
+	};
+	let mut text = Counter(0);
+	share.write_to(&mut text)?;
+	Ok(ShareSize { index, bytes, qr_version : qr_version(text.0) })
+    }).collect::<Result<Vec<_>>>()?;
+    Ok(Estimate { shares, exact })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::Registry;
+    use crate::split_shares;
+
+    #[test]
+    fn sizes() {
+	let registry = Registry::new();
+	let params = Params::new(3, 12, 8).with_set_id("ops");
+	let secret = b"a secret that's 32 bytes long...";
+	let shares = split_shares(secret, &params, |_, _| {}).unwrap();
+	for name in registry.names() {
+	    let codec = registry.get(name).unwrap();
+	    let estimate = estimate(&params, secret.len(), codec).unwrap();
+	    assert_eq!(estimate.exact, name != "rfc1751" && name != "yaml");
+	    assert_eq!(estimate.shares.len(), 12);
+	    for (size, share) in estimate.shares.iter().zip(shares.iter()) {
+		let mut line = Vec::new();
+		codec.write_line(share, &mut line).unwrap();
+		if estimate.exact {
+		    assert_eq!(size.bytes, line.len(), "{} share {}", name, share.index);
+		} else {
+		    assert!(size.bytes >= line.len(), "{} share {}", name, share.index);
+		}
+	    }
+	}
+	let text = estimate(&params, secret.len(), registry.get("text").unwrap()).unwrap();
+	// ops=3=8=1=<64 hex digits>= and a newline, then one more digit from share 10
+	assert_eq!(text.shares[0].bytes, 76);
+	assert_eq!(text.shares[11].bytes, 77);
+	assert_eq!(text.total(), 9 * 76 + 3 * 77);
+	assert_eq!(text.shares[0].qr_version, Some(5));
+	assert!(estimate(&Params::new(3, 2, 8), 32, registry.get("text").unwrap()).is_err());
+
+	assert_eq!(qr_version(1), Some(1));
+	assert_eq!(qr_version(14), Some(1));
+	assert_eq!(qr_version(15), Some(2));
+	assert_eq!(qr_version(2331), Some(40));
+	assert_eq!(qr_version(2332), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! Fingerprints to read out loud
+//!
+//! Custodians who want to be sure they hold the shares the dealer
+//! handed out (and that nobody has swapped or altered one since) can
+//! compare short codes over the phone rather than whole shares. A
+//! [Fingerprint] is the first 64 bits of a SHA-256 (or of whichever
+//! [hash](crate::hash) the manifest was made with), written as six
+//! RFC 1751 words, eg `RUG CAKE DOCK HOME HOWE TERM`. The words come
+//! from a fixed list of short English words, and the last one carries
+//! two parity bits, so most misheard words are caught.
+//!
+//! * a share's fingerprint is taken from the same hash of its text
+//!   that a [Manifest] records, so it can be read off either the
+//!   share or the manifest
+//! * a set's fingerprint covers its parameters and the hash of every
+//!   share, in share number order. It's the same whether it's worked
+//!   out from all of the shares or from the manifest, and any change
+//!   to any share changes it.
+
+use crate::error::{Error, Result};
+use crate::hash::Algorithm;
+use crate::manifest::{share_hash, Manifest};
+use crate::Share;
+use rfc1751::ToRfc1751;
+use std::convert::TryInto;
+
+/// A 64-bit code for a share or a set, shown as six words
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub [u8; 8]);
+
+impl Fingerprint {
+    fn of_hash(hash : &[u8]) -> Fingerprint {
+	Fingerprint(hash[..8].try_into().expect("hashes are longer than 8 bytes"))
+    }
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	write!(f, "{}", self.0.to_rfc1751().expect("8 bytes make six words"))
+    }
+}
+
+/// The fingerprint of one share
+pub fn of_share(share : &Share) -> Fingerprint {
+    of_share_with(share, Algorithm::Sha256)
+}
+
+/// As [of_share], from a hash made with `hash`
+pub fn of_share_with(share : &Share, hash : Algorithm) -> Fingerprint {
+    Fingerprint::of_hash(&hex::decode(share_hash(share, hash)).expect("share hashes are hex"))
+}
+
+// What a set's fingerprint is a hash of, given every share's hash
+fn of_parts(set_id : Option<&str>, quorum : u16, width : u16, hashes : &[(u64, String)],
+	    algorithm : Algorithm) -> Fingerprint {
+    let mut hashes = hashes.to_vec();
+    hashes.sort();
+    let mut hash = algorithm.hasher();
+    hash.update(format!("guff-ssss set v1\nset: {}\nquorum: {}\nwidth: {}\n",
+			set_id.unwrap_or(""), quorum, width).as_bytes());
+    for (index, share) in hashes.iter() {
+	hash.update(format!("share: {} {}\n", index, share).as_bytes());
+    }
+    Fingerprint::of_hash(&hash.finish())
+}
+
+/// The fingerprint of a whole set of shares. They must all be from
+/// the same split, with no share number given twice.
+pub fn of_set(shares : &[Share]) -> Result<Fingerprint> {
+    of_set_with(shares, Algorithm::Sha256)
+}
+
+/// As [of_set], with every hash made with `hash`
+pub fn of_set_with(shares : &[Share], hash : Algorithm) -> Result<Fingerprint> {
+    let first = shares.first()
+	.ok_or(Error::InsufficientShares { have : 0, need : 1 })?;
+    let mut hashes = Vec::new();
+    for share in shares {
+	if share.set_id != first.set_id || share.quorum != first.quorum
+	    || share.width != first.width || share.values.len() != first.values.len() {
+	    return Err(Error::InconsistentShares(format!(
+		"share {} is from a different split than share {}", share.index, first.index)))
+	}
+	if hashes.iter().any(|(index, _)| *index == share.index) {
+	    return Err(Error::InconsistentShares(format!("share {} is given twice", share.index)))
+	}
+	hashes.push((share.index, share_hash(share, hash)));
+    }
+    Ok(of_parts(first.set_id.as_deref(), first.quorum, first.width, &hashes, hash))
+}
+
+/// The fingerprint of the set a manifest records; the same as
+/// [of_set_with] on all of its shares and the manifest's hash
+pub fn of_manifest(manifest : &Manifest) -> Fingerprint {
+    of_parts(manifest.set_id.as_deref(), manifest.quorum, manifest.width, &manifest.share_hashes,
+	     manifest.hash)
+}
+
+/// The fingerprint of every share a manifest lists, by share number
+pub fn of_listed_shares(manifest : &Manifest) -> Result<Vec<(u64, Fingerprint)>> {
+    manifest.share_hashes.iter().map(|(index, hash)| {
+	let bytes = hex::decode(hash)
+	    .map_err(|_| Error::BadManifest(format!("bad hash for share {}", index)))?;
+	Ok((*index, Fingerprint::of_hash(&bytes)))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{split_with_params, Params};
+    use rfc1751::FromRfc1751;
+
+    #[test]
+    fn fingerprints() {
+	let params = Params::new(2, 3, 8).with_set_id("fp");
+	let lines = split_with_params(b"hello", &params, |_, _| {}).unwrap();
+	let shares : Vec<Share> = lines.iter().map(|l| l.parse().unwrap()).collect();
+	let manifest = Manifest::new(&params, b"hello", &lines).unwrap();
+
+	let words = of_share(&shares[0]).to_string();
+	assert_eq!(words.split(' ').count(), 6);
+	assert_eq!(words.from_rfc1751().unwrap(), of_share(&shares[0]).0);
+	let listed = of_listed_shares(&manifest).unwrap();
+	for (share, (index, print)) in shares.iter().zip(&listed) {
+	    assert_eq!((share.index, of_share(share)), (*index, *print));
+	}
+
+	// Any order, and the same from the manifest; not from a subset
+	let reversed : Vec<Share> = shares.iter().rev().cloned().collect();
+	assert_eq!(of_set(&reversed).unwrap(), of_set(&shares).unwrap());
+	assert_eq!(of_manifest(&manifest), of_set(&shares).unwrap());
+	assert_ne!(of_set(&shares[..2]).unwrap(), of_set(&shares).unwrap());
+
+	// One changed byte changes both
+	let mut tampered = shares.clone();
+	tampered[1].values[0] ^= 1;
+	assert_ne!(of_share(&tampered[1]), of_share(&shares[1]));
+	assert_ne!(of_set(&tampered).unwrap(), of_set(&shares).unwrap());
+
+	let twice = [shares[0].clone(), shares[0].clone()];
+	assert!(matches!(of_set(&twice), Err(Error::InconsistentShares(_))));
+	assert!(of_set(&[]).is_err());
+
+	// A SHA-512 manifest's fingerprints are SHA-512 ones
+	let manifest = Manifest::new_with_hash(&params, b"hello", &lines, Algorithm::Sha512).unwrap();
+	assert_eq!(of_manifest(&manifest), of_set_with(&shares, Algorithm::Sha512).unwrap());
+	assert_ne!(of_manifest(&manifest), of_set(&shares).unwrap());
+	assert_eq!(of_listed_shares(&manifest).unwrap()[0],
+		   (shares[0].index, of_share_with(&shares[0], Algorithm::Sha512)));
+    }
+}
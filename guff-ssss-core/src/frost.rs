@@ -0,0 +1,112 @@
+//! FROST key packages (with the `frost` feature)
+//!
+//! A signing key for FROST threshold signatures is shared out over
+//! the curve's scalar field, not over GF(2<sup>w</sup>), so shares of
+//! the usual kind can't be used to sign. When the secret being split
+//! is an Ed25519 or secp256k1 scalar, [split_frost] makes the split
+//! the FROST way instead, and gives back each participant's
+//! `KeyPackage` and the group's `PublicKeyPackage` in the
+//! serialization used by the ZF FROST libraries (`frost-ed25519`,
+//! `frost-secp256k1`), ready to seed a signing quorum.
+//!
+//! Participant identifiers are the share numbers 1 to n, as with
+//! ordinary shares. The scalar is in the curve's own encoding: 32
+//! bytes little-endian for Ed25519 (a scalar, not an RFC 8032 seed),
+//! 32 bytes big-endian for secp256k1.
+
+use crate::error::{Error, Result};
+use frost_core::keys::{split, IdentifierList, KeyPackage};
+use frost_core::{Ciphersuite, Identifier, SigningKey};
+use std::convert::TryFrom;
+
+/// Curves with FROST ciphersuites
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    /// FROST(Ed25519, SHA-512)
+    Ed25519,
+    /// FROST(secp256k1, SHA-256)
+    Secp256k1,
+}
+
+impl std::str::FromStr for Curve {
+    type Err = Error;
+
+    fn from_str(name : &str) -> Result<Curve> {
+	match name {
+	    "ed25519" => Ok(Curve::Ed25519),
+	    "secp256k1" => Ok(Curve::Secp256k1),
+	    _ => Err(Error::FieldError(format!("no FROST ciphersuite for curve '{}'", name))),
+	}
+    }
+}
+
+/// The serialized output of [split_frost]
+#[derive(Clone, Debug)]
+pub struct FrostSplit {
+    /// (participant identifier, serialized `KeyPackage`), for
+    /// identifiers 1 to n
+    pub key_packages : Vec<(u16, Vec<u8>)>,
+    /// The serialized `PublicKeyPackage`, which holds nothing secret
+    pub public_key_package : Vec<u8>,
+}
+
+fn frost_error<C : Ciphersuite>(error : frost_core::Error<C>) -> Error {
+    Error::FieldError(format!("FROST: {}", error))
+}
+
+fn split_with<C : Ciphersuite>(scalar : &[u8], k : u16, n : u16) -> Result<FrostSplit> {
+    let key = SigningKey::<C>::deserialize(scalar).map_err(frost_error)?;
+    let ids = (1..=n).map(Identifier::<C>::try_from).collect::<std::result::Result<Vec<_>, _>>()
+	.map_err(frost_error)?;
+    let (shares, public) = split(&key, n, k, IdentifierList::Custom(&ids), &mut rand::thread_rng())
+	.map_err(frost_error)?;
+    let key_packages = ids.iter().zip(1..=n).map(|(id, index)| {
+	let package = KeyPackage::try_from(shares[id].clone()).map_err(frost_error)?;
+	Ok((index, package.serialize().map_err(frost_error)?))
+    }).collect::<Result<Vec<_>>>()?;
+    Ok(FrostSplit {
+	key_packages,
+	public_key_package : public.serialize().map_err(frost_error)?,
+    })
+}
+
+/// Split a signing key (a scalar for `curve`) into FROST key packages,
+/// any `k` of the `n` of which can sign together
+pub fn split_frost(curve : Curve, scalar : &[u8], k : u16, n : u16) -> Result<FrostSplit> {
+    match curve {
+	Curve::Ed25519 => split_with::<frost_ed25519::Ed25519Sha512>(scalar, k, n),
+	Curve::Secp256k1 => split_with::<frost_secp256k1::Secp256K1Sha256>(scalar, k, n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reconstruct<C : Ciphersuite>(packages : &[(u16, Vec<u8>)]) -> Vec<u8> {
+	let packages : Vec<KeyPackage<C>> = packages.iter()
+	    .map(|(_, bytes)| KeyPackage::deserialize(bytes).unwrap()).collect();
+	frost_core::keys::reconstruct(&packages).unwrap().serialize()
+    }
+
+    #[test]
+    fn frost_key_packages() {
+	let mut scalar = [0u8; 32];
+	scalar[0] = 42;
+	let split = split_frost(Curve::Ed25519, &scalar, 2, 3).unwrap();
+	assert_eq!(split.key_packages.iter().map(|(i, _)| *i).collect::<Vec<_>>(), [1, 2, 3]);
+	assert_eq!(reconstruct::<frost_ed25519::Ed25519Sha512>(&split.key_packages[1..]), scalar);
+	frost_ed25519::keys::PublicKeyPackage::deserialize(&split.public_key_package).unwrap();
+
+	let mut scalar = [0u8; 32];
+	scalar[31] = 42;
+	let split = split_frost("secp256k1".parse().unwrap(), &scalar, 3, 5).unwrap();
+	assert_eq!(reconstruct::<frost_secp256k1::Secp256K1Sha256>(&split.key_packages[..3]),
+		   scalar);
+
+	// Not a scalar (too big), a trivial quorum, and an unknown curve
+	assert!(split_frost(Curve::Ed25519, &[0xff; 32], 2, 3).is_err());
+	assert!(split_frost(Curve::Ed25519, &scalar, 1, 3).is_err());
+	assert!("p256".parse::<Curve>().is_err());
+    }
+}
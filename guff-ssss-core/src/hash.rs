@@ -0,0 +1,178 @@
+//! Hash algorithms for checksums, fingerprints and manifests
+//!
+//! Everything that hashes shares or secrets uses SHA-256 unless told
+//! otherwise: armor checks, binary share checksums, a [Manifest]'s
+//! share hashes (and the commitment of a record made before it was
+//! stretched with argon2id), and the fingerprints taken from them.
+//! Deployments whose policy names another algorithm can choose
+//! SHA-512, or BLAKE3 with the `blake3` feature. The choice goes in
+//! the share or record itself, so nobody has to be told it to read
+//! one back:
+//!
+//! * armored shares get a `Hash:` header
+//! * binary shares are written as version 2, with an algorithm byte
+//! * manifests get a `hash:` line, covered by the signature
+//!
+//! A SHA-256 share or manifest is written exactly as it always was.
+//!
+//! [Manifest]: crate::manifest::Manifest
+
+use crate::error::{Error, Result};
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::str::FromStr;
+
+/// A hash algorithm
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Algorithm {
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "sha-256"))]
+    Sha256,
+    #[cfg_attr(feature = "serde", serde(rename = "sha-512"))]
+    Sha512,
+    #[cfg(feature = "blake3")]
+    #[cfg_attr(feature = "serde", serde(rename = "blake3"))]
+    Blake3,
+}
+
+impl Algorithm {
+    /// Every algorithm in this build
+    pub const ALL : &'static [Algorithm] = &[
+	Algorithm::Sha256,
+	Algorithm::Sha512,
+	#[cfg(feature = "blake3")]
+	Algorithm::Blake3,
+    ];
+
+    /// The name it's recorded under, eg `sha-256`
+    pub fn name(self) -> &'static str {
+	match self {
+	    Algorithm::Sha256 => "sha-256",
+	    Algorithm::Sha512 => "sha-512",
+	    #[cfg(feature = "blake3")]
+	    Algorithm::Blake3 => "blake3",
+	}
+    }
+
+    /// Its number in a binary share
+    pub(crate) fn code(self) -> u8 {
+	match self {
+	    Algorithm::Sha256 => 1,
+	    Algorithm::Sha512 => 2,
+	    #[cfg(feature = "blake3")]
+	    Algorithm::Blake3 => 3,
+	}
+    }
+
+    pub(crate) fn from_code(code : u8) -> Result<Algorithm> {
+	Algorithm::ALL.iter().copied().find(|a| a.code() == code)
+	    .ok_or_else(|| unknown(&format!("number {}", code)))
+    }
+
+    /// The hash of `data`: 32 bytes, or 64 for SHA-512
+    pub fn digest(self, data : &[u8]) -> Vec<u8> {
+	self.hasher().chain(data).finish()
+    }
+
+    /// A hasher to feed a piece at a time
+    pub fn hasher(self) -> Hasher {
+	match self {
+	    Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+	    Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+	    #[cfg(feature = "blake3")]
+	    Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+	}
+    }
+}
+
+fn unknown(what : &str) -> Error {
+    let names : Vec<&str> = Algorithm::ALL.iter().map(|a| a.name()).collect();
+    Error::FieldError(format!("unknown hash algorithm {} (this build has {})", what, names.join(", ")))
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	f.write_str(self.name())
+    }
+}
+
+// `sha512`, `SHA-512` and `sha-512` are all the same
+impl FromStr for Algorithm {
+    type Err = Error;
+
+    fn from_str(name : &str) -> Result<Algorithm> {
+	let bare = name.trim().to_ascii_lowercase().replace(['-', '_'], "");
+	if bare == "blake3" && !cfg!(feature = "blake3") {
+	    return Err(Error::FieldError("BLAKE3 needs the 'blake3' feature".into()))
+	}
+	Algorithm::ALL.iter().copied().find(|a| a.name().replace('-', "") == bare)
+	    .ok_or_else(|| unknown(name.trim()))
+    }
+}
+
+/// A hash in progress
+pub enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    /// Add `data`
+    pub fn update(&mut self, data : &[u8]) {
+	match self {
+	    Hasher::Sha256(hash) => hash.update(data),
+	    Hasher::Sha512(hash) => hash.update(data),
+	    #[cfg(feature = "blake3")]
+	    Hasher::Blake3(hash) => { hash.update(data); },
+	}
+    }
+
+    /// As [update](Hasher::update), for chaining
+    pub fn chain(mut self, data : &[u8]) -> Hasher {
+	self.update(data);
+	self
+    }
+
+    /// The hash of everything added
+    pub fn finish(self) -> Vec<u8> {
+	match self {
+	    Hasher::Sha256(hash) => hash.finalize().to_vec(),
+	    Hasher::Sha512(hash) => hash.finalize().to_vec(),
+	    #[cfg(feature = "blake3")]
+	    Hasher::Blake3(hash) => hash.finalize().as_bytes().to_vec(),
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algorithms() {
+	assert_eq!(Algorithm::default(), Algorithm::Sha256);
+	for name in ["sha-512", "SHA512", " sha_512 "] {
+	    assert_eq!(name.parse::<Algorithm>().unwrap(), Algorithm::Sha512);
+	}
+	assert!("md5".parse::<Algorithm>().unwrap_err().to_string().contains("sha-256, sha-512"));
+	assert_eq!("blake3".parse::<Algorithm>().is_ok(), cfg!(feature = "blake3"));
+	for &algorithm in Algorithm::ALL {
+	    assert_eq!(algorithm.name().parse::<Algorithm>().unwrap(), algorithm);
+	    assert_eq!(Algorithm::from_code(algorithm.code()).unwrap(), algorithm);
+	    assert_eq!(algorithm.hasher().chain(b"ab").chain(b"c").finish(), algorithm.digest(b"abc"));
+	}
+	assert!(Algorithm::from_code(0).is_err());
+
+	// FIPS 180-2 appendix B and the BLAKE3 test vectors
+	assert_eq!(hex::encode(Algorithm::Sha256.digest(b"abc")),
+		   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+	assert_eq!(hex::encode(&Algorithm::Sha512.digest(b"abc")[..16]),
+		   "ddaf35a193617abacc417349ae204131");
+	#[cfg(feature = "blake3")]
+	assert_eq!(hex::encode(Algorithm::Blake3.digest(b"")),
+		   "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+    }
+}
@@ -0,0 +1,135 @@
+//! Shares from Karney's original `secret` program
+//!
+//! The original worked in the integer field mod 257 rather than a
+//! Galois field, so its shares can't be fed to [Decoder](crate::Decoder):
+//! each value is a number from 0 to 256, and the x values are
+//! multiplied out mod 257. This module recombines them with
+//! arithmetic mod 257 so that old share sets can still be recovered,
+//! and, if wanted, split again into this crate's format.
+//!
+//! A legacy share line is `K=S=Values=`: the quorum, the share
+//! number (1 to 256) and the share values as decimal numbers
+//! separated by spaces or commas. This is the same layout as ours
+//! without the width field, which was only added when the field
+//! stopped being fixed.
+
+use crate::error::{Error, ParseError, Result};
+use std::convert::TryFrom;
+
+/// The prime the original program worked modulo
+pub const MODULUS : u32 = 257;
+
+/// One parsed legacy share
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LegacyShare {
+    pub quorum : usize,
+    pub index  : u32,
+    pub values : Vec<u32>,
+}
+
+/// Parse a single legacy share line
+pub fn parse_legacy_share(line : &str) -> std::result::Result<LegacyShare, ParseError> {
+    let fields : Vec<&str> = line.trim().split('=').collect();
+    if fields.len() != 4 { return Err(ParseError::FieldCount) }
+    if !fields[3].is_empty() { return Err(ParseError::TrailingData) }
+    let quorum : usize = fields[0].parse()
+	.map_err(|_| ParseError::BadNumber("quorum"))?;
+    let index : u32 = fields[1].parse()
+	.map_err(|_| ParseError::BadNumber("share number"))?;
+    if quorum == 0 || quorum >= MODULUS as usize { return Err(ParseError::BadNumber("quorum")) }
+    if index == 0 || index >= MODULUS { return Err(ParseError::BadNumber("share number")) }
+    let values = fields[2].split(|c : char| c == ',' || c.is_whitespace())
+	.filter(|v| !v.is_empty())
+	.map(|v| match v.parse::<u32>() {
+	    Ok(v) if v < MODULUS => Ok(v),
+	    _ => Err(ParseError::BadNumber("share value")),
+	})
+	.collect::<std::result::Result<Vec<u32>, ParseError>>()?;
+    Ok(LegacyShare { quorum, index, values })
+}
+
+// Fermat: a^(p-2) is the inverse of a mod p
+fn inverse(a : u32) -> u32 {
+    let (mut base, mut exp, mut result) = (a % MODULUS, MODULUS - 2, 1);
+    while exp > 0 {
+	if exp & 1 == 1 { result = result * base % MODULUS }
+	base = base * base % MODULUS;
+	exp >>= 1;
+    }
+    result
+}
+
+/// Recover the secret from legacy share lines
+///
+/// Lines after the first `K` with distinct share numbers are ignored,
+/// as with the regular decoder.
+pub fn combine_legacy(lines : &[&str]) -> Result<Vec<u8>> {
+    let mut shares : Vec<LegacyShare> = Vec::new();
+    for (line, text) in lines.iter().enumerate() {
+	let share = parse_legacy_share(text)
+	    .map_err(|error| Error::ParseError { line : line + 1, error })?;
+	if let Some(first) = shares.first() {
+	    if share.quorum != first.quorum || share.values.len() != first.values.len() {
+		return Err(Error::InconsistentShares(
+		    format!("legacy share {} doesn't match the first share", share.index)))
+	    }
+	}
+	if shares.iter().all(|s| s.index != share.index) {
+	    shares.push(share)
+	}
+    }
+    let need = shares.first().map(|s| s.quorum).unwrap_or(1);
+    if shares.len() < need {
+	return Err(Error::InsufficientShares { have : shares.len(), need })
+    }
+    shares.truncate(need);
+
+    // Lagrange interpolation at x = 0
+    let weights : Vec<u32> = shares.iter().map(|si| {
+	shares.iter().filter(|sj| sj.index != si.index).fold(1, |w, sj| {
+	    let diff = (sj.index + MODULUS - si.index) % MODULUS;
+	    w * sj.index % MODULUS * inverse(diff) % MODULUS
+	})
+    }).collect();
+    let len = shares.first().map(|s| s.values.len()).unwrap_or(0);
+    (0..len).map(|col| {
+	let byte = shares.iter().zip(weights.iter())
+	    .fold(0, |acc, (s, w)| (acc + s.values[col] * w) % MODULUS);
+	u8::try_from(byte).map_err(|_| Error::InconsistentShares(
+	    format!("legacy shares give {} for byte {}, which isn't a byte", byte, col + 1)))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Split mod 257 the way the original did, with fixed coefficients
+    fn legacy_split(secret : &[u8], coeffs : &[u32], n : u32) -> Vec<String> {
+	(1..=n).map(|x| {
+	    let values : Vec<String> = secret.iter().map(|&s| {
+		let y = coeffs.iter().rev()
+		    .fold(0, |acc, c| (acc * x + c) % MODULUS);
+		((y * x + s as u32) % MODULUS).to_string()
+	    }).collect();
+	    format!("{}={}={}=", coeffs.len() + 1, x, values.join(" "))
+	}).collect()
+    }
+
+    #[test]
+    fn legacy_round_trip() {
+	let shares = legacy_split(b"hello world", &[200, 17], 5);
+	let lines : Vec<&str> = shares.iter().map(|s| s.as_str()).collect();
+	assert_eq!(combine_legacy(&lines[2..]).unwrap(), b"hello world");
+	assert_eq!(combine_legacy(&[lines[4], lines[0], lines[0], lines[2]]).unwrap(),
+		   b"hello world");
+	assert!(matches!(combine_legacy(&lines[..2]),
+			 Err(Error::InsufficientShares { have : 2, need : 3 })));
+
+	assert_eq!(parse_legacy_share("2=3=0,256 7=").unwrap(),
+		   LegacyShare { quorum : 2, index : 3, values : vec![0, 256, 7] });
+	assert_eq!(parse_legacy_share("2=3=257="), Err(ParseError::BadNumber("share value")));
+	assert_eq!(parse_legacy_share("2=0=1="), Err(ParseError::BadNumber("share number")));
+	assert_eq!(parse_legacy_share("2=8=1=00="), Err(ParseError::FieldCount));
+    }
+}
@@ -0,0 +1,1923 @@
+//! Shamir's Secret Sharing Scheme over Galois fields GF(2<sup>w</sup>)
+//!
+//! An implementation of
+//!
+//! Shamir A.,
+//! How to Share a Secret,
+//! Communications of the ACM, 22, 1979, pp. 612--613.
+//!
+//! based on Charles Karney's original `secret` program, but using
+//! the [guff] crate's Galois fields instead of the integer field mod
+//! 257.
+//!
+//! This crate is just the algorithms and share formats: it doesn't
+//! read files or stdin, parse command lines or talk to the network.
+//! The `guff-ssss` crate builds the `shamir-*` programs on top of it.
+//!
+//! The simplest calls are [split], which gives the shares of a secret
+//! as [Share]s, and [combine], which takes any quorum of them back to
+//! the secret. A [Decoder] takes shares one at a time (eg, as lines of
+//! text) for [combine_decoder].
+
+extern crate hex;
+
+pub mod error;
+pub use error::{Error, ParseError, Result};
+
+pub mod binary;
+pub mod buffer;
+pub mod codec;
+pub mod correct;
+pub mod custodian;
+pub mod dkg;
+pub mod doctor;
+pub mod estimate;
+pub mod fingerprint;
+pub mod hash;
+pub mod legacy;
+pub mod manifest;
+pub mod participants;
+pub mod perl;
+pub mod refresh;
+pub mod selftest;
+pub mod vectors;
+pub mod vote;
+pub mod width;
+pub use correct::{combine_correcting, Correction};
+pub use vote::{check_recoverable, combine_majority, Vote};
+use width::{check_width, max_index, word_bytes, Elem, Width, U8};
+#[cfg(feature = "width-4")]
+use width::U4;
+#[cfg(feature = "width-16")]
+use width::U16;
+#[cfg(feature = "width-32")]
+use width::U32;
+
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+pub mod testing;
+
+#[cfg(feature = "vault")]
+pub mod vault;
+
+#[cfg(feature = "frost")]
+pub mod frost;
+
+#[cfg(feature = "simd")]
+pub mod simd;
+
+use guff::GaloisField;
+use num_traits::{One, Zero};
+use rand::Rng;
+use std::convert::TryFrom;
+
+// How many words to process between calls to a progress callback
+const PROGRESS_INTERVAL : usize = 4096;
+
+// Debug events go to `tracing` with the feature of the same name, and
+// nowhere otherwise. Only ever log sizes, counts and parameters: never
+// share values, coefficients or anything derived from the secret.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+	#[cfg(feature = "tracing")]
+	tracing::debug!($($arg)*);
+    }
+}
+
+// shares have the format:
+//
+// K=W=S=Values=
+//
+// K  quorum value
+// W  width of field in bits
+// S  this share number
+//
+// Values is a list of word-sized values, each of which is a share of
+// the corresponding word in the secret key.
+//
+// Each value in a share represents the result of calculating a
+// random polynomial. 
+//
+// f(x) = a_0 * 1  +  a_1 * x  +  a_2 * x**2  +  ...  +  a_o * x**o
+//
+// at x = S.
+//
+// The polynomial is of order o = K - 1.
+//
+// Shares may also carry a set ID as an extra leading field:
+//
+// I=K=W=S=Values=
+//
+// I  set ID (letters, digits, '-' or '_'), the same on every share
+//    produced by a single split
+//
+// This lets shares of several different secrets be told apart (and
+// sorted into their sets) without reconstructing anything.
+//
+// Version 2 lines (codec::V2Codec) carry the same fields in a header
+// after an `ssss2:` prefix, with the field polynomial and a checksum.
+// They're converted to this form as they're read, unless they say the
+// secret was padded to whole words (which this form can't say).
+
+// Decoding a single word of the original key proceeds by:
+//
+// * extracting all the S and f(S) values
+// * using them to solve for the shared coefficients a_1 .. a_o
+// * plugging the shared coefficients into any f(S) to recover a_0
+//
+// To check the correctness of our algorithm, we can repeat the third
+// step for all shares and verify that the produced a_0's all agree.
+// Pass 3 does the equivalent: given more shares than the quorum, it
+// evaluates the polynomial found from the first k at each of the
+// others' S, and checks that it gets their f(S).
+
+/// A single share, as found on one `K=W=S=Values=` line
+///
+/// With the `serde` feature, the values are serialized as a hex
+/// string (as in the text format) and a missing set ID is left out,
+/// as is `padded` unless it's set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Share {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub set_id : Option<String>,
+    pub quorum : u16,		// K
+    pub width  : u16,		// W
+    pub index  : u64,		// S
+    #[cfg_attr(feature = "serde", serde(with = "serde_hex"))]
+    pub values : Vec<u8>,	// little-endian words
+    /// The secret was padded to a whole number of words before it was
+    /// split (see [Params::with_word_padding]), and combining takes
+    /// the padding off again. The text format has no room to say so;
+    /// version 2 lines have a `pad=` field for it.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "std::ops::Not::not"))]
+    pub padded : bool,
+}
+
+// Hex for share values, which are as long as the secret. With the
+// `simd` feature this is faster-hex's vectorised codec (AVX2 where
+// there is it, found at run time); the output is the same either way.
+pub(crate) fn encode_hex(bytes : &[u8]) -> String {
+    #[cfg(feature = "simd")]
+    { faster_hex::hex_string(bytes) }
+    #[cfg(not(feature = "simd"))]
+    { hex::encode(bytes) }
+}
+
+// Hex a piece at a time, for writing out a long share without
+// holding all of its text at once
+const HEX_PIECE : usize = 4096;
+
+pub(crate) fn hex_pieces<E>(bytes : &[u8], mut emit : impl FnMut(&str) -> std::result::Result<(), E>)
+			    -> std::result::Result<(), E> {
+    let mut buf = [0u8; 2 * HEX_PIECE];
+    for piece in bytes.chunks(HEX_PIECE) {
+	let text = &mut buf[..2 * piece.len()];
+	#[cfg(feature = "simd")]
+	let hex = faster_hex::hex_encode(piece, text).ok().map(|hex| &*hex);
+	#[cfg(not(feature = "simd"))]
+	let hex : Option<&str> = None;
+	match hex {
+	    Some(hex) => emit(hex)?,
+	    None => emit(&hex_scalar(piece, text))?,
+	}
+    }
+    Ok(())
+}
+
+// Plain hex of a piece, by table lookup (the digits are all ASCII,
+// so there's nothing to go wrong turning them into a str)
+fn hex_scalar<'a>(piece : &[u8], text : &'a mut [u8]) -> std::borrow::Cow<'a, str> {
+    const DIGITS : &[u8; 16] = b"0123456789abcdef";
+    for (b, pair) in piece.iter().zip(text.chunks_mut(2)) {
+	pair[0] = DIGITS[(b >> 4) as usize];
+	if let Some(low) = pair.get_mut(1) { *low = DIGITS[(b & 15) as usize] }
+    }
+    String::from_utf8_lossy(text)
+}
+
+pub(crate) fn decode_hex(text : &str) -> Option<Vec<u8>> {
+    #[cfg(feature = "simd")]
+    {
+	let mut bytes = vec![0u8; text.len() / 2];
+	faster_hex::hex_decode(text.as_bytes(), &mut bytes).ok().map(|_| bytes)
+    }
+    #[cfg(not(feature = "simd"))]
+    { hex::decode(text).ok() }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) mod serde_hex {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S : Serializer>(bytes : &[u8], s : S) -> Result<S::Ok, S::Error> {
+	s.serialize_str(&crate::encode_hex(bytes))
+    }
+
+    pub fn deserialize<'de, D : Deserializer<'de>>(d : D) -> Result<Vec<u8>, D::Error> {
+	let text = String::deserialize(d)?;
+	crate::decode_hex(&text).ok_or_else(|| serde::de::Error::custom("bad hex"))
+    }
+}
+
+impl std::fmt::Display for Share {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	if let Some(id) = &self.set_id { write!(f, "{}=", id)? }
+	write!(f, "{}={}={}=", self.quorum, self.width, self.index)?;
+	hex_pieces(&self.values, |hex| f.write_str(hex))?;
+	f.write_str("=")
+    }
+}
+
+impl Share {
+    /// Write the share's text (as [Display](std::fmt::Display) gives
+    /// it) to `out`, a few kilobytes at a time
+    pub fn write_to<W : std::io::Write + ?Sized>(&self, out : &mut W) -> std::io::Result<()> {
+	if let Some(id) = &self.set_id { write!(out, "{}=", id)? }
+	write!(out, "{}={}={}=", self.quorum, self.width, self.index)?;
+	hex_pieces(&self.values, |hex| out.write_all(hex.as_bytes()))?;
+	out.write_all(b"=")
+    }
+}
+
+// Will store all field values as Vec<u8> rather than trying
+// to make a generic storage object.
+#[derive(Clone)]
+pub struct Decoder {
+    // use largest word size for these variables
+    pub quorum : u16,		// aka 'k'
+    pub width : u16,		// width in bits; GF(2**8) = width 8
+    pub hex_length : usize,	// length of hex string (nibbles)
+    pub set_id : Option<String>,	// from the optional leading field
+    pub padded : bool,		// secret padded to whole words
+    // problem ... we want vectors of appropriate size
+    // solution ... store as Vec<u8> for now and convert/iterate later
+    pub x_values    : Vec<u8>,	// taken from shares
+    pub shares : Vec<u8>,	// taken from shares
+    pub coefficients : Vec<u8>, // calculated in pass 1
+    pub chunk_size : buffer::ChunkSize, // how pass 2 is split up
+    #[allow(dead_code)]
+    solution : Vec<u8>,		// calculated in pass 2
+    count : u16,		// share lines seen so far
+    spares : Vec<Share>,	// valid, but not used (see add)
+    duplicates : usize,		// exact copies of shares we have
+    // don't store the field, pass it
+    // field : &'a impl GenericField<_, _>,
+}
+
+// // organise solver as a trait
+// trait Solver<T: NumericOps, P: NumericOps> { // : GenericField<T,P> {
+//     fn f(&self) -> &dyn GenericField<T,P>;
+//     fn pass_1(decoder : &mut Decoder, ) {
+//         // code for pass 1
+//     }
+//     fn pass_2() {
+//         // code for pass 2
+//     }
+//     // add default iterator?
+// }
+
+// struct GenericSolver<'a, T: NumericOps, P: NumericOps> {
+//     field : &'a dyn GenericField<T,P>
+// }
+
+// // impl<T: NumericOps, P: NumericOps> Solver<T,P> for GenericSolver<'_, u8,u8> {
+// impl Solver<T, P> for GenericSolver<'_, u8,u8>
+//     // where T: NumericOps, P: NumericOps {
+// 	{
+//     fn f(&self) -> &dyn GenericField<T,P> { self.field }
+// }
+	
+// I'm confused. 
+
+impl Default for Decoder {
+    fn default() -> Self { Self::new() }
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+	Decoder {
+	    quorum : 0, width : 0, hex_length : 0, set_id : None, padded : false,
+	    x_values     : Vec::<u8>::new(),
+	    shares       : Vec::<u8>::new(),
+	    coefficients : Vec::<u8>::new(),
+	    chunk_size   : buffer::ChunkSize::default(),
+	    solution     : Vec::<u8>::new(),
+	    count : 0,
+	    spares : Vec::new(),
+	    duplicates : 0,
+	}
+    }
+
+    /// Forget all the shares, ready for another set, but keep the
+    /// buffers (and the chunk size) to use again
+    pub fn clear(&mut self) {
+	self.quorum = 0;
+	self.width = 0;
+	self.hex_length = 0;
+	self.set_id = None;
+	self.padded = false;
+	self.x_values.clear();
+	self.shares.clear();
+	self.coefficients.clear();
+	self.solution.clear();
+	self.count = 0;
+	self.spares.clear();
+	self.duplicates = 0;
+    }
+
+    /// Number of shares that will be used for reconstruction so far
+    pub fn have(&self) -> u16 {
+	u16::try_from(self.x_values.len() / word_bytes(self.width)).unwrap_or(u16::MAX)
+    }
+
+    /// True once we have a quorum of shares
+    pub fn is_complete(&self) -> bool {
+	self.count > 0 && self.have() == self.quorum
+    }
+
+    /// Parse a single `K=W=S=Values=` line, or a version 2 `ssss2:`
+    /// one (see [V2Codec](codec::V2Codec)), and add it to the set.
+    ///
+    /// Returns `Ok(false)` if the share was valid but not needed,
+    /// because we already have a quorum or already have a share with
+    /// the same number. (Exact copies of a share are counted by
+    /// [duplicates](Decoder::duplicates) and otherwise forgotten.)
+    pub fn add_share(&mut self, line : &str) -> Result<bool> {
+	use codec::ShareCodec;
+	if codec::V2Codec.detect(line) { return self.add(codec::V2Codec.decode(line)?) }
+	let share = parse_share(line).map_err(|error| Error::ParseError {
+	    line : self.count as usize + 1, error
+	})?;
+	self.add(share)
+    }
+
+    /// Add an already-parsed share to the set. As [add_share](Decoder::add_share).
+    pub fn add(&mut self, share : Share) -> Result<bool> {
+	let (k, w, s) = (share.quorum, share.width, share.index);
+	let hlen = share.values.len() * 2;
+
+	check_width(w)?;
+	// shares that didn't come from parse_share may not have been
+	// checked
+	if let Some(id) = &share.set_id {
+	    if !valid_set_id(id) { return Err(Error::FieldError(format!("bad set ID {}", id))) }
+	}
+	if !(hlen * 4).is_multiple_of(w as usize) {
+	    return Err(Error::FieldError(format!("{} bytes of data is not a whole number \
+						  of {}-bit words", share.values.len(), w)))
+	}
+
+	// if this is the first line
+	if self.count == 0 {
+	    // stash k, w, in decoder
+	    check_range("quorum", k as u64, 1, w)?;
+	} else {
+	    // compare k, w with values in decoder
+	    let inconsistent = |msg| Err(Error::InconsistentShares(msg));
+	    if w != self.width { return inconsistent(format!("mismatched field width value {}", w)) }
+	    if k != self.quorum { return inconsistent(format!("mismatched quorum value {}", k)) }
+	    if hlen != self.hex_length {
+		return inconsistent(format!("wrong share length {}", hex::encode(&share.values)))
+	    }
+	    if share.set_id != self.set_id {
+		return inconsistent(format!("mismatched set ID {}",
+					    share.set_id.as_deref().unwrap_or("(none)")))
+	    }
+	    if share.padded != self.padded {
+		return inconsistent(format!("share {} {} the secret was padded, but the others {}",
+					    s, if share.padded { "says" } else { "doesn't say" },
+					    if share.padded { "don't" } else { "do" }))
+	    }
+	}
+	// Every word of the payload is in range whatever the width,
+	// since the hex digits of a word can't make a bigger number
+	// than the field has elements. The index is another matter.
+	check_range("share index", s, 1, w)?;
+	if self.count == 0 {
+	    self.quorum = k;
+	    self.width  = w;
+	    self.hex_length = hlen;
+	    self.set_id = share.set_id.clone();
+	    self.padded = share.padded;
+	    // Room for a quorum of shares at once, rather than copying
+	    // them all each time the buffer grows
+	    buffer::reserve(&mut self.shares, (k as usize).saturating_mul(share.values.len()));
+	}
+	self.count += 1;
+	// Use the first k shares with distinct share numbers. A second
+	// share with the same number as one we have would make the
+	// system unsolvable, so it (like anything past the quorum) is
+	// kept aside instead.
+	let in_use = match w {
+	    #[cfg(feature = "width-4")]
+	    4  => position::<U4>(&self.x_values, s),
+	    8  => position::<U8>(&self.x_values, s),
+	    #[cfg(feature = "width-16")]
+	    16 => position::<U16>(&self.x_values, s),
+	    #[cfg(feature = "width-32")]
+	    32 => position::<U32>(&self.x_values, s),
+	    _ => return Err(width::unsupported(w)),
+	};
+	// An exact copy of a share we already have (eg, pasted from two
+	// backups) isn't worth keeping, even as a spare
+	let len = self.hex_length / 2;
+	let copy = match in_use {
+	    Some(j) => self.shares.get(j * len..(j + 1) * len) == Some(&share.values[..]),
+	    None => false,
+	};
+	if copy || self.spares.contains(&share) {
+	    self.duplicates += 1;
+	    return Ok(false)
+	}
+	if in_use.is_some() || self.have() == k {
+	    self.spares.push(share);
+	    return Ok(false)
+	}
+	// store as little-endian byte stream
+	match w {
+	    #[cfg(feature = "width-4")]
+	    4  => U4::put_value(&mut self.x_values, U4::from_index(s)),
+	    8  => U8::put_value(&mut self.x_values, U8::from_index(s)),
+	    #[cfg(feature = "width-16")]
+	    16 => U16::put_value(&mut self.x_values, U16::from_index(s)),
+	    #[cfg(feature = "width-32")]
+	    32 => U32::put_value(&mut self.x_values, U32::from_index(s)),
+	    _ => return Err(width::unsupported(w)),
+	}
+	self.shares.extend_from_slice(&share.values);
+	Ok(true)
+    }
+
+    /// Valid shares that aren't being used for reconstruction: those
+    /// past the quorum, and any repeating the share number of one
+    /// already in use
+    pub fn spares(&self) -> &[Share] { &self.spares }
+
+    /// How many shares were dropped for being exact copies of ones
+    /// already added
+    pub fn duplicates(&self) -> usize { self.duplicates }
+
+    /// All the valid shares added so far: those in use, then the
+    /// spares
+    pub fn all_shares(&self) -> Vec<Share> {
+	let len = self.hex_length / 2;
+	let rows = self.shares.chunks(len.max(1));
+	let xs = self.x_values.chunks(word_bytes(self.width));
+	let mut all : Vec<Share> = xs.zip(rows).map(|(x, row)| Share {
+	    set_id : self.set_id.clone(),
+	    quorum : self.quorum, width : self.width,
+	    // little-endian, like the words of a share
+	    index  : x.iter().rev().fold(0, |n, &b| n << 8 | b as u64),
+	    values : row.to_vec(),
+	    padded : self.padded,
+	}).collect();
+	all.extend(self.spares.iter().cloned());
+	all
+    }
+}
+
+// Which of the x values held (if any) is share number s
+fn position<U : Width>(x_values : &[u8], s : u64) -> Option<usize> {
+    (0..U::values(x_values.len())).position(|j| U::get_value(x_values, j) == U::from_index(s))
+}
+
+/// Parse a single share line into its fields.
+///
+/// This only checks the syntax of the line (and that the hex data
+/// is a whole number of words for the given width); whether the
+/// field width is supported, and whether the quorum and share index
+/// are in range for it, is left to [Decoder::add]. It does no I/O
+/// and never panics, whatever the input.
+pub fn parse_share(line : &str) -> std::result::Result<Share, ParseError> {
+    // split/collect gives us str refs
+    let mut v: Vec<&str> = line.trim().split('=').collect();
+    let set_id = match v.len() {
+	5 => None,
+	6 => Some(v.remove(0).to_string()),
+	_ => return Err(ParseError::FieldCount),
+    };
+    if let Some(id) = &set_id {
+	if !valid_set_id(id) {
+	    return Err(ParseError::BadSetId(id.clone()))
+	}
+    }
+    // K=W=S=Values=
+    //
+    // K  quorum value
+    // W  width of field in bits
+    // S  this share number
+
+    // convert k, w, s
+    let k : u16 = v[0].parse().map_err(|_| ParseError::BadNumber("quorum"))?;
+    let w : u16 = v[1].parse().map_err(|_| ParseError::BadNumber("width"))?;
+    let s : u64 = v[2].parse().map_err(|_| ParseError::BadNumber("share index"))?;
+    let hlen = v[3].len();
+    if !v[4].is_empty() {	// nothing after final =
+	return Err(ParseError::TrailingData)
+    }
+
+    // only known widths say anything about how long the data should be
+    if w==4 || w==8 || w==16 || w==32 {
+	let hlen_bits = hlen * 4; // hex digit == 4 bits
+	if !hlen_bits.is_multiple_of(w as usize) {
+	    return Err(ParseError::BadLength(v[3].to_string()))
+	}
+	if w == 4 && !hlen.is_multiple_of(2) {
+	    return Err(ParseError::MissingPadding(v[3].to_string()))
+	}
+    }
+
+    // decode to Vec<u8>
+    let values = decode_hex(v[3]).ok_or_else(|| ParseError::BadHex(v[3].to_string()))?;
+    Ok(Share { set_id, quorum : k, width : w, index : s, values, padded : false })
+}
+
+impl std::str::FromStr for Share {
+    type Err = ParseError;
+    fn from_str(line : &str) -> std::result::Result<Share, ParseError> { parse_share(line) }
+}
+
+// Quorums, share counts and share indices all run up to 2**(w-1)
+fn check_range(what : &str, value : u64, min : u64, w : u16) -> Result<()> {
+    let max = max_index(w);
+    if value < min || value > max {
+	return Err(Error::FieldError(format!("{} {} out of range {}..={} for GF(2^{})",
+					     what, value, min, max, w)))
+    }
+    Ok(())
+}
+
+/// Set IDs are short labels made of letters, digits, '-' and '_'
+pub fn valid_set_id(id : &str) -> bool {
+    !id.is_empty() && id.len() <= 32
+	&& id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Share number (S) of a share line, without otherwise validating it.
+/// Version 2 lines give the `s=` field of their header.
+pub fn share_number(line : &str) -> Option<&str> {
+    use codec::ShareCodec;
+    if codec::V2Codec.detect(line) { return v2_field(line, "s") }
+    line.trim().strip_suffix('=')?.rsplit('=').nth(1)
+}
+
+/// Set ID of a share line, if it has one (not otherwise validated).
+/// Version 2 lines give the `id=` field of their header.
+pub fn share_set_id(line : &str) -> Option<&str> {
+    use codec::ShareCodec;
+    if codec::V2Codec.detect(line) { return v2_field(line, "id") }
+    let v : Vec<&str> = line.trim().split('=').collect();
+    if v.len() == 6 { Some(v[0]) } else { None }
+}
+
+// A field of a v2 line's header, by name
+fn v2_field<'a>(line : &'a str, name : &str) -> Option<&'a str> {
+    let header = line.trim().split(':').nth(1)?;
+    header.split(',').filter_map(|field| field.split_once('='))
+	.find(|(k, _)| k.trim().eq_ignore_ascii_case(name)).map(|(_, v)| v.trim())
+}
+
+/// Sort share lines into sets by set ID, keeping the order in which
+/// each set was first seen. Shares without a set ID go together
+/// under `None`.
+pub fn group_by_set<'a>(lines : &[&'a str]) -> Vec<(Option<&'a str>, Vec<&'a str>)> {
+    let mut groups : Vec<(Option<&str>, Vec<&str>)> = Vec::new();
+    for line in lines.iter() {
+	let id = share_set_id(line);
+	match groups.iter_mut().find(|(g, _)| *g == id) {
+	    Some((_, members)) => members.push(line),
+	    None => groups.push((id, vec![line])),
+	}
+    }
+    groups
+}
+
+/// Short fingerprint of a share line, for people to compare by eye
+/// or read out over the phone, eg `1a2b-3c4d`. It's the start of the
+/// SHA-256 hash of the trimmed line, so it says nothing useful about
+/// the share's contents.
+pub fn share_fingerprint(line : &str) -> String {
+    share_fingerprint_with(line, hash::Algorithm::Sha256)
+}
+
+/// As [share_fingerprint], but the start of a hash made with `hash`
+pub fn share_fingerprint_with(line : &str, hash : hash::Algorithm) -> String {
+    let hex = hex::encode(&hash.digest(line.trim().as_bytes())[..4]);
+    format!("{}-{}", &hex[..4], &hex[4..])
+}
+
+// Long shares can be wrapped over several lines for printing. Each
+// line but the last ends with a '\', and leading whitespace on the
+// lines after it is ignored, so they can also be indented.
+
+/// Wrap a share line so that no line is more than `cols` characters,
+/// ending every line but the last with `\`
+pub fn wrap_share(line : &str, cols : usize) -> String {
+    let line = line.trim();
+    let cols = cols.max(2) - 1;	// leave room for the '\'
+    let chars : Vec<char> = line.chars().collect();
+    chars.chunks(cols).map(|c| c.iter().collect::<String>())
+	.collect::<Vec<_>>().join("\\\n")
+}
+
+/// Join lines ending with `\` to the line after, undoing
+/// [wrap_share]. Other lines are passed through as they are.
+pub fn join_continuations<I, S>(lines : I) -> Vec<String>
+where I : IntoIterator<Item = S>, S : AsRef<str> {
+    let mut joined = Vec::new();
+    let mut pending : Option<String> = None;
+    for line in lines {
+	let line = line.as_ref();
+	let mut current = match pending.take() {
+	    Some(mut p) => { p.push_str(line.trim()); p },
+	    None => line.to_string(),
+	};
+	match current.trim_end().strip_suffix('\\') {
+	    Some(head) => {
+		current.truncate(head.len());
+		pending = Some(current);
+	    },
+	    None => joined.push(current),
+	}
+    }
+    // a '\' on the last line has nothing to join to
+    joined.extend(pending);
+    joined
+}
+
+/// Generate a random set ID (8 hex digits)
+pub fn new_set_id() -> String {
+    format!("{:08x}", rand::thread_rng().gen::<u32>())
+}
+
+// Padding to a security level
+//
+// Shares are as long as the secret, so they give its length away. To
+// hide it, the secret can be padded out to a fixed number of bits
+// first: a 0x80 byte is appended, then zeros up to the full length
+// (as in ISO/IEC 7816-4), so the padding can always be told apart
+// from the secret and removed again after combining.
+
+/// Pad `secret` to exactly `bits` bits (a multiple of 8)
+pub fn pad_secret(secret : &[u8], bits : usize) -> Result<Vec<u8>> {
+    let len = padded_len(secret.len(), bits)?;
+    let mut padded = secret.to_vec();
+    padded.push(0x80);
+    padded.resize(len, 0);
+    Ok(padded)
+}
+
+/// How many bytes [pad_secret] makes a secret of `len` bytes, or why
+/// it can't
+pub fn padded_len(len : usize, bits : usize) -> Result<usize> {
+    if !bits.is_multiple_of(8) {
+	return Err(Error::FieldError(format!("security level {} is not a whole number of bytes",
+					     bits)))
+    }
+    if len >= bits / 8 {
+	return Err(Error::FieldError(format!("secret of {} bytes doesn't fit in {} bits \
+					      (with at least one byte of padding)",
+					     len, bits)))
+    }
+    Ok(bits / 8)
+}
+
+/// Remove the padding added by [pad_secret]
+pub fn unpad_secret(padded : &[u8]) -> Result<Vec<u8>> {
+    let end = padded.iter().rposition(|&b| b != 0);
+    match end {
+	Some(end) if padded[end] == 0x80 => Ok(padded[..end].to_vec()),
+	_ => Err(Error::InconsistentShares("secret is not padded".into())),
+    }
+}
+
+// Padding to whole words
+//
+// At 16 and 32 bits the secret is split a word at a time, so one that
+// doesn't fill its last word is padded the same way, but only up to
+// the end of that word. The shares say they were padded (see
+// Share::padded), so a secret that simply ends in 0x80 or NULs, or
+// one split without padding, is never mistaken for a padded one.
+
+// `secret` padded to whole `width`-bit words, if it needs it
+pub(crate) fn pad_words(secret : &[u8], width : u16) -> Option<Vec<u8>> {
+    let bytes = word_bytes(width);
+    if secret.len().is_multiple_of(bytes) { return None }
+    let mut padded = secret.to_vec();
+    padded.push(0x80);
+    padded.resize(padded.len().div_ceil(bytes) * bytes, 0);
+    Some(padded)
+}
+
+// Take off what pad_words added
+fn unpad_words(secret : &mut Vec<u8>, width : u16) -> Result<()> {
+    let zeros = secret.iter().rev().take_while(|&&b| b == 0).count();
+    if zeros >= word_bytes(width) || secret.len() == zeros || secret[secret.len() - zeros - 1] != 0x80 {
+	return Err(Error::InconsistentShares("the shares say the secret was padded to whole \
+					      words, but it isn't".into()))
+    }
+    secret.truncate(secret.len() - zeros - 1);
+    Ok(())
+}
+
+// The solver and encoder are generic over the unit structs in
+// [width] (U8, ...) rather than over the field's storage types, so
+// each width gets its own monomorphised inner loops. Entry points
+// match on the run-time width once and call the right instance.
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all,
+						   fields(width = U::BITS)))]
+pub fn pass_1<U : Width>(field : &U::Field, decoder : &mut Decoder, hardened : bool)
+			 -> Result<()> {
+    // Pass 1: calculate common coefficients a1 .. a_k-1
+    debug!(k = decoder.quorum, hardened, "pass 1");
+    pass_1_at::<U>(field, decoder, Elem::<U>::zero(), hardened)
+}
+
+// The coefficients that make pass 2 evaluate the polynomial at x
+// rather than at 0: the Lagrange basis polynomials at x,
+//
+// L_j(x) = prod (x - x_l) / (x_j - x_l)  over l != j
+//
+// (subtraction being XOR). At x = 0 that's the secret. x must not be
+// one of the shares' own x values, since all but one L_j(x) would be
+// zero there.
+fn pass_1_at<U : Width>(field : &U::Field, decoder : &mut Decoder, x : Elem<U>, hardened : bool)
+			-> Result<()> {
+    check_rows::<U>(decoder, false)?;
+    // j and l are normal array indices
+    let k = decoder.quorum;
+    let mut rng = rand::thread_rng();
+    decoder.coefficients.clear();
+    for j in 0..k {
+	// Accumulate numerator and denominator separately so that we
+	// only need a single inversion per coefficient.
+	let mut num = Elem::<U>::one();
+	let mut den = Elem::<U>::one();
+	let xj = U::get_value(&decoder.x_values, j as usize);
+	for l in 0..k {
+	    if l != j {
+		let xl = U::get_value(&decoder.x_values, l as usize);
+		num = field.mul(num, x ^ xl);
+		den = field.mul(den, xj ^ xl);
+	    }
+	}
+	// Two shares with the same number leave nothing to invert
+	if den == Elem::<U>::zero() {
+	    return Err(Error::InconsistentShares("two shares have the same share number".into()))
+	}
+	let temp = if hardened {
+	    // Blind the inversion: 1/den = r * 1/(den * r) for any
+	    // non-zero r, so the value actually fed into the table
+	    // lookups is uniformly random and independent of the
+	    // share indices.
+	    let r = random_nonzero::<U>(&mut rng);
+	    let inv = field.inv(field.mul(den, r));
+	    field.mul(num, field.mul(inv, r))
+	} else {
+	    field.div(num, den)
+	};
+	if temp == Elem::<U>::zero() {
+	    return Err(Error::InconsistentShares("Linear independence not satisfied".into()))
+	}
+	U::put_value(&mut decoder.coefficients, temp);
+    }
+    Ok(())
+}
+
+// The decoder's fields are public, so before the passes index with
+// them, make sure they still hold k whole rows of share words, and an
+// x value (and, for pass 2, a coefficient) for each row
+fn check_rows<U : Width>(decoder : &Decoder, coefficients : bool) -> Result<()> {
+    let k = decoder.quorum as usize;
+    let bytes = decoder.hex_length / 2;
+    let inconsistent = |msg : String| Err(Error::InconsistentShares(msg));
+    if k == 0 {
+	return inconsistent("no quorum to solve for".into())
+    }
+    if !decoder.hex_length.is_multiple_of(2) || !bytes.is_multiple_of((U::BITS as usize / 8).max(1)) {
+	return inconsistent(format!("{} hex digits is not a whole number of {}-bit words",
+				    decoder.hex_length, U::BITS))
+    }
+    if bytes.checked_mul(k).is_none_or(|need| decoder.shares.len() < need) {
+	return inconsistent(format!("{} bytes of share data don't make {} rows of {} bytes",
+				    decoder.shares.len(), k, bytes))
+    }
+    if U::values(decoder.x_values.len()) < k {
+	return inconsistent(format!("{} share numbers for a quorum of {}",
+				    U::values(decoder.x_values.len()), k))
+    }
+    if coefficients && U::values(decoder.coefficients.len()) < k {
+	return inconsistent(format!("{} coefficients for a quorum of {}",
+				    U::values(decoder.coefficients.len()), k))
+    }
+    Ok(())
+}
+
+// Uniformly random non-zero field element, used for blinding
+pub fn random_nonzero<U : Width>(rng : &mut impl Rng) -> Elem<U> {
+    loop {
+	let r = U::random(rng);
+	if r != Elem::<U>::zero() { return r }
+    }
+}
+
+pub fn pass_2<U : Width>(field : &U::Field, decoder : &mut Decoder,
+			 progress : &mut dyn FnMut(usize, usize)) -> Result<Vec<u8>> {
+    let mut ans = Vec::new();
+    pass_2_into::<U>(field, decoder, &mut ans, progress)?;
+    Ok(ans)
+}
+
+/// As [pass_2], but into `ans` (replacing what was there), so that a
+/// loop of combines can use the same buffer each time
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all,
+						   fields(width = U::BITS)))]
+pub fn pass_2_into<U : Width>(field : &U::Field, decoder : &Decoder, ans : &mut Vec<u8>,
+			      progress : &mut dyn FnMut(usize, usize)) -> Result<()> {
+    check_rows::<U>(decoder, true)?;
+    let k = decoder.quorum as usize;
+
+    let words = U::words(decoder.hex_length / 2);
+
+    debug!(hex_length = decoder.hex_length, words,
+	   x_values = decoder.x_values.len(),
+	   coefficients = decoder.coefficients.len(), "pass 2");
+
+    // Unpack the coefficients once, and give each share its own row,
+    // so the loop below only reads share words and multiplies
+    let row_bytes = decoder.hex_length / 2;
+    let coefficients = (0..k).map(|j| U::get_value(&decoder.coefficients, j));
+    let rows = decoder.shares.chunks(row_bytes.max(1)).take(k);
+    buffer::small_slice(coefficients, |coefficients| buffer::small_slice(rows, |rows| {
+	ans.clear();
+	ans.reserve(row_bytes);
+	for i in 0..words {
+	    if i % PROGRESS_INTERVAL == 0 { progress(i, words) }
+	    let mut temp = Elem::<U>::zero();
+	    for (row, &r) in rows.iter().zip(coefficients) {
+		temp = temp ^ field.mul(U::get(row, i), r);
+	    }
+	    U::put(ans, i, temp);
+	}
+    }));
+    progress(words, words);
+    Ok(())
+}
+
+/// Reconstruct the secret from a decoder holding a quorum of shares.
+///
+/// Any [spares](Decoder::spares) are checked against the answer (see
+/// [pass_3]): if one of them doesn't agree, the shares aren't all
+/// from the same split, and that's an error rather than a secret that
+/// may be wrong.
+pub fn combine_decoder(decoder : &mut Decoder, hardened : bool) -> Result<Vec<u8>> {
+    combine_with_progress(decoder, hardened, |_, _| {})
+}
+
+/// As [combine_decoder], but calls `progress(done, total)` every so
+/// often with the number of secret words recovered so far
+pub fn combine_with_progress(decoder : &mut Decoder, hardened : bool,
+			     mut progress : impl FnMut(usize, usize)) -> Result<Vec<u8>> {
+    let mut secret = Vec::new();
+    combine_into(decoder, hardened, &mut secret, &mut progress)?;
+    Ok(secret)
+}
+
+/// As [combine_with_progress], but puts the secret in `secret`
+/// (replacing what was there). Combining many small secrets in a
+/// loop with the same `secret` buffer, and the same decoder (emptied
+/// with [Decoder::clear]), allocates nothing once the buffers have
+/// grown to size, for quorums up to [SMALL_K](buffer::SMALL_K).
+pub fn combine_into(decoder : &mut Decoder, hardened : bool, secret : &mut Vec<u8>,
+		    mut progress : impl FnMut(usize, usize)) -> Result<()> {
+    if !decoder.is_complete() {
+	return Err(Error::InsufficientShares {
+	    have : decoder.have() as usize, need : decoder.quorum as usize
+	})
+    }
+    check_width(decoder.width)?;
+    match decoder.width {
+	#[cfg(feature = "width-4")]
+	4  => solve::<U4>(decoder, hardened, secret, &mut progress),
+	8  => solve::<U8>(decoder, hardened, secret, &mut progress),
+	#[cfg(feature = "width-16")]
+	16 => solve::<U16>(decoder, hardened, secret, &mut progress),
+	#[cfg(feature = "width-32")]
+	32 => solve::<U32>(decoder, hardened, secret, &mut progress),
+	w => Err(width::unsupported(w)),
+    }?;
+    if decoder.padded { unpad_words(secret, decoder.width)? }
+    Ok(())
+}
+
+fn solve<U : Width>(decoder : &mut Decoder, hardened : bool, secret : &mut Vec<u8>,
+		    progress : &mut dyn FnMut(usize, usize)) -> Result<()> {
+    // The decoder's fields are public, so make sure they still
+    // describe whole words and one row per share before trusting
+    // them to index with
+    let bytes = decoder.hex_length / 2;
+    if !(decoder.hex_length * 4).is_multiple_of(U::BITS as usize)
+	|| decoder.shares.len() != bytes * decoder.have() as usize
+    {
+	return Err(Error::InconsistentShares(format!(
+	    "{} bytes of share data don't make {} shares of {} hex digits",
+	    decoder.shares.len(), decoder.have(), decoder.hex_length)))
+    }
+    // create a field of the appropriate size
+    let field = U::field();
+    // k = 1: the polynomial is just f(x) = a_0, so any share is
+    // already the secret
+    if decoder.quorum == 1 {
+	let words = decoder.hex_length * 4 / U::BITS as usize;
+	progress(words, words);
+	secret.clear();
+	secret.extend_from_slice(&decoder.shares);
+	return pass_3::<U>(&field, decoder, hardened)
+    }
+    pass_1::<U>(&field, decoder, hardened)?;
+    U::pass_2(&field, decoder, secret, progress)?;
+    if secret.len() * 2 != decoder.hex_length {
+	return Err(Error::InconsistentShares(format!(
+	    "recovered {} bytes, but shares hold {} hex digits",
+	    secret.len(), decoder.hex_length)))
+    }
+    pass_3::<U>(&field, decoder, hardened)
+}
+
+/// Pass 3: check the decoder's spares against the polynomial that
+/// the shares in use define. A spare with a share number of its own
+/// has to be what the polynomial gives at that number, and one that
+/// repeats the number of a share in use has to be the same as it.
+/// If any isn't, some share (in use or spare) is from another split
+/// or has been altered, and the secret can't be trusted.
+///
+/// The decoder's coefficients are left as pass 1 made them.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all,
+						   fields(width = U::BITS)))]
+pub fn pass_3<U : Width>(field : &U::Field, decoder : &mut Decoder, hardened : bool)
+			 -> Result<()> {
+    debug!(spares = decoder.spares.len(), "pass 3");
+    if decoder.spares.is_empty() { return Ok(()) }
+    let coefficients = std::mem::take(&mut decoder.coefficients);
+    let spares = std::mem::take(&mut decoder.spares);
+    let result = check_spares::<U>(field, decoder, &spares, hardened);
+    decoder.coefficients = coefficients;
+    decoder.spares = spares;
+    result
+}
+
+fn check_spares<U : Width>(field : &U::Field, decoder : &mut Decoder, spares : &[Share],
+			   hardened : bool) -> Result<()> {
+    let len = decoder.hex_length / 2;
+    let mut expected = Vec::with_capacity(len);
+    for spare in spares {
+	let agrees = match position::<U>(&decoder.x_values, spare.index) {
+	    Some(j) => decoder.shares.get(j * len..(j + 1) * len) == Some(&spare.values[..]),
+	    None => {
+		pass_1_at::<U>(field, decoder, U::from_index(spare.index), hardened)?;
+		U::pass_2(field, decoder, &mut expected, &mut |_, _| {})?;
+		expected == spare.values
+	    },
+	};
+	// The spare only shows that something is wrong: any of the
+	// shares used, or the spare itself, could be the bad one
+	if !agrees {
+	    return Err(Error::InconsistentShares(format!(
+		"the shares aren't consistent (checking spare share {} against the first {} \
+		 exposed it): one is damaged or from another split; try --correct or \
+		 --majority to find which", spare.index, decoder.quorum)))
+	}
+    }
+    Ok(())
+}
+
+/// Reconstruct the secret from a slice of [Share]s (eg, from
+/// [split]) without handling a [Decoder]. The first quorum of them
+/// with different share numbers is used, and the rest are checked
+/// against it as in [combine_decoder]. Use a decoder and
+/// [combine_decoder] for a hardened combine, or to see which shares
+/// were set aside.
+pub fn combine(shares : &[Share]) -> Result<Vec<u8>> {
+    let mut decoder = Decoder::new();
+    for share in shares {
+	decoder.add(share.clone())?;
+    }
+    combine_decoder(&mut decoder, false)
+}
+
+/// Work out the share at `x` that the same split would have made,
+/// from a quorum of its `shares` (any more are ignored), without
+/// recovering the secret.
+///
+/// This is for enrolment: someone joining gets a share with a fresh
+/// number, which works with the others like any share from the
+/// original split. The set ID, quorum and width are the others'. It's
+/// a copy if a share numbered `x` is among those given.
+pub fn derive_share(shares : &[Share], x : u64) -> Result<Share> {
+    let mut decoder = Decoder::new();
+    for share in shares {
+	decoder.add(share.clone())?;
+	if decoder.is_complete() { break }
+    }
+    if !decoder.is_complete() {
+	return Err(Error::InsufficientShares {
+	    have : decoder.have() as usize, need : decoder.quorum.max(1) as usize
+	})
+    }
+    check_range("share index", x, 1, decoder.width)?;
+    let mut values = Vec::new();
+    match decoder.width {
+	#[cfg(feature = "width-4")]
+	4  => derive::<U4>(&mut decoder, x, &mut values)?,
+	8  => derive::<U8>(&mut decoder, x, &mut values)?,
+	#[cfg(feature = "width-16")]
+	16 => derive::<U16>(&mut decoder, x, &mut values)?,
+	#[cfg(feature = "width-32")]
+	32 => derive::<U32>(&mut decoder, x, &mut values)?,
+	w => return Err(width::unsupported(w)),
+    }
+    Ok(Share {
+	set_id : decoder.set_id.clone(), quorum : decoder.quorum, width : decoder.width,
+	index : x, values, padded : decoder.padded,
+    })
+}
+
+fn derive<U : Width>(decoder : &mut Decoder, x : u64, values : &mut Vec<u8>) -> Result<()> {
+    if let Some(share) = decoder.all_shares().into_iter().find(|share| share.index == x) {
+	*values = share.values;
+	return Ok(())
+    }
+    let field = U::field();
+    pass_1_at::<U>(&field, decoder, U::from_index(x), false)?;
+    U::pass_2(&field, decoder, values, &mut |_, _| {})
+}
+
+// Encoding is the mirror image: for each word of the secret, pick
+// random coefficients a_1 .. a_o, set a_0 to the secret word, and
+// evaluate f(S) for each share number S (normally 1 .. n).
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all,
+						   fields(width = U::BITS, k, n = indices.len())))]
+fn encode_words<U : Width, R : Rng + ?Sized>(field : &U::Field, secret : &[u8],
+					    k : u16, indices : &[u64], rng : &mut R,
+					    progress : &mut dyn FnMut(usize, usize))
+					    -> Vec<Vec<u8>> {
+    let words = U::words(secret.len());
+    let n = indices.len();
+    debug!(words, "encoding");
+    // k = 1: f(x) = a_0 everywhere, so each share is a copy of the
+    // secret and there's nothing random to pick
+    if k == 1 {
+	progress(words, words);
+	return vec![secret.to_vec(); n]
+    }
+    let mut shares = vec![Vec::<u8>::with_capacity(secret.len()); n];
+    let mut coeffs = vec![Elem::<U>::zero(); k as usize];
+    for i in 0..words {
+	if i % PROGRESS_INTERVAL == 0 { progress(i, words) }
+	coeffs[0] = U::get(secret, i);
+	for c in coeffs[1..].iter_mut() {
+	    *c = U::random(rng);
+	}
+	for (&s, share) in indices.iter().zip(shares.iter_mut()) {
+	    let x = U::from_index(s);
+	    // Horner's rule, from the highest coefficient down
+	    let mut y = Elem::<U>::zero();
+	    for c in coeffs.iter().rev() {
+		y = field.mul(y, x) ^ *c;
+	    }
+	    U::put(share, i, y);
+	}
+    }
+    // don't leave the polynomial lying around
+    for c in coeffs.iter_mut() { *c = Elem::<U>::zero() }
+    progress(words, words);
+    shares
+}
+
+/// Parameters for splitting a secret
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Params {
+    pub quorum : u16,		// k
+    pub shares : u16,		// n
+    pub width  : u16,		// field width in bits
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub set_id : Option<String>,
+    /// Pad a secret that isn't a whole number of words (see
+    /// [with_word_padding](Params::with_word_padding))
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "std::ops::Not::not"))]
+    pub word_padding : bool,
+}
+
+impl Params {
+    pub fn new(quorum : u16, shares : u16, width : u16) -> Params {
+	Params { quorum, shares, width, set_id : None, word_padding : false }
+    }
+
+    /// Label every share with the given set ID
+    pub fn with_set_id(mut self, id : &str) -> Params {
+	self.set_id = Some(id.to_string());
+	self
+    }
+
+    /// Split a secret that doesn't fill its last 16- or 32-bit word
+    /// (which is otherwise an error) by padding it with 0x80 and then
+    /// zeros, as ISO/IEC 7816-4 does. The shares are marked
+    /// [padded](Share::padded), and combining them takes the padding
+    /// off again.
+    pub fn with_word_padding(mut self) -> Params {
+	self.word_padding = true;
+	self
+    }
+
+    /// Check that the width is available and that `k` and `n` fit in
+    /// the field, without splitting anything
+    pub fn check(&self) -> Result<()> {
+	let (k, n, width) = (self.quorum, self.shares, self.width);
+	check_width(width)?;
+	check_range("quorum", k as u64, 1, width)?;
+	if n < k {
+	    return Err(Error::FieldError(format!("can't make fewer shares ({}) than are \
+						  needed to recover the secret ({})", n, k)))
+	}
+	check_range("number of shares", n as u64, k as u64, width)?;
+	if let Some(id) = &self.set_id {
+	    if !valid_set_id(id) { return Err(Error::FieldError(format!("bad set ID {}", id))) }
+	}
+	Ok(())
+    }
+
+    /// As [check](Params::check), but also refuse a quorum of 1 unless
+    /// `allow_trivial` is set. Splitting with k = 1 works, but every
+    /// share is then a plain copy of the secret, which is rarely what
+    /// a person choosing the parameters meant.
+    pub fn check_sane(&self, allow_trivial : bool) -> Result<()> {
+	self.check()?;
+	if self.quorum < 2 && !allow_trivial {
+	    return Err(Error::FieldError("a quorum of 1 makes every share a copy of the \
+					  secret; choose a quorum of 2 or more".into()))
+	}
+	Ok(())
+    }
+}
+
+/// Split a secret into `n` shares, any `k` of which can recover it,
+/// in order of share number. Each [Share] displays as a
+/// `K=W=S=Values=` line.
+pub fn split(secret : &[u8], k : u16, n : u16, width : u16)
+	     -> Result<Vec<Share>> {
+    split_with_progress(secret, k, n, width, |_, _| {})
+}
+
+/// As [split], but calls `progress(done, total)` every so often with
+/// the number of secret words encoded so far
+pub fn split_with_progress(secret : &[u8], k : u16, n : u16, width : u16,
+			   progress : impl FnMut(usize, usize))
+			   -> Result<Vec<Share>> {
+    split_shares(secret, &Params::new(k, n, width), progress)
+}
+
+/// Split using a full set of [Params], giving the shares as
+/// `K=W=S=Values=` lines
+pub fn split_with_params(secret : &[u8], params : &Params,
+			 progress : impl FnMut(usize, usize))
+			 -> Result<Vec<String>> {
+    split_with_rng(secret, params, &mut rand::thread_rng(), progress)
+}
+
+// Splitting with a caller-supplied random number generator is only
+// for reproducible output (eg, test vectors), so it stays private to
+// the crate rather than inviting anyone to use a weak RNG.
+pub(crate) fn split_with_rng<R : Rng + ?Sized>(secret : &[u8], params : &Params, rng : &mut R,
+					       progress : impl FnMut(usize, usize))
+					       -> Result<Vec<String>> {
+    Ok(split_shares_with_rng(secret, params, rng, progress)?
+       .iter().map(|share| share.to_string()).collect())
+}
+
+/// As [split_with_params], but gives the shares as [Share]s (as
+/// [split] does) rather than text. A share's text is twice the size of the secret, so for
+/// a large secret it's better not to make it until it's written out
+/// (with [Share::write_to], or a codec's
+/// [write_line](codec::ShareCodec::write_line)).
+pub fn split_shares(secret : &[u8], params : &Params, progress : impl FnMut(usize, usize))
+		    -> Result<Vec<Share>> {
+    split_shares_with_rng(secret, params, &mut rand::thread_rng(), progress)
+}
+
+fn split_shares_with_rng<R : Rng + ?Sized>(secret : &[u8], params : &Params, rng : &mut R,
+					   mut progress : impl FnMut(usize, usize))
+					   -> Result<Vec<Share>> {
+    let indices : Vec<u64> = (1..=params.shares as u64).collect();
+    split_shares_at_with_rng(secret, params, &indices, rng, &mut progress)
+}
+
+/// As [split_shares], but making the shares numbered `indices`, in
+/// that order, rather than 1 to n (eg, numbers derived from the
+/// holders' names with [participants](crate::participants)). There
+/// must be `params.shares` of them, all different.
+pub fn split_shares_at(secret : &[u8], params : &Params, indices : &[u64],
+		       mut progress : impl FnMut(usize, usize)) -> Result<Vec<Share>> {
+    split_shares_at_with_rng(secret, params, indices, &mut rand::thread_rng(), &mut progress)
+}
+
+fn split_shares_at_with_rng<R : Rng + ?Sized>(secret : &[u8], params : &Params, indices : &[u64],
+					      rng : &mut R, progress : &mut dyn FnMut(usize, usize))
+					      -> Result<Vec<Share>> {
+    params.check()?;
+    let (k, width) = (params.quorum, params.width);
+    if indices.len() != params.shares as usize {
+	return Err(Error::FieldError(format!("{} share numbers given for {} shares",
+					     indices.len(), params.shares)))
+    }
+    let mut seen = std::collections::HashSet::with_capacity(indices.len());
+    for &s in indices {
+	check_range("share index", s, 1, width)?;
+	if !seen.insert(s) {
+	    return Err(Error::FieldError(format!("share number {} given twice", s)))
+	}
+    }
+    let padded = match params.word_padding { true => pad_words(secret, width), false => None };
+    let secret = padded.as_deref().unwrap_or(secret);
+    if !secret.len().is_multiple_of(word_bytes(width)) {
+	return Err(Error::FieldError(format!("secret of {} bytes is not a whole number of \
+					      {}-bit words", secret.len(), width)))
+    }
+    let shares = match width {
+	#[cfg(feature = "width-4")]
+	4  => encode_words::<U4, _>(&U4::field(), secret, k, indices, rng, progress),
+	8  => encode_words::<U8, _>(&U8::field(), secret, k, indices, rng, progress),
+	#[cfg(feature = "width-16")]
+	16 => encode_words::<U16, _>(&U16::field(), secret, k, indices, rng, progress),
+	#[cfg(feature = "width-32")]
+	32 => encode_words::<U32, _>(&U32::field(), secret, k, indices, rng, progress),
+	w => return Err(width::unsupported(w)),
+    };
+    Ok(shares.into_iter().zip(indices)
+       .map(|(values, &index)| Share {
+	   set_id : params.set_id.clone(), quorum : k, width, index, values,
+	   padded : padded.is_some(),
+       })
+       .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        assert_eq!(2 + 2, 4);
+    }
+
+    // "hello world" split with k = 3 using GF(2**8), poly 0x11b
+    const HELLO : [&str; 5] = [
+	"3=8=1=876ec0b40cf66736af2848=",
+	"3=8=2=a02282b29bd85113fd46ad=",
+	"3=8=3=4f292e6af80e414a200281=",
+	"3=8=4=d7b4290454ec2382cf869f=",
+	"3=8=5=38bf85dc373a33db12c2b3=",
+    ];
+
+    #[test]
+    fn combine_any_quorum() {
+	for lines in [&HELLO[0..3], &HELLO[2..5], &[HELLO[4], HELLO[0], HELLO[2]]] {
+	    let mut decoder = Decoder::new();
+	    for line in lines.iter() {
+		assert!(decoder.add_share(line).unwrap());
+	    }
+	    assert!(decoder.is_complete());
+	    assert_eq!(combine_decoder(&mut decoder, false).unwrap(), b"hello world");
+	}
+    }
+
+    #[test]
+    fn combine_reuses_buffers() {
+	let mut decoder = Decoder::new();
+	let mut secret = Vec::new();
+	let mut buffers = None;
+	for lines in [&HELLO[0..3], &HELLO[2..5], &HELLO[1..4]] {
+	    decoder.clear();
+	    for line in lines.iter() { decoder.add_share(line).unwrap(); }
+	    combine_into(&mut decoder, false, &mut secret, |_, _| {}).unwrap();
+	    assert_eq!(secret, b"hello world");
+	    // Nothing reallocated after the first time round
+	    let now = (secret.as_ptr(), decoder.shares.as_ptr(), decoder.coefficients.as_ptr());
+	    assert_eq!(*buffers.get_or_insert(now), now);
+	}
+	assert_eq!(decoder.coefficients.len(), 3);
+    }
+
+    #[test]
+    fn add_v2_lines() {
+	// as shamir-split writes them by default
+	use codec::{Registry, ShareCodec};
+	let shares = split_shares(b"hello world", &Params::new(2, 3, 8), |_, _| {}).unwrap();
+	let registry = Registry::new();
+	let mut decoder = Decoder::new();
+	for share in shares[1..].iter() {
+	    assert!(decoder.add_share(&registry.encode("v2", share).unwrap()).unwrap());
+	}
+	assert_eq!(combine_decoder(&mut decoder, false).unwrap(), b"hello world");
+
+	assert_eq!(share_number(&registry.encode("v2", &shares[2]).unwrap()), Some("3"));
+	let labelled = Share { set_id : Some("ops".into()), ..shares[2].clone() };
+	assert_eq!(share_set_id(&registry.encode("v2", &labelled).unwrap()), Some("ops"));
+	assert_eq!(share_set_id(&registry.encode("v2", &shares[2]).unwrap()), None);
+
+	let mut line = codec::V2Codec.encode(&shares[0]);
+	let last = line.pop().unwrap();
+	line.push(if last == '0' { '1' } else { '0' });
+	assert!(Decoder::new().add_share(&line).is_err());
+    }
+
+    #[test]
+    fn hardened_combine_agrees() {
+	let mut decoder = Decoder::new();
+	for line in HELLO[1..4].iter() {
+	    decoder.add_share(line).unwrap();
+	}
+	assert_eq!(combine_decoder(&mut decoder, true).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn derive_shares() {
+	let shares : Vec<Share> = HELLO.iter().map(|l| l.parse().unwrap()).collect();
+	// The shares the split made, from any quorum of the others
+	assert_eq!(derive_share(&shares[..3], 4).unwrap(), shares[3]);
+	assert_eq!(derive_share(&shares[2..], 1).unwrap(), shares[0]);
+	assert_eq!(derive_share(&shares, 2).unwrap(), shares[1]);
+	// A new one works with the originals
+	let new = derive_share(&shares[1..4], 100).unwrap();
+	assert_eq!((new.index, new.quorum, new.values.len()), (100, 3, 11));
+	let mut decoder = Decoder::new();
+	for share in [&shares[0], &new, &shares[4]] { decoder.add(share.clone()).unwrap(); }
+	assert_eq!(combine_decoder(&mut decoder, false).unwrap(), b"hello world");
+
+	assert!(matches!(derive_share(&shares[..2], 6),
+			 Err(Error::InsufficientShares { have : 2, need : 3 })));
+	assert!(derive_share(&shares[..3], 0).is_err());
+	assert!(derive_share(&shares[..3], 129).is_err());
+	assert!(derive_share(&[], 1).is_err());
+    }
+
+    #[cfg(feature = "width-4")]
+    #[test]
+    fn four_bit_shares() {
+	// f(x) = 0x5a + x for each nibble, two words to a byte
+	for pair in [["2=4=1=4b=", "2=4=2=78="], ["2=4=8=d2=", "2=4=1=4b="]] {
+	    let mut decoder = Decoder::new();
+	    for line in pair.iter() { decoder.add_share(line).unwrap(); }
+	    assert_eq!(combine_decoder(&mut decoder, false).unwrap(), [0x5a]);
+	    assert_eq!(decoder.all_shares()[0].to_string(), pair[0]);
+	}
+	// Three words and a padding nibble, which comes back as zero
+	assert_eq!(combine(&["2=4=1=4bd0=".parse().unwrap(), "2=4=2=78e0=".parse().unwrap()])
+		   .unwrap(), [0x5a, 0xc0]);
+	assert!(Decoder::new().add_share("2=4=9=4b=").is_err());
+
+	let secret = b"nibbles";
+	let shares = split_shares(secret, &Params::new(3, 8, 4), |_, _| {}).unwrap();
+	assert!(shares.iter().all(|s| s.values.len() == secret.len()));
+	for hardened in [false, true] {
+	    let mut decoder = Decoder::new();
+	    for share in shares[5..].iter() { decoder.add(share.clone()).unwrap(); }
+	    assert_eq!(combine_decoder(&mut decoder, hardened).unwrap(), secret);
+	}
+	assert_eq!(derive_share(&shares[..3], 8).unwrap(), shares[7]);
+	assert!(split_shares(secret, &Params::new(3, 9, 4), |_, _| {}).is_err());
+    }
+
+    #[cfg(feature = "width-16")]
+    #[test]
+    fn sixteen_bit_shares() {
+	// f(x) = 0x1234 + x, with each word written low byte first
+	for pair in [["2=16=1=3512=", "2=16=2=3612="], ["2=16=32768=3492=", "2=16=1=3512="]] {
+	    let mut decoder = Decoder::new();
+	    for line in pair.iter() { decoder.add_share(line).unwrap(); }
+	    assert_eq!(decoder.have(), 2);
+	    assert_eq!(combine_decoder(&mut decoder, false).unwrap(), [0x34, 0x12]);
+	    assert_eq!(decoder.all_shares()[0].to_string(), pair[0]);
+	}
+	assert!(Decoder::new().add_share("2=16=32769=3512=").is_err());
+
+	let params = Params::new(3, 1000, 16);
+	assert!(split_shares(b"odd length", &params, |_, _| {}).is_ok());
+	assert!(split_shares(b"odd length!", &params, |_, _| {}).is_err());
+	let secret = b"words of 16 bits..";
+	let shares = split_shares(secret, &params, |_, _| {}).unwrap();
+	assert!(shares.iter().all(|s| s.width == 16 && s.values.len() == secret.len()));
+	for hardened in [false, true] {
+	    let mut decoder = Decoder::new();
+	    for share in shares[997..].iter() { decoder.add(share.clone()).unwrap(); }
+	    assert_eq!(combine_decoder(&mut decoder, hardened).unwrap(), secret);
+	}
+	// a repeated share number is set aside, as with 8 bits
+	let mut decoder = Decoder::new();
+	for i in [5, 5, 700, 6] { decoder.add(shares[i].clone()).unwrap(); }
+	assert_eq!((decoder.have(), decoder.spares().len()), (3, 0));
+	assert_eq!(combine_decoder(&mut decoder, false).unwrap(), secret);
+	assert_eq!(derive_share(&shares[..3], 999).unwrap(), shares[998]);
+	assert_eq!(combine(&shares[500..]).unwrap(), secret);
+    }
+
+    #[cfg(feature = "width-32")]
+    #[test]
+    fn thirty_two_bit_shares() {
+	// f(x) = 0x12345678 + x, with each word written low byte first
+	let (one, two) = ("2=32=1=79563412=", "2=32=2=7a563412=");
+	for pair in [[one, two], ["2=32=2147483648=78563492=", one]] {
+	    let mut decoder = Decoder::new();
+	    for line in pair.iter() { decoder.add_share(line).unwrap(); }
+	    assert_eq!(combine_decoder(&mut decoder, false).unwrap(), [0x78, 0x56, 0x34, 0x12]);
+	    assert_eq!(decoder.all_shares()[0].to_string(), pair[0]);
+	}
+	// The same shares as Math::FastGF2 writes them, high byte first
+	let perl : Vec<Share> = ["2=32=1=12345679=", "2=32=2=1234567a="].iter()
+	    .map(|line| perl::parse_perl_share(line).unwrap()).collect();
+	assert_eq!(combine(&perl).unwrap(), [0x78, 0x56, 0x34, 0x12]);
+
+	let params = Params::new(4, 6, 32);
+	assert!(split_shares(b"not whole", &params, |_, _| {}).is_err());
+	let secret = b"words of thirty-two bits";
+	let shares = split_shares_at(secret, &params, &[1, 2, 3, 1 << 20, 1 << 30, 1 << 31],
+				     |_, _| {}).unwrap();
+	for hardened in [false, true] {
+	    let mut decoder = Decoder::new();
+	    for share in shares[2..].iter() { decoder.add(share.clone()).unwrap(); }
+	    assert_eq!(combine_decoder(&mut decoder, hardened).unwrap(), secret);
+	}
+	assert_eq!(derive_share(&shares[2..], 2).unwrap(), shares[1]);
+    }
+
+    #[test]
+    fn combine_share_slices() {
+	let shares = split_shares(b"attack at dawn", &Params::new(3, 5, 8), |_, _| {}).unwrap();
+	assert_eq!(combine(&shares).unwrap(), b"attack at dawn");
+	assert_eq!(combine(&shares[2..]).unwrap(), b"attack at dawn");
+	// A repeated share number is passed over for the next one
+	let repeated = [shares[0].clone(), shares[0].clone(), shares[3].clone(), shares[1].clone()];
+	assert_eq!(combine(&repeated).unwrap(), b"attack at dawn");
+	assert!(matches!(combine(&shares[..2]),
+			 Err(Error::InsufficientShares { have : 2, need : 3 })));
+    }
+
+    #[test]
+    fn extra_shares_ignored() {
+	let mut decoder = Decoder::new();
+	for line in HELLO[0..3].iter() {
+	    decoder.add_share(line).unwrap();
+	}
+	assert!(!decoder.add_share(HELLO[3]).unwrap());
+	assert_eq!(decoder.have(), 3);
+    }
+
+    #[test]
+    fn spares_checked() {
+	let inconsistent = |lines : &[&str], hardened| {
+	    let mut decoder = Decoder::new();
+	    for line in lines { decoder.add_share(line).unwrap(); }
+	    let result = combine_decoder(&mut decoder, hardened);
+	    if let Ok(secret) = &result { assert_eq!(secret, b"hello world") }
+	    assert_eq!(decoder.spares().len(), lines.len() - 3);
+	    matches!(result, Err(Error::InconsistentShares(_)))
+	};
+	for hardened in [false, true] {
+	    assert!(!inconsistent(&HELLO, hardened));
+	    // a share 4 with one byte changed
+	    let mut wrong = HELLO[3].to_string();
+	    wrong.replace_range(6..8, if &wrong[6..8] == "00" { "01" } else { "00" });
+	    assert!(inconsistent(&[HELLO[0], HELLO[1], HELLO[2], &wrong], hardened));
+	    // a damaged copy of share 1, whichever comes first
+	    let damaged = "3=8=1=006ec0b40cf66736af2848=";
+	    assert!(inconsistent(&[HELLO[0], damaged, HELLO[1], HELLO[2]], hardened));
+	    assert!(inconsistent(&[damaged, HELLO[0], HELLO[1], HELLO[2]], hardened));
+	}
+
+	// shares from two splits of the same secret don't mix
+	let params = Params::new(2, 3, 8);
+	let ours = split_shares(b"attack at dawn", &params, |_, _| {}).unwrap();
+	let theirs = split_shares(b"attack at dawn", &params, |_, _| {}).unwrap();
+	assert_eq!(combine(&ours).unwrap(), b"attack at dawn");
+	let mixed = [ours[0].clone(), ours[1].clone(), theirs[2].clone()];
+	assert!(matches!(combine(&mixed), Err(Error::InconsistentShares(_))));
+
+	// with a quorum of one, every share is the secret
+	let plain = split_shares(b"plain", &Params::new(1, 3, 8), |_, _| {}).unwrap();
+	assert_eq!(combine(&plain).unwrap(), b"plain");
+	let mut bad = plain.clone();
+	bad[2].values[0] ^= 1;
+	assert!(combine(&bad).is_err());
+    }
+
+    #[test]
+    fn split_then_combine() {
+	let shares = split(b"attack at dawn", 3, 6, 8).unwrap();
+	assert_eq!(shares.len(), 6);
+	assert!(shares[5].to_string().starts_with("3=8=6="));
+	for quorum in [&shares[0..3], &shares[3..6], &shares[1..4]] {
+	    assert_eq!(combine(quorum).unwrap(), b"attack at dawn");
+	    let mut decoder = Decoder::new();
+	    for line in quorum.iter() {
+		decoder.add_share(&line.to_string()).unwrap();
+	    }
+	    assert_eq!(combine_decoder(&mut decoder, false).unwrap(), b"attack at dawn");
+	}
+    }
+
+    #[test]
+    fn progress_reaches_total() {
+	let secret = vec![0x5a; 10000];
+	let mut calls = Vec::new();
+	let shares = split_with_progress(&secret, 2, 2, 8, |done, total| {
+	    calls.push((done, total))
+	}).unwrap();
+	assert_eq!(calls.first(), Some(&(0, 10000)));
+	assert_eq!(calls.last(), Some(&(10000, 10000)));
+
+	let mut decoder = Decoder::new();
+	for share in shares.iter() { decoder.add(share.clone()).unwrap(); }
+	let mut last = (0, 0);
+	let ans = combine_with_progress(&mut decoder, false, |d, t| last = (d, t)).unwrap();
+	assert_eq!(ans, secret);
+	assert_eq!(last, (10000, 10000));
+    }
+
+    #[test]
+    fn set_ids() {
+	let params = Params::new(2, 3, 8).with_set_id("a1b2c3d4");
+	let shares = split_with_params(b"abc", &params, |_, _| {}).unwrap();
+	assert!(shares[0].starts_with("a1b2c3d4=2=8=1="));
+	let mut decoder = Decoder::new();
+	decoder.add_share(&shares[0]).unwrap();
+	assert_eq!(decoder.set_id.as_deref(), Some("a1b2c3d4"));
+
+	// shares from another set (or no set) don't mix
+	let other = split_with_params(b"abc", &params.clone().with_set_id("x"),
+				      |_, _| {}).unwrap();
+	assert!(decoder.add_share(&other[1]).is_err());
+	assert!(decoder.add_share(&shares[1][9..]).is_err());
+	decoder.add_share(&shares[2]).unwrap();
+	assert_eq!(combine_decoder(&mut decoder, false).unwrap(), b"abc");
+
+	assert!(split_with_params(b"abc", &params.with_set_id("no spaces"),
+				  |_, _| {}).is_err());
+    }
+
+    #[test]
+    fn grouping() {
+	let lines = ["a=2=8=1=00=", "2=8=1=00=", "b=2=8=1=00=", "a=2=8=2=00="];
+	let groups = group_by_set(&lines);
+	assert_eq!(groups.len(), 3);
+	assert_eq!(groups[0], (Some("a"), vec![lines[0], lines[3]]));
+	assert_eq!(groups[1], (None, vec![lines[1]]));
+	assert_eq!(groups[2], (Some("b"), vec![lines[2]]));
+    }
+
+    #[test]
+    fn fingerprints() {
+	let fp = share_fingerprint(HELLO[0]);
+	assert_eq!(fp.len(), 9);
+	assert_eq!(fp, share_fingerprint(&format!("  {}\n", HELLO[0])));
+	assert_ne!(fp, share_fingerprint(HELLO[1]));
+	assert_ne!(fp, share_fingerprint_with(HELLO[0], hash::Algorithm::Sha512));
+    }
+
+    #[test]
+    fn split_rejects_bad_parameters() {
+	assert!(split(b"x", 0, 3, 8).is_err());
+	assert!(split(b"x", 4, 3, 8).is_err());
+	assert_eq!(split(b"x", 2, 200, 8).unwrap_err().to_string(),
+		   "number of shares 200 out of range 2..=128 for GF(2^8)");
+	assert!(Params::new(2, 200, 8).check().is_err());
+	assert!(Params::new(2, 128, 8).check().is_ok());
+	assert_eq!(Params::new(2, 3, 12).check().unwrap_err().to_string(), "bad field width 12");
+	assert_eq!(Params::new(4, 3, 8).check().unwrap_err().to_string(),
+		   "can't make fewer shares (3) than are needed to recover the secret (4)");
+	assert!(Params::new(1, 3, 8).check().is_ok());
+	assert!(Params::new(1, 3, 8).check_sane(false).is_err());
+	assert!(Params::new(1, 3, 8).check_sane(true).is_ok());
+    }
+
+    #[test]
+    fn share_index_range() {
+	let error = |line| Decoder::new().add_share(line).unwrap_err().to_string();
+	assert_eq!(error("3=8=0=00="), "share index 0 out of range 1..=128 for GF(2^8)");
+	assert_eq!(error("3=8=300=00="), "share index 300 out of range 1..=128 for GF(2^8)");
+	assert_eq!(error("3=8=18446744073709551615=00="),
+		   "share index 18446744073709551615 out of range 1..=128 for GF(2^8)");
+	assert_eq!(error("129=8=1=00="), "quorum 129 out of range 1..=128 for GF(2^8)");
+	assert!(Decoder::new().add_share("3=8=128=00=").is_ok());
+    }
+
+    #[test]
+    fn mismatched_shares_rejected() {
+	let mut decoder = Decoder::new();
+	decoder.add_share(HELLO[0]).unwrap();
+	assert!(matches!(decoder.add_share("2=8=2=a02282b29bd85113fd46ad="),
+			 Err(Error::InconsistentShares(_))));
+	assert!(matches!(decoder.add_share("3=8=2=a022="),
+			 Err(Error::InconsistentShares(_))));
+	assert!(matches!(decoder.add_share("3=8=2=zz2282b29bd85113fd46ad="),
+			 Err(Error::ParseError { line : 2, error : ParseError::BadHex(_) })));
+	assert!(matches!(decoder.add_share("3=8=2"),
+			 Err(Error::ParseError { error : ParseError::FieldCount, .. })));
+	assert!(matches!(decoder.add_share("3=7=2=a0="), Err(Error::FieldError(_))));
+	assert_eq!(decoder.have(), 1);
+	assert!(matches!(combine_decoder(&mut decoder, false),
+			 Err(Error::InsufficientShares { have : 1, need : 3 })));
+    }
+
+    #[test]
+    fn parse_share_fields() {
+	let share = parse_share(" ab-1=3=8=2=a022=\n").unwrap();
+	assert_eq!(share, Share { set_id : Some("ab-1".into()), quorum : 3, width : 8,
+				  index : 2, values : vec![0xa0, 0x22], padded : false });
+	assert_eq!(share.to_string(), "ab-1=3=8=2=a022=");
+	assert_eq!(HELLO[0].parse::<Share>().unwrap().to_string(), HELLO[0]);
+
+	assert_eq!(parse_share(""), Err(ParseError::FieldCount));
+	assert_eq!(parse_share("a b=3=8=2=a0="), Err(ParseError::BadSetId("a b".into())));
+	assert_eq!(parse_share("3=x=2=a0="), Err(ParseError::BadNumber("width")));
+	assert_eq!(parse_share("3=8=2=a0=1"), Err(ParseError::TrailingData));
+	assert_eq!(parse_share("3=16=2=a0="), Err(ParseError::BadLength("a0".into())));
+	assert_eq!(parse_share("3=4=2=a="), Err(ParseError::MissingPadding("a".into())));
+	// range checks are the decoder's business
+	assert!(parse_share("300=7=0==").is_ok());
+    }
+
+    // Capture everything logged at any level while running `f`
+    #[cfg(feature = "tracing")]
+    fn capture_log(f : impl FnOnce()) -> String {
+	use std::sync::{Arc, Mutex};
+	#[derive(Clone, Default)]
+	struct Buf(Arc<Mutex<Vec<u8>>>);
+	impl std::io::Write for Buf {
+	    fn write(&mut self, b : &[u8]) -> std::io::Result<usize> {
+		self.0.lock().unwrap().write(b)
+	    }
+	    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+	}
+	let buf = Buf::default();
+	let writer = buf.clone();
+	let subscriber = tracing_subscriber::fmt()
+	    .with_max_level(tracing::Level::TRACE)
+	    .without_time()
+	    .with_writer(move || writer.clone())
+	    .finish();
+	tracing::subscriber::with_default(subscriber, f);
+	let log = buf.0.lock().unwrap().clone();
+	String::from_utf8(log).unwrap()
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn log_has_no_secrets() {
+	let secret = b"hello world";
+	let mut shares = Vec::new();
+	let mut coefficients = Vec::new();
+	let log = capture_log(|| {
+	    shares = split(secret, 3, 5, 8).unwrap();
+	    let mut decoder = Decoder::new();
+	    for share in shares[1..4].iter() { decoder.add(share.clone()).unwrap(); }
+	    assert_eq!(combine_decoder(&mut decoder, true).unwrap(), secret);
+	    coefficients = decoder.coefficients.clone();
+	});
+	assert!(log.contains("pass 1") && log.contains("pass 2") && log.contains("encoding"));
+
+	let mut forbidden = vec![String::from_utf8_lossy(secret).into_owned(),
+				 hex::encode(secret), hex::encode(&coefficients)];
+	for share in shares.iter() {
+	    let values = parse_share(&share.to_string()).unwrap().values;
+	    forbidden.push(hex::encode(&values));
+	    // any run of 3 bytes from a share is too much
+	    forbidden.extend(values.windows(3).map(hex::encode));
+	}
+	forbidden.extend(secret.windows(3).map(hex::encode));
+	for f in forbidden.iter() {
+	    assert!(!log.contains(f.as_str()), "log leaks {}:\n{}", f, log);
+	}
+    }
+
+    // The serialized form is a stored format, so changing it needs a
+    // new schema version and a way to read the old one. These pin
+    // version 1.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_schema_v1() {
+	let share = parse_share("ab=3=8=2=a022=").unwrap();
+	let json = r#"{"set_id":"ab","quorum":3,"width":8,"index":2,"values":"a022"}"#;
+	assert_eq!(serde_json::to_string(&share).unwrap(), json);
+	assert_eq!(serde_json::from_str::<Share>(json).unwrap(), share);
+	let share = parse_share(HELLO[0]).unwrap();
+	let json = r#"{"quorum":3,"width":8,"index":1,"values":"876ec0b40cf66736af2848"}"#;
+	assert_eq!(serde_json::to_string(&share).unwrap(), json);
+	assert_eq!(serde_json::from_str::<Share>(json).unwrap(), share);
+	assert!(serde_json::from_str::<Share>(r#"{"quorum":3,"width":8,"index":1,"values":"zz"}"#).is_err());
+	let padded = Share { padded : true, ..share };
+	let json = r#"{"quorum":3,"width":8,"index":1,"values":"876ec0b40cf66736af2848","padded":true}"#;
+	assert_eq!(serde_json::to_string(&padded).unwrap(), json);
+	assert_eq!(serde_json::from_str::<Share>(json).unwrap(), padded);
+
+	let params = Params::new(3, 5, 8).with_set_id("x");
+	let json = r#"{"quorum":3,"shares":5,"width":8,"set_id":"x"}"#;
+	assert_eq!(serde_json::to_string(&params).unwrap(), json);
+	assert_eq!(serde_json::from_str::<Params>(json).unwrap(), params);
+	assert_eq!(serde_json::from_str::<Params>(r#"{"quorum":3,"shares":5,"width":8}"#).unwrap(),
+		   Params::new(3, 5, 8));
+    }
+
+    #[test]
+    fn unchecked_set_id_rejected() {
+	let mut share = parse_share(HELLO[0]).unwrap();
+	share.set_id = Some("not valid".into());
+	assert!(matches!(Decoder::new().add(share), Err(Error::FieldError(_))));
+    }
+
+    #[test]
+    fn repeated_share_number_set_aside() {
+	let mut decoder = Decoder::new();
+	// a damaged copy of share 1 followed by the real thing
+	assert!(decoder.add_share("3=8=1=006ec0b40cf66736af2848=").unwrap());
+	assert!(!decoder.add_share(HELLO[0]).unwrap());
+	assert!(decoder.add_share(HELLO[1]).unwrap());
+	assert!(!decoder.is_complete());
+	assert!(decoder.add_share(HELLO[2]).unwrap());
+	assert!(!decoder.add_share(HELLO[3]).unwrap());
+	assert_eq!(decoder.spares().iter().map(|s| s.index).collect::<Vec<_>>(), [1, 4]);
+	assert_eq!(decoder.duplicates(), 0);
+	// previously this tried to solve with x = 1 twice
+	let mut decoder = Decoder::new();
+	for line in [HELLO[0], HELLO[0], HELLO[1], HELLO[2]] {
+	    decoder.add_share(line).unwrap();
+	}
+	assert_eq!(combine_decoder(&mut decoder, false).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn exact_copies_dropped() {
+	let mut decoder = Decoder::new();
+	let upper = HELLO[1].to_uppercase();
+	for line in [HELLO[0], HELLO[1], upper.as_str(), HELLO[0], HELLO[3], HELLO[3]] {
+	    decoder.add_share(line).unwrap();
+	}
+	assert_eq!(decoder.have(), 3);
+	assert_eq!(decoder.duplicates(), 3);
+	assert!(decoder.spares().is_empty());
+    }
+
+    #[test]
+    fn quorum_of_one() {
+	let shares = split(b"plain", 1, 3, 8).unwrap();
+	let lines : Vec<String> = shares.iter().map(|share| share.to_string()).collect();
+	assert_eq!(lines, ["1=8=1=706c61696e=", "1=8=2=706c61696e=", "1=8=3=706c61696e="]);
+	for share in shares {
+	    let mut decoder = Decoder::new();
+	    assert!(decoder.add(share).unwrap());
+	    assert!(decoder.is_complete());
+	    assert_eq!(combine_decoder(&mut decoder, true).unwrap(), b"plain");
+	}
+    }
+
+    #[test]
+    fn all_shares_required() {
+	for n in [2, 5, 128] {
+	    let shares = split(b"every one", n, n, 8).unwrap();
+	    let mut decoder = Decoder::new();
+	    for share in shares[1..].iter() { assert!(decoder.add(share.clone()).unwrap()); }
+	    assert!(matches!(combine_decoder(&mut decoder.clone(), false),
+			     Err(Error::InsufficientShares { .. })));
+	    assert!(decoder.add(shares[0].clone()).unwrap());
+	    assert_eq!(combine_decoder(&mut decoder, false).unwrap(), b"every one");
+	}
+    }
+
+    #[test]
+    fn tampered_lengths_rejected() {
+	let mut decoder = Decoder::new();
+	for line in HELLO[0..3].iter() { decoder.add_share(line).unwrap(); }
+	let mut short = decoder.clone();
+	short.hex_length -= 2;
+	assert!(matches!(combine_decoder(&mut short, false), Err(Error::InconsistentShares(_))));
+	let mut truncated = decoder.clone();
+	truncated.shares.pop();
+	assert!(matches!(combine_decoder(&mut truncated, false), Err(Error::InconsistentShares(_))));
+	assert_eq!(combine_decoder(&mut decoder, false).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn tampered_decoder_is_an_error() {
+	let mut decoder = Decoder::new();
+	for line in HELLO[0..3].iter() { decoder.add_share(line).unwrap(); }
+	let inconsistent = |d : &mut Decoder, hardened| {
+	    matches!(combine_decoder(d, hardened), Err(Error::InconsistentShares(_)))
+	};
+	// Two x values the same would mean inverting zero
+	for hardened in [false, true] {
+	    let mut same = decoder.clone();
+	    same.x_values[1] = same.x_values[0];
+	    assert!(inconsistent(&mut same, hardened));
+	}
+	let mut wide = decoder.clone();
+	wide.width = 7;
+	assert!(matches!(combine_decoder(&mut wide, false), Err(Error::FieldError(_))));
+	let mut odd = decoder.clone();
+	odd.hex_length += 1;
+	assert!(inconsistent(&mut odd, false));
+
+	// The passes, called directly, check what they're given
+	let field = U8::field();
+	let mut bare = decoder.clone();
+	bare.x_values.pop();
+	assert!(pass_1::<U8>(&field, &mut bare, false).is_err());
+	assert!(pass_2::<U8>(&field, &mut decoder.clone(), &mut |_, _| {}).is_err());
+	let mut zero = decoder.clone();
+	zero.quorum = 0;
+	assert!(pass_1::<U8>(&field, &mut zero, false).is_err());
+	assert_eq!(bare.all_shares().len(), 2);
+
+	pass_1::<U8>(&field, &mut decoder, false).unwrap();
+	assert_eq!(pass_2::<U8>(&field, &mut decoder, &mut |_, _| {}).unwrap(), b"hello world");
+	assert_eq!(max_index(0), 0);
+	assert_eq!(max_index(65), u64::MAX);
+    }
+
+    #[test]
+    fn wrapped_shares() {
+	let wrapped = wrap_share(HELLO[0], 10);
+	assert_eq!(wrapped, "3=8=1=876\\\nec0b40cf6\\\n6736af284\\\n8=");
+	assert!(wrapped.lines().all(|l| l.len() <= 10));
+	assert_eq!(wrap_share(HELLO[0], 80), HELLO[0]);
+
+	let text = format!("# comment\n{}\n  {}\n", wrapped.replace('\n', "\n    "), HELLO[1]);
+	assert_eq!(join_continuations(text.lines()), ["# comment", HELLO[0], "  3=8=2=a02282b29bd85113fd46ad="]);
+	assert_eq!(join_continuations(["3=8=\\"]), ["3=8="]);
+    }
+
+    #[test]
+    fn hex_codec() {
+	let bytes : Vec<u8> = (0..=255).chain(0..100).collect();
+	for len in [0, 1, 15, 16, 17, 31, 32, 33, 64, 356] {
+	    let text = encode_hex(&bytes[..len]);
+	    assert_eq!(text, hex::encode(&bytes[..len]));
+	    assert_eq!(decode_hex(&text).unwrap(), &bytes[..len]);
+	    assert_eq!(decode_hex(&text.to_uppercase()).unwrap(), &bytes[..len]);
+	}
+	let long = "0123456789abcdef".repeat(5);
+	assert!(decode_hex(&long[1..]).is_none());
+	assert!(decode_hex(&long.replace('7', "g")).is_none());
+    }
+
+    #[test]
+    fn padding() {
+	let padded = pad_secret(b"hello", 128).unwrap();
+	assert_eq!(padded, b"hello\x80\0\0\0\0\0\0\0\0\0\0");
+	assert_eq!(unpad_secret(&padded).unwrap(), b"hello");
+	assert_eq!(unpad_secret(&pad_secret(b"", 8).unwrap()).unwrap(), b"");
+	assert_eq!(unpad_secret(&pad_secret(b"\x80\0", 32).unwrap()).unwrap(), b"\x80\0");
+	assert!(pad_secret(b"16 bytes exactly", 128).is_err());
+	assert!(pad_secret(b"hello", 100).is_err());
+	assert!(unpad_secret(b"hello").is_err());
+
+	let shares = split(&padded, 2, 3, 8).unwrap();
+	let mut decoder = Decoder::new();
+	for share in shares.iter().take(2) { decoder.add(share.clone()).unwrap(); }
+	assert_eq!(decoder.hex_length, 32);
+	assert_eq!(unpad_secret(&combine_decoder(&mut decoder, false).unwrap()).unwrap(), b"hello");
+    }
+
+    #[cfg(all(feature = "width-16", feature = "width-32"))]
+    #[test]
+    fn word_padding() {
+	use codec::ShareCodec;
+	assert!(split_shares(b"abc", &Params::new(2, 3, 16), |_, _| {}).is_err());
+	// Whatever the secret ends in, only padding that was added comes off
+	for width in [16, 32] {
+	    let params = Params::new(2, 3, width).with_word_padding();
+	    for secret in [&b"ab\0"[..], b"a\x80", b"\0", b"abcd", b"abcde\0\0"] {
+		let shares = split_shares(secret, &params, |_, _| {}).unwrap();
+		let whole = secret.len() % (width as usize / 8) == 0;
+		assert_eq!(shares[0].padded, !whole);
+		assert_eq!(combine(&shares[1..]).unwrap(), secret);
+		let line = codec::V2Codec.encode(&shares[0]);
+		let mut decoder = Decoder::new();
+		decoder.add_share(&line).unwrap();
+		decoder.add(shares[2].clone()).unwrap();
+		assert_eq!(combine_decoder(&mut decoder, false).unwrap(), secret);
+	    }
+	}
+	// A share that doesn't say it was padded doesn't go with ones that do
+	let shares = split_shares(b"abc", &Params::new(2, 3, 16).with_word_padding(), |_, _| {})
+	    .unwrap();
+	let unmarked = Share { padded : false, ..shares[1].clone() };
+	let mut decoder = Decoder::new();
+	decoder.add(shares[0].clone()).unwrap();
+	assert!(matches!(decoder.add(unmarked), Err(Error::InconsistentShares(_))));
+	// ... and a padded share whose secret isn't padded is an error
+	let fake = split_shares(b"abcd", &Params::new(2, 3, 16), |_, _| {}).unwrap();
+	let fake : Vec<Share> = fake.into_iter().map(|share| Share { padded : true, ..share }).collect();
+	assert!(matches!(combine(&fake), Err(Error::InconsistentShares(_))));
+    }
+}
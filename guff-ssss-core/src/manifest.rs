@@ -0,0 +1,436 @@
+//! Public verification records
+//!
+//! A [Manifest] is written alongside the shares when a secret is
+//! split, and later used to check that a set of shares is the one the
+//! dealer handed out:
+//!
+//! * a hash of every share, so shares can be checked one at a time
+//!   before anything is reconstructed
+//! * a salted commitment to the secret, so the result of combining
+//!   can be checked too
+//! * optionally, the dealer's Ed25519 signature over all of the above
+//!
+//! None of that helps to put the shares together, but the commitment
+//! does let anyone holding the record test a guess at the secret. A
+//! random key can't be guessed, but a password or passphrase can, so
+//! the commitment is made with argon2id (whose costs are recorded in
+//! the `stretch:` line) to make each guess slow. Even so, the record
+//! of a guessable secret is better kept with the shares than published.
+//!
+//! The text form is one `key: value` per line:
+//!
+//! ```text
+//! guff-ssss manifest v1
+//! set: tv0001
+//! quorum: 2
+//! shares: 3
+//! width: 8
+//! stretch: argon2id m=19456,t=2,p=1
+//! salt: <32 hex digits>
+//! commitment: <argon2id of secret, with salt>
+//! share: 1 <SHA-256 of share line>
+//! ...
+//! signed-by: <Ed25519 public key>
+//! signature: <Ed25519 signature>
+//! ```
+//!
+//! The signature covers every line before `signed-by`, exactly as
+//! written by [Display](std::fmt::Display). A record made with a
+//! [hash](crate::hash) other than SHA-256 says which in a `hash:` line
+//! after `width:`, and its share hashes are made with that instead.
+//! Records from before the commitment was stretched have no `stretch:`
+//! line, and their commitment is a plain hash of salt and secret.
+
+use crate::error::{Error, Result};
+use crate::hash::Algorithm;
+use crate::{valid_set_id, Params, Share};
+use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
+use rand::RngCore;
+use std::convert::TryInto;
+
+/// The dealer's signing key (an Ed25519 key, made from 32 secret bytes)
+pub use ed25519_dalek::SigningKey as DealerKey;
+
+const HEADER : &str = "guff-ssss manifest v1";
+
+// Bytes of argon2id output in a commitment
+const STRETCHED_LEN : usize = 32;
+
+// The most memory a record can ask argon2id for (in KiB), so that a
+// hostile record can't make checking a secret take all of it
+const MAX_MEMORY : u32 = 1 << 20;
+
+/// The argon2id costs a commitment is made with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stretch {
+    /// Memory, in KiB
+    pub memory : u32,
+    /// Passes over the memory
+    pub passes : u32,
+    /// Degree of parallelism
+    pub lanes : u32,
+}
+
+impl Default for Stretch {
+    /// argon2's recommended costs (19 MiB, 2 passes, 1 lane)
+    fn default() -> Stretch {
+	Stretch {
+	    memory : argon2::Params::DEFAULT_M_COST,
+	    passes : argon2::Params::DEFAULT_T_COST,
+	    lanes : argon2::Params::DEFAULT_P_COST,
+	}
+    }
+}
+
+impl Stretch {
+    fn hasher(&self) -> Result<argon2::Argon2<'static>> {
+	if self.memory > MAX_MEMORY {
+	    return Err(bad(&format!("argon2id memory {} KiB is more than {} KiB",
+				    self.memory, MAX_MEMORY)))
+	}
+	let params = argon2::Params::new(self.memory, self.passes, self.lanes,
+					 Some(STRETCHED_LEN))
+	    .map_err(|e| bad(&format!("bad argon2id costs: {}", e)))?;
+	Ok(argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params))
+    }
+}
+
+impl std::fmt::Display for Stretch {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	write!(f, "argon2id m={},t={},p={}", self.memory, self.passes, self.lanes)
+    }
+}
+
+impl std::str::FromStr for Stretch {
+    type Err = Error;
+
+    fn from_str(text : &str) -> Result<Stretch> {
+	let costs = text.strip_prefix("argon2id ")
+	    .ok_or_else(|| bad(&format!("unknown stretch '{}'", text)))?;
+	let mut fields = costs.split(',').map(|field| field.split_once('='));
+	let mut cost = |name| match fields.next() {
+	    Some(Some((key, value))) if key == name => value.parse()
+		.map_err(|_| bad(&format!("bad argon2id {} cost", name))),
+	    _ => Err(bad(&format!("argon2id costs should be m=,t=,p=, not '{}'", costs))),
+	};
+	let stretch = Stretch { memory : cost("m")?, passes : cost("t")?, lanes : cost("p")? };
+	if fields.next().is_some() {
+	    return Err(bad(&format!("argon2id costs should be m=,t=,p=, not '{}'", costs)))
+	}
+	stretch.hasher()?;
+	Ok(stretch)
+    }
+}
+
+/// A record of one split
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub set_id : Option<String>,
+    pub quorum : u16,
+    pub shares : u16,
+    pub width  : u16,
+    /// What the share hashes (and an unstretched commitment) are made
+    /// with
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_sha256"))]
+    pub hash : Algorithm,
+    /// How the commitment was stretched; None in records made before
+    /// it was, whose commitment is a plain hash of salt and secret
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub stretch : Option<Stretch>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub salt : Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub commitment : Vec<u8>,
+    /// (share number, hash of the share's text)
+    pub share_hashes : Vec<(u64, String)>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub signed_by : Option<String>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub signature : Option<String>,
+}
+
+#[cfg(feature = "serde")]
+fn is_sha256(hash : &Algorithm) -> bool { *hash == Algorithm::Sha256 }
+
+fn commit(salt : &[u8], secret : &[u8], hash : Algorithm, stretch : Option<Stretch>)
+	  -> Result<Vec<u8>> {
+    let stretch = match stretch {
+	Some(stretch) => stretch,
+	None => return Ok(hash.hasher().chain(salt).chain(secret).finish()),
+    };
+    let mut commitment = vec![0u8; STRETCHED_LEN];
+    stretch.hasher()?.hash_password_into(secret, salt, &mut commitment)
+	.map_err(|e| bad(&format!("can't make the commitment: {}", e)))?;
+    Ok(commitment)
+}
+
+// Shares are hashed in their canonical text form, so that case or
+// whitespace differences in how they were typed back in don't matter
+pub(crate) fn share_hash(share : &Share, hash : Algorithm) -> String {
+    hex::encode(hash.digest(share.to_string().as_bytes()))
+}
+
+fn bad(what : &str) -> Error { Error::BadManifest(what.to_string()) }
+fn failed(what : String) -> Error { Error::Verification(what) }
+
+impl Manifest {
+    /// Record a split of `secret` with `params` into `shares`
+    pub fn new(params : &Params, secret : &[u8], shares : &[String]) -> Result<Manifest> {
+	Manifest::new_with_hash(params, secret, shares, Algorithm::Sha256)
+    }
+
+    /// As [new](Manifest::new), hashing shares with `hash` rather
+    /// than SHA-256
+    pub fn new_with_hash(params : &Params, secret : &[u8], shares : &[String], hash : Algorithm)
+			 -> Result<Manifest> {
+	let mut salt = vec![0u8; 16];
+	rand::thread_rng().fill_bytes(&mut salt);
+	let share_hashes = shares.iter().enumerate().map(|(i, line)| {
+	    let share : Share = line.parse()
+		.map_err(|error| Error::ParseError { line : i + 1, error })?;
+	    Ok((share.index, share_hash(&share, hash)))
+	}).collect::<Result<Vec<_>>>()?;
+	let stretch = Some(Stretch::default());
+	Ok(Manifest {
+	    set_id : params.set_id.clone(),
+	    quorum : params.quorum, shares : params.shares, width : params.width, hash, stretch,
+	    commitment : commit(&salt, secret, hash, stretch)?, salt, share_hashes,
+	    signed_by : None, signature : None,
+	})
+    }
+
+    // Everything the signature covers
+    fn body(&self) -> String {
+	let mut text = format!("{}\n", HEADER);
+	if let Some(id) = &self.set_id { text += &format!("set: {}\n", id) }
+	text += &format!("quorum: {}\nshares: {}\nwidth: {}\n", self.quorum, self.shares, self.width);
+	if self.hash != Algorithm::Sha256 { text += &format!("hash: {}\n", self.hash) }
+	if let Some(stretch) = &self.stretch { text += &format!("stretch: {}\n", stretch) }
+	text += &format!("salt: {}\ncommitment: {}\n",
+			 hex::encode(&self.salt), hex::encode(&self.commitment));
+	for (index, hash) in self.share_hashes.iter() {
+	    text += &format!("share: {} {}\n", index, hash);
+	}
+	text
+    }
+
+    /// Sign the record with the dealer's key
+    pub fn sign(&mut self, key : &DealerKey) {
+	let signature = key.sign(self.body().as_bytes());
+	self.signed_by = Some(hex::encode(key.verifying_key().as_bytes()));
+	self.signature = Some(hex::encode(signature.to_bytes()));
+    }
+
+    /// Check the dealer's signature, returning the public key it was
+    /// made with, or None if the record isn't signed
+    pub fn verify_signature(&self) -> Result<Option<String>> {
+	let (key, signature) = match (&self.signed_by, &self.signature) {
+	    (Some(key), Some(signature)) => (key, signature),
+	    (None, None) => return Ok(None),
+	    _ => return Err(bad("signed-by and signature must come together")),
+	};
+	let key_bytes : [u8; 32] = hex::decode(key).ok()
+	    .and_then(|k| k.try_into().ok()).ok_or_else(|| bad("bad signed-by key"))?;
+	let sig_bytes : [u8; 64] = hex::decode(signature).ok()
+	    .and_then(|s| s.try_into().ok()).ok_or_else(|| bad("bad signature"))?;
+	let verifying = VerifyingKey::from_bytes(&key_bytes)
+	    .map_err(|_| bad("bad signed-by key"))?;
+	verifying.verify(self.body().as_bytes(), &Signature::from_bytes(&sig_bytes))
+	    .map_err(|_| failed("dealer signature doesn't match the manifest".into()))?;
+	Ok(Some(key.clone()))
+    }
+
+    /// Check that a share is one of those the record lists
+    pub fn check_share(&self, share : &Share) -> Result<()> {
+	if share.set_id != self.set_id || share.quorum != self.quorum
+	    || share.width != self.width {
+	    return Err(failed(format!("share {} is from a different split", share.index)))
+	}
+	let hash = share_hash(share, self.hash);
+	if self.share_hashes.iter().any(|(index, h)| *index == share.index && *h == hash) {
+	    Ok(())
+	} else {
+	    Err(failed(format!("share {} is not listed in the manifest", share.index)))
+	}
+    }
+
+    /// The record as a YAML document (with the `yaml` feature)
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml(&self) -> String {
+	serde_yaml::to_string(self).expect("manifests always serialize")
+    }
+
+    /// Read a record written by [to_yaml](Manifest::to_yaml)
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(text : &str) -> Result<Manifest> {
+	serde_yaml::from_str(text).map_err(|e| Error::BadManifest(e.to_string()))
+    }
+
+    /// Check a recovered secret against the commitment
+    pub fn check_secret(&self, secret : &[u8]) -> Result<()> {
+	if commit(&self.salt, secret, self.hash, self.stretch)? == self.commitment {
+	    Ok(())
+	} else {
+	    Err(failed("recovered secret doesn't match the commitment".into()))
+	}
+    }
+}
+
+impl std::fmt::Display for Manifest {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	write!(f, "{}", self.body())?;
+	if let (Some(key), Some(signature)) = (&self.signed_by, &self.signature) {
+	    write!(f, "signed-by: {}\nsignature: {}\n", key, signature)?;
+	}
+	Ok(())
+    }
+}
+
+impl std::str::FromStr for Manifest {
+    type Err = Error;
+
+    fn from_str(text : &str) -> Result<Manifest> {
+	let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+	if lines.next() != Some(HEADER) { return Err(bad("missing header line")) }
+	let mut manifest = Manifest {
+	    set_id : None, quorum : 0, shares : 0, width : 0, hash : Algorithm::Sha256,
+	    stretch : None, salt : Vec::new(), commitment : Vec::new(), share_hashes : Vec::new(),
+	    signed_by : None, signature : None,
+	};
+	let number = |v : &str, what| v.parse().map_err(|_| bad(what));
+	let bytes = |v : &str, what| hex::decode(v).map_err(|_| bad(what));
+	for line in lines {
+	    let (key, value) = match line.find(": ") {
+		Some(i) => (&line[..i], &line[i + 2..]),
+		None => return Err(bad(&format!("can't read line '{}'", line))),
+	    };
+	    match key {
+		"set" if valid_set_id(value) => manifest.set_id = Some(value.to_string()),
+		"set" => return Err(bad("bad set ID")),
+		"quorum" => manifest.quorum = number(value, "bad quorum")?,
+		"shares" => manifest.shares = number(value, "bad share count")?,
+		"width" => manifest.width = number(value, "bad width")?,
+		"hash" => manifest.hash = value.parse()
+		    .map_err(|_| bad(&format!("unknown hash {}", value)))?,
+		"stretch" => manifest.stretch = Some(value.parse()?),
+		"salt" => manifest.salt = bytes(value, "bad salt")?,
+		"commitment" => manifest.commitment = bytes(value, "bad commitment")?,
+		"share" => {
+		    let mut parts = value.split_whitespace();
+		    let index = parts.next().and_then(|i| i.parse().ok())
+			.ok_or_else(|| bad("bad share number"))?;
+		    let hash = parts.next().filter(|h| hex::decode(h).is_ok())
+			.ok_or_else(|| bad("bad share hash"))?;
+		    manifest.share_hashes.push((index, hash.to_lowercase()));
+		},
+		"signed-by" => manifest.signed_by = Some(value.to_lowercase()),
+		"signature" => manifest.signature = Some(value.to_lowercase()),
+		_ => return Err(bad(&format!("unknown field '{}'", key))),
+	    }
+	}
+	if manifest.quorum == 0 || manifest.width == 0 || manifest.commitment.is_empty() {
+	    return Err(bad("quorum, width and commitment are required"))
+	}
+	let digits = manifest.hash.digest(b"").len() * 2;
+	if manifest.share_hashes.iter().any(|(_, hash)| hash.len() != digits) {
+	    return Err(bad("bad share hash"))
+	}
+	Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split_with_params;
+
+    #[test]
+    fn signed_manifest() {
+	let params = Params::new(2, 3, 8).with_set_id("m1");
+	let shares = split_with_params(b"hello", &params, |_, _| {}).unwrap();
+	let mut manifest = Manifest::new(&params, b"hello", &shares).unwrap();
+	let key = DealerKey::from_bytes(&[7; 32]);
+	manifest.sign(&key);
+
+	let text = manifest.to_string();
+	let read : Manifest = text.parse().unwrap();
+	assert_eq!(read, manifest);
+	assert_eq!(read.verify_signature().unwrap(),
+		   Some(hex::encode(key.verifying_key().as_bytes())));
+	for line in shares.iter() {
+	    read.check_share(&line.parse().unwrap()).unwrap();
+	}
+	read.check_secret(b"hello").unwrap();
+	assert!(matches!(read.check_secret(b"jello"), Err(Error::Verification(_))));
+
+	// A share from another split, and a tampered record
+	let other = split_with_params(b"hello", &params, |_, _| {}).unwrap();
+	assert!(read.check_share(&other[0].parse().unwrap()).is_err());
+	let tampered : Manifest = text.replace("quorum: 2", "quorum: 3").parse().unwrap();
+	assert!(matches!(tampered.verify_signature(), Err(Error::Verification(_))));
+	assert!(matches!("quorum: 2".parse::<Manifest>(), Err(Error::BadManifest(_))));
+
+	// SHA-512 throughout, said so in the record and signed
+	let mut sha512 = Manifest::new_with_hash(&params, b"hello", &shares, Algorithm::Sha512).unwrap();
+	sha512.sign(&key);
+	let text = sha512.to_string();
+	assert!(text.contains("width: 8\nhash: sha-512\nstretch: "));
+	let read : Manifest = text.parse().unwrap();
+	assert_eq!(read, sha512);
+	assert!(read.verify_signature().unwrap().is_some());
+	read.check_share(&shares[0].parse().unwrap()).unwrap();
+	read.check_secret(b"hello").unwrap();
+	assert!(text.replace("hash: sha-512\n", "").parse::<Manifest>().is_err());
+	assert!(!manifest.to_string().contains("hash:"));
+
+	#[cfg(feature = "yaml")]
+	{
+	    let read = Manifest::from_yaml(&sha512.to_yaml()).unwrap();
+	    assert_eq!(read.hash, Algorithm::Sha512);
+	    let yaml = manifest.to_yaml();
+	    assert!(!yaml.contains("hash:"));
+	    assert!(yaml.contains("signed_by: "));
+	    let read = Manifest::from_yaml(&yaml).unwrap();
+	    assert_eq!(read, manifest);
+	    assert!(read.verify_signature().unwrap().is_some());
+	}
+    }
+
+    #[test]
+    fn stretched_commitment() {
+	let params = Params::new(2, 3, 8);
+	let shares = split_with_params(b"hunter2", &params, |_, _| {}).unwrap();
+	let manifest = Manifest::new(&params, b"hunter2", &shares).unwrap();
+	let text = manifest.to_string();
+	assert!(text.contains("width: 8\nstretch: argon2id m=19456,t=2,p=1\nsalt: "));
+	assert_ne!(manifest.commitment,
+		   commit(&manifest.salt, b"hunter2", Algorithm::Sha256, None).unwrap());
+	let read : Manifest = text.parse().unwrap();
+	assert_eq!(read.stretch, Some(Stretch::default()));
+	read.check_secret(b"hunter2").unwrap();
+	assert!(matches!(read.check_secret(b"hunter3"), Err(Error::Verification(_))));
+
+	// The costs are part of the record: changing them changes the
+	// commitment, and ones argon2id can't use (or that would take
+	// too much memory) are refused
+	let cheaper : Manifest = text.replace("t=2", "t=1").parse().unwrap();
+	assert!(cheaper.check_secret(b"hunter2").is_err());
+	for costs in ["argon2id m=19456,t=0,p=1", "argon2id m=4194304,t=2,p=1",
+		      "argon2id t=2,m=19456,p=1", "argon2id m=19456,t=2", "scrypt n=15"] {
+	    let bad = text.replace("argon2id m=19456,t=2,p=1", costs);
+	    assert!(matches!(bad.parse::<Manifest>(), Err(Error::BadManifest(_))), "{}", costs);
+	}
+
+	// A record from before the commitment was stretched
+	let mut old = manifest.clone();
+	old.stretch = None;
+	old.commitment = commit(&old.salt, b"hunter2", Algorithm::Sha256, None).unwrap();
+	let read : Manifest = old.to_string().parse().unwrap();
+	assert!(!old.to_string().contains("stretch:"));
+	read.check_secret(b"hunter2").unwrap();
+	assert!(read.check_secret(b"hunter3").is_err());
+    }
+}
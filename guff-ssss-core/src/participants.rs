@@ -0,0 +1,92 @@
+//! Share numbers from participants' names
+//!
+//! Rather than number shares 1 to n and keep a note of who got which,
+//! a dealer can give each holder the share whose number comes from a
+//! hash of their name. Anyone who knows the names can then tell whose
+//! a share is from its number alone, and work the numbers out again
+//! later (eg, to [derive](crate::derive_share) a lost share afresh).
+//!
+//! Names are compared without regard to case or surrounding spaces,
+//! so "Alice" and " alice" are the same person. GF(2<sup>8</sup>) only
+//! has 128 share numbers to go round, so two names will sometimes get
+//! the same one: [indices] refuses those, and one of the two has to be
+//! written differently (a surname, say).
+
+use crate::error::{Error, Result};
+use crate::width::{check_width, max_index};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+
+// The form a name is hashed in
+fn normalize(name : &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// The share number `name` gets in a field of `width` bits (which
+/// must be a valid width)
+pub fn index(name : &str, width : u16) -> u64 {
+    let mut hash = Sha256::new();
+    hash.update(b"guff-ssss participant v1\n");
+    hash.update(normalize(name).as_bytes());
+    let bytes : [u8; 8] = hash.finalize()[..8].try_into().expect("SHA-256 is longer than 8 bytes");
+    u64::from_be_bytes(bytes) % max_index(width) + 1
+}
+
+/// The share number of each name, in the same order. It's an error
+/// if a name is empty or given twice, or if two names get the same
+/// number.
+pub fn indices(names : &[&str], width : u16) -> Result<Vec<u64>> {
+    check_width(width)?;
+    let mut numbers : Vec<u64> = Vec::with_capacity(names.len());
+    for name in names {
+	if normalize(name).is_empty() {
+	    return Err(Error::FieldError("a participant's name is empty".into()))
+	}
+	let number = index(name, width);
+	if let Some(j) = numbers.iter().position(|&n| n == number) {
+	    return Err(Error::FieldError(if normalize(names[j]) == normalize(name) {
+		format!("{} is named twice", name.trim())
+	    } else {
+		format!("{} and {} would both get share {}: write one of the names differently",
+			names[j].trim(), name.trim(), number)
+	    }))
+	}
+	numbers.push(number);
+    }
+    Ok(numbers)
+}
+
+/// Whose share number `number` is, out of `names`
+pub fn owner<'a>(number : u64, names : &[&'a str], width : u16) -> Option<&'a str> {
+    names.iter().find(|name| index(name, width) == number).map(|name| name.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{combine_decoder, split_shares_at, Decoder, Params};
+
+    #[test]
+    fn names() {
+	let names = ["alice", "bob", "carol"];
+	let numbers = indices(&names, 8).unwrap();
+	assert_eq!(numbers, indices(&["Alice ", " BOB", "carol"], 8).unwrap());
+	assert!(numbers.iter().all(|&n| (1..=128).contains(&n)));
+	assert_eq!(owner(numbers[1], &names, 8), Some("bob"));
+	assert!(indices(&["alice", "ALICE"], 8).unwrap_err().to_string().contains("named twice"));
+	assert!(indices(&["alice", " "], 8).is_err());
+
+	// 129 names can't all have different numbers out of 128
+	let many : Vec<String> = (0..129).map(|i| format!("holder {}", i)).collect();
+	let many : Vec<&str> = many.iter().map(String::as_str).collect();
+	assert!(indices(&many, 8).unwrap_err().to_string().contains("would both get share"));
+
+	let shares = split_shares_at(b"secret", &Params::new(2, 3, 8), &numbers, |_, _| {}).unwrap();
+	assert_eq!(shares.iter().map(|s| s.index).collect::<Vec<_>>(), numbers);
+	let mut decoder = Decoder::new();
+	for share in shares.iter().skip(1) { decoder.add(share.clone()).unwrap(); }
+	assert_eq!(combine_decoder(&mut decoder, false).unwrap(), b"secret");
+	assert!(split_shares_at(b"secret", &Params::new(2, 3, 8), &numbers[..2], |_, _| {}).is_err());
+	assert!(split_shares_at(b"secret", &Params::new(2, 3, 8), &[1, 2, 1], |_, _| {}).is_err());
+    }
+}
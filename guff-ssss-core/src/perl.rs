@@ -0,0 +1,81 @@
+//! Shares from the Perl shamir scripts in Math::FastGF2
+//!
+//! The Perl scripts on CPAN use the same fields (polynomials 0x11b,
+//! 0x1002b and 0x10000008d) and the same `K=W=S=Values=` lines, so
+//! GF(2<sup>8</sup>) shares from them combine as they are. Two things
+//! differ for the wider fields:
+//!
+//! * Math::FastGF2 packs words in network (big-endian) byte order,
+//!   where this crate writes them little-endian, so the hex of a 16-
+//!   or 32-bit share has the bytes of each word the other way round.
+//! * The secret is padded with NUL bytes to a whole number of words
+//!   before splitting, and the Perl combine script strips trailing
+//!   NULs from what it recovers.
+//!
+//! The Perl scripts also print shares in whatever order Perl's hash
+//! gives them, and accept upper-case hex; neither matters here.
+
+use crate::error::ParseError;
+use crate::{parse_share, Share};
+
+/// Swap the bytes of each word between the Perl (big-endian) and
+/// our (little-endian) order. Doing it twice gets back the original.
+pub fn swap_word_order(share : &Share) -> Share {
+    let bytes = match share.width { 16 => 2, 32 => 4, _ => 1 };
+    let mut share = share.clone();
+    for word in share.values.chunks_mut(bytes) {
+	word.reverse();
+    }
+    share
+}
+
+/// Parse a share line written by the Perl scripts
+pub fn parse_perl_share(line : &str) -> std::result::Result<Share, ParseError> {
+    parse_share(line).map(|share| swap_word_order(&share))
+}
+
+/// Write a share the way the Perl scripts do
+pub fn to_perl_share(share : &Share) -> String {
+    swap_word_order(share).to_string()
+}
+
+/// Pad a secret with NULs to a whole number of `width`-bit words, as
+/// the Perl split script does
+pub fn pad_words(secret : &[u8], width : u16) -> Vec<u8> {
+    let bytes = (width as usize / 8).max(1);
+    let mut padded = secret.to_vec();
+    padded.resize(secret.len().div_ceil(bytes) * bytes, 0);
+    padded
+}
+
+/// Remove trailing NULs from a recovered secret, as the Perl combine
+/// script does
+pub fn strip_padding(secret : &[u8]) -> &[u8] {
+    let end = secret.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &secret[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perl_shares() {
+	// Same field and layout at 8 bits: nothing to do, whatever the case
+	let share = parse_perl_share("3=8=1=876EC0B40CF66736AF2848=\r\n").unwrap();
+	assert_eq!(share.to_string(), "3=8=1=876ec0b40cf66736af2848=");
+	assert_eq!(to_perl_share(&share), "3=8=1=876ec0b40cf66736af2848=");
+
+	let share = parse_perl_share("2=16=3=12345678=").unwrap();
+	assert_eq!(share.values, [0x34, 0x12, 0x78, 0x56]);
+	assert_eq!(to_perl_share(&share), "2=16=3=12345678=");
+	let share = parse_perl_share("2=32=3=12345678=").unwrap();
+	assert_eq!(share.values, [0x78, 0x56, 0x34, 0x12]);
+
+	assert_eq!(pad_words(b"abc", 8), b"abc");
+	assert_eq!(pad_words(b"abc", 32), b"abc\0");
+	assert_eq!(pad_words(b"abcd", 16), b"abcd");
+	assert_eq!(strip_padding(b"abc\0\0"), b"abc");
+	assert_eq!(strip_padding(b"\0\0"), b"");
+    }
+}
@@ -0,0 +1,241 @@
+//! Proactive refresh
+//!
+//! A refresh gives every holder a new share of the same secret, so
+//! that old shares (including any an attacker has been collecting)
+//! no longer combine with the new ones. Each holder deals a sharing of
+//! zero to the others, the same way as in [dkg](crate::dkg), and adds
+//! the deals it receives to its own share. Neither the secret nor the
+//! shares ever leave their holders: only [Message]s do.
+//!
+//! A holder with a deal from everyone has its new share, and
+//! acknowledges that to everyone else. Old shares should only be
+//! destroyed once every holder has acknowledged ([Refresh::is_done]):
+//! a holder left with only an old share can't combine with anybody.
+//! For the same reason every holder in the set has to take part.
+//!
+//! [Refresh] drives one holder through a round and leaves the
+//! transport to the caller: send what [start](Refresh::start)
+//! returns, pass everything received to [handle](Refresh::handle),
+//! and send whatever that returns. Deals go to one holder
+//! ([Message::to]) over a private channel; acknowledgments go to
+//! everyone. With the `serde` feature, messages serialize with a
+//! `type` of `deal` or `ack`.
+
+use crate::dkg::{deal_secret, finish, Deal};
+use crate::error::{Error, Result};
+use crate::{Params, Share};
+use std::collections::BTreeSet;
+
+/// Wire messages for one refresh round
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "lowercase"))]
+pub enum Message {
+    /// A share of zero from `deal.from` for holder `deal.share.index`
+    Deal { round : String, deal : Deal },
+    /// Holder `from` has all its deals and its new share
+    Ack { round : String, from : u64 },
+}
+
+impl Message {
+    /// The round this message belongs to
+    pub fn round(&self) -> &str {
+	match self {
+	    Message::Deal { round, .. } | Message::Ack { round, .. } => round,
+	}
+    }
+
+    /// The holder this message is for, or None for everyone
+    pub fn to(&self) -> Option<u64> {
+	match self {
+	    Message::Deal { deal, .. } => Some(deal.share.index),
+	    Message::Ack { .. } => None,
+	}
+    }
+}
+
+/// One holder's side of a refresh round
+#[derive(Debug)]
+pub struct Refresh {
+    share     : Share,
+    parties   : u16,
+    round     : String,
+    started   : bool,
+    deals     : Vec<Deal>,
+    acks      : BTreeSet<u64>,
+    new_share : Option<Share>,
+}
+
+impl Refresh {
+    /// Refresh `share`, one of `parties` shares in its set, in the
+    /// round called `round` (any name all the holders agree on, so
+    /// that stray messages from other rounds are refused)
+    pub fn new(share : Share, parties : u16, round : &str) -> Result<Refresh> {
+	if share.index < 1 || share.index > parties as u64 {
+	    return Err(Error::FieldError(format!("share {} isn't one of 1 to {}",
+						 share.index, parties)))
+	}
+	if parties < share.quorum {
+	    return Err(Error::FieldError(format!("{} holders can't refresh a quorum of {}",
+						 parties, share.quorum)))
+	}
+	Ok(Refresh {
+	    share, parties, round : round.to_string(), started : false,
+	    deals : Vec::new(), acks : BTreeSet::new(), new_share : None,
+	})
+    }
+
+    /// Deal our share of zero: the deals for the other holders, and
+    /// an acknowledgment if we already had all of theirs
+    pub fn start(&mut self) -> Result<Vec<Message>> {
+	if self.started { return Ok(Vec::new()) }
+	let params = Params {
+	    quorum : self.share.quorum, shares : self.parties, width : self.share.width,
+	    set_id : self.share.set_id.clone(), word_padding : false,
+	};
+	let zero = vec![0u8; self.share.values.len()];
+	let mut out = Vec::new();
+	for deal in deal_secret(&params, self.share.index as u16, &zero)? {
+	    if deal.share.index == self.share.index {
+		out.extend(self.receive(deal)?);
+	    } else {
+		out.push(Message::Deal { round : self.round.clone(), deal });
+	    }
+	}
+	self.started = true;
+	Ok(out)
+    }
+
+    /// Take in a message from another holder, giving back any
+    /// messages to send in reply
+    pub fn handle(&mut self, message : Message) -> Result<Vec<Message>> {
+	if message.round() != self.round {
+	    return Err(Error::InconsistentShares(format!("message for round {}, not {}",
+							 message.round(), self.round)))
+	}
+	match message {
+	    Message::Deal { deal, .. } => {
+		if deal.share.index != self.share.index {
+		    return Err(Error::InconsistentShares(format!(
+			"deal for holder {} sent to holder {}", deal.share.index, self.share.index)))
+		}
+		if deal.from as u64 == self.share.index {
+		    return Err(Error::InconsistentShares("deal from ourselves".into()))
+		}
+		self.receive(deal)
+	    },
+	    Message::Ack { from, .. } => {
+		if from < 1 || from > self.parties as u64 {
+		    return Err(Error::FieldError(format!("ack from holder {}, who isn't one \
+							  of 1 to {}", from, self.parties)))
+		}
+		self.acks.insert(from);
+		Ok(Vec::new())
+	    },
+	}
+    }
+
+    fn receive(&mut self, deal : Deal) -> Result<Vec<Message>> {
+	if let Some(had) = self.deals.iter().find(|d| d.from == deal.from) {
+	    // The same deal delivered twice is harmless
+	    if *had == deal { return Ok(Vec::new()) }
+	    return Err(Error::InconsistentShares(format!("two deals from holder {}", deal.from)))
+	}
+	let share = &deal.share;
+	if share.quorum != self.share.quorum || share.width != self.share.width
+	    || share.set_id != self.share.set_id
+	    || share.values.len() != self.share.values.len() {
+	    return Err(Error::InconsistentShares(format!(
+		"deal from holder {} doesn't match our share", deal.from)))
+	}
+	self.deals.push(deal);
+	if self.deals.len() < self.parties as usize { return Ok(Vec::new()) }
+
+	let zero = finish(&self.deals, self.parties)?;
+	let mut share = self.share.clone();
+	share.values.iter_mut().zip(zero.values.iter()).for_each(|(v, z)| *v ^= z);
+	self.new_share = Some(share);
+	self.acks.insert(self.share.index);
+	Ok(vec![Message::Ack { round : self.round.clone(), from : self.share.index }])
+    }
+
+    /// Our new share, once we have a deal from every holder. Keep the
+    /// old one until [is_done](Refresh::is_done).
+    pub fn new_share(&self) -> Option<&Share> { self.new_share.as_ref() }
+
+    /// Holders that have acknowledged so far (ourselves included)
+    pub fn acknowledged(&self) -> Vec<u64> { self.acks.iter().copied().collect() }
+
+    /// True once every holder has its new share, so old shares can go
+    pub fn is_done(&self) -> bool {
+	self.new_share.is_some() && self.acks.len() == self.parties as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{combine_decoder, split_with_params, Decoder};
+
+    fn combine_shares(shares : &[&Share]) -> Vec<u8> {
+	let mut decoder = Decoder::new();
+	for share in shares { decoder.add((*share).clone()).unwrap(); }
+	combine_decoder(&mut decoder, false).unwrap()
+    }
+
+    #[test]
+    fn refresh_round() {
+	let params = Params::new(2, 3, 8).with_set_id("r1");
+	let old : Vec<Share> = split_with_params(b"hello world", &params, |_, _| {}).unwrap()
+	    .iter().map(|s| s.parse().unwrap()).collect();
+	let mut holders : Vec<Refresh> = old.iter()
+	    .map(|s| Refresh::new(s.clone(), 3, "2021-06").unwrap()).collect();
+
+	// Everyone starts, then everything is delivered
+	let mut queue = Vec::new();
+	for h in holders.iter_mut() { queue.extend(h.start().unwrap()); }
+	while let Some(message) = queue.pop() {
+	    for h in holders.iter_mut() {
+		if message.to().is_none_or(|to| to == h.share.index) {
+		    if let Message::Ack { from, .. } = message {
+			if from == h.share.index { continue }
+		    }
+		    queue.extend(h.handle(message.clone()).unwrap());
+		}
+	    }
+	}
+	assert!(holders.iter().all(|h| h.is_done() && h.acknowledged() == [1, 2, 3]));
+
+	let new : Vec<&Share> = holders.iter().map(|h| h.new_share().unwrap()).collect();
+	assert!(new.iter().zip(old.iter()).all(|(n, o)| n.values != o.values));
+	assert_eq!(combine_shares(&[new[0], new[2]]), b"hello world");
+	assert_eq!(combine_shares(&[new[1], new[2]]), b"hello world");
+	assert_ne!(combine_shares(&[&old[0], new[1]]), b"hello world");
+
+	// Messages from the wrong round, or for someone else
+	let mut h = Refresh::new(old[0].clone(), 3, "2021-06").unwrap();
+	let mut other = Refresh::new(old[1].clone(), 3, "2021-07").unwrap();
+	let deals = other.start().unwrap();
+	assert!(h.handle(deals[0].clone()).is_err());
+	let ack = Message::Ack { round : "2021-06".into(), from : 4 };
+	assert!(h.handle(ack).is_err());
+	assert!(Refresh::new(old[0].clone(), 1, "x").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn refresh_messages_serde() {
+	let ack = Message::Ack { round : "q3".into(), from : 2 };
+	let json = r#"{"type":"ack","round":"q3","from":2}"#;
+	assert_eq!(serde_json::to_string(&ack).unwrap(), json);
+	assert_eq!(serde_json::from_str::<Message>(json).unwrap(), ack);
+
+	let deal = Message::Deal {
+	    round : "q3".into(),
+	    deal : Deal { from : 1, share : "r1=2=8=3=a022=".parse().unwrap() },
+	};
+	let json = serde_json::to_string(&deal).unwrap();
+	assert!(json.starts_with(r#"{"type":"deal","round":"q3","deal":{"from":1,"share":"#));
+	assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), deal);
+    }
+}
@@ -0,0 +1,205 @@
+//! Checks of the field arithmetic, at run time
+//!
+//! The arithmetic is tested when this crate is built and tested, but
+//! a binary may end up on a target nobody ran the tests on: another
+//! architecture, an odd compiler, a CPU that reports vector
+//! instructions it gets wrong. [run] repeats the essential checks on
+//! the machine itself, quickly enough to do before a ceremony:
+//!
+//! * the GF(2<sup>8</sup>) field uses the polynomial shares are
+//!   defined over (0x11b)
+//! * its log/exp tables agree with plain shift-and-add
+//!   multiplication, and inversion with the extended Euclidean
+//!   algorithm, for every element
+//! * field identities (a·a<sup>-1</sup> = 1, (a·b)/b = a,
+//!   commutativity, distributivity) hold
+//! * products from FIPS-197, which uses the same field
+//! * with the `simd` feature, the vector kernel this machine uses
+//!   agrees with the scalar code
+//! * a known set of shares combines to its known secret, and a fresh
+//!   split combines back
+//!
+//! A check that panics (a table that fails to build, say) is a
+//! failure like any other rather than the end of the run.
+
+use crate::width::{Width, U8};
+use crate::{combine_decoder, split_shares, Decoder, Params};
+use guff::GaloisField;
+use rand::Rng;
+
+/// The outcome of one check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    /// What was checked
+    pub name : &'static str,
+    /// What went wrong, if anything
+    pub failure : Option<String>,
+}
+
+impl Check {
+    /// True if the check passed
+    pub fn passed(&self) -> bool { self.failure.is_none() }
+}
+
+type Outcome = std::result::Result<(), String>;
+type Test = fn() -> Outcome;
+
+// Products from FIPS-197 (AES) sections 4.2 and 4.2.1, and an inverse
+// from section 5.1.1
+const FIPS_197 : [(u8, u8, u8); 4] = [(0x57, 0x83, 0xc1), (0x57, 0x13, 0xfe),
+				      (0x57, 0x02, 0xae), (0x53, 0xca, 0x01)];
+
+// Shares of "hello world" made by an earlier release
+const HELLO : [&str; 3] = [
+    "3=8=1=876ec0b40cf66736af2848=",
+    "3=8=3=4f292e6af80e414a200281=",
+    "3=8=5=38bf85dc373a33db12c2b3=",
+];
+
+fn polynomial() -> Outcome {
+    let field = U8::field();
+    match (field.poly(), field.full_poly()) {
+	(0x1b, 0x11b) => Ok(()),
+	(_, full) => Err(format!("polynomial is {:#x}, not 0x11b", full)),
+    }
+}
+
+fn tables() -> Outcome {
+    let (fast, reference) = (U8::field(), guff::new_gf8(0x11b, 0x1b));
+    for a in 0..=255u8 {
+	for b in 0..=255u8 {
+	    if fast.mul(a, b) != reference.mul(a, b) {
+		return Err(format!("{:#04x} * {:#04x} is {:#04x} by table, {:#04x} by shifting",
+				   a, b, fast.mul(a, b), reference.mul(a, b)))
+	    }
+	}
+	if a != 0 && fast.inv(a) != reference.inv(a) {
+	    return Err(format!("1 / {:#04x} is {:#04x} by table, {:#04x} by Euclid",
+			       a, fast.inv(a), reference.inv(a)))
+	}
+    }
+    Ok(())
+}
+
+fn identities() -> Outcome {
+    let field = U8::field();
+    let mut rng = rand::thread_rng();
+    for a in 0..=255u8 {
+	if field.mul(a, 1) != a || field.mul(a, 0) != 0 {
+	    return Err(format!("{:#04x} * 1 or {:#04x} * 0 is wrong", a, a))
+	}
+	if a != 0 && field.mul(a, field.inv(a)) != 1 {
+	    return Err(format!("{:#04x} * 1/{:#04x} isn't 1", a, a))
+	}
+	for b in 0..=255u8 {
+	    if field.mul(a, b) != field.mul(b, a) {
+		return Err(format!("{:#04x} * {:#04x} depends on the order", a, b))
+	    }
+	    if b != 0 && field.div(field.mul(a, b), b) != a {
+		return Err(format!("({:#04x} * {:#04x}) / {:#04x} isn't {:#04x}", a, b, b, a))
+	    }
+	    let c : u8 = rng.gen();
+	    if field.mul(a, b ^ c) != field.mul(a, b) ^ field.mul(a, c) {
+		return Err(format!("{:#04x} * ({:#04x} + {:#04x}) doesn't distribute", a, b, c))
+	    }
+	}
+    }
+    Ok(())
+}
+
+fn known_products() -> Outcome {
+    let field = U8::field();
+    for (a, b, product) in FIPS_197.iter().copied() {
+	if field.mul(a, b) != product {
+	    return Err(format!("{:#04x} * {:#04x} is {:#04x}, not {:#04x}",
+			       a, b, field.mul(a, b), product))
+	}
+    }
+    Ok(())
+}
+
+#[cfg(feature = "simd")]
+fn vector_kernel() -> Outcome {
+    let field = U8::field();
+    let mut rng = rand::thread_rng();
+    // Lengths either side of the vector sizes and the table cutoff
+    for len in [1, 31, 32, 63, 64, 65, 255, 256, 1000, 4099] {
+	let src : Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+	let start : Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+	let c : u8 = rng.gen();
+	let mut dst = start.clone();
+	crate::simd::mul_acc(&field, &mut dst, &src, c);
+	for i in 0..len {
+	    if dst[i] != start[i] ^ field.mul(src[i], c) {
+		return Err(format!("{:?} kernel is wrong at byte {} of {}",
+				   crate::simd::kernel(), i, len))
+	    }
+	}
+    }
+    Ok(())
+}
+
+fn known_shares() -> Outcome {
+    let mut decoder = Decoder::new();
+    for line in HELLO.iter() { decoder.add_share(line).map_err(|e| e.to_string())?; }
+    match combine_decoder(&mut decoder, false) {
+	Ok(secret) if secret == b"hello world" => Ok(()),
+	Ok(secret) => Err(format!("combined to {:?}, not \"hello world\"",
+				  String::from_utf8_lossy(&secret))),
+	Err(e) => Err(e.to_string()),
+    }
+}
+
+fn round_trip() -> Outcome {
+    let secret : Vec<u8> = (0..1000).map(|_| rand::random()).collect();
+    let shares = split_shares(&secret, &Params::new(3, 5, 8), |_, _| {})
+	.map_err(|e| e.to_string())?;
+    for hardened in [false, true] {
+	let mut decoder = Decoder::new();
+	for share in shares.iter().rev().take(3) {
+	    decoder.add(share.clone()).map_err(|e| e.to_string())?;
+	}
+	if combine_decoder(&mut decoder, hardened).map_err(|e| e.to_string())? != secret {
+	    return Err(format!("shares 3, 4 and 5 don't give back the secret{}",
+			       if hardened { " (hardened)" } else { "" }))
+	}
+    }
+    Ok(())
+}
+
+/// Run every check, in the order above
+pub fn run() -> Vec<Check> {
+    let mut checks : Vec<(&'static str, Test)> = vec![
+	("GF(2^8) polynomial is 0x11b", polynomial),
+	("GF(2^8) tables match shift-and-add and Euclid", tables),
+	("GF(2^8) identities", identities),
+	("GF(2^8) products from FIPS-197", known_products),
+    ];
+    #[cfg(feature = "simd")]
+    checks.push(("Vector kernel matches scalar code", vector_kernel));
+    checks.push(("Known shares combine to their secret", known_shares));
+    checks.push(("Split and combine round trip", round_trip));
+    checks.into_iter().map(|(name, check)| {
+	let failure = match std::panic::catch_unwind(check) {
+	    Ok(outcome) => outcome.err(),
+	    Err(panic) => Some(panic.downcast_ref::<String>().cloned()
+			       .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+			       .unwrap_or_else(|| "panicked".to_string())),
+	};
+	Check { name, failure }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_pass() {
+	let checks = run();
+	assert_eq!(checks.len(), if cfg!(feature = "simd") { 7 } else { 6 });
+	for check in checks {
+	    assert!(check.passed(), "{}: {:?}", check.name, check.failure);
+	}
+    }
+}
@@ -0,0 +1,222 @@
+//! Vectorised GF(2<sup>8</sup>) arithmetic
+//!
+//! With the `simd` feature, pass 2 of [combine](crate::combine) for
+//! GF(2<sup>8</sup>) shares works a row at a time: each share's data
+//! is multiplied by its coefficient and added into the secret with
+//! [mul_acc]. On x86-64 processors with GFNI (Ice Lake and later,
+//! Zen 4 and later) that's one `GF2P8MULB` per 64 bytes with AVX-512,
+//! or per 32 bytes with AVX, which leaves a bulk combine limited by
+//! memory bandwidth rather than arithmetic. `GF2P8MULB` works modulo
+//! the AES polynomial 0x11b, the same as [U8](crate::width::U8).
+//!
+//! The instructions are looked for at run time, so a binary built
+//! with the feature still runs anywhere. Without them, each row goes
+//! through a 256-entry product table for its coefficient. With them,
+//! nothing is looked up by share data, which also suits `--hardened`.
+
+use crate::buffer::{aligned_chunks, zeroed, ChunkSize, CACHE_LINE};
+use crate::width::U8;
+use crate::{Decoder, Result, PROGRESS_INTERVAL};
+use guff::GaloisField;
+use guff::good::F8_0x11b;
+use std::ops::Range;
+use std::time::Instant;
+
+// Sizes ChunkSize::Auto tries, from part of L1 to a typical L2, each
+// on this much of the secret. Secrets too short to have much left
+// after the trials just use the default.
+const CANDIDATES : [usize; 5] = [4 << 10, 16 << 10, 64 << 10, 256 << 10, 1 << 20];
+const TRIAL : usize = 1 << 20;
+
+/// The kernel [mul_acc] uses on this machine
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    /// `GF2P8MULB` on 64-byte vectors
+    Avx512Gfni,
+    /// `GF2P8MULB` on 32-byte vectors
+    AvxGfni,
+    /// A product table per coefficient
+    Table,
+}
+
+/// Which kernel this machine gets
+pub fn kernel() -> Kernel {
+    #[cfg(target_arch = "x86_64")]
+    {
+	if is_x86_feature_detected!("gfni") {
+	    if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+		return Kernel::Avx512Gfni
+	    }
+	    if is_x86_feature_detected!("avx") { return Kernel::AvxGfni }
+	}
+    }
+    Kernel::Table
+}
+
+/// `dst[i] ^= src[i] * c` for every `i`, in GF(2<sup>8</sup>) with
+/// polynomial 0x11b
+pub fn mul_acc(field : &F8_0x11b, dst : &mut [u8], src : &[u8], c : u8) {
+    mul_acc_with(kernel(), field, dst, src, c)
+}
+
+fn mul_acc_with(kernel : Kernel, field : &F8_0x11b, dst : &mut [u8], src : &[u8], c : u8) {
+    assert_eq!(dst.len(), src.len());
+    let done = match kernel {
+	#[cfg(target_arch = "x86_64")]
+	// Safety: kernel() only picks these if the CPU has the features
+	Kernel::Avx512Gfni => unsafe { x86::mul_acc_avx512(dst, src, c) },
+	#[cfg(target_arch = "x86_64")]
+	Kernel::AvxGfni => unsafe { x86::mul_acc_avx(dst, src, c) },
+	_ => 0,
+    };
+    let (dst, src) = (&mut dst[done..], &src[done..]);
+    // Building a table only pays off for a decent run of bytes
+    if src.len() < 256 {
+	for (d, s) in dst.iter_mut().zip(src) { *d ^= field.mul(*s, c) }
+    } else {
+	let mut table = [0u8; 256];
+	for (x, t) in table.iter_mut().enumerate() { *t = field.mul(x as u8, c) }
+	for (d, s) in dst.iter_mut().zip(src) { *d ^= table[*s as usize] }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    // Each returns how many bytes it did: whole vectors only
+
+    #[target_feature(enable = "gfni,avx512f,avx512bw")]
+    pub unsafe fn mul_acc_avx512(dst : &mut [u8], src : &[u8], c : u8) -> usize {
+	let c = _mm512_set1_epi8(c as i8);
+	let whole = dst.len() / 64 * 64;
+	for i in (0..whole).step_by(64) {
+	    let s = _mm512_loadu_si512(src.as_ptr().add(i).cast());
+	    let d = _mm512_loadu_si512(dst.as_ptr().add(i).cast());
+	    let d = _mm512_xor_si512(d, _mm512_gf2p8mul_epi8(s, c));
+	    _mm512_storeu_si512(dst.as_mut_ptr().add(i).cast(), d);
+	}
+	whole
+    }
+
+    #[target_feature(enable = "gfni,avx")]
+    pub unsafe fn mul_acc_avx(dst : &mut [u8], src : &[u8], c : u8) -> usize {
+	let c = _mm256_set1_epi8(c as i8);
+	let whole = dst.len() / 32 * 32;
+	for i in (0..whole).step_by(32) {
+	    let s = _mm256_loadu_si256(src.as_ptr().add(i).cast());
+	    let d = _mm256_loadu_si256(dst.as_ptr().add(i).cast());
+	    // No integer XOR in plain AVX, but the float one is the same bits
+	    let d = _mm256_castps_si256(_mm256_xor_ps(_mm256_castsi256_ps(d),
+						      _mm256_castsi256_ps(_mm256_gf2p8mul_epi8(s, c))));
+	    _mm256_storeu_si256(dst.as_mut_ptr().add(i).cast(), d);
+	}
+	whole
+    }
+}
+
+/// Pass 2 for GF(2<sup>8</sup>), a row at a time. Rows are taken in
+/// chunks (of the decoder's [ChunkSize]) so that the secret being
+/// built stays in cache, split where the secret's cache lines start
+/// so that stores are to whole lines.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(width = 8)))]
+pub(crate) fn pass_2(field : &F8_0x11b, decoder : &Decoder, ans : &mut Vec<u8>,
+		     progress : &mut dyn FnMut(usize, usize)) -> Result<()> {
+    crate::check_rows::<U8>(decoder, true)?;
+    let words = decoder.hex_length / 2;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(hex_length = decoder.hex_length, words, kernel = ?kernel(), "pass 2");
+    if ans.capacity() < words {
+	*ans = zeroed(words);
+    } else {
+	ans.clear();
+	ans.resize(words, 0);
+    }
+    let mut done = 0;
+    let size = match decoder.chunk_size {
+	ChunkSize::Fixed(size) => size.max(CACHE_LINE),
+	ChunkSize::Auto if words < 2 * CANDIDATES.len() * TRIAL => PROGRESS_INTERVAL,
+	ChunkSize::Auto => {
+	    let mut best = (f64::INFINITY, PROGRESS_INTERVAL);
+	    for &size in CANDIDATES.iter() {
+		let start = Instant::now();
+		rows(field, decoder, ans, done..done + TRIAL, size, progress);
+		let secs = start.elapsed().as_secs_f64();
+		if secs < best.0 { best = (secs, size) }
+		done += TRIAL;
+	    }
+	    #[cfg(feature = "tracing")]
+	    tracing::debug!(chunk_size = best.1, "tuned");
+	    best.1
+	},
+    };
+    rows(field, decoder, ans, done..words, size, progress);
+    progress(words, words);
+    Ok(())
+}
+
+// Add every share's row, times its coefficient, into ans[span]
+fn rows(field : &F8_0x11b, decoder : &Decoder, ans : &mut [u8], span : Range<usize>,
+	size : usize, progress : &mut dyn FnMut(usize, usize)) {
+    let words = ans.len();
+    let k = decoder.quorum as usize;
+    let base = span.start;
+    for chunk in aligned_chunks(&ans[span], size) {
+	let (start, end) = (base + chunk.start, base + chunk.end);
+	progress(start, words);
+	for (j, &c) in decoder.coefficients[..k].iter().enumerate() {
+	    let row = &decoder.shares[j * words..(j + 1) * words];
+	    mul_acc(field, &mut ans[start..end], &row[start..end], c);
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guff::good::new_gf8_0x11b;
+    use rand::Rng;
+
+    #[test]
+    fn kernels_agree() {
+	let field = new_gf8_0x11b();
+	let mut rng = rand::thread_rng();
+	let mut kernels = vec![Kernel::Table];
+	if kernel() != Kernel::Table { kernels.push(Kernel::AvxGfni) }
+	if kernel() == Kernel::Avx512Gfni { kernels.push(Kernel::Avx512Gfni) }
+	// Lengths either side of the vector sizes and the table cutoff
+	for &len in &[0, 1, 31, 32, 63, 64, 65, 255, 256, 1000] {
+	    let src : Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+	    let start : Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+	    for c in [0u8, 1, 2, 0x53, 0xff] {
+		let want : Vec<u8> = start.iter().zip(&src)
+		    .map(|(d, s)| d ^ field.mul(*s, c)).collect();
+		for &k in &kernels {
+		    let mut dst = start.clone();
+		    mul_acc_with(k, &field, &mut dst, &src, c);
+		    assert_eq!(dst, want, "{:?}, {} bytes, c = {}", k, len, c);
+		}
+	    }
+	}
+    }
+
+    #[test]
+    fn auto_chunk_size() {
+	// Long enough to be tuned, and not a whole number of chunks
+	let words = 2 * CANDIDATES.len() * TRIAL + 1000;
+	let mut decoder = Decoder::new();
+	decoder.quorum = 3;
+	decoder.width = 8;
+	decoder.hex_length = 2 * words;
+	decoder.x_values = vec![1, 2, 3];
+	decoder.coefficients = vec![0x53, 0x01, 0xca];
+	decoder.shares = (0..3 * words).map(|i| ((i * 7) ^ (i >> 9)) as u8).collect();
+	let field = new_gf8_0x11b();
+	let mut calls = 0;
+	let (mut fixed, mut auto) = (Vec::new(), Vec::new());
+	pass_2(&field, &decoder, &mut fixed, &mut |_, _| {}).unwrap();
+	decoder.chunk_size = ChunkSize::Auto;
+	pass_2(&field, &decoder, &mut auto, &mut |_, _| calls += 1).unwrap();
+	assert!(fixed == auto && calls > CANDIDATES.len());
+    }
+}
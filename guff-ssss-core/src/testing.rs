@@ -0,0 +1,172 @@
+//! Generators for fuzzing and property testing
+//!
+//! With the `arbitrary` feature, [Share] and [Params] implement
+//! [arbitrary::Arbitrary], so they can be used directly as cargo-fuzz
+//! inputs. With the `proptest` feature, [share] and [params] give
+//! proptest strategies, and both types work with `any::<T>()`.
+//!
+//! Everything generated is valid: set IDs pass
+//! [valid_set_id](crate::valid_set_id), and
+//! quorum, share count and share index are in range for the field
+//! width, so a generated share is always accepted by
+//! [Decoder::add_share](crate::Decoder::add_share) and a generated
+//! [Params] is always accepted by [split_with_params](crate::split_with_params).
+//!
+//! The crate's own property tests, like the targets in `fuzz/`, also
+//! feed the decoders and the solver input that isn't valid at all, to
+//! check that they give an error for it rather than panicking.
+
+use crate::{Params, Share};
+
+// Everything generated is GF(2**8), the one width every build has
+const WIDTH : u16 = 8;
+const MAX_INDEX : u16 = 1 << (WIDTH - 1);
+
+#[cfg(feature = "arbitrary")]
+mod fuzz {
+    use super::*;
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    const SET_ID_CHARS : &[u8] =
+	b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    fn set_id(u : &mut Unstructured) -> Result<Option<String>> {
+	if !u.arbitrary::<bool>()? { return Ok(None) }
+	let len = u.int_in_range(1..=32)?;
+	let mut id = String::with_capacity(len);
+	for _ in 0..len {
+	    id.push(*u.choose(SET_ID_CHARS)? as char);
+	}
+	Ok(Some(id))
+    }
+
+    impl<'a> Arbitrary<'a> for Share {
+	fn arbitrary(u : &mut Unstructured<'a>) -> Result<Self> {
+	    Ok(Share {
+		set_id : set_id(u)?,
+		quorum : u.int_in_range(1..=MAX_INDEX)?,
+		width  : WIDTH,
+		index  : u.int_in_range(1..=MAX_INDEX as u64)?,
+		values : u.arbitrary()?,
+		padded : false,
+	    })
+	}
+    }
+
+    impl<'a> Arbitrary<'a> for Params {
+	fn arbitrary(u : &mut Unstructured<'a>) -> Result<Self> {
+	    let quorum = u.int_in_range(1..=MAX_INDEX)?;
+	    Ok(Params {
+		quorum,
+		shares : u.int_in_range(quorum..=MAX_INDEX)?,
+		width  : WIDTH,
+		set_id : set_id(u)?,
+		word_padding : false,
+	    })
+	}
+    }
+}
+
+#[cfg(feature = "proptest")]
+pub use self::strategies::*;
+
+#[cfg(feature = "proptest")]
+mod strategies {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Optional set ID
+    pub fn set_id() -> impl Strategy<Value = Option<String>> {
+	proptest::option::of("[A-Za-z0-9_-]{1,32}")
+    }
+
+    /// A single valid share, holding up to 64 bytes of values
+    pub fn share() -> impl Strategy<Value = Share> {
+	(set_id(), 1..=MAX_INDEX, 1..=MAX_INDEX as u64,
+	 proptest::collection::vec(any::<u8>(), 0..64))
+	    .prop_map(|(set_id, quorum, index, values)| Share {
+		set_id, quorum, width : WIDTH, index, values, padded : false,
+	    })
+    }
+
+    /// Valid splitting parameters
+    pub fn params() -> impl Strategy<Value = Params> {
+	(set_id(), 1..=MAX_INDEX)
+	    .prop_flat_map(|(set_id, quorum)| (Just(set_id), Just(quorum), quorum..=MAX_INDEX))
+	    .prop_map(|(set_id, quorum, shares)| Params {
+		quorum, shares, width : WIDTH, set_id, word_padding : false,
+	    })
+    }
+
+    impl Arbitrary for Share {
+	type Parameters = ();
+	type Strategy = BoxedStrategy<Share>;
+	fn arbitrary_with(_ : ()) -> Self::Strategy { share().boxed() }
+    }
+
+    impl Arbitrary for Params {
+	type Parameters = ();
+	type Strategy = BoxedStrategy<Params>;
+	fn arbitrary_with(_ : ()) -> Self::Strategy { params().boxed() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{split_with_params, Decoder};
+    #[cfg(feature = "proptest")]
+    use proptest::{collection::vec, prelude::any};
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_values_are_valid() {
+	use arbitrary::{Arbitrary, Unstructured};
+	let bytes : Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+	let mut u = Unstructured::new(&bytes);
+	for _ in 0..20 {
+	    let share = Share::arbitrary(&mut u).unwrap();
+	    assert!(Decoder::new().add_share(&share.to_string()).is_ok(), "{}", share);
+	    let params = Params::arbitrary(&mut u).unwrap();
+	    assert!(split_with_params(b"x", &params, |_, _| {}).is_ok(), "{:?}", params);
+	}
+    }
+
+    #[cfg(feature = "proptest")]
+    proptest::proptest! {
+	#[test]
+	fn generated_shares_are_accepted(share in share()) {
+	    proptest::prop_assert!(Decoder::new().add_share(&share.to_string()).is_ok());
+	}
+
+	#[test]
+	fn generated_params_can_split(params in params()) {
+	    let shares = split_with_params(b"secret", &params, |_, _| {}).unwrap();
+	    proptest::prop_assert_eq!(shares.len(), params.shares as usize);
+	}
+
+	// The entry points give errors, rather than panicking, whatever
+	// they're fed
+	#[test]
+	fn any_text_decodes_or_fails(text in ".{0,200}", bytes in vec(any::<u8>(), 0..200)) {
+	    let _ = crate::parse_share(&text);
+	    let _ = crate::codec::Registry::new().decode(&text);
+	    let _ = crate::codec::decode_armor_stream(&text);
+	    let _ = crate::codec::decode_kubernetes_stream(&text);
+	    let _ = crate::binary::decode_binary(&bytes);
+	}
+
+	#[test]
+	fn any_shares_combine_or_fail(fields in vec((0..=300u16, 0..=40u16, 0..=300u64,
+						     vec(any::<u8>(), 0..8)), 0..6),
+				      x in 0..=300u64, hardened : bool) {
+	    let shares : Vec<Share> = fields.into_iter().map(|(quorum, width, index, values)| Share {
+		set_id : None, quorum, width, index, values, padded : false,
+	    }).collect();
+	    let mut decoder = Decoder::new();
+	    for share in shares.iter() { let _ = decoder.add(share.clone()); }
+	    let _ = crate::combine_decoder(&mut decoder, hardened);
+	    let _ = crate::derive_share(&shares, x);
+	}
+    }
+}
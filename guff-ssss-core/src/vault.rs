@@ -0,0 +1,185 @@
+//! Password-manager import and export (with the `vault` feature)
+//!
+//! Custodians often keep their share in the password manager they
+//! already use. This module writes shares as files that KeePass (2.x
+//! XML) and Bitwarden (unencrypted JSON export) can import, one entry
+//! per share, and reads shares back out of exports from either.
+//!
+//! Each share goes in as a text-format line: the Password field of a
+//! KeePass entry, and the notes of a Bitwarden secure note. When
+//! reading, every value in those places (and in Bitwarden custom
+//! fields and login passwords) is tried with a [Registry], so entries
+//! that have been edited into another share format still work.
+//! Anything that isn't a share, such as the rest of someone's vault,
+//! is skipped.
+
+use crate::codec::Registry;
+use crate::error::{Error, Result};
+use crate::Share;
+use base64::Engine;
+use rand::RngCore;
+use serde_json::{json, Value};
+
+fn title(share : &Share) -> String {
+    match &share.set_id {
+	Some(id) => format!("guff-ssss share {} ({})", share.index, id),
+	None => format!("guff-ssss share {}", share.index),
+    }
+}
+
+fn description(share : &Share) -> String {
+    format!("Share {} of a secret split with guff-ssss; any {} shares from the same \
+	     set recover it with shamir-combine.", share.index, share.quorum)
+}
+
+// Values that decode as shares, in order, skipping everything else
+fn decode_values<'a>(values : impl Iterator<Item = &'a str>) -> Vec<Share> {
+    let registry = Registry::new();
+    values.filter_map(|v| registry.decode(v.trim()).ok()).collect()
+}
+
+fn none_found(format : &str) -> Error {
+    Error::UnknownFormat(format!("{} (no shares found)", format))
+}
+
+// KeePass 2.x XML
+
+fn xml_escape(text : &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+	.replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+fn xml_unescape(text : &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"")
+	.replace("&apos;", "'").replace("&amp;", "&")
+}
+
+fn keepass_uuid() -> String {
+    let mut uuid = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut uuid);
+    base64::engine::general_purpose::STANDARD.encode(uuid)
+}
+
+/// True if `text` looks like a KeePass XML file
+pub fn is_keepass_xml(text : &str) -> bool {
+    text.contains("<KeePassFile")
+}
+
+/// A KeePass 2.x XML file holding `shares`, one entry each, in a
+/// group called `guff-ssss`
+pub fn to_keepass_xml(shares : &[Share]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n\
+				<KeePassFile>\n\t<Root>\n\t\t<Group>\n");
+    xml += &format!("\t\t\t<UUID>{}</UUID>\n\t\t\t<Name>guff-ssss</Name>\n", keepass_uuid());
+    for share in shares {
+	xml += &format!("\t\t\t<Entry>\n\t\t\t\t<UUID>{}</UUID>\n", keepass_uuid());
+	for (key, value, protect) in [("Title", title(share), false),
+				      ("UserName", String::new(), false),
+				      ("Password", share.to_string(), true),
+				      ("Notes", description(share), false)] {
+	    xml += &format!("\t\t\t\t<String>\n\t\t\t\t\t<Key>{}</Key>\n\t\t\t\t\t<Value{}>{}</Value>\n\
+			     \t\t\t\t</String>\n",
+			    key, if protect { " ProtectInMemory=\"True\"" } else { "" },
+			    xml_escape(&value));
+	}
+	xml += "\t\t\t</Entry>\n";
+    }
+    xml += "\t\t</Group>\n\t</Root>\n</KeePassFile>\n";
+    xml
+}
+
+/// Read the shares out of a KeePass XML export
+pub fn from_keepass_xml(text : &str) -> Result<Vec<Share>> {
+    if !is_keepass_xml(text) {
+	return Err(Error::UnknownFormat("keepass (not a KeePass file)".into()))
+    }
+    let mut values = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<Value") {
+	rest = &rest[start..];
+	let open = match rest.find('>') { Some(open) => open, None => break };
+	// <Value/> is empty
+	if rest[..open].ends_with('/') { rest = &rest[open..]; continue }
+	let close = match rest.find("</Value>") { Some(close) => close, None => break };
+	values.push(xml_unescape(&rest[open + 1..close]));
+	rest = &rest[close..];
+    }
+    let shares = decode_values(values.iter().map(|v| v.as_str()));
+    if shares.is_empty() { return Err(none_found("keepass")) }
+    Ok(shares)
+}
+
+// Bitwarden JSON
+
+/// True if `text` looks like a Bitwarden JSON export
+pub fn is_bitwarden_json(text : &str) -> bool {
+    text.trim_start().starts_with('{') && text.contains("\"items\"")
+}
+
+/// A Bitwarden (unencrypted) JSON export holding `shares`, each as a
+/// secure note
+pub fn to_bitwarden_json(shares : &[Share]) -> String {
+    let items : Vec<Value> = shares.iter().map(|share| json!({
+	"type" : 2,
+	"name" : title(share),
+	"notes" : share.to_string(),
+	"favorite" : false,
+	"secureNote" : { "type" : 0 },
+	"fields" : [
+	    { "name" : "description", "value" : description(share), "type" : 0 },
+	],
+    })).collect();
+    let export = json!({ "encrypted" : false, "folders" : [], "items" : items });
+    serde_json::to_string_pretty(&export).expect("exports always serialize") + "\n"
+}
+
+/// Read the shares out of a Bitwarden JSON export
+pub fn from_bitwarden_json(text : &str) -> Result<Vec<Share>> {
+    let export : Value = serde_json::from_str(text)
+	.map_err(|e| Error::UnknownFormat(format!("bitwarden ({})", e)))?;
+    if export["encrypted"] == json!(true) {
+	return Err(Error::UnknownFormat("bitwarden (export is encrypted)".into()))
+    }
+    let items = export["items"].as_array()
+	.ok_or_else(|| Error::UnknownFormat("bitwarden (no items)".into()))?;
+    let mut values = Vec::new();
+    for item in items {
+	values.push(&item["notes"]);
+	values.push(&item["login"]["password"]);
+	if let Some(fields) = item["fields"].as_array() {
+	    values.extend(fields.iter().map(|f| &f["value"]));
+	}
+    }
+    let shares = decode_values(values.into_iter().filter_map(|v| v.as_str()));
+    if shares.is_empty() { return Err(none_found("bitwarden")) }
+    Ok(shares)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vault_round_trip() {
+	let shares : Vec<Share> = ["ops=3=8=1=876ec0b40cf66736af2848=",
+				   "3=8=2=a02282b29bd85113fd46ad="]
+	    .iter().map(|s| s.parse().unwrap()).collect();
+
+	let xml = to_keepass_xml(&shares);
+	assert!(is_keepass_xml(&xml) && !is_bitwarden_json(&xml));
+	assert!(xml.contains("<Value>guff-ssss share 1 (ops)</Value>"));
+	assert_eq!(from_keepass_xml(&xml).unwrap(), shares);
+
+	let json = to_bitwarden_json(&shares);
+	assert!(is_bitwarden_json(&json) && !is_keepass_xml(&json));
+	assert_eq!(from_bitwarden_json(&json).unwrap(), shares);
+
+	// Shares kept in a login's password, among other things
+	let vault = r#"{"encrypted":false,"items":[
+	    {"type":1,"name":"mail","login":{"username":"me","password":"hunter2"}},
+	    {"type":1,"name":"ssss","login":{"password":"SHARE_2='3=8=2=a02282b29bd85113fd46ad='"}}]}"#;
+	assert_eq!(from_bitwarden_json(vault).unwrap(), &shares[1..]);
+	assert!(from_bitwarden_json(r#"{"encrypted":true,"items":[]}"#).is_err());
+	assert!(from_keepass_xml("<KeePassFile><Value/><Value>x</Value></KeePassFile>").is_err());
+    }
+}
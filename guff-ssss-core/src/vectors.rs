@@ -0,0 +1,90 @@
+//! Test vectors
+//!
+//! [generate] produces a fixed suite of splits from a seed, so that
+//! other implementations (and bindings to this one) can check that
+//! they combine the same shares to the same secrets, byte for byte.
+//! The random numbers all come from ChaCha20 seeded with the given
+//! value, so a seed always gives the same vectors.
+
+use crate::{split_with_rng, Params, Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// One split: the parameters and secret that went in, and the shares
+/// (as `K=W=S=Values=` lines) that came out
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TestVector {
+    pub params : Params,
+    pub secret : Vec<u8>,
+    pub shares : Vec<String>,
+}
+
+// (k, n, secret length) for each vector in the suite. Covers the
+// degenerate k = 1 and k = n cases, an empty secret, odd lengths and
+// the largest quorum GF(2**8) allows.
+const CASES : [(u16, u16, usize); 10] = [
+    (1, 1, 0), (1, 1, 1), (1, 3, 16), (2, 2, 1), (2, 3, 16),
+    (3, 5, 11), (3, 5, 32), (5, 8, 33), (10, 16, 64), (128, 128, 16),
+];
+
+/// Generate the test-vector suite for `seed`.
+///
+/// Every other vector carries a set ID, so that both share formats
+/// are covered.
+pub fn generate(seed : u64) -> Result<Vec<TestVector>> {
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let mut vectors = Vec::with_capacity(CASES.len());
+    for (i, &(k, n, len)) in CASES.iter().enumerate() {
+	let mut params = Params::new(k, n, 8);
+	if i % 2 == 1 {
+	    params = params.with_set_id(&format!("tv{:08x}", rng.gen::<u32>()));
+	}
+	let secret : Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+	let shares = split_with_rng(&secret, &params, &mut rng, |_, _| {})?;
+	vectors.push(TestVector { params, secret, shares });
+    }
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{combine_decoder, Decoder};
+
+    #[test]
+    fn deterministic() {
+	assert_eq!(generate(1).unwrap(), generate(1).unwrap());
+	assert_ne!(generate(1).unwrap(), generate(2).unwrap());
+    }
+
+    #[test]
+    fn vectors_combine() {
+	for v in generate(42).unwrap() {
+	    assert_eq!(v.shares.len(), v.params.shares as usize);
+	    // use the last k shares, so that share 1 isn't always in
+	    let mut decoder = Decoder::new();
+	    for line in v.shares.iter().rev().take(v.params.quorum as usize) {
+		assert!(decoder.add_share(line).unwrap());
+	    }
+	    assert_eq!(combine_decoder(&mut decoder, false).unwrap(), v.secret);
+	}
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+	let vectors = generate(7).unwrap();
+	let json = serde_json::to_string(&vectors).unwrap();
+	assert_eq!(serde_json::from_str::<Vec<TestVector>>(&json).unwrap(), vectors);
+    }
+
+    // Pin the output, so that any change to how vectors (or shares)
+    // are generated shows up here rather than in someone else's
+    // test suite
+    #[test]
+    fn known_answer() {
+	let v = generate(0).unwrap();
+	assert_eq!(v[5].shares[0], "tv77e39ab3=3=8=1=c00412d7e194b931b0ce5f=");
+    }
+}
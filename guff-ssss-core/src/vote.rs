@@ -0,0 +1,248 @@
+//! Majority-vote reconstruction
+//!
+//! With more than k shares to hand, a single damaged share can be
+//! outvoted: reconstruct from several different k-subsets and go with
+//! the answer most of them agree on. Subsets that disagree are
+//! reported, so the caller can flag the run as degraded. The dealer
+//! can do the same with a new set of shares, [check_recoverable], to
+//! be sure its subsets work before handing any of them out.
+
+use crate::{combine_decoder, Decoder, Error, Result, Share};
+use rand::seq::index::sample;
+use std::collections::HashSet;
+
+/// Outcome of [combine_majority]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vote {
+    /// The answer given by the most subsets
+    pub secret : Vec<u8>,
+    /// How many subsets were tried
+    pub tried : usize,
+    /// How many of those gave `secret`
+    pub agreed : usize,
+    /// Share numbers making up each subset that gave a different
+    /// answer, or couldn't be solved at all
+    pub disagreeing : Vec<Vec<u64>>,
+}
+
+impl Vote {
+    /// True if any subset disagreed with the majority
+    pub fn is_degraded(&self) -> bool { !self.disagreeing.is_empty() }
+
+    /// Share numbers that took part in every disagreeing subset,
+    /// which are the first ones to check against their backups
+    pub fn suspects(&self) -> Vec<u64> {
+	let mut sets = self.disagreeing.iter();
+	let mut common = match sets.next() {
+	    Some(first) => first.clone(),
+	    None => return Vec::new(),
+	};
+	for set in sets { common.retain(|i| set.contains(i)) }
+	common.sort_unstable();
+	common.dedup();
+	common
+    }
+
+    /// A report of which subsets disagreed, one `key: value` item
+    /// per line, for people (or scripts) to follow up on
+    pub fn report(&self) -> String {
+	let list = |set : &[u64]| set.iter().map(|i| i.to_string())
+	    .collect::<Vec<_>>().join(",");
+	let mut report = format!("subsets: {}\nagreeing: {}\n", self.tried, self.agreed);
+	for set in self.disagreeing.iter() {
+	    report.push_str(&format!("disagreeing: {}\n", list(set)));
+	}
+	report.push_str(&format!("suspects: {}\n", list(&self.suspects())));
+	report
+    }
+}
+
+// n choose k, saturating
+fn choose(n : usize, k : usize) -> usize {
+    let mut c : usize = 1;
+    for i in 0..k {
+	c = match c.checked_mul(n - i) {
+	    Some(c) => c / (i + 1),
+	    None => return usize::MAX,
+	};
+    }
+    c
+}
+
+// Pick up to `budget` different k-subsets of `shares` (as sorted
+// lists of positions) whose share numbers are all distinct. Every
+// subset is used if there are few enough; otherwise they're chosen
+// at random. The first is always the decoder's own choice, 0..k.
+fn subsets(shares : &[Share], k : usize, budget : usize) -> Vec<Vec<usize>> {
+    let distinct = |set : &[usize]| {
+	let mut seen = HashSet::new();
+	set.iter().all(|&i| seen.insert(shares[i].index))
+    };
+    let m = shares.len();
+    let mut chosen = vec![(0..k).collect::<Vec<usize>>()];
+    if choose(m, k) <= budget {
+	// every combination, in lexicographic order
+	let mut set : Vec<usize> = (0..k).collect();
+	loop {
+	    let mut i = k;
+	    while i > 0 && set[i - 1] == m - k + i - 1 { i -= 1 }
+	    if i == 0 { break }
+	    set[i - 1] += 1;
+	    for j in i..k { set[j] = set[j - 1] + 1 }
+	    if distinct(&set) { chosen.push(set.clone()) }
+	}
+    } else {
+	let mut rng = rand::thread_rng();
+	let mut seen : HashSet<Vec<usize>> = chosen.iter().cloned().collect();
+	// give up eventually if most subsets repeat a share number
+	for _ in 0..budget.saturating_mul(8) {
+	    if chosen.len() >= budget { break }
+	    let mut set = sample(&mut rng, m, k).into_vec();
+	    set.sort_unstable();
+	    if distinct(&set) && seen.insert(set.clone()) { chosen.push(set) }
+	}
+    }
+    chosen
+}
+
+/// Reconstruct from up to `budget` different k-subsets of all the
+/// shares in `decoder` (including its spares) and return the answer
+/// that most subsets agree on.
+///
+/// It's an error if no answer gets more votes than every other (a
+/// tie, or every subset failing).
+pub fn combine_majority(decoder : &Decoder, hardened : bool, budget : usize)
+			-> Result<Vote> {
+    if !decoder.is_complete() {
+	return Err(Error::InsufficientShares {
+	    have : decoder.have() as usize, need : decoder.quorum as usize
+	})
+    }
+    let shares = decoder.all_shares();
+    let sets = subsets(&shares, decoder.quorum as usize, budget.max(1));
+
+    let mut results = Vec::with_capacity(sets.len());
+    for set in sets.iter() {
+	let mut d = Decoder::new();
+	let mut result = Ok(());
+	for &i in set.iter() {
+	    if let Err(e) = d.add(shares[i].clone()) { result = Err(e); break }
+	}
+	results.push(result.and_then(|_| combine_decoder(&mut d, hardened)).ok());
+    }
+
+    // tally the answers
+    let mut tally : Vec<(&Vec<u8>, usize)> = Vec::new();
+    for answer in results.iter().flatten() {
+	match tally.iter_mut().find(|(a, _)| *a == answer) {
+	    Some((_, n)) => *n += 1,
+	    None => tally.push((answer, 1)),
+	}
+    }
+    tally.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+    let (secret, agreed) = match tally.as_slice() {
+	[] => return Err(Error::InconsistentShares("no subset of shares could be solved".into())),
+	[(_, a), (_, b), ..] if a == b =>
+	    return Err(Error::InconsistentShares(format!("no majority answer from {} subsets",
+							 sets.len()))),
+	[(secret, agreed), ..] => ((*secret).clone(), *agreed),
+    };
+
+    let disagreeing = sets.iter().zip(results.iter())
+	.filter(|(_, r)| r.as_ref() != Some(&secret))
+	.map(|(set, _)| set.iter().map(|&i| shares[i].index).collect())
+	.collect();
+    Ok(Vote { secret, tried : sets.len(), agreed, disagreeing })
+}
+
+/// Reconstruct from up to `budget` different k-subsets of a new set
+/// of `shares` and check that every one gives back `secret`, before
+/// any of them is handed out. Returns how many subsets were checked;
+/// every subset is, if there are few enough.
+pub fn check_recoverable(secret : &[u8], shares : &[Share], budget : usize) -> Result<usize> {
+    let quorum = shares.first().map_or(0, |share| share.quorum as usize);
+    if shares.is_empty() || shares.len() < quorum {
+	return Err(Error::InsufficientShares { have : shares.len(), need : quorum.max(1) })
+    }
+    let sets = subsets(shares, quorum, budget.max(1));
+    for set in sets.iter() {
+	let mut d = Decoder::new();
+	for &i in set.iter() { d.add(shares[i].clone())?; }
+	if combine_decoder(&mut d, false)? != secret {
+	    let numbers : Vec<String> = set.iter().map(|&i| shares[i].index.to_string()).collect();
+	    return Err(Error::InconsistentShares(format!(
+		"shares {} don't give back the secret", numbers.join(","))))
+	}
+    }
+    Ok(sets.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO : [&str; 5] = [
+	"3=8=1=876ec0b40cf66736af2848=",
+	"3=8=2=a02282b29bd85113fd46ad=",
+	"3=8=3=4f292e6af80e414a200281=",
+	"3=8=4=d7b4290454ec2382cf869f=",
+	"3=8=5=38bf85dc373a33db12c2b3=",
+    ];
+
+    fn decoder(lines : &[&str]) -> Decoder {
+	let mut d = Decoder::new();
+	for line in lines.iter() { d.add_share(line).unwrap(); }
+	d
+    }
+
+    #[test]
+    fn all_agree() {
+	let vote = combine_majority(&decoder(&HELLO), false, 100).unwrap();
+	assert_eq!(vote.secret, b"hello world");
+	assert_eq!((vote.tried, vote.agreed), (10, 10));
+	assert!(!vote.is_degraded());
+	assert!(vote.suspects().is_empty());
+	// random subsets when the budget is smaller than 5C3
+	let vote = combine_majority(&decoder(&HELLO), false, 4).unwrap();
+	assert_eq!((vote.tried, vote.agreed), (4, 4));
+    }
+
+    #[test]
+    fn outvote_damaged_share() {
+	let mut lines = HELLO;
+	lines[0] = "3=8=1=876ec0b40cf66736af2849=";
+	let vote = combine_majority(&decoder(&lines), false, 100).unwrap();
+	assert_eq!(vote.secret, b"hello world");
+	assert_eq!((vote.tried, vote.agreed), (10, 4));
+	assert!(vote.is_degraded());
+	assert!(vote.disagreeing.iter().all(|set| set.contains(&1)));
+	assert_eq!(vote.suspects(), [1]);
+	let report = vote.report();
+	assert!(report.starts_with("subsets: 10\nagreeing: 4\ndisagreeing: 1,2,3\n"));
+	assert!(report.ends_with("\nsuspects: 1\n"));
+    }
+
+    #[test]
+    fn no_majority() {
+	// two versions of share 3 and nothing else spare: one vote each
+	let lines = [HELLO[0], HELLO[1], HELLO[2], "3=8=3=4f292e6af80e414a200280="];
+	assert!(matches!(combine_majority(&decoder(&lines), false, 100),
+			 Err(Error::InconsistentShares(_))));
+	assert!(matches!(combine_majority(&decoder(&HELLO[..2]), false, 100),
+			 Err(Error::InsufficientShares { have : 2, need : 3 })));
+    }
+
+    #[test]
+    fn recoverable() {
+	let shares : Vec<Share> = HELLO.iter().map(|l| l.parse().unwrap()).collect();
+	assert_eq!(check_recoverable(b"hello world", &shares, 100).unwrap(), 10);
+	assert_eq!(check_recoverable(b"hello world", &shares, 3).unwrap(), 3);
+	assert!(matches!(check_recoverable(b"hello there", &shares, 100),
+			 Err(Error::InconsistentShares(_))));
+	let mut damaged = shares.clone();
+	damaged[4].values[0] ^= 1;
+	assert!(check_recoverable(b"hello world", &damaged, 100).is_err());
+	assert!(matches!(check_recoverable(b"hello world", &shares[..2], 100),
+			 Err(Error::InsufficientShares { have : 2, need : 3 })));
+    }
+}
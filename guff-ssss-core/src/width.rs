@@ -0,0 +1,318 @@
+//! Field widths as types
+//!
+//! Each supported width GF(2<sup>w</sup>) is a unit struct
+//! implementing the sealed [Width] trait, which ties together the
+//! [guff] field used for the arithmetic and the way words of that
+//! width are packed into the byte vectors that hold shares and
+//! secrets. The solver and encoder are generic over `U : Width`, so
+//! their inner loops are monomorphised for each width and work
+//! directly on the field's own element type. Only the entry points
+//! ([combine](crate::combine), [split](crate::split) and friends)
+//! look at a run-time width value.
+//!
+//! [Width::field] builds the field afresh for every split or
+//! combine. For the wider fields that's cheap enough not to cache
+//! anywhere: guff's table-driven GF(2<sup>16</sup>) takes about 5ms
+//! to build its inverse and reduction tables (see the `gf16 tables`
+//! benchmark), and GF(2<sup>32</sup>) has no tables at all, with a
+//! combine needing only one inversion per share. A cache on disk
+//! would need to save more than that to be worth its upkeep.
+//!
+//! Words wider than a byte are stored little-endian, so the hex of a
+//! GF(2<sup>16</sup>) share has the low byte of each word first.
+
+use crate::{Decoder, Error, Result};
+use guff::GaloisField;
+use guff::good::{new_gf8_0x11b, F8_0x11b};
+#[cfg(feature = "width-4")]
+use guff::good::{new_gf4_0x13, F4_0x13};
+#[cfg(feature = "width-16")]
+use guff::good::{new_gf16_0x1002b, F16_0x1002b};
+#[cfg(feature = "width-32")]
+use guff::{new_gf32, F32};
+use rand::Rng;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Element type of the field for width `U`
+pub type Elem<U> = <<U as Width>::Field as GaloisField>::E;
+
+/// A field width that shares can be split and combined in
+pub trait Width : sealed::Sealed {
+    /// Width in bits, as it appears in the `W` field of a share
+    const BITS : u16;
+
+    /// The field that arithmetic is done in
+    type Field : GaloisField;
+
+    /// Create the field (which may involve building lookup tables,
+    /// so do it once per split or combine)
+    fn field() -> Self::Field;
+
+    /// Number of words held in `bytes` bytes
+    fn words(bytes : usize) -> usize {
+	bytes * 8 / Self::BITS as usize
+    }
+
+    /// Read word `i` from packed storage
+    fn get(bytes : &[u8], i : usize) -> Elem<Self>;
+
+    /// Append word `i` to packed storage that holds words 0 to i - 1
+    /// already. Only words narrower than a byte need `i`, to know
+    /// whether the last byte has room.
+    fn put(bytes : &mut Vec<u8>, i : usize, e : Elem<Self>);
+
+    /// Number of share numbers (or coefficients) held in `bytes`
+    /// bytes. These are stored a word apiece, or a byte apiece for
+    /// words narrower than a byte, so that each takes [word_bytes].
+    fn values(bytes : usize) -> usize {
+	Self::words(bytes)
+    }
+
+    /// Read share number (or coefficient) `j`
+    fn get_value(bytes : &[u8], j : usize) -> Elem<Self> {
+	Self::get(bytes, j)
+    }
+
+    /// Append a share number (or coefficient)
+    fn put_value(bytes : &mut Vec<u8>, e : Elem<Self>) {
+	Self::put(bytes, Self::words(bytes.len()), e)
+    }
+
+    /// A share number (already known to be in range) as a field
+    /// element
+    fn from_index(x : u64) -> Elem<Self>;
+
+    /// Uniformly random field element (possibly zero)
+    fn random<R : Rng + ?Sized>(rng : &mut R) -> Elem<Self>;
+
+    /// Pass 2 of combine, which can be specialised for a width
+    fn pass_2(field : &Self::Field, decoder : &Decoder, secret : &mut Vec<u8>,
+	      progress : &mut dyn FnMut(usize, usize)) -> Result<()> where Self : Sized {
+	crate::pass_2_into::<Self>(field, decoder, secret, progress)
+    }
+}
+
+/// GF(2<sup>4</sup>) with polynomial 0x13
+///
+/// Two words go in each byte, the first in the high nibble, so that
+/// they're in order in the hex of a share. A secret of whole bytes
+/// always makes a whole number of bytes of words, so there's no
+/// padding nibble in shares made here; a share from elsewhere whose
+/// hex ends in one (as a `K=4=S=Values=` line must, if its words don't
+/// fill the last byte) is combined with the padding as a last word,
+/// which comes back as a zero nibble.
+#[cfg(feature = "width-4")]
+pub struct U4;
+
+#[cfg(feature = "width-4")]
+impl sealed::Sealed for U4 {}
+
+#[cfg(feature = "width-4")]
+impl Width for U4 {
+    const BITS : u16 = 4;
+    type Field = F4_0x13;
+
+    fn field() -> F4_0x13 { new_gf4_0x13() }
+    fn get(bytes : &[u8], i : usize) -> u8 {
+	if i.is_multiple_of(2) { bytes[i / 2] >> 4 } else { bytes[i / 2] & 15 }
+    }
+    fn put(bytes : &mut Vec<u8>, i : usize, e : u8) {
+	match bytes.last_mut() {
+	    Some(last) if !i.is_multiple_of(2) => *last |= e & 15,
+	    _ => bytes.push(e << 4),
+	}
+    }
+    fn values(bytes : usize) -> usize { bytes }
+    fn get_value(bytes : &[u8], j : usize) -> u8 { bytes[j] }
+    fn put_value(bytes : &mut Vec<u8>, e : u8) { bytes.push(e) }
+    fn from_index(x : u64) -> u8 { x as u8 }
+    fn random<R : Rng + ?Sized>(rng : &mut R) -> u8 { rng.gen::<u8>() & 15 }
+}
+
+/// GF(2<sup>8</sup>) with polynomial 0x11b
+pub struct U8;
+
+impl sealed::Sealed for U8 {}
+
+impl Width for U8 {
+    const BITS : u16 = 8;
+    type Field = F8_0x11b;
+
+    fn field() -> F8_0x11b { new_gf8_0x11b() }
+    fn get(bytes : &[u8], i : usize) -> u8 { bytes[i] }
+    fn put(bytes : &mut Vec<u8>, _ : usize, e : u8) { bytes.push(e) }
+    fn from_index(x : u64) -> u8 { x as u8 }
+    fn random<R : Rng + ?Sized>(rng : &mut R) -> u8 { rng.gen() }
+
+    #[cfg(feature = "simd")]
+    fn pass_2(field : &F8_0x11b, decoder : &Decoder, secret : &mut Vec<u8>,
+	      progress : &mut dyn FnMut(usize, usize)) -> Result<()> {
+	crate::simd::pass_2(field, decoder, secret, progress)
+    }
+}
+
+/// GF(2<sup>16</sup>) with polynomial 0x1002b
+#[cfg(feature = "width-16")]
+pub struct U16;
+
+#[cfg(feature = "width-16")]
+impl sealed::Sealed for U16 {}
+
+#[cfg(feature = "width-16")]
+impl Width for U16 {
+    const BITS : u16 = 16;
+    type Field = F16_0x1002b;
+
+    fn field() -> F16_0x1002b { new_gf16_0x1002b() }
+    fn get(bytes : &[u8], i : usize) -> u16 { u16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]]) }
+    fn put(bytes : &mut Vec<u8>, _ : usize, e : u16) { bytes.extend_from_slice(&e.to_le_bytes()) }
+    fn from_index(x : u64) -> u16 { x as u16 }
+    fn random<R : Rng + ?Sized>(rng : &mut R) -> u16 { rng.gen() }
+}
+
+/// GF(2<sup>32</sup>) with polynomial 0x10000008d. Multiplication is
+/// guff's plain shift-and-add, there being no tables small enough to
+/// build for a field this size.
+#[cfg(feature = "width-32")]
+pub struct U32;
+
+#[cfg(feature = "width-32")]
+impl sealed::Sealed for U32 {}
+
+#[cfg(feature = "width-32")]
+impl Width for U32 {
+    const BITS : u16 = 32;
+    type Field = F32;
+
+    fn field() -> F32 { new_gf32(0x1_0000_008d, 0x8d) }
+    fn get(bytes : &[u8], i : usize) -> u32 {
+	u32::from_le_bytes([bytes[4 * i], bytes[4 * i + 1], bytes[4 * i + 2], bytes[4 * i + 3]])
+    }
+    fn put(bytes : &mut Vec<u8>, _ : usize, e : u32) { bytes.extend_from_slice(&e.to_le_bytes()) }
+    fn from_index(x : u64) -> u32 { x as u32 }
+    fn random<R : Rng + ?Sized>(rng : &mut R) -> u32 { rng.gen() }
+}
+
+/// Bytes taken by one word (or share number) of width `w`
+pub fn word_bytes(w : u16) -> usize {
+    (w as usize / 8).max(1)
+}
+
+/// Largest quorum, number of shares or share index for width `w`
+/// (zero for a width of zero, and saturating past 64 bits)
+pub fn max_index(w : u16) -> u64 {
+    match w {
+	0 => 0,
+	w => 1u64.checked_shl(w as u32 - 1).unwrap_or(u64::MAX),
+    }
+}
+
+/// Check that shares of width `w` can be handled by this build.
+///
+/// GF(2<sup>8</sup>) is always available; the other widths the
+/// share format allows can each be left out with a cargo feature
+/// (`width-4`, `width-16`, `width-32`) to keep the code small.
+pub fn check_width(w : u16) -> Result<()> {
+    match w {
+	8  => Ok(()),
+	4  if cfg!(feature = "width-4") => Ok(()),
+	16 if cfg!(feature = "width-16") => Ok(()),
+	32 if cfg!(feature = "width-32") => Ok(()),
+	4 | 16 | 32 => Err(unsupported(w)),
+	_  => Err(Error::FieldError(format!("bad field width {}", w))),
+    }
+}
+
+/// The polynomial of the field used for width `w` (with its top bit),
+/// whether or not this build has that width
+pub fn polynomial(w : u16) -> Option<u64> {
+    match w {
+	4  => Some(0x13),
+	8  => Some(0x11b),
+	16 => Some(0x1002b),
+	32 => Some(0x1_0000_008d),
+	_  => None,
+    }
+}
+
+// A width the share format allows, but that this build leaves out
+pub(crate) fn unsupported(w : u16) -> Error {
+    Error::FieldError(format!("field width {} not enabled in this build \
+			       (needs the 'width-{}' feature)", w, w))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn u8_words() {
+	let mut bytes = Vec::new();
+	for i in 1..=3 { U8::put(&mut bytes, i as usize - 1, U8::from_index(i)) }
+	assert_eq!(bytes, [1, 2, 3]);
+	assert_eq!(U8::words(bytes.len()), 3);
+	assert_eq!(U8::get(&bytes, 2), 3);
+    }
+
+    #[cfg(feature = "width-4")]
+    #[test]
+    fn u4_words() {
+	let mut bytes = Vec::new();
+	for (i, e) in [0xa, 0xb, 0xc].iter().enumerate() { U4::put(&mut bytes, i, *e) }
+	// two to a byte, in hex digit order, with the last padded
+	assert_eq!(bytes, [0xab, 0xc0]);
+	assert_eq!(U4::words(bytes.len()), 4);
+	assert_eq!((U4::get(&bytes, 0), U4::get(&bytes, 1), U4::get(&bytes, 2)), (0xa, 0xb, 0xc));
+	// share numbers a byte apiece
+	let mut xs = Vec::new();
+	for x in [3, 8] { U4::put_value(&mut xs, U4::from_index(x)) }
+	assert_eq!((xs.len(), U4::values(xs.len()), U4::get_value(&xs, 1)), (2, 2, 8));
+	assert_eq!(word_bytes(4), 1);
+	let field = U4::field();
+	assert_eq!(field.mul(0x8, 2), 0x3);
+    }
+
+    #[cfg(feature = "width-16")]
+    #[test]
+    fn u16_words() {
+	let mut bytes = Vec::new();
+	for (i, e) in [0x1234, 0xfffe].iter().enumerate() { U16::put(&mut bytes, i, *e) }
+	assert_eq!(bytes, [0x34, 0x12, 0xfe, 0xff]);
+	assert_eq!(U16::words(bytes.len()), 2);
+	assert_eq!(U16::get(&bytes, 1), 0xfffe);
+	assert_eq!(U16::from_index(32768), 0x8000);
+	assert_eq!(word_bytes(16), 2);
+    }
+
+    #[cfg(feature = "width-32")]
+    #[test]
+    fn u32_words() {
+	let mut bytes = Vec::new();
+	for (i, e) in [0x12345678, 0xfffffffe].iter().enumerate() { U32::put(&mut bytes, i, *e) }
+	assert_eq!(bytes, [0x78, 0x56, 0x34, 0x12, 0xfe, 0xff, 0xff, 0xff]);
+	assert_eq!(U32::words(bytes.len()), 2);
+	assert_eq!(U32::get(&bytes, 1), 0xfffffffe);
+	// x^31 * x = x^32 = x^7 + x^3 + x^2 + 1
+	let field = U32::field();
+	assert_eq!(field.mul(0x8000_0000, 2), 0x8d);
+	assert_eq!(field.mul(0x1234_5678, field.inv(0x1234_5678)), 1);
+    }
+
+    #[test]
+    fn widths() {
+	assert!(check_width(8).is_ok());
+	for w in [0, 7, 64] {
+	    assert!(check_width(w).unwrap_err().to_string().starts_with("bad field width"));
+	}
+	for (w, enabled) in [(4, cfg!(feature = "width-4")), (16, cfg!(feature = "width-16")),
+			     (32, cfg!(feature = "width-32"))] {
+	    match check_width(w) {
+		Ok(()) => assert!(enabled),
+		Err(e) => assert!(!enabled && e.to_string().contains("not enabled")),
+	    }
+	}
+    }
+}
@@ -0,0 +1,122 @@
+//! Threshold identities for age
+//!
+//! Used by `age-plugin-ssss`. A threshold identity is an X25519 key
+//! pair whose private half only ever exists as Shamir shares: age
+//! encrypts to the public half (the `age1ssss1...` recipient), and
+//! decrypting needs a quorum of the shares to be combined by the
+//! plugin.
+//!
+//! File keys are wrapped the same way as age's own X25519 recipients,
+//! with our own stanza tag and labels:
+//!
+//! ```text
+//! -> ssss <ephemeral share> <key tag>
+//! <ChaCha20-Poly1305 of the file key>
+//! ```
+//!
+//! The key tag is a short hash of the recipient, so the plugin can
+//! tell which stanzas are for which identity before asking anyone
+//! for their shares. The identity itself (`AGE-PLUGIN-SSSS-1...`)
+//! holds only the public key, so it can be handed out freely.
+
+use age_core::format::{FileKey, Stanza, FILE_KEY_BYTES};
+use age_core::primitives::{aead_decrypt, aead_encrypt, hkdf};
+use age_core::secrecy::ExposeSecret;
+use base64::engine::general_purpose::STANDARD_NO_PAD as BASE64;
+use base64::Engine;
+use std::convert::TryInto;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+/// The plugin's name, as in `age-plugin-ssss` and `age1ssss1...`
+pub const PLUGIN_NAME : &str = "ssss";
+
+/// Stanza tag for file keys wrapped to a threshold identity
+pub const STANZA_TAG : &str = "ssss";
+
+const WRAP_LABEL : &[u8] = b"guff-ssss/age/v1";
+const TAG_LABEL : &[u8] = b"guff-ssss/age/v1/tag";
+
+/// A new threshold key: the public key, and the private key that
+/// should be split and then forgotten
+pub fn generate() -> (PublicKey, StaticSecret) {
+    let secret = StaticSecret::random_from_rng(rand::thread_rng());
+    (PublicKey::from(&secret), secret)
+}
+
+/// The public key for a private key recovered from shares, or None
+/// if it isn't 32 bytes
+pub fn recover(secret : &[u8]) -> Option<(PublicKey, StaticSecret)> {
+    let bytes : [u8; 32] = secret.try_into().ok()?;
+    let secret = StaticSecret::from(bytes);
+    Some((PublicKey::from(&secret), secret))
+}
+
+/// The short tag that marks stanzas for `recipient`
+pub fn key_tag(recipient : &PublicKey) -> String {
+    BASE64.encode(&hkdf(&[], TAG_LABEL, recipient.as_bytes())[..4])
+}
+
+/// Read a recipient or identity (both are just the public key)
+pub fn parse_public_key(bytes : &[u8]) -> Option<PublicKey> {
+    let bytes : [u8; 32] = bytes.try_into().ok()?;
+    Some(PublicKey::from(bytes))
+}
+
+/// Wrap a file key to a threshold recipient
+pub fn wrap(recipient : &PublicKey, file_key : &FileKey) -> Stanza {
+    let ephemeral = EphemeralSecret::random_from_rng(rand::thread_rng());
+    let epk = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(recipient);
+    let mut salt = epk.as_bytes().to_vec();
+    salt.extend_from_slice(recipient.as_bytes());
+    let key = hkdf(&salt, WRAP_LABEL, shared.as_bytes());
+    Stanza {
+	tag : STANZA_TAG.to_string(),
+	args : vec![BASE64.encode(epk.as_bytes()), key_tag(recipient)],
+	body : aead_encrypt(&key, file_key.expose_secret()),
+    }
+}
+
+/// Is this a stanza for the threshold identity `recipient`?
+pub fn is_for(stanza : &Stanza, recipient : &PublicKey) -> bool {
+    stanza.tag == STANZA_TAG && stanza.args.get(1) == Some(&key_tag(recipient))
+}
+
+/// Unwrap a file key with the recovered private key. Errors are for
+/// stanzas that are malformed or don't decrypt.
+pub fn unwrap(stanza : &Stanza, secret : &StaticSecret) -> Result<FileKey, String> {
+    if stanza.args.len() != 2 { return Err("ssss stanza needs two arguments".into()) }
+    let epk : [u8; 32] = BASE64.decode(&stanza.args[0]).ok()
+	.and_then(|b| b.try_into().ok())
+	.ok_or_else(|| "bad ephemeral key in ssss stanza".to_string())?;
+    let epk = PublicKey::from(epk);
+    let recipient = PublicKey::from(secret);
+    let shared = secret.diffie_hellman(&epk);
+    let mut salt = epk.as_bytes().to_vec();
+    salt.extend_from_slice(recipient.as_bytes());
+    let key = hkdf(&salt, WRAP_LABEL, shared.as_bytes());
+    let file_key = aead_decrypt(&key, FILE_KEY_BYTES, &stanza.body)
+	.map_err(|_| "file key doesn't decrypt with the combined shares".to_string())?;
+    Ok(FileKey::init_with_mut(|k| k.copy_from_slice(&file_key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{combine, split};
+
+    #[test]
+    fn threshold_wrap() {
+	let (public, secret) = generate();
+	let shares = split(secret.as_bytes(), 2, 3, 8).unwrap();
+	let file_key = FileKey::init_with_mut(|k| k.copy_from_slice(&[9; FILE_KEY_BYTES]));
+	let stanza = wrap(&public, &file_key);
+	assert!(is_for(&stanza, &public));
+	assert!(!is_for(&stanza, &generate().0));
+
+	let (recovered, secret) = recover(&combine(&shares[1..]).unwrap()).unwrap();
+	assert_eq!(recovered, public);
+	assert_eq!(unwrap(&stanza, &secret).unwrap().expose_secret(), &[9; FILE_KEY_BYTES]);
+	assert!(unwrap(&stanza, &generate().1).is_err());
+    }
+}
@@ -0,0 +1,250 @@
+//! Packing several files into one secret
+//!
+//! `shamir-split --pack` splits a whole set of files and directories
+//! at once, and `shamir-combine --unpack DIR` puts them back. The
+//! container is a simple tar-like stream that keeps each entry's
+//! relative path, Unix permission bits and modification time along
+//! with the file contents, so what comes out is the same tree, bit for
+//! bit.
+//!
+//! Layout (all numbers little-endian):
+//!
+//! ```text
+//! magic "GSSPACK1"
+//! entry*: kind (u8: 0 file, 1 directory)
+//!         path length (u16), path (UTF-8, '/'-separated, relative)
+//!         mode (u32), mtime (i64, seconds since the epoch)
+//!         data length (u64), data (files only)
+//! ```
+//!
+//! Directories come before anything inside them. Symbolic links and
+//! other special files aren't supported.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+const MAGIC : &[u8; 8] = b"GSSPACK1";
+const FILE : u8 = 0;
+const DIR : u8 = 1;
+
+fn invalid(msg : String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// True if `secret` looks like a container made by [pack]
+pub fn is_pack(secret : &[u8]) -> bool {
+    secret.starts_with(MAGIC)
+}
+
+#[cfg(unix)]
+fn mode_of(meta : &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode_of(meta : &fs::Metadata) -> u32 {
+    if meta.permissions().readonly() { 0o444 } else { 0o644 }
+}
+
+fn mtime_of(meta : &fs::Metadata) -> i64 {
+    match meta.modified().map(|t| t.duration_since(UNIX_EPOCH)) {
+	Ok(Ok(since)) => since.as_secs() as i64,
+	Ok(Err(before)) => -(before.duration().as_secs() as i64),
+	Err(_) => 0,
+    }
+}
+
+fn write_entry(out : &mut Vec<u8>, kind : u8, name : &str, meta : &fs::Metadata,
+	       data : &[u8]) -> io::Result<()> {
+    let len = u16::try_from(name.len())
+	.map_err(|_| invalid(format!("{}: path too long", name)))?;
+    out.push(kind);
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(name.as_bytes());
+    out.extend_from_slice(&mode_of(meta).to_le_bytes());
+    out.extend_from_slice(&mtime_of(meta).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    out.extend_from_slice(data);
+    Ok(())
+}
+
+fn pack_path(out : &mut Vec<u8>, path : &Path, name : &str) -> io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.is_file() {
+	write_entry(out, FILE, name, &meta, &fs::read(path)?)
+    } else if meta.is_dir() {
+	write_entry(out, DIR, name, &meta, &[])?;
+	let mut children : Vec<_> = fs::read_dir(path)?
+	    .map(|e| e.map(|e| e.file_name()))
+	    .collect::<io::Result<_>>()?;
+	children.sort();
+	for child in children {
+	    let child_name = child.to_str()
+		.ok_or_else(|| invalid(format!("{}: file name isn't UTF-8", path.display())))?;
+	    pack_path(out, &path.join(&child), &format!("{}/{}", name, child_name))?;
+	}
+	Ok(())
+    } else {
+	Err(invalid(format!("{}: only regular files and directories can be packed",
+			    path.display())))
+    }
+}
+
+/// Pack files and directories (recursively) into a container. Each
+/// one is stored under its own final path component.
+pub fn pack(paths : &[&Path]) -> io::Result<Vec<u8>> {
+    let mut out = MAGIC.to_vec();
+    for path in paths {
+	let name = path.file_name().and_then(|n| n.to_str())
+	    .ok_or_else(|| invalid(format!("{}: can't pack this path", path.display())))?;
+	pack_path(&mut out, path, name)?;
+    }
+    Ok(out)
+}
+
+/// One entry read back from a container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path  : String,
+    pub dir   : bool,
+    pub mode  : u32,
+    pub mtime : i64,
+    pub data  : Vec<u8>,
+}
+
+/// Read the entries of a container, without touching the filesystem
+pub fn entries(pack : &[u8]) -> io::Result<Vec<Entry>> {
+    if !is_pack(pack) { return Err(invalid("not a packed set of files".into())) }
+    let mut input = &pack[MAGIC.len()..];
+    let mut entries = Vec::new();
+    let truncated = |_| invalid("packed files are truncated".into());
+    while !input.is_empty() {
+	let mut kind = [0u8; 1];
+	let mut len = [0u8; 2];
+	input.read_exact(&mut kind).map_err(truncated)?;
+	input.read_exact(&mut len).map_err(truncated)?;
+	let mut path = vec![0u8; u16::from_le_bytes(len) as usize];
+	input.read_exact(&mut path).map_err(truncated)?;
+	let (mut mode, mut mtime, mut size) = ([0u8; 4], [0u8; 8], [0u8; 8]);
+	input.read_exact(&mut mode).map_err(truncated)?;
+	input.read_exact(&mut mtime).map_err(truncated)?;
+	input.read_exact(&mut size).map_err(truncated)?;
+	let size = u64::from_le_bytes(size);
+	if size > input.len() as u64 { return Err(invalid("packed files are truncated".into())) }
+	let (data, rest) = input.split_at(size as usize);
+	input = rest;
+	let path = String::from_utf8(path).map_err(|_| invalid("bad path in pack".into()))?;
+	// Never write outside the target directory
+	if path.is_empty() || !Path::new(&path).components().all(|c| matches!(c, Component::Normal(_))) {
+	    return Err(invalid(format!("unsafe path '{}' in pack", path)))
+	}
+	entries.push(Entry {
+	    dir : match kind[0] {
+		FILE => false,
+		DIR => true,
+		k => return Err(invalid(format!("unknown entry type {} in pack", k))),
+	    },
+	    path, mode : u32::from_le_bytes(mode), mtime : i64::from_le_bytes(mtime),
+	    data : data.to_vec(),
+	});
+    }
+    Ok(entries)
+}
+
+fn set_mtime(path : &Path, mtime : i64) -> io::Result<()> {
+    let time = if mtime >= 0 {
+	UNIX_EPOCH + Duration::from_secs(mtime as u64)
+    } else {
+	UNIX_EPOCH - Duration::from_secs(mtime.unsigned_abs())
+    };
+    fs::File::open(path)?.set_modified(time)
+}
+
+#[cfg(unix)]
+fn set_mode(path : &Path, mode : u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(path : &Path, mode : u32) -> io::Result<()> {
+    let mut perms = fs::metadata(path)?.permissions();
+    perms.set_readonly(mode & 0o222 == 0);
+    fs::set_permissions(path, perms)
+}
+
+/// Unpack a container into `dir`, which is created if need be.
+/// Existing files are not overwritten. Returns the paths written.
+pub fn unpack(pack : &[u8], dir : &Path) -> io::Result<Vec<PathBuf>> {
+    let entries = entries(pack)?;
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for entry in entries.iter() {
+	let path = dir.join(&entry.path);
+	if entry.dir {
+	    fs::create_dir(&path)?;
+	} else {
+	    fs::OpenOptions::new().write(true).create_new(true).open(&path)?
+		.write_all(&entry.data)?;
+	    set_mtime(&path, entry.mtime)?;
+	    set_mode(&path, entry.mode)?;
+	}
+	written.push(path);
+    }
+    // Directories last, deepest first, since writing into them
+    // changes their mtime and a read-only one can't be written to
+    for entry in entries.iter().rev().filter(|e| e.dir) {
+	let path = dir.join(&entry.path);
+	set_mtime(&path, entry.mtime)?;
+	set_mode(&path, entry.mode)?;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_round_trip() {
+	let base = std::env::temp_dir().join(format!("guff-ssss-pack-{}", std::process::id()));
+	let _ = fs::remove_dir_all(&base);
+	let src = base.join("keys");
+	fs::create_dir_all(src.join("sub")).unwrap();
+	fs::write(src.join("a.key"), b"\x00\x01binary\xff").unwrap();
+	fs::write(src.join("sub/b.txt"), b"hello").unwrap();
+	set_mtime(&src.join("a.key"), 1_000_000_000).unwrap();
+	set_mode(&src.join("sub/b.txt"), 0o600).unwrap();
+	set_mtime(&src.join("sub"), 1_500_000_000).unwrap();
+
+	let packed = pack(&[src.as_path()]).unwrap();
+	assert!(is_pack(&packed));
+	let names : Vec<String> = entries(&packed).unwrap().into_iter().map(|e| e.path).collect();
+	assert_eq!(names, ["keys", "keys/a.key", "keys/sub", "keys/sub/b.txt"]);
+
+	let out = base.join("out");
+	unpack(&packed, &out).unwrap();
+	assert_eq!(fs::read(out.join("keys/a.key")).unwrap(), b"\x00\x01binary\xff");
+	assert_eq!(fs::read(out.join("keys/sub/b.txt")).unwrap(), b"hello");
+	let meta = |p : &str| fs::metadata(out.join(p)).unwrap();
+	assert_eq!(mtime_of(&meta("keys/a.key")), 1_000_000_000);
+	assert_eq!(mtime_of(&meta("keys/sub")), 1_500_000_000);
+	#[cfg(unix)]
+	assert_eq!(mode_of(&meta("keys/sub/b.txt")), 0o600);
+	// Nothing is overwritten
+	assert!(unpack(&packed, &out).is_err());
+	fs::remove_dir_all(&base).unwrap();
+
+	let mut evil = MAGIC.to_vec();
+	evil.push(FILE);
+	evil.extend_from_slice(&5u16.to_le_bytes());
+	evil.extend_from_slice(b"../x1");
+	evil.extend_from_slice(&[0; 20]);
+	assert!(entries(&evil).is_err());
+	assert!(entries(&packed[..packed.len() - 1]).is_err());
+    }
+}
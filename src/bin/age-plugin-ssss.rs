@@ -0,0 +1,227 @@
+// age plugin for threshold identities
+//
+// age-plugin-ssss --generate -t K -n N --shares-dir DIR > identity.txt
+//
+// makes a new X25519 key, writes its private half to DIR as N shares
+// (any K of which recover it) and prints an identity file holding
+// only the public half, with the age1ssss1... recipient in a comment.
+// Encrypt with `age -r age1ssss1...`, or `age -R identity.txt`.
+// Decrypting with `age -d -i identity.txt` starts this plugin, which
+// asks (through age) for shares until it has a quorum. See
+// src/age.rs for how file keys are wrapped.
+
+extern crate clap;
+use age_core::format::{FileKey, Stanza};
+use age_core::secrecy::ExposeSecret;
+use age_plugin::identity::{self, IdentityPluginV1};
+use age_plugin::recipient::{self, RecipientPluginV1};
+use age_plugin::{print_new_identity, run_state_machine, Callbacks, PluginHandler};
+use clap::{Arg, App};
+use guff_ssss::age::{generate, is_for, key_tag, parse_public_key, recover, unwrap, wrap,
+		      PLUGIN_NAME};
+use guff_ssss::{combine_decoder, new_set_id, split_with_params, Decoder, Params, Share};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+mod common;
+use common::Failure;
+
+struct Handler;
+
+impl PluginHandler for Handler {
+    type RecipientV1 = RecipientPlugin;
+    type IdentityV1 = IdentityPlugin;
+
+    fn recipient_v1(self) -> io::Result<RecipientPlugin> {
+	Ok(RecipientPlugin { keys : Vec::new() })
+    }
+
+    fn identity_v1(self) -> io::Result<IdentityPlugin> {
+	Ok(IdentityPlugin { keys : Vec::new() })
+    }
+}
+
+// Encrypting: recipients and identities both name the public key
+struct RecipientPlugin {
+    keys : Vec<PublicKey>,
+}
+
+impl RecipientPluginV1 for RecipientPlugin {
+    fn add_recipient(&mut self, index : usize, plugin_name : &str, bytes : &[u8])
+		     -> Result<(), recipient::Error> {
+	match parse_public_key(bytes).filter(|_| plugin_name == PLUGIN_NAME) {
+	    Some(key) => { self.keys.push(key); Ok(()) },
+	    None => Err(recipient::Error::Recipient {
+		index, message : "not a threshold recipient".into()
+	    }),
+	}
+    }
+
+    fn add_identity(&mut self, index : usize, plugin_name : &str, bytes : &[u8])
+		    -> Result<(), recipient::Error> {
+	match parse_public_key(bytes).filter(|_| plugin_name == PLUGIN_NAME) {
+	    Some(key) => { self.keys.push(key); Ok(()) },
+	    None => Err(recipient::Error::Identity {
+		index, message : "not a threshold identity".into()
+	    }),
+	}
+    }
+
+    fn labels(&mut self) -> HashSet<String> { HashSet::new() }
+
+    fn wrap_file_keys(&mut self, file_keys : Vec<FileKey>,
+		      _callbacks : impl Callbacks<recipient::Error>)
+		      -> io::Result<Result<Vec<Vec<Stanza>>, Vec<recipient::Error>>> {
+	Ok(Ok(file_keys.iter()
+	      .map(|file_key| self.keys.iter().map(|key| wrap(key, file_key)).collect())
+	      .collect()))
+    }
+}
+
+// Decrypting: ask for shares once per identity that has stanzas
+struct IdentityPlugin {
+    keys : Vec<PublicKey>,
+}
+
+// Prompt through age until the shares make up a quorum. The user can
+// give up with an empty answer, or by cancelling the prompt.
+fn collect_shares(identity : &PublicKey, callbacks : &mut impl Callbacks<identity::Error>)
+		  -> io::Result<Result<StaticSecret, String>> {
+    let mut decoder = Decoder::new();
+    let name = key_tag(identity);
+    while !decoder.is_complete() {
+	let prompt = match decoder.have() {
+	    0 => format!("Share for threshold identity {} (empty to give up):", name),
+	    have => format!("Share {} of {} for threshold identity {}:",
+			    have + 1, decoder.quorum, name),
+	};
+	let line = match callbacks.request_secret(&prompt)? {
+	    Ok(line) => line.expose_secret().trim().to_string(),
+	    Err(_) => return Ok(Err("cancelled".into())),
+	};
+	if line.is_empty() { return Ok(Err("no shares given".into())) }
+	if let Err(e) = decoder.add_share(&line) {
+	    let _ = callbacks.message(&format!("Not used: {}", e))?;
+	}
+    }
+    let secret = combine_decoder(&mut decoder, false).map_err(|e| e.to_string());
+    Ok(secret.and_then(|secret| match recover(&secret) {
+	Some((public, secret)) if public == *identity => Ok(secret),
+	_ => Err("the shares don't belong to this identity".into()),
+    }))
+}
+
+impl IdentityPluginV1 for IdentityPlugin {
+    fn add_identity(&mut self, index : usize, plugin_name : &str, bytes : &[u8])
+		    -> Result<(), identity::Error> {
+	match parse_public_key(bytes).filter(|_| plugin_name == PLUGIN_NAME) {
+	    Some(key) => { self.keys.push(key); Ok(()) },
+	    None => Err(identity::Error::Identity {
+		index, message : "not a threshold identity".into()
+	    }),
+	}
+    }
+
+    fn unwrap_file_keys(&mut self, files : Vec<Vec<Stanza>>,
+			mut callbacks : impl Callbacks<identity::Error>)
+			-> io::Result<HashMap<usize, Result<FileKey, Vec<identity::Error>>>> {
+	let mut secrets : HashMap<[u8; 32], StaticSecret> = HashMap::new();
+	let mut results = HashMap::new();
+	for (file_index, stanzas) in files.iter().enumerate() {
+	    let found = stanzas.iter().enumerate().find_map(|(stanza_index, stanza)| {
+		self.keys.iter().find(|key| is_for(stanza, key))
+		    .map(|key| (stanza_index, stanza, *key))
+	    });
+	    let (stanza_index, stanza, key) = match found {
+		Some(found) => found,
+		None => continue,
+	    };
+	    if !secrets.contains_key(key.as_bytes()) {
+		match collect_shares(&key, &mut callbacks)? {
+		    Ok(secret) => { secrets.insert(*key.as_bytes(), secret); },
+		    Err(message) => {
+			let error = identity::Error::Internal { message };
+			results.insert(file_index, Err(vec![error]));
+			continue
+		    },
+		}
+	    }
+	    let result = unwrap(stanza, &secrets[key.as_bytes()]).map_err(|message| {
+		vec![identity::Error::Stanza { file_index, stanza_index, message }]
+	    });
+	    results.insert(file_index, result);
+	}
+	Ok(results)
+    }
+}
+
+fn write_shares(dir : &Path, shares : &[String]) -> Result<(), Failure> {
+    fs::create_dir_all(dir)
+	.map_err(|e| Failure::about(e.into(), format!("Can't create {}", dir.display())))?;
+    for line in shares {
+	let share : Share = line.parse().unwrap();
+	let file = dir.join(format!("{}-share-{}.txt", share.set_id.as_deref().unwrap(),
+				    share.index));
+	common::write_owned(&file, format!("{}\n", line).as_bytes(), 0o600, None)
+	    .map_err(|e| Failure::about(e.into(), format!("Can't write {}", file.display())))?;
+	eprintln!("Share {} -> {}", share.index, file.display());
+    }
+    Ok(())
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let matches = App::new("age-plugin-ssss")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("age plugin for threshold identities, whose key is split into shares")
+	.arg(Arg::with_name("age-plugin")
+	     .long("age-plugin")
+	     .value_name("STATE_MACHINE")
+	     .help("Run the given age plugin state machine (used by age itself)"))
+	.arg(Arg::with_name("generate")
+	     .long("generate")
+	     .conflicts_with("age-plugin")
+	     .requires_all(&["threshold", "shares", "shares-dir"])
+	     .help("Make a new threshold identity"))
+	.arg(Arg::with_name("threshold")
+	     .short("t")
+	     .takes_value(true)
+	     .help("Number of shares needed to decrypt"))
+	.arg(Arg::with_name("shares")
+	     .short("n")
+	     .takes_value(true)
+	     .help("Number of shares to make"))
+	.arg(Arg::with_name("shares-dir")
+	     .long("shares-dir")
+	     .value_name("DIR")
+	     .help("Where --generate writes the shares, one file each"))
+	.get_matches();
+
+    if let Some(state_machine) = matches.value_of("age-plugin") {
+	return run_state_machine(state_machine, Handler)
+	    .map_err(|e| Failure::about(e.into(), "age plugin protocol"))
+    }
+    if !matches.is_present("generate") {
+	return Err("Nothing to do: use --generate to make a threshold identity".into())
+    }
+    let k : u16 = matches.value_of("threshold").unwrap().parse()
+	.unwrap_or_else(|_| panic!("threshold must be a number"));
+    let n : u16 = matches.value_of("shares").unwrap().parse()
+	.unwrap_or_else(|_| panic!("number of shares must be a number"));
+    let params = Params::new(k, n, 8).with_set_id(&new_set_id());
+    params.check()?;
+    params.check_sane(false)?;
+
+    let (public, secret) = generate();
+    let shares = split_with_params(secret.as_bytes(), &params, |_, _| {})?;
+    drop(secret);
+    write_shares(Path::new(matches.value_of("shares-dir").unwrap()), &shares)?;
+    println!("# threshold identity: {} of {} shares needed", k, n);
+    print_new_identity(PLUGIN_NAME, public.as_bytes(), public.as_bytes());
+    Ok(())
+}
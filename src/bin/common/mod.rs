@@ -0,0 +1,632 @@
+// Helpers shared between the command-line tools
+//
+// Each binary pulls this in with `mod common;` and only uses some of
+// it, hence the blanket allow.
+#![allow(dead_code)]
+
+use guff_ssss::binary::{decode_binary, is_binary_share};
+use guff_ssss::codec::{ArmorCodec, KubernetesCodec, Registry, ShareCodec};
+use guff_ssss::manifest::Manifest;
+use guff_ssss::{join_continuations, Share};
+use console::{Style, StyledObject};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+// Quiet mode (-q), for scripts
+//
+// Everything said on stderr that isn't an error goes through note!(),
+// which stays silent in quiet mode, and errors (panics) come out as a
+// single "error: ..." line without the thread name and location (as
+// they always do for the tools started with main(), below).
+static QUIET : AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+    clean_panics();
+}
+
+fn clean_panics() {
+    std::panic::set_hook(Box::new(|info| {
+	eprintln!("error: {}", Failure::describe(info.payload()));
+    }));
+}
+
+// Exit codes
+//
+// Each tool's run() returns a Failure for whatever stops it, and
+// main() prints it as an "error: ..." line (as in quiet mode) and
+// exits with a code that says what kind of failure it was, for
+// scripts: a library error exits with the error's exit_code() (2 for
+// malformed shares, 4 for too few, and so on), and anything else
+// with 1. Deep in a tool, where there's no Result to return, or_exit()
+// and fail() stop with a panic carrying the Failure instead, which
+// main() maps to its code in just the same way.
+#[derive(Debug)]
+pub enum Failure {
+    /// A library error, and what it was about (if anything)
+    Library { error : guff_ssss::Error, context : Option<String> },
+    /// Anything else
+    Other(String),
+}
+
+impl Failure {
+    /// A library error about `context`
+    pub fn about(error : guff_ssss::Error, context : impl Display) -> Failure {
+	Failure::Library { error, context : Some(context.to_string()) }
+    }
+
+    /// The same failure, said to be about `context`
+    pub fn within(self, context : impl Display) -> Failure {
+	match self {
+	    Failure::Library { error, context : None } => Failure::about(error, context),
+	    Failure::Library { error, context : Some(inner) } => {
+		Failure::about(error, format!("{}: {}", context, inner))
+	    },
+	    Failure::Other(message) => Failure::Other(format!("{}: {}", context, message)),
+	}
+    }
+
+    /// The exit code for this failure (the only place they're decided)
+    pub fn code(&self) -> i32 {
+	match self {
+	    Failure::Library { error, .. } => error.exit_code(),
+	    Failure::Other(_) => 1,
+	}
+    }
+
+    /// The failure a panic carried: a Failure from or_exit() or
+    /// fail(), or the message of any other panic
+    pub fn from_panic(payload : Box<dyn std::any::Any + Send>) -> Failure {
+	match payload.downcast::<Failure>() {
+	    Ok(failure) => *failure,
+	    Err(payload) => Failure::Other(Failure::describe(&*payload)),
+	}
+    }
+
+    fn describe(payload : &(dyn std::any::Any + Send)) -> String {
+	payload.downcast_ref::<Failure>().map(|f| f.to_string())
+	    .or_else(|| payload.downcast_ref::<String>().cloned())
+	    .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+	    .unwrap_or_else(|| "unknown error".into())
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    Failure::Library { error, context : Some(context) } => write!(f, "{}: {}", context, error),
+	    Failure::Library { error, context : None } => write!(f, "{}", error),
+	    Failure::Other(message) => write!(f, "{}", message),
+	}
+    }
+}
+
+impl From<guff_ssss::Error> for Failure {
+    fn from(error : guff_ssss::Error) -> Failure {
+	Failure::Library { error, context : None }
+    }
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(error : std::io::Error) -> Failure { Failure::from(guff_ssss::Error::from(error)) }
+}
+
+impl From<String> for Failure {
+    fn from(message : String) -> Failure { Failure::Other(message) }
+}
+
+impl From<&str> for Failure {
+    fn from(message : &str) -> Failure { Failure::Other(message.to_string()) }
+}
+
+/// Run a tool's `run` function, exiting with the code for whatever
+/// failure stops it
+pub fn main(run : fn() -> Result<(), Failure>) {
+    clean_panics();
+    let failure = match std::panic::catch_unwind(run) {
+	Ok(Ok(())) => return,
+	Ok(Err(failure)) => {
+	    eprintln!("error: {}", failure);
+	    failure
+	},
+	// The panic hook has already said what it was
+	Err(payload) => Failure::from_panic(payload),
+    };
+    std::process::exit(failure.code())
+}
+
+/// Stop with a library error (or any failure), saying what it was
+/// about
+pub fn fail(error : impl Into<Failure>, context : impl Display) -> ! {
+    std::panic::panic_any(error.into().within(context))
+}
+
+pub trait OrExit<T> {
+    /// The value, or stop with the error
+    fn or_exit(self) -> T;
+}
+
+impl<T> OrExit<T> for guff_ssss::Result<T> {
+    fn or_exit(self) -> T {
+	self.unwrap_or_else(|e| std::panic::panic_any(Failure::from(e)))
+    }
+}
+
+impl<T> OrExit<T> for Result<T, Failure> {
+    fn or_exit(self) -> T {
+	self.unwrap_or_else(|failure| std::panic::panic_any(failure))
+    }
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+macro_rules! note {
+    ($($arg:tt)*) => { if !$crate::common::quiet() { eprintln!($($arg)*) } }
+}
+#[allow(unused_imports)]
+pub(crate) use note;
+
+// Windows consoles
+//
+// Rust's own stdin and stdout never translate line endings, but the
+// C runtime does for any linked library that uses its descriptors,
+// so the tools that pass secrets or binary shares through stdio put
+// both in binary mode first. The console itself can only show text,
+// so raw bytes that aren't UTF-8 are refused there with advice,
+// rather than failing partway through.
+#[cfg(windows)]
+extern "C" {
+    fn _setmode(fd : i32, mode : i32) -> i32;
+}
+
+/// Put stdin and stdout in binary mode (Windows; elsewhere a no-op)
+pub fn binary_stdio() {
+    #[cfg(windows)]
+    {
+	const O_BINARY : i32 = 0x8000;
+	// Safety: 0 and 1 are always open in a console program
+	unsafe {
+	    _setmode(0, O_BINARY);
+	    _setmode(1, O_BINARY);
+	}
+    }
+}
+
+/// Write `bytes` to stdout as they are
+pub fn write_raw(bytes : &[u8]) {
+    if cfg!(windows) && io::stdout().is_terminal() && std::str::from_utf8(bytes).is_err() {
+	panic!("This isn't text, and the Windows console can only show text: \
+		redirect it to a file (> secret.bin) or use --copy")
+    }
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(bytes).and_then(|_| stdout.flush())
+	.unwrap_or_else(|e| panic!("Can't write to stdout: {}", e));
+}
+
+// Colour (--no-color, NO_COLOR)
+//
+// Summaries meant for people (share tables, quorum progress,
+// warnings) are coloured when they go to a terminal. Anything a
+// script might read (shares, secrets, --report output) is never
+// styled, and colour is off altogether with --no-color or when
+// NO_COLOR is set to anything (https://no-color.org).
+pub fn set_color(no_color : bool) {
+    let wanted = !no_color && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+    console::set_colors_enabled(wanted && io::stdout().is_terminal());
+    console::set_colors_enabled_stderr(wanted && io::stderr().is_terminal());
+}
+
+/// What a piece of a summary is saying
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tone { Good, Warn, Bad, Dim, Bold }
+
+fn style(tone : Tone) -> Style {
+    match tone {
+	Tone::Good => Style::new().green(),
+	Tone::Warn => Style::new().yellow(),
+	Tone::Bad  => Style::new().red().bold(),
+	Tone::Dim  => Style::new().dim(),
+	Tone::Bold => Style::new().bold(),
+    }
+}
+
+/// `text` styled for stderr
+pub fn paint<D : Display>(tone : Tone, text : D) -> StyledObject<D> {
+    style(tone).for_stderr().apply_to(text)
+}
+
+/// `text` styled for stdout
+pub fn paint_out<D : Display>(tone : Tone, text : D) -> StyledObject<D> {
+    style(tone).apply_to(text)
+}
+
+#[allow(unused_macros)]
+macro_rules! warning {
+    ($($arg:tt)*) => {
+	eprintln!("{} {}", $crate::common::paint($crate::common::Tone::Warn, "Warning:"), format!($($arg)*))
+    }
+}
+#[allow(unused_imports)]
+pub(crate) use warning;
+
+/// True if there's someone at a terminal to show a summary to
+pub fn on_terminal() -> bool {
+    !quiet() && io::stderr().is_terminal()
+}
+
+/// `have` of `need` as a row of boxes, eg `■■□ 2 of 3`
+pub fn quorum_progress(have : usize, need : usize) -> String {
+    let boxes = "■".repeat(have.min(need)) + &"□".repeat(need.saturating_sub(have));
+    let tone = if have >= need { Tone::Good } else { Tone::Warn };
+    format!("{} {} of {}", paint(tone, boxes), have, need)
+}
+
+/// One share in a [share_table]
+pub struct ShareRow {
+    pub index       : u64,
+    pub set         : Option<String>,
+    pub fingerprint : String,
+    pub status      : (Tone, String),
+}
+
+/// Print a table of shares on stderr, for people to check over
+pub fn share_table(rows : &[ShareRow]) {
+    if quiet() || rows.is_empty() { return }
+    let set = |row : &ShareRow| row.set.clone().unwrap_or_else(|| "-".to_string());
+    let width = rows.iter().map(|r| set(r).len()).max().unwrap_or(0).max(3);
+    eprintln!("{}", paint(Tone::Bold, format!("{:>5}  {:<width$}  {:<11}  {}",
+					       "Share", "Set", "Fingerprint", "Status")));
+    for row in rows {
+	eprintln!("{:>5}  {:<width$}  {:<11}  {}", row.index, set(row), row.fingerprint,
+		  paint(row.status.0, &row.status.1));
+    }
+}
+
+// Don't bother drawing a bar for anything smaller than this
+const PROGRESS_THRESHOLD : usize = 1 << 20;
+
+/// Progress bar for an operation over `total` bytes, or None if the
+/// job is small or nobody is watching stderr
+pub fn progress_bar(total : usize) -> Option<ProgressBar> {
+    if total < PROGRESS_THRESHOLD || quiet() || !io::stderr().is_terminal() {
+	return None
+    }
+    let bar = ProgressBar::new(total as u64);
+    bar.set_style(ProgressStyle::with_template(
+	"{bar:40} {bytes}/{total_bytes} {binary_bytes_per_sec} ETA {eta}")
+		  .unwrap());
+    Some(bar)
+}
+
+/// Adapt an optional progress bar to the library's progress callback
+pub fn progress_callback(bar : &Option<ProgressBar>) -> impl FnMut(usize, usize) + '_ {
+    move |done, _total| {
+	if let Some(bar) = bar { bar.set_position(done as u64) }
+    }
+}
+
+/// Print a prompt and read a line from the controlling terminal,
+/// even if stdin is redirected. Returns None at end of input.
+pub fn prompt_line(prompt : &str) -> Option<String> {
+    let tty = File::open(if cfg!(windows) { "CONIN$" } else { "/dev/tty" })
+	.unwrap_or_else(|e| panic!("Can't open terminal: {}", e));
+    eprint!("{}", prompt);
+    io::stderr().flush().unwrap();
+    let mut line = String::new();
+    match BufReader::new(tty).read_line(&mut line) {
+	Ok(0) | Err(_) => None,
+	Ok(_) => Some(line.trim_end_matches(&['\r', '\n'][..]).to_string()),
+    }
+}
+
+/// Read a secret from the terminal without echoing it, asking twice
+/// so that a typo doesn't end up in every share. Asks again until the
+/// two entries match.
+pub fn prompt_secret_twice(prompt : &str) -> Vec<u8> {
+    loop {
+	let prompt = if quiet() { "" } else { prompt };
+	let first = rpassword::prompt_password(prompt)
+	    .unwrap_or_else(|e| panic!("Can't read secret: {}", e));
+	let second = rpassword::prompt_password(if quiet() { "" } else { "Enter it again: " })
+	    .unwrap_or_else(|e| panic!("Can't read secret: {}", e));
+	if first == second { return first.into_bytes() }
+	note!("The two entries don't match; try again");
+    }
+}
+
+// Share files
+//
+// Each custodian's file is written next to where it's going, synced
+// to disk and renamed into place, so a crash or a pulled USB stick
+// leaves either the whole share or none of it. The files are written
+// all at once, one thread each (or through io_uring, with the `uring`
+// feature on Linux), so that n slow devices (or one slow network
+// mount) cost the time of one write rather than n.
+
+/// Read a whole file, through io_uring if we can. Errors name the
+/// file.
+pub fn read_file(path : &Path) -> io::Result<Vec<u8>> {
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    if guff_ssss::uring::available() {
+	return guff_ssss::uring::read_files(&[path]).map(|mut data| data.remove(0))
+    }
+    fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/// Write `data` to `path` by way of `path.tmp`, synced before the
+/// rename
+pub fn write_atomic(path : &Path, data : &[u8]) -> io::Result<()> {
+    write_atomic_as(path, data, fs::OpenOptions::new().write(true).create(true).truncate(true),
+		    |_| Ok(()))
+}
+
+/// [write_atomic], but readable by the owner alone from the start
+pub fn write_private(path : &Path, data : &[u8]) -> io::Result<()> {
+    write_owned(path, data, 0o400, None)
+}
+
+/// [write_atomic], with the file given permission bits `mode` (not
+/// masked by the umask) and, if given, handed to `owner` (user, and
+/// group if that's given too) before it's renamed into place. Until
+/// then only we can read it. Both are for Unix; elsewhere the file is
+/// just written.
+pub fn write_owned(path : &Path, data : &[u8], mode : u32, owner : Option<(u32, Option<u32>)>)
+		   -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+	use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+	options.mode(0o600);
+	write_atomic_as(path, data, &options, |file| {
+	    if let Some((uid, gid)) = owner { std::os::unix::fs::fchown(file, Some(uid), gid)? }
+	    file.set_permissions(fs::Permissions::from_mode(mode))
+	})
+    }
+    #[cfg(not(unix))]
+    {
+	let _ = (mode, owner);
+	write_atomic_as(path, data, &options, |_| Ok(()))
+    }
+}
+
+fn write_atomic_as(path : &Path, data : &[u8], options : &fs::OpenOptions,
+		   finish : impl FnOnce(&File) -> io::Result<()>) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    // A read-only leftover from an earlier try can't be opened again
+    let _ = fs::remove_file(&tmp);
+    let written = options.open(&tmp).and_then(|mut file| {
+	file.write_all(data)?;
+	finish(&file)?;
+	file.sync_all()
+    }).and_then(|_| fs::rename(&tmp, path));
+    if written.is_err() { let _ = fs::remove_file(&tmp); }
+    written
+}
+
+/// Write every file with [write_owned], concurrently, readable and
+/// writable by the owner alone since they hold shares. Once all have
+/// been tried, fails with the first that couldn't be written (the
+/// rest are warned about), or with a panic in a writer.
+pub fn write_files(files : &[(PathBuf, Vec<u8>)]) -> Result<(), Failure> {
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    if guff_ssss::uring::available() {
+	guff_ssss::uring::write_files(files)
+	    .map_err(|e| Failure::about(e.into(), "Can't write the shares"))?;
+	sync_dirs(files);
+	return Ok(())
+    }
+    let mut failed = thread::scope(|scope| {
+	let writers : Vec<_> = files.iter().map(|(path, data)| scope.spawn(move || {
+	    write_owned(path, data, 0o600, None)
+		.map_err(|e| Failure::about(e.into(), format!("Can't write {}", path.display())))
+	})).collect();
+	writers.into_iter()
+	    .filter_map(|w| w.join().unwrap_or_else(|payload| Err(Failure::from_panic(payload))).err())
+	    .collect::<Vec<_>>()
+    }).into_iter();
+    if let Some(first) = failed.next() {
+	for other in failed { warning!("{}", other) }
+	return Err(first)
+    }
+    sync_dirs(files);
+    Ok(())
+}
+
+// The renames are only durable once the directories are synced too
+// (which not every filesystem allows, hence no complaint)
+fn sync_dirs(files : &[(PathBuf, Vec<u8>)]) {
+    #[cfg(unix)]
+    {
+	let mut dirs : Vec<&Path> = files.iter().filter_map(|(path, _)| path.parent()).collect();
+	dirs.sort();
+	dirs.dedup();
+	for dir in dirs {
+	    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+	    let _ = File::open(dir).and_then(|d| d.sync_all());
+	}
+    }
+    #[cfg(not(unix))]
+    let _ = files;
+}
+
+// systemd credentials (LoadCredential=, ImportCredential=)
+//
+// systemd hands a service its credentials as files in a private
+// $CREDENTIALS_DIRECTORY, looked up by name in the credential stores
+// (/run/credstore first) or loaded from a path the unit gives. A
+// secret or shares put in a store that way never pass through the
+// service's environment, which its children inherit and /proc shows.
+// Shares are stored as NAME.1, NAME.2 and so on, so that
+// `ImportCredential=NAME.*` brings in every one on the machine.
+pub const CREDSTORE : &str = "/run/credstore";
+
+/// Credential names become file names, so systemd's rules are the
+/// same. Container secrets (shamir-combine --oci-secret) follow them too.
+pub fn check_credential_name(name : &str) {
+    if name.is_empty() || name == "." || name == ".." || name.len() > 255
+	|| name.contains(['/', '\0']) {
+	panic!("{:?} can't be a credential name", name)
+    }
+}
+
+/// Put `data` in the credential store `dir` as `name`, with both
+/// readable by the owner (normally root) alone
+pub fn write_credential(dir : &Path, name : &str, data : &[u8]) -> PathBuf {
+    check_credential_name(name);
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir).unwrap_or_else(|e| fail(e, format!("Can't create {}", dir.display())));
+    let path = dir.join(name);
+    write_private(&path, data).unwrap_or_else(|e| fail(e, format!("Can't write {}", path.display())));
+    path
+}
+
+/// Files in the service's credentials directory: credential `name`
+/// and `name.*`, or all of them, in name order
+pub fn credential_files(name : Option<&str>) -> Vec<PathBuf> {
+    let dir = std::env::var_os("CREDENTIALS_DIRECTORY")
+	.unwrap_or_else(|| panic!("CREDENTIALS_DIRECTORY isn't set: run under systemd with \
+				   LoadCredential= or ImportCredential="));
+    let dir = Path::new(&dir);
+    let mut files : Vec<PathBuf> = fs::read_dir(dir)
+	.unwrap_or_else(|e| fail(e, format!("Can't read {}", dir.display())))
+	.filter_map(|entry| entry.ok())
+	.filter(|entry| {
+	    let file = entry.file_name().to_string_lossy().into_owned();
+	    name.is_none_or(|name| file == name
+			    || file.strip_prefix(name).is_some_and(|rest| rest.starts_with('.')))
+	})
+	.map(|entry| entry.path()).collect();
+    files.sort();
+    if files.is_empty() {
+	panic!("No credentials{} in {}", name.map(|n| format!(" named {}", n)).unwrap_or_default(),
+	       dir.display())
+    }
+    files
+}
+
+// Watching a directory (shamir-combine --watch, shamir-split --watch)
+//
+// Only whether something in the directory was written, created or
+// renamed into it matters, not what.
+#[cfg(target_os = "linux")]
+pub struct DirWatcher { fd : i32 }
+
+#[cfg(target_os = "linux")]
+impl DirWatcher {
+    pub fn new(dir : &Path) -> DirWatcher {
+	use std::os::unix::ffi::OsStrExt;
+	let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).unwrap();
+	let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+	if fd < 0 { panic!("inotify_init1: {}", io::Error::last_os_error()) }
+	let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE;
+	if unsafe { libc::inotify_add_watch(fd, path.as_ptr(), mask) } < 0 {
+	    panic!("inotify_add_watch: {}", io::Error::last_os_error())
+	}
+	DirWatcher { fd }
+    }
+
+    // Block until at least one event arrives. We don't care which
+    // file changed since callers rescan anyway.
+    pub fn wait(&mut self) {
+	let mut buf = [0u8; 4096];
+	unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+    }
+}
+
+// No inotify, so fall back to polling
+#[cfg(not(target_os = "linux"))]
+pub struct DirWatcher {}
+
+#[cfg(not(target_os = "linux"))]
+impl DirWatcher {
+    pub fn new(_dir : &Path) -> DirWatcher { DirWatcher {} }
+    pub fn wait(&mut self) { thread::sleep(std::time::Duration::from_secs(1)) }
+}
+
+// Public verification records (--manifest FILE, --dealer-key KEY)
+//
+// The signature is checked first. Without a dealer key it only shows
+// that the record hasn't been altered since it was signed, so the key
+// is printed for the user to compare against one they trust.
+pub fn read_manifest(path : &Path, dealer_key : Option<&str>) -> Result<Manifest, Failure> {
+    let about = |e| Failure::about(e, path.display());
+    let text = fs::read_to_string(path).map_err(|e| about(e.into()))?;
+    #[cfg(feature = "yaml")]
+    let manifest = if text.trim_start().starts_with("guff-ssss manifest") {
+	text.parse()
+    } else {
+	Manifest::from_yaml(&text)
+    };
+    #[cfg(not(feature = "yaml"))]
+    let manifest : guff_ssss::Result<Manifest> = text.parse();
+    let manifest = manifest.map_err(about)?;
+    let signer = manifest.verify_signature().map_err(about)?;
+    let unverified = |why| Err(about(guff_ssss::Error::Verification(why)));
+    match (signer, dealer_key) {
+	(Some(signer), Some(key)) if signer != key.to_lowercase() =>
+	    return unverified(format!("signed by {}, not the expected dealer key", signer)),
+	(Some(signer), _) => note!("Manifest signed by {}", signer),
+	(None, Some(_)) => return unverified("manifest is not signed".into()),
+	(None, None) => note!("Manifest is not signed"),
+    }
+    Ok(manifest)
+}
+
+// Reading shares for the tools that work on shares one at a time
+// (shamir-reencode, shamir-fingerprint). Sealed, locked or wrapped
+// shares aren't opened: those need shamir-combine.
+
+// Every share in `text`: a stream of multi-line documents (armor,
+// Kubernetes, YAML), or one share per line in any registered codec,
+// with wrapped lines joined back up and blank lines and comments
+// skipped
+fn shares_in_text(registry : &Registry, text : &str) -> Result<Vec<Share>, Failure> {
+    if ArmorCodec.detect(text) {
+	return Ok(guff_ssss::codec::decode_armor_stream(text)?)
+    }
+    if KubernetesCodec.detect(text) {
+	return Ok(guff_ssss::codec::decode_kubernetes_stream(text)?)
+    }
+    #[cfg(feature = "yaml")]
+    if guff_ssss::codec::YamlCodec.detect(text) {
+	return Ok(guff_ssss::codec::decode_yaml_stream(text)?)
+    }
+    join_continuations(text.lines()).into_iter()
+	.filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+	.map(|line| registry.decode(line.trim()).map_err(|e| Failure::about(e, line.trim())))
+	.collect()
+}
+
+/// Every share in a file: binary, text, or (with the `qr` feature)
+/// an image of QR codes
+pub fn read_shares(path : &Path) -> Result<Vec<Share>, Failure> {
+    #[cfg(feature = "qr")]
+    if guff_ssss::qr::is_image(path) {
+	let lines = guff_ssss::qr::decode_image(path)?;
+	return shares_in_text(&Registry::new(), &lines.join("\n"))
+    }
+    let bytes = read_file(path).map_err(guff_ssss::Error::from)?;
+    shares_in(bytes).map_err(|e| e.within(path.display()))
+}
+
+/// Every share in the contents of a file, or stdin
+pub fn shares_in(bytes : Vec<u8>) -> Result<Vec<Share>, Failure> {
+    if is_binary_share(&bytes) {
+	return Ok(vec![decode_binary(&bytes)?])
+    }
+    let text = String::from_utf8(bytes).map_err(|_| "not a text or binary share")?;
+    shares_in_text(&Registry::new(), &text)
+}
@@ -0,0 +1,129 @@
+// git credential helper that keeps the token in shares
+//
+// A CI machine that pushes or clones with a token usually has it
+// sitting in a file. Instead, split the token, leave some shares on
+// the machine and put the rest somewhere it has to ask for them (a
+// secrets manager, another host over ssh), and let git put the token
+// together each time it needs it:
+//
+//   git config credential.helper \
+//     'shamir --host github.com --username ci --share /etc/ci/share-1.txt \
+//            --fetch "ssh escrow cat shares/ci-2.txt"'
+//
+// Local shares are read first, then each --fetch command is run in
+// turn (through the shell) and whatever shares it prints are added,
+// until there are enough. The token is printed to git and forgotten;
+// `store` and `erase` are accepted and ignored, since there's nothing
+// stored to change. With --host, requests for any other host get no
+// answer, so git moves on to its next helper.
+
+extern crate clap;
+use clap::{App, Arg};
+use guff_ssss::credential::{write_reply, Request};
+use guff_ssss::{combine_decoder, Decoder, Error, Share};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+mod common;
+use common::Failure;
+
+// The shares a --fetch command prints
+fn fetch(command : &str) -> Result<Vec<Share>, Failure> {
+    #[cfg(windows)]
+    let mut shell = Command::new("cmd");
+    #[cfg(windows)]
+    shell.arg("/C");
+    #[cfg(not(windows))]
+    let mut shell = Command::new("sh");
+    #[cfg(not(windows))]
+    shell.arg("-c");
+    let output = shell.arg(command).stdin(Stdio::null()).stderr(Stdio::inherit()).output()
+	.map_err(|e| format!("Can't run {}: {}", command, e))?;
+    if !output.status.success() {
+	return Err(format!("{} failed ({})", command, output.status).into())
+    }
+    common::shares_in(output.stdout).map_err(|e| e.within(command))
+}
+
+// Add shares until there are enough; false if they're all used up
+fn add_all(decoder : &mut Decoder, shares : Vec<Share>) -> Result<bool, Failure> {
+    for share in shares {
+	if decoder.is_complete() { break }
+	decoder.add_share(&share.to_string())
+	    .map_err(|e| Failure::about(e, format!("Share {}", share.index)))?;
+    }
+    Ok(decoder.is_complete())
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let matches = App::new("git-credential-shamir")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("git credential helper that combines shares of the token when git asks for it")
+	.arg(Arg::with_name("share")
+	     .long("share")
+	     .value_name("FILE")
+	     .multiple(true)
+	     .number_of_values(1)
+	     .help("A file of shares on this machine"))
+	.arg(Arg::with_name("fetch")
+	     .long("fetch")
+	     .value_name("COMMAND")
+	     .multiple(true)
+	     .number_of_values(1)
+	     .help("A shell command that prints more shares, run only if they're needed"))
+	.arg(Arg::with_name("host")
+	     .long("host")
+	     .value_name("HOST")
+	     .help("Only answer for this host"))
+	.arg(Arg::with_name("username")
+	     .long("username")
+	     .value_name("NAME")
+	     .help("Username to give with the token (default: whatever git asked with)"))
+	.arg(Arg::with_name("hardened")
+	     .long("hardened")
+	     .help("Combine in constant time"))
+	.arg(Arg::with_name("OPERATION")
+	     .required(true)
+	     .possible_values(&["get", "store", "erase"])
+	     .help("What git wants (only 'get' does anything)"))
+	.get_matches();
+
+    // Anything said on stderr goes straight to the user running git
+    common::set_quiet();
+    let stdin = io::stdin();
+    let request = Request::read(stdin.lock()).map_err(|e| Failure::about(e.into(), "stdin"))?;
+    if matches.value_of("OPERATION") != Some("get") { return Ok(()) }
+    if let Some(host) = matches.value_of("host") {
+	if request.get("host") != Some(host) { return Ok(()) }
+    }
+
+    let mut decoder = Decoder::new();
+    let mut complete = false;
+    for path in matches.values_of("share").into_iter().flatten() {
+	complete = add_all(&mut decoder, common::read_shares(Path::new(path))?)?;
+	if complete { break }
+    }
+    for command in matches.values_of("fetch").into_iter().flatten() {
+	if complete { break }
+	complete = add_all(&mut decoder, fetch(command)?)?;
+    }
+    if decoder.have() == 0 {
+	return Err("No shares of the token: give --share or --fetch".into())
+    }
+    if !complete {
+	let (have, need) = (decoder.have() as usize, decoder.quorum as usize);
+	return Err(Failure::about(Error::InsufficientShares { have, need }, "The token"))
+    }
+
+    let token = combine_decoder(&mut decoder, matches.is_present("hardened"))?;
+    // A token file usually ends in a newline that isn't part of it
+    let token = token.strip_suffix(b"\n").unwrap_or(&token);
+    let username = matches.value_of("username").or_else(|| request.get("username"));
+    write_reply(&mut io::stdout().lock(), username, token)
+	.map_err(|e| Failure::about(e.into(), "stdout"))?;
+    Ok(())
+}
@@ -0,0 +1,217 @@
+// Quorum collection agent for Shamir's Secret Sharing Scheme
+//
+// Listens on a unix socket and accepts shares one at a time, possibly
+// from several different custodians logging in separately. Once a
+// quorum has been collected, the secret is released to an authorised
+// consumer (identified by the peer uid on the socket) and the agent
+// wipes its state and exits.
+//
+// The protocol is line-based text, so `socat - UNIX-CONNECT:path` or
+// `nc -U path` is all a custodian needs:
+//
+//   SUBMIT K=W=S=Values=   ->  OK have/k | IGNORED have/k | ERR message
+//   STATUS                 ->  STATUS have/k
+//   RELEASE                ->  SECRET hex | ERR message
+
+extern crate clap;
+#[cfg(unix)]
+use clap::{Arg, App};
+
+mod common;
+#[cfg(unix)]
+use common::Failure;
+
+#[cfg(unix)]
+mod agent {
+    use guff_ssss::{Decoder, combine_decoder};
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::time::Duration;
+
+    pub struct Agent {
+	decoder   : Decoder,
+	consumers : Vec<u32>,
+	hardened  : bool,
+    }
+
+    // Overwrite a buffer in a way the optimiser can't elide
+    fn wipe(v : &mut [u8]) {
+	for b in v.iter_mut() {
+	    unsafe { std::ptr::write_volatile(b, 0) }
+	}
+    }
+
+    // uid of the process at the other end of the socket
+    #[cfg(target_os = "linux")]
+    fn peer_uid(stream : &UnixStream) -> Option<u32> {
+	let mut cred = libc::ucred { pid : 0, uid : 0, gid : 0 };
+	let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+	let rc = unsafe {
+	    libc::getsockopt(stream.as_raw_fd(), libc::SOL_SOCKET,
+			     libc::SO_PEERCRED,
+			     &mut cred as *mut libc::ucred as *mut libc::c_void,
+			     &mut len)
+	};
+	if rc == 0 { Some(cred.uid) } else { None }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn peer_uid(stream : &UnixStream) -> Option<u32> {
+	let mut uid : libc::uid_t = 0;
+	let mut gid : libc::gid_t = 0;
+	let rc = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+	if rc == 0 { Some(uid) } else { None }
+    }
+
+    impl Agent {
+	pub fn new(consumers : Vec<u32>, hardened : bool) -> Agent {
+	    Agent { decoder : Decoder::new(), consumers, hardened }
+	}
+
+	fn progress(&self) -> String {
+	    if self.decoder.quorum == 0 {
+		"0/?".to_string()
+	    } else {
+		format!("{}/{}", self.decoder.have(), self.decoder.quorum)
+	    }
+	}
+
+	// Handle one command. Returns the reply and whether the agent
+	// should shut down afterwards.
+	fn command(&mut self, line : &str, uid : Option<u32>) -> (String, bool) {
+	    let line = line.trim();
+	    let (verb, arg) = match line.find(' ') {
+		Some(i) => (&line[..i], line[i + 1..].trim()),
+		None    => (line, ""),
+	    };
+	    match verb {
+		"SUBMIT" => match self.decoder.add_share(arg) {
+		    Ok(true) => {
+			eprintln!("accepted share from uid {:?} ({})",
+				  uid, self.progress());
+			(format!("OK {}", self.progress()), false)
+		    },
+		    Ok(false) => (format!("IGNORED {}", self.progress()), false),
+		    Err(e) => (format!("ERR {}", e), false),
+		},
+		"STATUS" => (format!("STATUS {}", self.progress()), false),
+		"RELEASE" => {
+		    match uid {
+			Some(uid) if self.consumers.contains(&uid) => {},
+			_ => {
+			    eprintln!("refused release to uid {:?}", uid);
+			    return ("ERR not an authorised consumer".into(), false)
+			}
+		    }
+		    if !self.decoder.is_complete() {
+			return (format!("ERR quorum not met ({})", self.progress()), false)
+		    }
+		    let mut secret = match combine_decoder(&mut self.decoder, self.hardened) {
+			Ok(secret) => secret,
+			Err(e) => return (format!("ERR {}", e), false),
+		    };
+		    let reply = format!("SECRET {}", hex::encode(&secret));
+		    wipe(&mut secret);
+		    wipe(&mut self.decoder.shares);
+		    wipe(&mut self.decoder.coefficients);
+		    eprintln!("released secret to uid {:?}", uid);
+		    (reply, true)
+		},
+		_ => ("ERR unknown command".into(), false),
+	    }
+	}
+
+	// Serve a single connection until EOF. Returns true once the
+	// secret has been released.
+	fn session(&mut self, stream : UnixStream) -> bool {
+	    let uid = peer_uid(&stream);
+	    let _ = stream.set_read_timeout(Some(Duration::from_secs(60)));
+	    let mut writer = match stream.try_clone() {
+		Ok(w) => w,
+		Err(_) => return false,
+	    };
+	    for line in BufReader::new(stream).lines() {
+		let line = match line {
+		    Ok(line) => line,
+		    Err(_) => break,	// timeout or bad UTF-8
+		};
+		let (mut reply, done) = self.command(&line, uid);
+		reply.push('\n');
+		let _ = writer.write_all(reply.as_bytes());
+		// SAFETY: wiping the reply's bytes leaves valid UTF-8 (NULs)
+		wipe(unsafe { reply.as_bytes_mut() });
+		if done { return true }
+	    }
+	    false
+	}
+
+	pub fn run(&mut self, path : &str, mode : u32) -> std::io::Result<()> {
+	    let listener = UnixListener::bind(path)?;
+	    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+	    eprintln!("listening on {}", path);
+	    for stream in listener.incoming() {
+		match stream {
+		    Ok(stream) => if self.session(stream) { break },
+		    Err(e) => eprintln!("accept failed: {}", e),
+		}
+	    }
+	    fs::remove_file(path)
+	}
+    }
+}
+
+#[cfg(unix)]
+fn main() { common::main(run) }
+
+#[cfg(unix)]
+fn run() -> Result<(), Failure> {
+    let matches = App::new("shamir-agent")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Collects shares over a unix socket and releases the secret once a quorum is met")
+	.arg(Arg::with_name("socket")
+	     .short("s")
+	     .long("socket")
+	     .value_name("PATH")
+	     .required(true)
+	     .help("Unix socket to listen on"))
+	.arg(Arg::with_name("consumer")
+	     .short("u")
+	     .long("consumer")
+	     .value_name("UID")
+	     .multiple(true)
+	     .number_of_values(1)
+	     .help("uid allowed to RELEASE the secret (default: our own uid)"))
+	.arg(Arg::with_name("mode")
+	     .long("mode")
+	     .value_name("OCTAL")
+	     .default_value("660")
+	     .help("Permissions for the socket"))
+	.arg(Arg::with_name("hardened")
+	     .long("hardened")
+	     .help("Blind field inversions with random values \
+		    (side-channel mitigation)"))
+	.get_matches();
+
+    let consumers : Vec<u32> = match matches.values_of("consumer") {
+	Some(uids) => uids.map(|u| u.parse()
+			       .unwrap_or_else(|_| panic!("bad uid {}", u)))
+	    .collect(),
+	None => vec![unsafe { libc::getuid() }],
+    };
+    let mode = u32::from_str_radix(matches.value_of("mode").unwrap(), 8)
+	.unwrap_or_else(|_| panic!("--mode expects an octal value"));
+
+    let mut agent = agent::Agent::new(consumers, matches.is_present("hardened"));
+    agent.run(matches.value_of("socket").unwrap(), mode)
+	.map_err(|e| Failure::about(e.into(), "Agent stopped"))
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("shamir-agent requires unix domain sockets");
+    std::process::exit(1);
+}
@@ -21,321 +21,1002 @@
 // n = number of shares
 
 extern crate clap;
-use clap::{Arg, App, SubCommand};
-use gf_2px::field;
-use gf_2px::field::*;
+use clap::{Arg, App};
+use guff_ssss::{archive, Decoder, combine_decoder, combine_correcting, combine_majority, combine_with_progress,
+		 group_by_set, join_continuations, share_fingerprint_with, share_number, unpad_secret, Error,
+		 Share};
+use guff_ssss::binary::{decode_binary, is_binary_share};
+use guff_ssss::buffer::ChunkSize;
+use guff_ssss::codec::{v2_hash, ArmorCodec, KubernetesCodec, Registry, ShareCodec, TemplateCodec,
+		       TextCodec, V2Codec};
+use guff_ssss::hash::Algorithm;
+use guff_ssss::legacy::combine_legacy;
+use guff_ssss::perl::{parse_perl_share, strip_padding};
+use guff_ssss::manifest::Manifest;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+mod common;
+use common::{note, Failure, OrExit, paint, ShareRow, Tone};
+
+// The share format and the decoding algorithm are described in the
+// library (src/lib.rs).
+
+// Blank lines and comments (as written by shamir-split --batch)
+// aren't shares. Wrapped shares (lines ending in '\\') are joined
+// back up before this is applied.
+fn is_share_line(line : &str) -> bool {
+    !line.trim().is_empty() && !line.trim_start().starts_with('#')
+}
 
+// Share lines from a file: text, a binary share file, or (with the
+// `qr` feature) an image of one or more QR codes
+fn read_file_lines(path : &Path, strict : bool) -> Result<Vec<String>, Failure> {
+    #[cfg(feature = "qr")]
+    if guff_ssss::qr::is_image(path) {
+	return Ok(guff_ssss::qr::decode_image(path)?)
+    }
+    let contents = common::read_file(path).map_err(guff_ssss::Error::from)?;
+    if is_binary_share(&contents) {
+	return decode_binary(&contents).map(|share| vec![share.to_string()])
+	    .map_err(|e| Failure::about(e, path.display()))
+    }
+    let contents = String::from_utf8(contents)
+	.map_err(|_| format!("{}: not a text or binary share file", path.display()))?;
+    share_lines(&contents, strict).map_err(|e| e.within(path.display()))
+}
 
+// Timelocked shares (with the `timelock` feature) are opened with
+// their round's signature from drand, fetched once per round. Until
+// the round comes, there's nothing to be done but wait.
+#[cfg(feature = "timelock")]
+fn unlock_shares(text : &str) -> Result<Vec<String>, String> {
+    use guff_ssss::timelock::parse_locked_shares;
+    use std::collections::HashMap;
+    let mut signatures : HashMap<(String, u64), Vec<u8>> = HashMap::new();
+    let mut lines = Vec::new();
+    for locked in parse_locked_shares(text).map_err(|e| e.to_string())? {
+	let key = (locked.url.clone(), locked.round);
+	if !signatures.contains_key(&key) {
+	    let signature = locked.fetch_signature().map_err(|e| e.to_string())?;
+	    signatures.insert(key.clone(), signature);
+	}
+	lines.push(locked.unlock(&signatures[&key]).map_err(|e| e.to_string())?);
+    }
+    note!("Opened {} timelocked share(s)", lines.len());
+    Ok(lines)
+}
 
-// shares have the format:
-//
-// K=W=S=Values=
-//
-// K  quorum value
-// W  width of field in bits
-// S  this share number
-//
-// Values is a list of word-sized values, each of which is a share of
-// the corresponding word in the secret key.
-//
-// Each value in a share represents the result of calculating a
-// random polynomial. 
-//
-// f(x) = a_0 * 1  +  a_1 * x  +  a_2 * x**2  +  ...  +  a_o * x**o
-//
-// at x = S.
-//
-// The polynomial is of order o = K - 1.
+#[cfg(not(feature = "timelock"))]
+fn unlock_shares(_text : &str) -> Result<Vec<String>, String> {
+    Err("Built without the 'timelock' feature; can't open timelocked shares".into())
+}
 
-// Decoding a single word of the original key proceeds by:
-//
-// * extracting all the S and f(S) values
-// * using them to solve for the shared coefficients a_1 .. a_o
-// * plugging the shared coefficients into any f(S) to recover a_0
-//
-// To check the correctness of our algorithm, we can repeat the third
-// step for all shares and verify that the produced a_0's all agree.
-
-extern crate hex;
-use std::io::{self, BufRead};
-
-// Will store all field values as Vec<u8> rather than trying
-// to make a generic storage object.
-struct Decoder {
-    // use largest word size for these variables
-    quorum : u16,		// aka 'k'
-    width : u16,		// width in bits; GF(2**8) = width 8
-    hex_length : usize,		// length of hex string (nibbles)
-    // problem ... we want vectors of appropriate size
-    // solution ... store as Vec<u8> for now and convert/iterate later
-    x_values    : Vec<u8>,	// taken from shares
-    shares : Vec<u8>,		// taken from shares
-    coefficients : Vec<u8>,     // calculated in pass 1
-    solution : Vec<u8>,		// calculated in pass 2
-    // don't store the field, pass it
-    // field : &'a impl GenericField<_, _>,
-}
-
-// // organise solver as a trait
-// trait Solver<T: NumericOps, P: NumericOps> { // : GenericField<T,P> {
-//     fn f(&self) -> &dyn GenericField<T,P>;
-//     fn pass_1(decoder : &mut Decoder, ) {
-//         // code for pass 1
-//     }
-//     fn pass_2() {
-//         // code for pass 2
-//     }
-//     // add default iterator?
-// }
-
-// struct GenericSolver<'a, T: NumericOps, P: NumericOps> {
-//     field : &'a dyn GenericField<T,P>
-// }
-
-// // impl<T: NumericOps, P: NumericOps> Solver<T,P> for GenericSolver<'_, u8,u8> {
-// impl Solver<T, P> for GenericSolver<'_, u8,u8>
-//     // where T: NumericOps, P: NumericOps {
-// 	{
-//     fn f(&self) -> &dyn GenericField<T,P> { self.field }
-// }
-	
-// I'm confused. 
-
-fn parse_shares() -> Decoder {
-
-    let stdin = io::stdin();
-
-    let mut count = 0;
-    let mut decoder = Decoder {
-	quorum : 0, width : 0, hex_length : 0,
-	x_values     : Vec::<u8>::new(),
-	shares       : Vec::<u8>::new(),
-	coefficients : Vec::<u8>::new(),
-	solution     : Vec::<u8>::new(),
-    };
+// A share sealed to this machine's TPM (with the `tpm` feature) is
+// only given back on the machine that sealed it, booted the same way
+#[cfg(feature = "tpm")]
+fn unseal_share(text : &str) -> Result<Vec<String>, String> {
+    use guff_ssss::tpm::SealedShare;
+    let sealed : SealedShare = text.parse().map_err(|e : io::Error| e.to_string())?;
+    let share = sealed.unseal().map_err(|e| e.to_string())?;
+    note!("Unsealed a share from the TPM");
+    Ok(vec![share])
+}
 
-    for line in stdin.lock().lines() {
-	let line = line.unwrap();
+#[cfg(not(feature = "tpm"))]
+fn unseal_share(_text : &str) -> Result<Vec<String>, String> {
+    Err("Built without the 'tpm' feature; can't unseal a share from the TPM".into())
+}
 
-	// split/collect gives us str refs
-	let v: Vec<&str> = line.trim().split("=").collect();
-	if v.len() != 5 {
-	    panic!("Line {}: wrong number of fields", count + 1)
-	}
-	// K=W=S=Values=
-	//
-	// K  quorum value
-	// W  width of field in bits
-	// S  this share number
-
-	// convert k, w, s
-	let k : u16 = v[0].parse().unwrap();
-	let w : u16 = v[1].parse().unwrap();
-	let s : u64 = v[2].parse().unwrap();
-	let hlen = v[3].len();
-	assert_eq!(v[4].len(), 0); // nothing after final =
-	
-	// we can't stash references to line because it goes out of
-	// scope, so rather than copying the data, might as well do
-	// hex conversion here.
-
-	let hlen_bits = hlen * 4; // hex digit == 4 bits
-	if hlen_bits % w as usize != 0 {
-	    panic!("Line {}: hex data {} is not a multiple of field width", count + 1, v[3])
+// A share wrapped under a YubiKey (with the `yubikey` feature) needs
+// that token plugged in
+#[cfg(feature = "yubikey")]
+fn unwrap_yubikey(text : &str) -> Result<Vec<String>, String> {
+    use guff_ssss::yubikey::{ykchalresp, WrappedShare};
+    let wrapped : WrappedShare = text.parse().map_err(|e : io::Error| e.to_string())?;
+    note!("Asking the YubiKey (touch it if it flashes)");
+    let share = wrapped.unwrap(ykchalresp).map_err(|e| e.to_string())?;
+    Ok(vec![share])
+}
+
+#[cfg(not(feature = "yubikey"))]
+fn unwrap_yubikey(_text : &str) -> Result<Vec<String>, String> {
+    Err("Built without the 'yubikey' feature; can't unwrap a share with a YubiKey".into())
+}
+
+// Share lines from text: KeePass or Bitwarden exports (with the
+// `vault` feature), timelocked shares, TPM-sealed and YubiKey-wrapped
+// shares, armored blocks (eg, pasted
+// from an email), Kubernetes Secret manifests, a stream of YAML
+// documents (with the `yaml` feature), or lines in the text format.
+// A line that can't be converted (eg, a v2 share with a bad checksum)
+// is skipped with a note, so one damaged share doesn't stop recovery
+// from the others, unless `strict`.
+fn share_lines(text : &str, strict : bool) -> Result<Vec<String>, Failure> {
+    #[cfg(feature = "vault")]
+    {
+	use guff_ssss::vault::*;
+	let shares = if is_keepass_xml(text) {
+	    Some(from_keepass_xml(text))
+	} else if is_bitwarden_json(text) {
+	    Some(from_bitwarden_json(text))
+	} else {
+	    None
+	};
+	if let Some(shares) = shares {
+	    return Ok(shares?.iter().map(|s| s.to_string()).collect())
 	}
-	if w == 4 && (hlen % 2) != 0 {
-	    panic!("Line {}: hex data {} missing final (padding) nibble", count + 1, v[3])
+    }
+    if text.lines().any(|l| l.trim() == "guff-ssss timelock v1") {
+	return Ok(unlock_shares(text)?)
+    }
+    if text.lines().any(|l| l.trim() == "guff-ssss tpm v1") {
+	return Ok(unseal_share(text)?)
+    }
+    if text.lines().any(|l| l.trim() == "guff-ssss yubikey v1") {
+	return Ok(unwrap_yubikey(text)?)
+    }
+    if ArmorCodec.detect(text) {
+	return Ok(guff_ssss::codec::decode_armor_stream(text)?
+		  .iter().map(|s| s.to_string()).collect())
+    }
+    if KubernetesCodec.detect(text) {
+	return Ok(guff_ssss::codec::decode_kubernetes_stream(text)?
+		  .iter().map(|s| s.to_string()).collect())
+    }
+    #[cfg(feature = "yaml")]
+    if looks_like_yaml(text) {
+	return Ok(guff_ssss::codec::decode_yaml_stream(text)?
+		  .iter().map(|s| s.to_string()).collect())
+    }
+    let registry = Registry::new();
+    let mut lines = Vec::new();
+    for line in join_continuations(text.lines()).into_iter().filter(|l| is_share_line(l)) {
+	match to_text_format(&registry, line) {
+	    Ok(line) => lines.push(line),
+	    Err(e) if strict => return Err(e),
+	    Err(e) => note!("Skipping share: {}", e),
 	}
+    }
+    Ok(lines)
+}
 
-	// decode to Vec<u8>
-	let mut vector = hex::decode(v[3])
-	    .unwrap();
-		//format!("Problem with hex conversion for {}", vec[3]));
-	decoder.shares.append(&mut vector);
-
-	// if this is the first line
-	if count == 0 {
-	    // stash k, w, in decoder
-	    if w!=4 && w!=8 && w!=16 && w!=32 { panic!("bad field width") }
-	    if k < 1 || k > 1 << (w-1) { panic!("bad quorum value {}", k) }
-	    decoder.quorum = k;
-	    decoder.width  = w;
-	    decoder.hex_length = hlen;
+// The hash the first v2 share read names, which shamir-split made the
+// fingerprints with (see fingerprint_hash)
+static NAMED_HASH : OnceLock<Algorithm> = OnceLock::new();
+
+// What to make fingerprints with: --hash, or the manifest's, or
+// NAMED_HASH, or SHA-256
+fn fingerprint_hash(given : Option<Algorithm>, manifest : Option<&Manifest>) -> Algorithm {
+    given.or(manifest.map(|manifest| manifest.hash)).or(NAMED_HASH.get().copied()).unwrap_or_default()
+}
+
+// Shares in any other single-line format the registry knows (eg,
+// v2 lines or dotenv assignments) are converted to the text format,
+// except that a share of a padded secret (see Share::padded) is made
+// a v2 line, since only that can say so. Anything not recognised is
+// passed on as it is, so that the decoder can report what's wrong
+// with it. A v2 line can only be a share, so it's an error here if it
+// doesn't decode (eg, a bad checksum).
+fn to_text_format(registry : &Registry, line : String) -> Result<String, Failure> {
+    if TextCodec.detect(&line) { return Ok(line) }
+    if let Some(hash) = v2_hash(&line) { let _ = NAMED_HASH.set(hash); }
+    match registry.decode(&line) {
+	Ok(share) if share.padded => Ok(V2Codec.encode(&share)),
+	Ok(share) => Ok(share.to_string()),
+	Err(e) if V2Codec.detect(&line) => Err(Failure::about(e, line.trim())),
+	Err(_) => Ok(line),
+    }
+}
+
+// A share line after to_text_format: the text format, or v2
+fn parse_line(line : &str) -> guff_ssss::Result<Share> {
+    match V2Codec.detect(line) {
+	true => V2Codec.decode(line),
+	false => TextCodec.decode(line),
+    }
+}
+
+// Fingerprints are made from the text format, as shamir-split makes
+// them, even for a v2 line
+fn fingerprint(line : &str, hash : Algorithm) -> String {
+    match parse_line(line) {
+	Ok(share) if V2Codec.detect(line) => share_fingerprint_with(&share.to_string(), hash),
+	_ => share_fingerprint_with(line, hash),
+    }
+}
+
+// Text-format shares never have a ':' in them
+#[cfg(feature = "yaml")]
+fn looks_like_yaml(text : &str) -> bool {
+    match text.lines().map(str::trim).find(|l| is_share_line(l)) {
+	Some(line) => line == "---" || ["set_id:", "quorum:", "width:", "index:", "values:"]
+	    .iter().any(|key| line.starts_with(key)),
+	None => false,
+    }
+}
+
+// Mixed-source collection
+//
+// Real recoveries rarely have every share on the same medium, so one
+// run can take shares from stdin, text files and QR images, and then
+// prompt on the terminal for whatever is still missing. Everything
+// ends up merged into a single quorum.
+//
+// Files are named on the command line, with `-` meaning stdin. With
+// no files, stdin is read as before, unless we're going to prompt and
+// stdin is the terminal.
+fn collect_share_lines(files : &[&str], prompting : bool, strict : bool)
+		       -> Result<Vec<String>, Failure> {
+    let mut lines = Vec::new();
+    let read_stdin = files.contains(&"-")
+	|| (files.is_empty() && !(prompting && io::stdin().is_terminal()));
+    if read_stdin {
+	let mut text = String::new();
+	io::stdin().read_to_string(&mut text).map_err(|e| Failure::about(e.into(), "stdin"))?;
+	lines.extend(share_lines(&text, strict).map_err(|e| e.within("stdin"))?);
+    }
+    for file in files.iter().filter(|f| **f != "-") {
+	lines.extend(read_file_lines(Path::new(file), strict)?);
+    }
+    Ok(lines)
+}
+
+// Ask for shares on the terminal until we have a quorum. Fingerprints
+// are made with the hash a v2 share names, as shamir-split made them,
+// or else `hash`.
+fn prompt_for_shares(decoder : &mut Decoder, strict : bool, hash : Algorithm) -> Result<(), Failure> {
+    let registry = Registry::new();
+    while !decoder.is_complete() {
+	let prompt = if decoder.quorum == 0 {
+	    "Enter share: ".to_string()
 	} else {
-	    // compare k, w with values in decoder
-	    if w != decoder.width { panic!("mismatched field width value {}", w) }
-	    if k != decoder.quorum { panic!("mismatched quorum value {}", k) }
-	    if hlen != decoder.hex_length { panic!("wrong share length {}", v[3]) }
+	    format!("Enter share ({} of {} so far): ", decoder.have(), decoder.quorum)
+	};
+	let mut line = match common::prompt_line(&prompt) {
+	    Some(line) => line,
+	    None => break,
+	};
+	// Keep reading while the share is wrapped
+	while line.trim_end().ends_with('\\') {
+	    match common::prompt_line("... ") {
+		Some(more) => line = join_continuations([line, more]).concat(),
+		None => break,
+	    }
+	}
+	if !is_share_line(&line) { continue }
+	let hash = v2_hash(&line).unwrap_or(hash);
+	let line = match to_text_format(&registry, line) {
+	    Ok(line) => line,
+	    Err(e) if strict => return Err(e.within("Rejected share")),
+	    Err(e) => { note!("{} share: {}", paint(Tone::Bad, "Rejected"), e); continue },
+	};
+	// Acknowledge each share so that participants in a ceremony
+	// can confirm their contribution was accepted
+	let duplicates = decoder.duplicates();
+	let (number, print) = (share_number(&line).unwrap_or("?"), fingerprint(&line, hash));
+	match decoder.add_share(&line) {
+	    Ok(false) if decoder.duplicates() > duplicates => {
+		note!("{}", paint(Tone::Dim, format!("Share {} (fingerprint {}) was already entered",
+						     number, print)))
+	    },
+	    Ok(false) if strict => return Err(Error::InconsistentShares(unused_share(number)).into()),
+	    Err(e) if strict => return Err(Failure::about(e, format!("Rejected share (fingerprint {})", print))),
+	    Ok(false) => note!("{}", paint(Tone::Warn, format!(
+		"Share {} (fingerprint {}) not needed: already have a share with that number",
+		number, print))),
+	    Ok(true) => {
+		note!("{} share {} (fingerprint {}); {}", paint(Tone::Good, "Accepted"), number, print,
+		      common::quorum_progress(decoder.have() as usize, decoder.quorum as usize));
+	    },
+	    Err(e) => note!("{} share (fingerprint {}): {}", paint(Tone::Bad, "Rejected"), print, e),
 	}
-	count += 1;
-	if s < 1 || s > 1 << (w - 1) { panic!("bad share index {}", s) }
-	if count > k {
-	    println!("Ignoring share {}", s);
+    }
+    Ok(())
+}
+
+// The shares about to be combined, for whoever is at the terminal:
+// which are used, which are spare, and how close to the quorum
+fn summarize(decoder : &Decoder, hash : Algorithm) {
+    if !common::on_terminal() || decoder.have() == 0 { return }
+    let rows : Vec<ShareRow> = decoder.all_shares().into_iter().enumerate().map(|(i, share)| {
+	let status = if i < decoder.have() as usize {
+	    (Tone::Good, "used".to_string())
+	} else {
+	    (Tone::Dim, "spare".to_string())
+	};
+	ShareRow { index : share.index, set : share.set_id.clone(),
+		   fingerprint : share_fingerprint_with(&share.to_string(), hash), status }
+    }).collect();
+    common::share_table(&rows);
+    note!("Quorum: {}", common::quorum_progress(decoder.have() as usize, decoder.quorum as usize));
+}
+
+// Spare shares are mentioned as they're checked against the quorum,
+// unless --majority or --correct is going to use them all anyway
+fn decode_lines(lines : &[&str], report_spares : bool, strict : bool)
+		-> guff_ssss::Result<Decoder> {
+    let mut decoder = Decoder::new();
+    let mut numbers = HashSet::new();
+    for line in lines.iter() {
+	let duplicates = decoder.duplicates();
+	let used = decoder.add_share(line)?;
+	if decoder.duplicates() > duplicates { continue }
+	let n = share_number(line).unwrap_or("?");
+	// Not a copy, so a second share with this number is a different
+	// share claiming to be the same one
+	if !numbers.insert(n) {
+	    let conflict = format!("share {} was given twice with different values", n);
+	    if strict { return Err(guff_ssss::Error::InconsistentShares(conflict)) }
+	    common::warning!("{}; at most one of them is right", conflict);
 	    continue
 	}
-	// store as little-endian byte stream
-	match w {
-	    8 => { decoder.x_values.push((s & 255) as u8); },
-	    _ => { unimplemented!(); }
+	if !used {
+	    if strict { return Err(guff_ssss::Error::InconsistentShares(unused_share(n))) }
+	    if report_spares { note!("Checking spare share {}", n) }
 	}
     }
-    decoder
+    Ok(decoder)
 }
 
+// Identical copies of a share are dropped without fuss, since the
+// same share turning up in more than one backup is normal
+fn report_duplicates(decoder : &Decoder) {
+    match decoder.duplicates() {
+	0 => {},
+	1 => note!("Dropped 1 duplicate copy of a share"),
+	n => note!("Dropped {} duplicate copies of shares", n),
+    }
+}
 
-
-fn take_something_implementing_field<T,P>(field : &impl GenericField<T,P>)
-where T: NumericOps, P: NumericOps {
-    panic!("Got field size {}", field.order());
+// Strict mode (--strict)
+//
+// For ceremonies that need an audit trail, every share offered must
+// be valid and must actually be used: extra shares, repeats and
+// anything that fails to parse stop the run instead of being skipped.
+fn unused_share(number : &str) -> String {
+    format!("share {} would not be used (--strict)", number)
 }
 
-// non-storable structs used as type parameters to Iter
-// struct U4();
-// struct U8();
-// struct U16();
-// struct U32();
+// Batch combine
+//
+// If the input holds shares from several sets (eg, the output of
+// shamir-split --batch), reconstruct each set on its own and report
+// how each one went rather than giving up at the first share that
+// doesn't match. Returns the number of sets that failed.
+fn combine_batch(groups : &[(Option<&str>, Vec<&str>)], hardened : bool, strict : bool,
+		 padded : bool) -> usize {
+    let mut failed = 0;
+    for (id, lines) in groups.iter() {
+	let id = id.unwrap_or("(none)");
+	let result = decode_lines(lines, true, strict).and_then(|mut decoder| {
+	    report_duplicates(&decoder);
+	    let ans = combine_decoder(&mut decoder, hardened)?;
+	    if padded { unpad_secret(&ans) } else { Ok(ans) }
+	});
+	match result {
+	    Ok(ans) => println!("Answer (set {}): {:?}", id, String::from_utf8(ans)),
+	    Err(e) => {
+		println!("Failed (set {}): {}", id, e);
+		failed += 1;
+	    },
+	}
+    }
+    failed
+}
 
-// //trait Iter<T> {
-// //    type Output;		// type of return
-// //    fn next(&mut self) -> Option<Self::Output>;
-// //    fn rewind(&mut self);
-// //}
+// Watch-directory mode (--watch DIR)
+//
+// Intended for air-gapped workflows where custodians drop share files
+// onto a USB stick one after the other. Every time something in the
+// directory changes we rescan all the files from scratch; anything
+// that doesn't parse as a share (or belongs to a different set than
+// the first share found) is skipped with a warning.
+
+// Read every share line from every regular file in dir. Warnings are
+// only printed the first time we see them.
+fn scan_dir(dir : &Path, warned : &mut HashSet<String>, strict : bool) -> Decoder {
+    let mut decoder = Decoder::new();
+    let mut entries : Vec<_> = match fs::read_dir(dir) {
+	Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+	Err(e) => common::fail(e, format!("Can't read directory {}", dir.display())),
+    };
+    entries.sort();		// deterministic choice of shares
+    for path in entries {
+	if !path.is_file() { continue }
+	// The whole file is skipped if any share in it is damaged, and
+	// (like binary junk or a file we can't read) only warned about once
+	let lines = match read_file_lines(&path, true) {
+	    Ok(lines) => lines,
+	    Err(e) if strict => common::fail(e, path.display()),
+	    Err(e) => {
+		let warning = format!("skipping: {}", e);
+		if warned.insert(warning.clone()) { note!("{}", warning) }
+		continue
+	    },
+	};
+	for line in lines.iter() {
+	    let duplicates = decoder.duplicates();
+	    match decoder.add_share(line) {
+		Ok(true) => {},
+		Ok(false) if strict && decoder.duplicates() == duplicates => {
+		    let unused = unused_share(share_number(line).unwrap_or("?"));
+		    common::fail(Error::InconsistentShares(unused), path.display())
+		},
+		Ok(false) => {},
+		Err(e) if strict => common::fail(e, path.display()),
+		Err(e) => {
+		    let warning = format!("{}: skipping: {}", path.display(), e);
+		    if warned.insert(warning.clone()) { note!("{}", warning) }
+		},
+	    }
+	}
+    }
+    decoder
+}
 
-// // impl<T> Iter<P = Type> {}
+fn watch_dir(dir : &Path, strict : bool) -> Decoder {
+    let mut watcher = common::DirWatcher::new(dir);
+    let mut last_have = None;
+    let mut warned = HashSet::new();
+    loop {
+	let decoder = scan_dir(dir, &mut warned, strict);
+	if decoder.is_complete() { return decoder }
+	let have = (decoder.have(), decoder.quorum);
+	if last_have != Some(have) {
+	    if have.1 == 0 {
+		note!("Waiting for shares in {}", dir.display());
+	    } else {
+		note!("Have {} of {} shares; waiting for more", have.0, have.1);
+	    }
+	    last_have = Some(have);
+	}
+	watcher.wait();
+    }
+}
 
-// struct IterState<'a> {
-//     offset : usize,
-//     slice : &'a [u8]
-// }
+// Clipboard support for --copy
+//
+// Rather than pull in a windowing-system crate, we pipe the secret
+// into whichever clipboard utility the platform provides. The secret
+// is always written to the child's stdin so that it never appears on
+// a command line (and hence in `ps` output).
+//
+// Each entry is (copy command, paste command). The paste command is
+// used when clearing so that we don't trample on something else the
+// user copied in the meantime.
+const CLIPBOARD_TOOLS : &[(&[&str], &[&str])] = &[
+    (&["wl-copy"], &["wl-paste", "-n"]),
+    (&["xclip", "-selection", "clipboard"],
+     &["xclip", "-selection", "clipboard", "-o"]),
+    (&["xsel", "--clipboard", "--input"],
+     &["xsel", "--clipboard", "--output"]),
+    (&["pbcopy"], &["pbpaste"]),
+    (&["clip.exe"], &["powershell.exe", "-noprofile", "-command", "Get-Clipboard"]),
+];
+
+// Try each copy tool in turn, returning the index of the first one
+// that accepted the data.
+fn clipboard_copy(data : &[u8]) -> Option<usize> {
+    for (index, (copy, _)) in CLIPBOARD_TOOLS.iter().enumerate() {
+	let child = Command::new(copy[0])
+	    .args(&copy[1..])
+	    .stdin(Stdio::piped())
+	    .stdout(Stdio::null())
+	    .stderr(Stdio::null())
+	    .spawn();
+	let mut child = match child {
+	    Ok(child) => child,
+	    Err(_) => continue,	// tool not installed
+	};
+	let written = child.stdin.take().unwrap().write_all(data).is_ok();
+	match child.wait() {
+	    Ok(status) if status.success() && written => return Some(index),
+	    _ => continue,
+	}
+    }
+    None
+}
 
-// impl<'a, T> Iter<T> for IterState<'a> {
-// //    type Output;
-//     fn next(&mut self) -> Option<Self::Output> { None }
-//     fn rewind(&mut self) {}
-// }
+fn clipboard_paste(tool : usize) -> Option<Vec<u8>> {
+    let paste = CLIPBOARD_TOOLS[tool].1;
+    let mut child = Command::new(paste[0])
+	.args(&paste[1..])
+	.stdin(Stdio::null())
+	.stdout(Stdio::piped())
+	.stderr(Stdio::null())
+	.spawn().ok()?;
+    let mut contents = Vec::new();
+    child.stdout.take().unwrap().read_to_end(&mut contents).ok()?;
+    if !child.wait().ok()?.success() { return None }
+    Some(contents)
+}
 
-// fn new_iter<T> (slice : &[u8]) -> impl Iter<T> + '_ { // IterState<'_> 
-//     IterState { offset : 0, slice : slice }
-// }
+// Put the secret on the clipboard, then block until the timeout
+// expires and clear it again. A timeout of zero leaves the secret in
+// place.
+fn copy_to_clipboard(secret : &[u8], clear_after : u64) {
+    let tool = match clipboard_copy(secret) {
+	Some(tool) => tool,
+	None => panic!("No usable clipboard utility found \
+			(tried wl-copy, xclip, xsel, pbcopy, clip.exe)"),
+    };
+    if clear_after == 0 {
+	note!("Secret copied to clipboard");
+	return
+    }
+    note!("Secret copied to clipboard; clearing in {} seconds",
+	      clear_after);
+    thread::sleep(Duration::from_secs(clear_after));
+
+    // Only clear if the clipboard still holds our secret. Some tools
+    // add or strip a trailing newline, so compare modulo that. If we
+    // can't read the clipboard back, err on the side of clearing it.
+    let ours = match clipboard_paste(tool) {
+	Some(contents) => {
+	    let trim = |s : &[u8]| s.strip_suffix(b"\n").unwrap_or(s).to_vec();
+	    trim(&contents) == trim(secret)
+	},
+	None => true,
+    };
+    if ours {
+	clipboard_copy(b"");
+	note!("Clipboard cleared");
+    } else {
+	note!("Clipboard contents changed; not clearing");
+    }
+}
 
-// Eventually want to make solver/iterator stuff generic over unit
-// structs
-struct U4 {}
-struct U8 {}
-struct U16 {}
-struct U32 {}
+// Shares written through a template are turned back into the text
+// format. Lines that don't match (headings in the same document, say)
+// are skipped, unless --strict.
+fn from_template(template : &str, lines : &[String], strict : bool) -> Vec<String> {
+    let template = TemplateCodec::new(template).or_exit();
+    lines.iter().filter_map(|line| match template.decode(line) {
+	Ok(share) => Some(share.to_string()),
+	Err(e) if strict => common::fail(e, line),
+	Err(_) => None,
+    }).collect()
+}
 
-// So I think we'll have:
+// Shares from the Perl scripts in Math::FastGF2 (--perl)
 //
-// fn solve<U, T, P >(field : impl GenericField<T,P>)
-// where U: CustomIter<U>, T : NumericOps, P: NumericOps {
+// Converted to our byte order as they're read, so everything after
+// this sees ordinary shares. With --convert they're printed instead
+// of combined, for moving a share set over to this version for good.
+fn from_perl(lines : &[String], strict : bool) -> Vec<String> {
+    lines.iter().filter_map(|line| match parse_perl_share(line) {
+	Ok(share) => Some(share.to_string()),
+	Err(e) if strict => panic!("{}: {}", line, e),
+	Err(_) => None,
+    }).collect()
+}
+
+// Public verification record (--manifest FILE), read with
+// common::read_manifest
 //
-// This looks worse than the version without U, but it should help me
-// separate out issue of creating iterators over a new, clean type
-// rather than having a mess of <T,P> types each with a foreign
-// dependency. Also, by using unit structs in the same way, I think
-// that I should be able to refactor the library code so that it
-// becomes generic for <U> rather than <T,P> (by using associated
-// types), which should hopefully be much cleaner/less confusing.
-
-
-// I know that the signature from take_something_implementing_field()
-// works, so use it for solver routines. I'll implement the rest of
-// the algorithm for u8 first, then work on adding the U type for
-// field-specific iterators.
-fn pass_1<T,P>(field : &impl GenericField<T,P>, decoder : &mut Decoder)
-where T: NumericOps, P: NumericOps {
-    // panic!("Got field size {} in pass 1", field.order());
-    // Pass 1: calculate common coefficients a1 .. a_k-1
-    // j and l are normal array indices
-    let k = decoder.quorum;
-    eprintln!("pass 1: k is {}", k);
-    for j in 0..k {
-	let mut temp : T = T::one();
-	for l in 0..k {
-	    if l != j {
-		temp = field.mul(temp, T::from(decoder.x_values[l as usize]).unwrap());
-		temp = field.div(temp, T::from(decoder.x_values[j as usize]).unwrap()
-	 			     ^ T::from(decoder.x_values[l as usize]).unwrap())
-	    }
+// Shares that aren't in the manifest are set aside (or fatal, with
+// --strict) before anything is reconstructed
+fn listed_shares<'a>(manifest : &Manifest, lines : &'a [String], strict : bool) -> Vec<&'a str> {
+    lines.iter().map(|l| l.as_str()).filter(|line| {
+	let checked = parse_line(line).and_then(|share| manifest.check_share(&share));
+	match checked {
+	    Ok(()) => true,
+	    Err(e) if strict => common::fail(e, "Rejected share"),
+	    Err(e) => {
+		note!("Ignoring share (fingerprint {}): {}", fingerprint(line, manifest.hash), e);
+		false
+	    },
 	}
-	if temp == T::zero() { panic!("Linear independence not satisfied") }
-	decoder.coefficients.push(temp.to_u8().unwrap());
-    }
-}
-
-fn pass_2<T,P>(field : &impl GenericField<T,P>, decoder : &mut Decoder)
-    -> Vec<u8>
-where T: NumericOps, P: NumericOps {
-    //    panic!("Got field size {} in pass 2", field.order());
-    let w = decoder.width;	// width in bits
-    let k = decoder.quorum;
-    let stride = w / 8;
-
-    let words = decoder.hex_length / 2;
-
-    eprintln!("pass 2: hex length {}, words {}", decoder.hex_length, words);
-    eprintln!("x_values has length {}", decoder.x_values.len());
-    eprintln!("coefficients has length {}", decoder.coefficients.len());
-    eprintln!("hex length {}, stride {}", decoder.hex_length, stride);
-    
-    let mut ans = Vec::<u8>::new();
-    for i in 0..words {
-	let mut temp : T = T::zero();
-	eprintln!("i = {}", i);
-	// if k == 1 {continue};
-	for j in 0..k {
-	    eprintln!("j = {}", j);
-	    	    let lindex = i as usize + (j as usize * words);
-	    //	    let lindex = i as usize;
-	    eprintln!("lindex = {}", lindex);
-	    let l = decoder.shares[lindex];
-	    let r = decoder.coefficients[j as usize];
-	    temp = temp
-		^ field.mul( l, r); //T::from(l).unwrap(), T::from(r).unwrap())
+    }).collect()
+}
+
+// A TLS key split with shamir-split --tls-key comes back as PEM, and
+// with --tls-cert only once it's been checked against the certificate
+#[cfg(feature = "x509")]
+fn tls_key_pem(secret : &[u8], cert : Option<&str>) -> Option<String> {
+    use guff_ssss::x509::{is_tls_key, TlsKey};
+    if !is_tls_key(secret) {
+	if cert.is_some() { panic!("--tls-cert: the secret isn't a TLS key split with --tls-key") }
+	return None
+    }
+    let key = TlsKey::from_secret(secret).unwrap_or_else(|e| panic!("{}", e));
+    if let Some(path) = cert {
+	let cert = fs::read(path).unwrap_or_else(|e| common::fail(e, format!("Can't read {}", path)));
+	if !key.matches_certificate(&cert).unwrap_or_else(|e| panic!("{}: {}", path, e)) {
+	    panic!("The recovered key doesn't match the certificate in {}", path)
 	}
-	ans.push(temp.to_u8().unwrap());
+	note!("Recovered key matches the certificate in {}", path);
     }
-    ans
+    Some(key.to_pem())
 }
 
-fn pass_3<T,P>(field : &impl GenericField<T,P>, decoder : &mut Decoder)
-where T: NumericOps, P: NumericOps {
-    panic!("Got field size {} in pass 3", field.order());
+#[cfg(not(feature = "x509"))]
+fn tls_key_pem(secret : &[u8], cert : Option<&str>) -> Option<String> {
+    if secret.starts_with(b"GSSTLSK1") || cert.is_some() {
+	panic!("Built without the 'x509' feature; can't recover TLS keys")
+    }
+    None
 }
 
-fn main() {
+// Only the vectorised pass 2 works in chunks; the plain one goes a
+// word at a time through every share at once
+#[cfg(feature = "simd")]
+fn chunk_size(size : &str) -> ChunkSize {
+    size.parse().unwrap_or_else(|e| panic!("--chunk-size: {}", e))
+}
 
+#[cfg(not(feature = "simd"))]
+fn chunk_size(_size : &str) -> ChunkSize {
+    panic!("Built without the 'simd' feature; --chunk-size only applies to the vectorised combine")
+}
+
+// In quiet mode the secret is written out exactly as recovered, for
+// the next program in the pipeline
+fn print_secret(secret : &[u8], copy : bool, clear_after : u64, unpack : Option<&str>) {
+    if let Some(dir) = unpack {
+	let written = archive::unpack(secret, Path::new(dir))
+	    .unwrap_or_else(|e| panic!("{}: {}", dir, e));
+	note!("Unpacked {} files and directories into {}", written.len(), dir);
+    } else if copy {
+	copy_to_clipboard(secret, clear_after);
+    } else if common::quiet() {
+	common::write_raw(secret);
+    } else {
+	println!("Answer: {:?}", String::from_utf8(secret.to_vec()));
+    }
+}
+
+// Where the secret goes instead of stdout
+enum Store<'a> {
+    // A systemd credential store (--credstore NAME)
+    Credential { dir : &'a Path, name : &'a str },
+    // A container's secrets directory (--oci-secret NAME), as Docker
+    // and Kubernetes mount them: a file per secret, owned by whoever
+    // the service runs as. An init container that shares the
+    // directory with the service's can combine into it.
+    Container { path : PathBuf, mode : u32, owner : Option<(u32, Option<u32>)> },
+}
+
+impl Store<'_> {
+    fn write(&self, secret : &[u8]) {
+	match self {
+	    Store::Credential { dir, name } => {
+		let path = common::write_credential(dir, name, secret);
+		note!("Secret stored as credential {} ({})", name, path.display());
+	    },
+	    Store::Container { path, mode, owner } => {
+		if let Some(dir) = path.parent() {
+		    fs::create_dir_all(dir)
+			.unwrap_or_else(|e| common::fail(e, format!("Can't create {}", dir.display())));
+		}
+		common::write_owned(path, secret, *mode, *owner)
+		    .unwrap_or_else(|e| common::fail(e, format!("Can't write {}", path.display())));
+		note!("Secret written to {}", path.display());
+	    },
+	}
+    }
+}
+
+// --owner UID[:GID]
+fn parse_owner(owner : &str) -> (u32, Option<u32>) {
+    let number = |id : &str| id.parse().unwrap_or_else(|_| {
+	panic!("--owner expects numeric IDs, UID or UID:GID (got {})", owner)
+    });
+    match owner.split_once(':') {
+	Some((uid, gid)) => (number(uid), Some(number(gid))),
+	None => (number(owner), None),
+    }
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+
+    let hashes : Vec<&str> = Algorithm::ALL.iter().map(|hash| hash.name()).collect();
     let matches = App::new("shamir-combine")
 	.version("1.0")
 	.author("Declan Malone <idablack@users.sourceforge.net>")
 	.about("Shamir's Secret Sharing Scheme")
-	.usage("cat share1 share2 ... | shamir-combine")
+	.usage("cat share1 share2 ... | shamir-combine [OPTIONS]\n    \
+		shamir-combine [OPTIONS] [--prompt] [FILE|-]...")
+	.arg(Arg::with_name("copy")
+	     .long("copy")
+	     .help("Place the secret on the clipboard instead of printing it"))
+	.arg(Arg::with_name("clear-after")
+	     .long("clear-after")
+	     .value_name("SECS")
+	     .requires("copy")
+	     .help("Clear the clipboard after SECS seconds (default 45, 0 = never)"))
+	.arg(Arg::with_name("hardened")
+	     .long("hardened")
+	     .help("Blind field inversions with random values \
+		    (side-channel mitigation)"))
+	.arg(Arg::with_name("chunk-size")
+	     .long("chunk-size")
+	     .value_name("SIZE")
+	     .help("Work through a large secret SIZE bytes at a time (eg, 64K), or 'auto' to \
+		    time a few sizes on its first megabytes and keep the fastest \
+		    (with the 'simd' feature)"))
+	.arg(Arg::with_name("majority")
+	     .long("majority")
+	     .value_name("N")
+	     .help("With more than a quorum of shares, reconstruct from up to N \
+		    different subsets and take the majority answer"))
+	.arg(Arg::with_name("correct")
+	     .long("correct")
+	     .conflicts_with_all(&["majority", "hardened"])
+	     .help("With more than a quorum of shares, recover the secret even if up to half \
+		    of the extra shares are wrong, and say which ones were"))
+	.arg(Arg::with_name("hash")
+	     .long("hash")
+	     .value_name("ALGORITHM")
+	     .possible_values(&hashes)
+	     .help("Hash for share fingerprints, if shamir-split was given --hash (default: \
+		    the manifest's, or the one the v2 shares name, or sha-256)"))
+	.arg(Arg::with_name("strict")
+	     .long("strict")
+	     .conflicts_with_all(&["majority", "correct"])
+	     .help("Fail if any share is invalid or would not be used"))
+	.arg(Arg::with_name("manifest")
+	     .long("manifest")
+	     .value_name("FILE")
+	     .conflicts_with_all(&["watch", "prompt", "legacy"])
+	     .help("Only use shares listed in the record FILE written by \
+		    shamir-split --manifest, and check the secret against it"))
+	.arg(Arg::with_name("dealer-key")
+	     .long("dealer-key")
+	     .value_name("HEX")
+	     .requires("manifest")
+	     .help("Require the manifest to be signed by this public key"))
+	.arg(Arg::with_name("template")
+	     .long("template")
+	     .value_name("TEMPLATE")
+	     .conflicts_with_all(&["watch", "prompt", "legacy"])
+	     .help("Read shares written with shamir-split --template TEMPLATE"))
+	.arg(Arg::with_name("unpack")
+	     .long("unpack")
+	     .value_name("DIR")
+	     .conflicts_with("copy")
+	     .help("The secret is files packed by shamir-split --pack: restore them into DIR"))
+	.arg(Arg::with_name("tls-cert")
+	     .long("tls-cert")
+	     .value_name("CERT")
+	     .conflicts_with_all(&["unpack", "legacy", "perl"])
+	     .help("The secret is a TLS key: check it belongs to the certificate in CERT \
+		    (PEM or DER) before printing it (needs the 'x509' feature)"))
+	.arg(Arg::with_name("padded")
+	     .long("padded")
+	     .help("Remove the padding added by shamir-split --security"))
+	.arg(Arg::with_name("perl")
+	     .long("perl")
+	     .conflicts_with_all(&["watch", "prompt", "legacy", "template", "padded"])
+	     .help("Read shares made by the Perl shamir-split in Math::FastGF2"))
+	.arg(Arg::with_name("convert")
+	     .long("convert")
+	     .requires("perl")
+	     .help("With --perl, print the shares in this version's format instead \
+		    of combining them (the secret keeps the Perl NUL padding)"))
+	.arg(Arg::with_name("legacy")
+	     .long("legacy")
+	     .conflicts_with_all(&["majority", "correct", "strict", "watch", "prompt"])
+	     .help("Read shares made by Karney's original mod 257 `secret` program"))
+	.arg(Arg::with_name("credentials")
+	     .long("credentials")
+	     .value_name("NAME")
+	     .min_values(0)
+	     .max_values(1)
+	     .conflicts_with_all(&["watch", "legacy"])
+	     .help("Also read shares from the systemd credentials NAME and NAME.* (default: \
+		    every credential) given to this service"))
+	.arg(Arg::with_name("credstore")
+	     .long("credstore")
+	     .value_name("NAME")
+	     .conflicts_with_all(&["copy", "unpack"])
+	     .help("Store the secret as systemd credential NAME, for a service's \
+		    LoadCredential=NAME or ImportCredential=NAME, instead of printing it"))
+	.arg(Arg::with_name("credstore-dir")
+	     .long("credstore-dir")
+	     .value_name("DIR")
+	     .requires("credstore")
+	     .help("Credential store for --credstore (default: /run/credstore)"))
+	.arg(Arg::with_name("oci-secret")
+	     .long("oci-secret")
+	     .value_name("NAME")
+	     .conflicts_with_all(&["copy", "unpack", "credstore"])
+	     .help("Write the secret to a container secrets file, /run/secrets/NAME, instead \
+		    of printing it (eg, from an init container)"))
+	.arg(Arg::with_name("secrets-dir")
+	     .long("secrets-dir")
+	     .value_name("DIR")
+	     .requires("oci-secret")
+	     .help("Directory for --oci-secret (default: /run/secrets)"))
+	.arg(Arg::with_name("owner")
+	     .long("owner")
+	     .value_name("UID[:GID]")
+	     .requires("oci-secret")
+	     .help("Give the --oci-secret file to this user (and group)"))
+	.arg(Arg::with_name("mode")
+	     .long("mode")
+	     .value_name("OCTAL")
+	     .requires("oci-secret")
+	     .help("Permissions of the --oci-secret file (default: 0400, so give --owner \
+		    the user the service runs as)"))
+	.arg(Arg::with_name("watch")
+	     .long("watch")
+	     .value_name("DIR")
+	     .help("Read shares from files in DIR, waiting until a quorum appears"))
+	.arg(Arg::with_name("prompt")
+	     .long("prompt")
+	     .help("Prompt on the terminal for shares still missing after reading the inputs"))
+	.arg(Arg::with_name("quiet")
+	     .short("q")
+	     .long("quiet")
+	     .conflicts_with("prompt")
+	     .help("Print only the secret, as raw bytes, and errors as a single line"))
+	.arg(Arg::with_name("no-color")
+	     .long("no-color")
+	     .help("Don't colour the summaries on the terminal (also NO_COLOR=1)"))
+	.arg(Arg::with_name("FILE")
+	     .multiple(true)
+	     .conflicts_with("watch")
+	     .help("Files holding shares (text, QR images with the 'qr' feature, or KeePass \
+		    and Bitwarden exports with the 'vault' feature); - for stdin"))
 	.get_matches();
 
-    let mut decoder = parse_shares();
-
-    // create a field of the appropriate size
-    match decoder.width {
-	4  => {
-	    let field = gf_2px::field::new_gf4(19,3);
-	    take_something_implementing_field(&field)
+    if matches.is_present("quiet") { common::set_quiet() }
+    common::set_color(matches.is_present("no-color"));
+    common::binary_stdio();
+    let clear_after : u64 = matches.value_of("clear-after").unwrap_or("45")
+	.parse().unwrap_or_else(|_| panic!("--clear-after expects a number of seconds"));
+    // Before any shares are asked for, as it's joined to --secrets-dir
+    if let Some(name) = matches.value_of("oci-secret") { common::check_credential_name(name) }
+
+    let hardened = matches.is_present("hardened");
+    let majority : Option<usize> = matches.value_of("majority").map(|n| {
+	n.parse().unwrap_or_else(|_| panic!("--majority expects a number of subsets"))
+    });
+    let strict = matches.is_present("strict");
+    let correct = matches.is_present("correct");
+    let manifest = matches.value_of("manifest")
+	.map(|path| common::read_manifest(Path::new(path), matches.value_of("dealer-key")))
+	.transpose()?;
+    let hash : Option<Algorithm> = matches.value_of("hash").map(|name| name.parse().unwrap());
+    if matches.is_present("legacy") {
+	// Old share sets can be moved to the current format by piping
+	// the answer back into shamir-split
+	let files : Vec<&str> = matches.values_of("FILE")
+	    .map(|v| v.collect()).unwrap_or_default();
+	let lines = collect_share_lines(&files, false, strict)?;
+	let lines : Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+	let ans = combine_legacy(&lines)?;
+	print_secret(&ans, matches.is_present("copy"), clear_after, matches.value_of("unpack"));
+	return Ok(())
+    }
+    let mut decoder = match matches.value_of("watch") {
+	Some(dir) => watch_dir(Path::new(dir), strict),
+	None => {
+	    let mut files : Vec<&str> = matches.values_of("FILE")
+		.map(|v| v.collect()).unwrap_or_default();
+	    let credentials : Vec<String> = match matches.is_present("credentials") {
+		true => common::credential_files(matches.value_of("credentials")).iter()
+		    .map(|path| path.to_string_lossy().into_owned()).collect(),
+		false => Vec::new(),
+	    };
+	    files.extend(credentials.iter().map(String::as_str));
+	    let prompting = matches.is_present("prompt");
+	    let mut lines = collect_share_lines(&files, prompting, strict)?;
+	    if let Some(template) = matches.value_of("template") {
+		lines = from_template(template, &lines, strict);
+	    }
+	    if matches.is_present("perl") {
+		lines = from_perl(&lines, strict);
+		if matches.is_present("convert") {
+		    for line in lines.iter() { println!("{}", line) }
+		    return Ok(())
+		}
+	    }
+	    let lines : Vec<&str> = match &manifest {
+		Some(manifest) => listed_shares(manifest, &lines, strict),
+		None => lines.iter().map(|l| l.as_str()).collect(),
+	    };
+	    let groups = group_by_set(&lines);
+	    if groups.len() > 1 && !prompting {
+		if matches.is_present("copy") {
+		    panic!("--copy can't be used when combining several share sets")
+		}
+		let failed = combine_batch(&groups, hardened, strict,
+					   matches.is_present("padded"));
+		return match failed {
+		    0 => Ok(()),
+		    _ => Err(format!("{} of {} share sets failed", failed, groups.len()).into()),
+		}
+	    }
+	    let mut decoder = decode_lines(&lines, majority.is_none() && !correct, strict)?;
+	    if prompting {
+		prompt_for_shares(&mut decoder, strict, fingerprint_hash(hash, manifest.as_ref()))?
+	    }
+	    report_duplicates(&decoder);
+	    summarize(&decoder, fingerprint_hash(hash, manifest.as_ref()));
+	    decoder
 	},
-	8  => { //field = gf_2px::field::new_gf8(0x11b,0x1b);
-	    let field = gf_2px::fast_gf_2p8::new();
-	    pass_1(&field, &mut decoder);
-	    let ans = pass_2(&field, &mut decoder);
-	    println!("Answer: {:?}", String::from_utf8(ans));
+    };
+
+    if let Some(size) = matches.value_of("chunk-size") { decoder.chunk_size = chunk_size(size) }
+    let ans = match majority {
+	_ if correct => {
+	    let correction = combine_correcting(&decoder)?;
+	    if correction.corrupted.is_empty() {
+		note!("No wrong shares found (up to {} could have been corrected)", correction.capacity);
+	    } else {
+		let numbers : Vec<String> = correction.corrupted.iter().map(|n| n.to_string()).collect();
+		common::warning!("corrected {} wrong share(s): {}", numbers.len(), numbers.join(", "));
+	    }
+	    correction.secret
 	},
-	16 => {
-	    let field = gf_2px::field::new_gf16(0x1002b,0x002b);
-	    take_something_implementing_field(&field);
+	Some(budget) => {
+	    let vote = combine_majority(&decoder, hardened, budget)?;
+	    if vote.is_degraded() {
+		common::warning!("degraded: {} of {} subsets of shares disagreed with the majority",
+				 vote.tried - vote.agreed, vote.tried);
+		eprint!("{}", vote.report());
+	    } else {
+		note!("All {} subsets of shares agree", vote.tried);
+	    }
+	    vote.secret
 	},
-	32 => {
-	    let field = gf_2px::field::new_gf32(0x10000008d,0x0000008d);
-	    take_something_implementing_field(&field);
+	None => {
+	    let bar = common::progress_bar(decoder.hex_length / 2);
+	    let ans = combine_with_progress(&mut decoder, hardened,
+					    common::progress_callback(&bar))?;
+	    if let Some(bar) = bar { bar.finish_and_clear() }
+	    ans
 	},
-	_ => { panic!() },	    
+    };
+    if let Some(manifest) = &manifest {
+	manifest.check_secret(&ans)?;
+	note!("Recovered secret matches the manifest's commitment");
     }
-
+    let ans = if matches.is_present("padded") {
+	unpad_secret(&ans)?
+    } else if matches.is_present("perl") {
+	strip_padding(&ans).to_vec()
+    } else {
+	ans
+    };
+    let store = match (matches.value_of("credstore"), matches.value_of("oci-secret")) {
+	(Some(name), _) => Some(Store::Credential {
+	    dir : Path::new(matches.value_of("credstore-dir").unwrap_or(common::CREDSTORE)), name,
+	}),
+	(None, Some(name)) => Some(Store::Container {
+	    path : Path::new(matches.value_of("secrets-dir").unwrap_or("/run/secrets")).join(name),
+	    mode : u32::from_str_radix(matches.value_of("mode").unwrap_or("0400"), 8).ok()
+		.filter(|mode| *mode <= 0o7777)
+		.unwrap_or_else(|| panic!("--mode expects octal permissions, eg 0440")),
+	    owner : matches.value_of("owner").map(parse_owner),
+	}),
+	(None, None) => None,
+    };
+    if let Some(pem) = tls_key_pem(&ans, matches.value_of("tls-cert")) {
+	if let Some(store) = &store {
+	    store.write(pem.as_bytes())
+	} else if matches.is_present("copy") {
+	    copy_to_clipboard(pem.as_bytes(), clear_after)
+	} else {
+	    print!("{}", pem)
+	}
+	return Ok(())
+    }
+    match store {
+	Some(store) => store.write(&ans),
+	None => print_secret(&ans, matches.is_present("copy"), clear_after,
+			     matches.value_of("unpack")),
+    }
+    Ok(())
 }
@@ -0,0 +1,133 @@
+// Dealer-less generation of a shared secret
+//
+// Every party runs
+//
+//   shamir-dkg deal -t K -n N --party F --set-id ID --out-dir DIR
+//
+// which writes DIR/dkg-F-to-J.txt for every party J, and sends each
+// file to its party over whatever private channel the ceremony uses
+// (keeping dkg-F-to-F.txt). With a file from every party, each one
+// then runs
+//
+//   shamir-dkg finish -n N FILE... > my-share.txt
+//
+// to get its share of a secret that none of them ever saw. Any K of
+// the shares combine with shamir-combine as usual. See the dkg module
+// in guff-ssss-core for how it works and what it doesn't check.
+
+extern crate clap;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use guff_ssss::dkg::{deal, finish, Deal};
+use guff_ssss::Params;
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{note, Failure};
+
+fn run_deal(matches : &ArgMatches) -> Result<(), Failure> {
+    let number = |name : &str| -> u16 {
+	matches.value_of(name).unwrap().parse()
+	    .unwrap_or_else(|_| panic!("{} must be a number", name))
+    };
+    let (k, n, from) = (number("threshold"), number("shares"), number("party"));
+    let length : usize = matches.value_of("length").unwrap().parse()
+	.unwrap_or_else(|_| panic!("length must be a number"));
+    let params = Params::new(k, n, 8).with_set_id(matches.value_of("set-id").unwrap());
+    params.check_sane(false)?;
+
+    let dir = Path::new(matches.value_of("out-dir").unwrap());
+    fs::create_dir_all(dir)
+	.map_err(|e| Failure::about(e.into(), format!("Can't create {}", dir.display())))?;
+    for d in deal(&params, from, length)? {
+	let file = dir.join(format!("dkg-{}-to-{}.txt", from, d.share.index));
+	common::write_owned(&file, format!("{}\n", d).as_bytes(), 0o600, None)
+	    .map_err(|e| Failure::about(e.into(), format!("Can't write {}", file.display())))?;
+	note!("Deal for party {} -> {}", d.share.index, file.display());
+    }
+    Ok(())
+}
+
+fn run_finish(matches : &ArgMatches) -> Result<(), Failure> {
+    let n : u16 = matches.value_of("shares").unwrap().parse()
+	.unwrap_or_else(|_| panic!("shares must be a number"));
+    let mut deals = Vec::new();
+    for name in matches.values_of("FILE").unwrap() {
+	let text = fs::read_to_string(name)
+	    .map_err(|e| Failure::about(e.into(), format!("Can't read {}", name)))?;
+	for line in text.lines().filter(|l| !l.trim().is_empty()) {
+	    let d : Deal = line.parse().map_err(|e| Failure::about(e, name))?;
+	    deals.push(d);
+	}
+    }
+    let share = finish(&deals, n)?;
+    note!("Added deals from {} parties into share {}", deals.len(), share.index);
+    println!("{}", share);
+    Ok(())
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let matches = App::new("shamir-dkg")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Generates a shared secret without a dealer: every party deals, \
+		then adds up what it was dealt")
+	.arg(Arg::with_name("quiet")
+	     .short("q")
+	     .long("quiet")
+	     .global(true)
+	     .help("Only print errors"))
+	.subcommand(SubCommand::with_name("deal")
+		    .about("Deal this party's random contribution to every party")
+		    .arg(Arg::with_name("threshold")
+			 .short("t")
+			 .takes_value(true)
+			 .required(true)
+			 .help("Number of shares needed to recover the secret"))
+		    .arg(Arg::with_name("shares")
+			 .short("n")
+			 .takes_value(true)
+			 .required(true)
+			 .help("Number of parties (and shares)"))
+		    .arg(Arg::with_name("party")
+			 .long("party")
+			 .value_name("F")
+			 .required(true)
+			 .help("Our party number, from 1 to n"))
+		    .arg(Arg::with_name("set-id")
+			 .long("set-id")
+			 .value_name("ID")
+			 .required(true)
+			 .help("Set ID agreed by all parties"))
+		    .arg(Arg::with_name("length")
+			 .long("length")
+			 .value_name("BYTES")
+			 .default_value("32")
+			 .help("Length of the secret, the same for all parties"))
+		    .arg(Arg::with_name("out-dir")
+			 .long("out-dir")
+			 .value_name("DIR")
+			 .required(true)
+			 .help("Where to write the deals, one file per party")))
+	.subcommand(SubCommand::with_name("finish")
+		    .about("Add up the deals sent to this party into its share")
+		    .arg(Arg::with_name("shares")
+			 .short("n")
+			 .takes_value(true)
+			 .required(true)
+			 .help("Number of parties (and shares)"))
+		    .arg(Arg::with_name("FILE")
+			 .required(true)
+			 .multiple(true)
+			 .help("Files holding the deals sent to this party, one from each")))
+	.get_matches();
+
+    if matches.is_present("quiet") { common::set_quiet() }
+    match matches.subcommand() {
+	("deal", Some(sub)) => run_deal(sub),
+	("finish", Some(sub)) => run_finish(sub),
+	_ => Err("Nothing to do: use 'deal' or 'finish' (see --help)".into()),
+    }
+}
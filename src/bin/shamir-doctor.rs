@@ -0,0 +1,130 @@
+// Explain why shares won't combine
+//
+// shamir-combine stops at the first thing wrong with its input. Given
+// the same pile of shares, this looks at all of them and lists every
+// problem it can find, each with a suggested next step:
+//
+//   shamir-doctor shares.txt scanned.png
+//   Line 2 isn't a share (problem with hex conversion for ...): 3=8=2=a0228Ob2...
+//     -> It reads as a share if 'O' is read as '0': 3=8=2=a02280b2...
+//   Only 2 of the 3 shares needed for set ops
+//     -> Find 1 more share(s) of the set, with numbers not already here
+//
+// With --manifest, each share is also checked against the dealer's
+// record. The exit status is 1 if anything was found.
+
+extern crate clap;
+use clap::{App, Arg};
+use guff_ssss::binary::{decode_binary, is_binary_share};
+use guff_ssss::codec::{ArmorCodec, KubernetesCodec, ShareCodec};
+use guff_ssss::doctor::{diagnose, Finding};
+use guff_ssss::join_continuations;
+use std::io::{self, Read};
+use std::path::Path;
+
+mod common;
+use common::{paint_out, Failure, Tone};
+
+// Lines that might be shares. Documents holding several shares
+// (armor, Kubernetes, YAML) are taken apart into text-format lines,
+// but anything else is left as it was written, mistakes and all.
+fn candidate_lines(bytes : Vec<u8>) -> Result<Vec<String>, String> {
+    if is_binary_share(&bytes) {
+	return decode_binary(&bytes).map(|share| vec![share.to_string()]).map_err(|e| e.to_string())
+    }
+    let text = String::from_utf8(bytes).map_err(|_| "not a text or binary share".to_string())?;
+    #[cfg(feature = "yaml")]
+    let documents = ArmorCodec.detect(&text) || KubernetesCodec.detect(&text)
+	|| guff_ssss::codec::YamlCodec.detect(&text);
+    #[cfg(not(feature = "yaml"))]
+    let documents = ArmorCodec.detect(&text) || KubernetesCodec.detect(&text);
+    if documents {
+	return common::shares_in(text.into_bytes())
+	    .map(|shares| shares.iter().map(|s| s.to_string()).collect())
+	    .map_err(|e| e.to_string())
+    }
+    Ok(join_continuations(text.lines()).into_iter()
+       .filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+       .collect())
+}
+
+fn file_lines(path : &Path) -> Result<Vec<String>, String> {
+    #[cfg(feature = "qr")]
+    if guff_ssss::qr::is_image(path) {
+	return guff_ssss::qr::decode_image(path)
+    }
+    let bytes = common::read_file(path).map_err(|e| e.to_string())?;
+    candidate_lines(bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let matches = App::new("shamir-doctor")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Find out why a set of shares won't combine, and what to do about it")
+	.arg(Arg::with_name("manifest")
+	     .long("manifest")
+	     .value_name("FILE")
+	     .help("Check each share against the record written by shamir-split --manifest"))
+	.arg(Arg::with_name("dealer-key")
+	     .long("dealer-key")
+	     .value_name("KEY")
+	     .requires("manifest")
+	     .help("Require the manifest to be signed by this public key"))
+	.arg(Arg::with_name("no-color")
+	     .long("no-color")
+	     .help("Don't colour the findings on the terminal (also NO_COLOR=1)"))
+	.arg(Arg::with_name("FILES")
+	     .multiple(true)
+	     .help("Files holding the shares (text, binary, or QR images with the 'qr' feature; \
+		    default: stdin)"))
+	.get_matches();
+
+    common::set_color(matches.is_present("no-color"));
+    let manifest = matches.value_of("manifest")
+	.map(|path| common::read_manifest(Path::new(path), matches.value_of("dealer-key")))
+	.transpose()?;
+
+    // A file that can't be read at all is a finding of its own
+    let mut problems = 0;
+    let mut lines = Vec::new();
+    match matches.values_of("FILES") {
+	Some(paths) => for path in paths {
+	    match file_lines(Path::new(path)) {
+		Ok(more) => lines.extend(more),
+		Err(e) => {
+		    println!("{}\n  -> Check the file is the one meant, and whole",
+			     paint_out(Tone::Bad, format!("Can't read {}", e)));
+		    problems += 1;
+		},
+	    }
+	},
+	None => {
+	    let mut bytes = Vec::new();
+	    io::stdin().read_to_end(&mut bytes).map_err(|e| Failure::about(e.into(), "stdin"))?;
+	    lines = candidate_lines(bytes).map_err(|e| format!("stdin: {}", e))?;
+	},
+    }
+
+    let refs : Vec<&str> = lines.iter().map(String::as_str).collect();
+    let findings = diagnose(&refs, manifest.as_ref());
+    for finding in findings.iter() {
+	// Repeats are the one thing that needs no action
+	let tone = match finding {
+	    Finding::Duplicate { identical : true, .. } => Tone::Warn,
+	    _ => Tone::Bad,
+	};
+	println!("{}\n  -> {}", paint_out(tone, finding), finding.advice());
+    }
+    problems += findings.len();
+    if problems == 0 {
+	println!("{} with the {} share(s). If they combine to the wrong secret, one of exactly \
+		  a quorum may be wrong: add a spare share, or use --manifest.",
+		 paint_out(Tone::Good, "No problems found"), lines.len());
+	Ok(())
+    } else {
+	Err(format!("{} problem(s) found", problems).into())
+    }
+}
@@ -0,0 +1,206 @@
+// Envelope encryption with a split key-encryption key
+//
+//   shamir-envelope seal -t K -n N --key KEYFILE --data DATAFILE [INPUT] > shares.txt
+//
+// encrypts INPUT (or stdin) into DATAFILE under a new data key, wraps
+// that under a new key-encryption key in KEYFILE and prints shares of
+// the key-encryption key. Then:
+//
+//   shamir-envelope open --key KEYFILE --data DATAFILE [SHARES...]
+//   shamir-envelope rewrap -t K -n N --key KEYFILE [SHARES...] > new-shares.txt
+//   shamir-envelope rotate --key KEYFILE --data DATAFILE [SHARES...]
+//
+// decrypt to stdout, move the data key to a new share set (only
+// KEYFILE changes), or re-encrypt DATAFILE under a new data key (the
+// shares stay the same). Shares are read from the SHARES files, or
+// stdin. See src/envelope.rs for the file formats.
+
+extern crate clap;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use guff_ssss::envelope::{decrypt, new_key, rewrap, rotate, seal_new, unwrap_dek, WrappedKey,
+			  KEY_BYTES};
+use guff_ssss::{combine_decoder, new_set_id, split_with_params, Decoder, Params};
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+
+mod common;
+use common::{note, Failure, OrExit};
+
+fn read_file(name : &str) -> Vec<u8> {
+    fs::read(name).unwrap_or_else(|e| common::fail(e, format!("Can't read {}", name)))
+}
+
+// Write next to the old file and rename over it, so an interrupted
+// rewrite never leaves a half-written key or payload behind
+fn replace_file(name : &str, data : &[u8]) {
+    let tmp = format!("{}.tmp", name);
+    fs::write(&tmp, data).unwrap_or_else(|e| common::fail(e, format!("Can't write {}", tmp)));
+    fs::rename(&tmp, name).unwrap_or_else(|e| common::fail(e, format!("Can't replace {}", name)));
+}
+
+fn read_key(matches : &ArgMatches) -> WrappedKey {
+    let name = matches.value_of("key").unwrap();
+    String::from_utf8_lossy(&read_file(name)).parse()
+	.unwrap_or_else(|e| panic!("{}: {}", name, e))
+}
+
+// Recombine the KEK from the share files given, or stdin
+fn read_kek(matches : &ArgMatches) -> Vec<u8> {
+    let mut decoder = Decoder::new();
+    let mut add = |line : &str| {
+	if !line.trim().is_empty() && !decoder.is_complete() {
+	    decoder.add_share(line.trim()).or_exit();
+	}
+    };
+    match matches.values_of("SHARES") {
+	Some(names) => for name in names {
+	    String::from_utf8_lossy(&read_file(name)).lines().for_each(&mut add)
+	},
+	None => for line in io::stdin().lock().lines() {
+	    add(&line.unwrap_or_else(|e| panic!("Can't read stdin: {}", e)))
+	},
+    }
+    let kek = combine_decoder(&mut decoder, false).or_exit();
+    if kek.len() != KEY_BYTES {
+	panic!("Shares are of a {}-byte secret, not a key-encryption key", kek.len())
+    }
+    kek
+}
+
+// Parameters for a new KEK's shares, which carry the set ID written
+// in the key file
+fn kek_params(matches : &ArgMatches, set_id : &str) -> Params {
+    let number = |name : &str| -> u16 {
+	matches.value_of(name).unwrap().parse()
+	    .unwrap_or_else(|_| panic!("{} must be a number", name))
+    };
+    let params = Params::new(number("threshold"), number("shares"), 8).with_set_id(set_id);
+    params.check_sane(false).or_exit();
+    params
+}
+
+fn print_shares(kek : &[u8], params : &Params) {
+    for share in split_with_params(kek, params, |_, _| {}).or_exit() {
+	println!("{}", share);
+    }
+}
+
+fn run_seal(matches : &ArgMatches) -> Result<(), Failure> {
+    let payload = match matches.value_of("INPUT") {
+	Some(name) => read_file(name),
+	None => {
+	    let mut payload = Vec::new();
+	    io::stdin().read_to_end(&mut payload)
+		.unwrap_or_else(|e| panic!("Can't read stdin: {}", e));
+	    payload
+	},
+    };
+    let set_id = new_set_id();
+    let params = kek_params(matches, &set_id);
+    let (key_name, data_name) = (matches.value_of("key").unwrap(), matches.value_of("data").unwrap());
+    if Path::new(key_name).exists() { return Err(format!("{} already exists", key_name).into()) }
+    let (kek, key, data) = seal_new(&payload, Some(&set_id));
+    replace_file(data_name, &data);
+    replace_file(key_name, key.to_string().as_bytes());
+    print_shares(&kek, &params);
+    note!("Sealed {} bytes into {}; key file {}", payload.len(), data_name, key_name);
+    Ok(())
+}
+
+fn run_open(matches : &ArgMatches) -> Result<(), Failure> {
+    let key = read_key(matches);
+    let dek = unwrap_dek(&read_kek(matches), &key).map_err(|e| e.to_string())?;
+    let payload = decrypt(&dek, &read_file(matches.value_of("data").unwrap()))
+	.map_err(|e| e.to_string())?;
+    common::write_raw(&payload);
+    Ok(())
+}
+
+fn run_rewrap(matches : &ArgMatches) -> Result<(), Failure> {
+    let key = read_key(matches);
+    let old_kek = read_kek(matches);
+    // Check the old shares before handing out any new ones
+    unwrap_dek(&old_kek, &key).map_err(|e| e.to_string())?;
+    let (new_kek, set_id) = (new_key(), new_set_id());
+    print_shares(&new_kek, &kek_params(matches, &set_id));
+    let key = rewrap(&old_kek, &key, &new_kek, Some(&set_id)).map_err(|e| e.to_string())?;
+    let name = matches.value_of("key").unwrap();
+    replace_file(name, key.to_string().as_bytes());
+    note!("Rewrapped {} for new share set {}; the old shares no longer open it", name, set_id);
+    Ok(())
+}
+
+fn run_rotate(matches : &ArgMatches) -> Result<(), Failure> {
+    let key = read_key(matches);
+    let data_name = matches.value_of("data").unwrap();
+    let (key, data) = rotate(&read_kek(matches), &key, &read_file(data_name))
+	.map_err(|e| e.to_string())?;
+    replace_file(data_name, &data);
+    replace_file(matches.value_of("key").unwrap(), key.to_string().as_bytes());
+    note!("Re-encrypted {} under a new data key", data_name);
+    Ok(())
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let key = Arg::with_name("key")
+	.long("key")
+	.value_name("KEYFILE")
+	.required(true)
+	.help("Key file holding the wrapped data key");
+    let data = Arg::with_name("data")
+	.long("data")
+	.value_name("DATAFILE")
+	.required(true)
+	.help("Encrypted payload");
+    let shares = Arg::with_name("SHARES")
+	.multiple(true)
+	.help("Files holding shares of the key-encryption key (default: stdin)");
+    let threshold = Arg::with_name("threshold")
+	.short("t")
+	.takes_value(true)
+	.required(true)
+	.help("Number of shares needed to recover the key-encryption key");
+    let count = Arg::with_name("shares")
+	.short("n")
+	.takes_value(true)
+	.required(true)
+	.help("Number of shares to make");
+
+    let matches = App::new("shamir-envelope")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Encrypts data under a key whose key-encryption key is split into shares")
+	.arg(Arg::with_name("quiet")
+	     .short("q")
+	     .long("quiet")
+	     .global(true)
+	     .help("Only print errors"))
+	.subcommand(SubCommand::with_name("seal")
+		    .about("Encrypt data and print shares of a new key-encryption key")
+		    .arg(threshold.clone()).arg(count.clone()).arg(key.clone()).arg(data.clone())
+		    .arg(Arg::with_name("INPUT")
+			 .help("File to encrypt (default: stdin)")))
+	.subcommand(SubCommand::with_name("open")
+		    .about("Decrypt data to stdout")
+		    .arg(key.clone()).arg(data.clone()).arg(shares.clone()))
+	.subcommand(SubCommand::with_name("rewrap")
+		    .about("Move the data key to a new share set, printing the new shares")
+		    .arg(threshold).arg(count).arg(key.clone()).arg(shares.clone()))
+	.subcommand(SubCommand::with_name("rotate")
+		    .about("Re-encrypt the data under a new data key, keeping the shares")
+		    .arg(key).arg(data).arg(shares))
+	.get_matches();
+
+    if matches.is_present("quiet") { common::set_quiet() }
+    common::binary_stdio();
+    match matches.subcommand() {
+	("seal", Some(sub)) => run_seal(sub),
+	("open", Some(sub)) => run_open(sub),
+	("rewrap", Some(sub)) => run_rewrap(sub),
+	("rotate", Some(sub)) => run_rotate(sub),
+	_ => Err("Nothing to do: use 'seal', 'open', 'rewrap' or 'rotate' (see --help)".into()),
+    }
+}
@@ -0,0 +1,100 @@
+// Short codes for custodians to read to each other
+//
+// Comparing whole shares over the phone isn't practical (and reading
+// a share out loud isn't wise), so this prints a six-word fingerprint
+// for each share instead:
+//
+//   shamir-fingerprint my-share.txt
+//   Share 2: RUG CAKE DOCK HOME HOWE TERM
+//
+// Given several shares, it also prints the fingerprint of all of them
+// together; given every share of a split, that's the set's
+// fingerprint. With --manifest, it prints the fingerprints of the set
+// and of every share the manifest records, so whoever holds the
+// manifest can read out what each custodian should see. The words
+// are RFC 1751's, with parity in the last word of each code, so a
+// misheard word usually shows up as an invalid code rather than a
+// wrong one. Fingerprints come from SHA-256, unless the manifest was
+// made with another hash (shamir-split --hash) or --hash says so.
+
+extern crate clap;
+use clap::{App, Arg};
+use guff_ssss::fingerprint::{of_listed_shares, of_manifest, of_set_with, of_share_with};
+use guff_ssss::hash::Algorithm;
+use std::io::{self, Read};
+use std::path::Path;
+
+mod common;
+use common::Failure;
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+
+    let hashes : Vec<&str> = Algorithm::ALL.iter().map(|hash| hash.name()).collect();
+    let matches = App::new("shamir-fingerprint")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Print word fingerprints of shares and sets to compare over the phone")
+	.arg(Arg::with_name("manifest")
+	     .long("manifest")
+	     .value_name("FILE")
+	     .help("Also print the fingerprints recorded by shamir-split --manifest"))
+	.arg(Arg::with_name("dealer-key")
+	     .long("dealer-key")
+	     .value_name("KEY")
+	     .requires("manifest")
+	     .help("Require the manifest to be signed by this public key"))
+	.arg(Arg::with_name("hash")
+	     .long("hash")
+	     .value_name("ALGORITHM")
+	     .possible_values(&hashes)
+	     .help("Hash the fingerprints are made from (default: the manifest's, or sha-256)"))
+	.arg(Arg::with_name("SHARES")
+	     .multiple(true)
+	     .help("Files holding shares (default: stdin, unless there's a manifest)"))
+	.get_matches();
+
+    let manifest = matches.value_of("manifest")
+	.map(|path| common::read_manifest(Path::new(path), matches.value_of("dealer-key")))
+	.transpose()?;
+
+    let hash : Algorithm = match (matches.value_of("hash"), &manifest) {
+	(Some(name), _) => name.parse().unwrap(),
+	(None, Some(manifest)) => manifest.hash,
+	(None, None) => Algorithm::Sha256,
+    };
+
+    let mut shares = Vec::new();
+    match matches.values_of("SHARES") {
+	Some(paths) => for path in paths {
+	    shares.extend(common::read_shares(Path::new(path))?)
+	},
+	None if manifest.is_some() => {},
+	None => {
+	    let mut bytes = Vec::new();
+	    io::stdin().read_to_end(&mut bytes).map_err(|e| Failure::about(e.into(), "stdin"))?;
+	    shares = common::shares_in(bytes).map_err(|e| e.within("stdin"))?;
+	    if shares.is_empty() { return Err("No shares on stdin".into()) }
+	},
+    }
+
+    for share in shares.iter() {
+	println!("Share {}: {}", share.index, of_share_with(share, hash));
+    }
+    if shares.len() > 1 {
+	let numbers : Vec<String> = shares.iter().map(|s| s.index.to_string()).collect();
+	println!("Shares {} together: {}", numbers.join(", "),
+		 of_set_with(&shares, hash)?);
+    }
+
+    if let Some(manifest) = manifest {
+	let set = manifest.set_id.as_deref().unwrap_or("(no set ID)");
+	println!("Set {} ({} shares, quorum {}): {}", set, manifest.share_hashes.len(),
+		 manifest.quorum, of_manifest(&manifest));
+	for (index, print) in of_listed_shares(&manifest)? {
+	    println!("  share {}: {}", index, print);
+	}
+    }
+    Ok(())
+}
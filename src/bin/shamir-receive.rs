@@ -0,0 +1,71 @@
+// Receive a single share from shamir-send over the network
+//
+// Listens on a TCP port and prints a one-time pairing code that the
+// sending custodian needs to enter. The received share is written to
+// stdout (or appended to a file) in the usual K=W=S=Values= format,
+// ready to be fed to shamir-combine. See src/transfer.rs for how the
+// channel is secured.
+
+extern crate clap;
+use clap::{Arg, App};
+use guff_ssss::Decoder;
+use guff_ssss::transfer::{self, Channel, Role};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::TcpListener;
+
+mod common;
+use common::Failure;
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let matches = App::new("shamir-receive")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Receive a share from shamir-send over an encrypted channel")
+	.arg(Arg::with_name("listen")
+	     .long("listen")
+	     .value_name("ADDR:PORT")
+	     .default_value("0.0.0.0:7341")
+	     .help("Address to listen on"))
+	.arg(Arg::with_name("output")
+	     .short("o")
+	     .long("output")
+	     .value_name("FILE")
+	     .help("Append the share to FILE instead of printing it"))
+	.get_matches();
+
+    let listener = TcpListener::bind(matches.value_of("listen").unwrap())
+	.unwrap_or_else(|e| panic!("Can't listen: {}", e));
+    let code = transfer::new_code();
+    eprintln!("Listening on {}", listener.local_addr().unwrap());
+    eprintln!("Pairing code: {}", code);
+
+    // One code, one attempt: SPAKE2 only protects a short code if
+    // the attacker can't keep guessing.
+    let (stream, peer) = listener.accept()
+	.unwrap_or_else(|e| panic!("Accept failed: {}", e));
+    eprintln!("Connection from {}", peer);
+    let mut chan = Channel::handshake(stream, &code, Role::Receiver)
+	.unwrap_or_else(|e| panic!("Handshake failed: {}", e));
+    let share = chan.recv().unwrap_or_else(|e| panic!("Receive failed: {}", e));
+    let share = String::from_utf8_lossy(&share).into_owned();
+
+    if let Err(e) = Decoder::new().add_share(&share) {
+	let _ = chan.send(format!("bad share: {}", e).as_bytes());
+	return Err(Failure::about(e, "Received an invalid share"))
+    }
+
+    match matches.value_of("output") {
+	Some(file) => {
+	    let mut f = OpenOptions::new().create(true).append(true).open(file)
+		.unwrap_or_else(|e| common::fail(e, format!("Can't open {}", file)));
+	    writeln!(f, "{}", share).unwrap();
+	},
+	None => println!("{}", share),
+    }
+    chan.send(b"OK").unwrap_or_else(|e| panic!("Acknowledgement failed: {}", e));
+    eprintln!("Share received");
+    Ok(())
+}
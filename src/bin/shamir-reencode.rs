@@ -0,0 +1,164 @@
+// Convert shares from one encoding to another
+//
+// Shares move between formats as they move between people and
+// systems: the text lines shamir-split writes, JSON for a
+// provisioning tool, armor for email, RFC 1751 words to read over the
+// phone, or a QR code to print. This converts them one share at a
+// time, without the rest of the set and without ever recovering the
+// secret:
+//
+//   shamir-reencode --to json share-1.txt share-2.txt > shares.json
+//   shamir-reencode --to qr --output printouts/ shares.txt
+//
+// Input is in any format shamir-combine reads (but for sealed or
+// locked shares, which would have to be opened first). Whatever the
+// format, the set ID, quorum, width and index come through as they
+// were, and every re-encoded share is read back and compared with the
+// original before it's written, so a format that can't carry some of
+// it is an error rather than a quietly different share. Checksums
+// (armor's CRC, the binary format's SHA-256) are checked on the way
+// in and made afresh on the way out.
+
+extern crate clap;
+use clap::{App, Arg};
+use guff_ssss::binary::{decode_binary, encode_binary};
+use guff_ssss::codec::Registry;
+use guff_ssss::Share;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+mod common;
+use common::Failure;
+
+// Where a share goes in an output directory, named as shamir-split
+// names binary shares
+fn file_name(share : &Share, ext : &str) -> String {
+    match &share.set_id {
+	Some(id) => format!("{}-share-{}.{}", id, share.index, ext),
+	None => format!("share-{}.{}", share.index, ext),
+    }
+}
+
+// Panics if `back`, read from the re-encoded share, isn't `share`
+fn check_same(format : &str, share : &Share, back : Result<Share, String>) {
+    match back {
+	Ok(ref back) if back == share => {},
+	Ok(_) => panic!("Share {} doesn't survive as {}: some of it would be lost", share.index, format),
+	Err(e) => panic!("Share {} doesn't read back as {}: {}", share.index, format, e),
+    }
+}
+
+// One file per share in `dir`
+fn write_binary(dir : &Path, shares : &[Share]) -> Result<(), Failure> {
+    let files : Vec<(PathBuf, Vec<u8>)> = shares.iter().map(|share| {
+	let data = encode_binary(share);
+	check_same("binary", share, decode_binary(&data).map_err(|e| e.to_string()));
+	(dir.join(file_name(share, "gssb")), data)
+    }).collect();
+    common::write_files(&files)?;
+    for (share, (file, _)) in shares.iter().zip(&files) {
+	common::note!("Share {} -> {}", share.index, file.display());
+    }
+    Ok(())
+}
+
+// One image per share in `dir`, each read back to check it
+#[cfg(feature = "qr")]
+fn write_qr(registry : &Registry, dir : &Path, shares : &[Share]) {
+    use guff_ssss::qr::{decode_image, encode_image};
+    for share in shares {
+	let file = dir.join(file_name(share, "png"));
+	encode_image(&share.to_string(), &file).unwrap_or_else(|e| panic!("{}", e));
+	check_same("qr", share, decode_image(&file)
+		   .and_then(|lines| registry.decode(&lines.join("\n")).map_err(|e| e.to_string())));
+	common::note!("Share {} -> {}", share.index, file.display());
+    }
+}
+
+#[cfg(not(feature = "qr"))]
+fn write_qr(_ : &Registry, _ : &Path, _ : &[Share]) {
+    panic!("Built without the 'qr' feature; rebuild with --features qr to write QR codes")
+}
+
+// Shares in one of the registry's codecs, all to `out`
+fn write_text(registry : &Registry, format : &str, shares : &[Share], out : &mut dyn Write) {
+    let codec = registry.get(format).expect("format was checked against the registry");
+    for share in shares {
+	let mut line = Vec::new();
+	codec.write_line(share, &mut line).unwrap_or_else(|e| panic!("Can't encode: {}", e));
+	let back = String::from_utf8(line.clone()).map_err(|e| e.to_string())
+	    .and_then(|text| codec.decode(&text).map_err(|e| e.to_string()));
+	check_same(format, share, back);
+	out.write_all(&line).unwrap_or_else(|e| panic!("Can't write: {}", e));
+    }
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+
+    let registry = Registry::new();
+    let mut formats : Vec<&str> = registry.names();
+    formats.extend(["binary", "qr"]);
+
+    let matches = App::new("shamir-reencode")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Convert shares between encodings, without combining them")
+	.arg(Arg::with_name("to")
+	     .long("to")
+	     .value_name("FORMAT")
+	     .required(true)
+	     .possible_values(&formats)
+	     .help("Encoding to write (binary and qr write a file per share into --output)"))
+	.arg(Arg::with_name("output")
+	     .short("o")
+	     .long("output")
+	     .value_name("PATH")
+	     .help("File to write (default: stdout), or directory for binary and qr"))
+	.arg(Arg::with_name("quiet")
+	     .short("q")
+	     .long("quiet")
+	     .help("Don't say where each share went"))
+	.arg(Arg::with_name("SHARES")
+	     .multiple(true)
+	     .help("Files holding shares (default: stdin)"))
+	.get_matches();
+
+    if matches.is_present("quiet") { common::set_quiet() }
+    let format = matches.value_of("to").unwrap();
+
+    let mut shares = Vec::new();
+    match matches.values_of("SHARES") {
+	Some(paths) => for path in paths {
+	    shares.extend(common::read_shares(Path::new(path))?)
+	},
+	None => {
+	    let mut bytes = Vec::new();
+	    io::stdin().read_to_end(&mut bytes).map_err(|e| Failure::about(e.into(), "stdin"))?;
+	    shares = common::shares_in(bytes).map_err(|e| e.within("stdin"))?;
+	},
+    }
+    if shares.is_empty() { return Err("No shares to convert".into()) }
+
+    match format {
+	"binary" | "qr" => {
+	    let dir = Path::new(matches.value_of("output")
+				.unwrap_or_else(|| panic!("--to {} needs an --output directory", format)));
+	    fs::create_dir_all(dir)
+		.map_err(|e| Failure::about(e.into(), format!("Can't create {}", dir.display())))?;
+	    if format == "binary" { write_binary(dir, &shares)? } else { write_qr(&registry, dir, &shares) }
+	},
+	_ => match matches.value_of("output") {
+	    Some(path) => {
+		let mut text = Vec::new();
+		write_text(&registry, format, &shares, &mut text);
+		common::write_owned(Path::new(path), &text, 0o600, None)
+		    .map_err(|e| Failure::about(e.into(), format!("Can't write {}", path)))?;
+	    },
+	    None => write_text(&registry, format, &shares, &mut io::stdout().lock()),
+	},
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+// Check the field arithmetic on this machine
+//
+// Worth running once on any new platform, and before a ceremony on a
+// machine nobody has used for one before:
+//
+//   shamir-selftest
+//   ok    GF(2^8) polynomial is 0x11b
+//   ok    GF(2^8) tables match shift-and-add and Euclid
+//   ...
+//
+// The checks are guff_ssss::selftest's. The exit status is 1 if any
+// of them failed, in which case don't trust this build with shares.
+
+extern crate clap;
+use clap::{App, Arg};
+use guff_ssss::selftest;
+
+mod common;
+use common::{paint_out, Failure, Tone};
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let matches = App::new("shamir-selftest")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Check that the field arithmetic works on this machine")
+	.arg(Arg::with_name("quiet")
+	     .short("q")
+	     .long("quiet")
+	     .help("Print only the checks that fail"))
+	.arg(Arg::with_name("no-color")
+	     .long("no-color")
+	     .help("Don't colour the results on the terminal (also NO_COLOR=1)"))
+	.get_matches();
+
+    common::set_color(matches.is_present("no-color"));
+    let quiet = matches.is_present("quiet");
+    let checks = selftest::run();
+    for check in checks.iter() {
+	match &check.failure {
+	    None if quiet => {},
+	    None => println!("{}    {}", paint_out(Tone::Good, "ok"), check.name),
+	    Some(failure) => println!("{}  {}: {}", paint_out(Tone::Bad, "FAIL"), check.name, failure),
+	}
+    }
+    let failed = checks.iter().filter(|check| !check.passed()).count();
+    if failed > 0 {
+	return Err(format!("{} of {} checks failed: don't use this build with real shares",
+			   failed, checks.len()).into())
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+// Send a single share to a waiting shamir-receive over the network
+//
+// The receiver prints a pairing code; type (or paste) it here. See
+// src/transfer.rs for how the channel is secured.
+
+extern crate clap;
+use clap::{Arg, App};
+use guff_ssss::Decoder;
+use guff_ssss::transfer::{Channel, Role};
+use std::io::{self, BufRead, Write};
+use std::net::TcpStream;
+
+mod common;
+use common::Failure;
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let matches = App::new("shamir-send")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Send a share to shamir-receive over an encrypted channel")
+	.usage("shamir-send --to HOST:PORT [--code CODE] < share")
+	.arg(Arg::with_name("to")
+	     .long("to")
+	     .value_name("HOST:PORT")
+	     .required(true)
+	     .help("Address shamir-receive is listening on"))
+	.arg(Arg::with_name("code")
+	     .long("code")
+	     .value_name("CODE")
+	     .help("Pairing code shown by shamir-receive (prompted for if absent)"))
+	.get_matches();
+
+    // Read and sanity-check the share before going anywhere near the
+    // network
+    let stdin = io::stdin();
+    let mut share = String::new();
+    stdin.lock().read_line(&mut share).unwrap();
+    let share = share.trim().to_string();
+    Decoder::new().add_share(&share).map_err(|e| Failure::about(e, "Not a valid share"))?;
+
+    // If stdin held the share, prompt on the terminal for the code
+    let code = match matches.value_of("code") {
+	Some(code) => code.to_string(),
+	None => {
+	    eprint!("Pairing code: ");
+	    io::stderr().flush().unwrap();
+	    let tty = std::fs::File::open(if cfg!(windows) { "CON" } else { "/dev/tty" })
+		.unwrap_or_else(|e| panic!("No --code given and can't open terminal: {}", e));
+	    let mut code = String::new();
+	    io::BufReader::new(tty).read_line(&mut code).unwrap();
+	    code
+	}
+    };
+
+    let stream = TcpStream::connect(matches.value_of("to").unwrap())
+	.unwrap_or_else(|e| panic!("Can't connect: {}", e));
+    let mut chan = Channel::handshake(stream, &code, Role::Sender)
+	.unwrap_or_else(|e| panic!("Handshake failed: {}", e));
+    chan.send(share.as_bytes())
+	.unwrap_or_else(|e| panic!("Send failed: {}", e));
+    let reply = chan.recv()
+	.unwrap_or_else(|e| panic!("No acknowledgement: {}", e));
+    let reply = String::from_utf8_lossy(&reply);
+    if reply != "OK" {
+	return Err(format!("Receiver rejected share: {}", reply).into())
+    }
+    eprintln!("Share delivered");
+    Ok(())
+}
@@ -0,0 +1,265 @@
+// HTTP(S) API for Shamir's Secret Sharing Scheme
+//
+// Lets the crate back an internal key-recovery service without any
+// glue code. Every request must carry `Authorization: Bearer TOKEN`.
+// Custodians use the submit token; the operator who receives
+// reconstructed secrets (and asks for new splits) uses the admin
+// token, which must differ from the submit token. Without
+// --admin-token-file the admin endpoints are switched off.
+//
+//   POST   /shares           body: share line(s)      (submit token)
+//   GET    /status                                    (submit token)
+//   GET    /secret           hex secret, then reset   (admin token)
+//   DELETE /shares           discard collected shares (admin token)
+//   POST   /split?k=K&n=N    body: raw secret         (admin token)
+//
+// Responses are small JSON objects. Plain HTTP is intended for use
+// behind a TLS-terminating proxy or on localhost; build with the
+// `tls` feature to serve HTTPS directly.
+
+extern crate clap;
+use clap::{Arg, App};
+use guff_ssss::{Decoder, Error, combine_decoder, split};
+use std::fs;
+use std::io::Read;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+mod common;
+use common::Failure;
+
+// Largest request body we'll accept
+const MAX_BODY : u64 = 1 << 20;
+
+struct State {
+    decoder      : Decoder,
+    submit_token : String,
+    admin_token  : Option<String>,
+    hardened     : bool,
+}
+
+// Compare tokens without leaking how many leading bytes matched
+fn token_eq(a : &[u8], b : &[u8]) -> bool {
+    if a.len() != b.len() { return false }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn bearer(request : &Request) -> Option<String> {
+    request.headers().iter()
+	.find(|h| h.field.equiv("Authorization"))
+	.and_then(|h| h.value.as_str().strip_prefix("Bearer ").map(|t| t.trim().to_string()))
+}
+
+fn json_escape(s : &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+	match c {
+	    '"'  => out.push_str("\\\""),
+	    '\\' => out.push_str("\\\\"),
+	    c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+	    c => out.push(c),
+	}
+    }
+    out
+}
+
+fn reply(status : u16, body : String) -> Response<std::io::Cursor<Vec<u8>>> {
+    let content_type = Header::from_bytes(&b"Content-Type"[..],
+					  &b"application/json"[..]).unwrap();
+    Response::from_string(body).with_status_code(status).with_header(content_type)
+}
+
+fn error(status : u16, msg : &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    reply(status, format!("{{\"error\":\"{}\"}}", json_escape(msg)))
+}
+
+// Pull k and n out of a query string like "k=3&n=5"
+fn query_param(query : &str, name : &str) -> Option<u16> {
+    query.split('&')
+	.filter_map(|kv| kv.split_once('='))
+	.find(|(k, _)| *k == name)
+	.and_then(|(_, v)| v.parse().ok())
+}
+
+impl State {
+    fn status(&self) -> String {
+	format!("{{\"have\":{},\"quorum\":{},\"complete\":{}}}",
+		self.decoder.have(), self.decoder.quorum, self.decoder.is_complete())
+    }
+
+    fn handle(&mut self, request : &mut Request)
+	      -> Response<std::io::Cursor<Vec<u8>>> {
+	let token = bearer(request).unwrap_or_default();
+	let is_admin  = self.admin_token.as_ref()
+	    .is_some_and(|admin| token_eq(token.as_bytes(), admin.as_bytes()));
+	let is_submit = is_admin || token_eq(token.as_bytes(), self.submit_token.as_bytes());
+	if !is_submit { return error(401, "missing or bad bearer token") }
+
+	let url = request.url().to_string();
+	let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+	// One byte more than we'll take, to tell a full body from a cut-off one
+	let mut body = Vec::new();
+	if request.as_reader().take(MAX_BODY + 1).read_to_end(&mut body).is_err() {
+	    return error(400, "can't read request body")
+	}
+	if body.len() as u64 > MAX_BODY {
+	    return error(413, &format!("request body is over {} bytes", MAX_BODY))
+	}
+
+	match (request.method(), path) {
+	    (Method::Post, "/shares") => {
+		let body = match String::from_utf8(body) {
+		    Ok(body) => body,
+		    Err(_) => return error(400, "shares must be text"),
+		};
+		// All the lines or none of them, so a client can fix a bad
+		// line and send the same body again
+		let mut decoder = self.decoder.clone();
+		for (n, line) in body.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+		    let problem = match decoder.add_share(line) {
+			Ok(_) => continue,
+			// its line number counts shares, not lines of the body
+			Err(Error::ParseError { error, .. }) => error.to_string(),
+			Err(e) => e.to_string(),
+		    };
+		    return error(422, &format!("line {}: {} (no shares were added)", n + 1, problem))
+		}
+		self.decoder = decoder;
+		reply(200, self.status())
+	    },
+	    (Method::Get, "/status") => reply(200, self.status()),
+	    (Method::Get, "/secret") | (Method::Delete, "/shares") | (Method::Post, "/split")
+		if self.admin_token.is_none() => {
+		error(403, "admin endpoints are disabled (no --admin-token-file)")
+	    },
+	    (Method::Get, "/secret") | (Method::Delete, "/shares") if !is_admin => {
+		error(403, "admin token required")
+	    },
+	    (Method::Get, "/secret") => {
+		if !self.decoder.is_complete() {
+		    return error(409, "quorum not met")
+		}
+		let result = combine_decoder(&mut self.decoder, self.hardened);
+		self.decoder = Decoder::new();
+		match result {
+		    Ok(secret) => reply(200, format!("{{\"secret\":\"{}\"}}", hex::encode(secret))),
+		    Err(e) => error(422, &e.to_string()),
+		}
+	    },
+	    (Method::Delete, "/shares") => {
+		self.decoder = Decoder::new();
+		reply(200, self.status())
+	    },
+	    (Method::Post, "/split") if !is_admin => error(403, "admin token required"),
+	    (Method::Post, "/split") => {
+		let (k, n) = match (query_param(query, "k"), query_param(query, "n")) {
+		    (Some(k), Some(n)) => (k, n),
+		    _ => return error(400, "need k and n query parameters"),
+		};
+		match split(&body, k, n, 8) {
+		    Ok(shares) => {
+			let list : Vec<String> = shares.iter()
+			    .map(|s| format!("\"{}\"", s)).collect();
+			reply(200, format!("{{\"shares\":[{}]}}", list.join(",")))
+		    },
+		    Err(e) => error(422, &e.to_string()),
+		}
+	    },
+	    _ => error(404, "no such endpoint"),
+	}
+    }
+}
+
+fn read_token(file : &str) -> String {
+    let token = fs::read_to_string(file)
+	.unwrap_or_else(|e| common::fail(e, format!("Can't read token file {}", file)));
+    let token = token.trim().to_string();
+    if token.len() < 16 { panic!("Token in {} is too short (need 16+ chars)", file) }
+    token
+}
+
+#[cfg(feature = "tls")]
+fn new_server(addr : &str, cert : Option<&str>, key : Option<&str>) -> Server {
+    match (cert, key) {
+	(Some(cert), Some(key)) => {
+	    let config = tiny_http::SslConfig {
+		certificate : fs::read(cert)
+		    .unwrap_or_else(|e| common::fail(e, format!("Can't read {}", cert))),
+		private_key : fs::read(key)
+		    .unwrap_or_else(|e| common::fail(e, format!("Can't read {}", key))),
+	    };
+	    Server::https(addr, config).unwrap_or_else(|e| panic!("Can't listen: {}", e))
+	},
+	_ => Server::http(addr).unwrap_or_else(|e| panic!("Can't listen: {}", e)),
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn new_server(addr : &str, cert : Option<&str>, _key : Option<&str>) -> Server {
+    if cert.is_some() { panic!("Built without the 'tls' feature; can't serve HTTPS") }
+    Server::http(addr).unwrap_or_else(|e| panic!("Can't listen: {}", e))
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let matches = App::new("shamir-serve")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("HTTP(S) API for submitting shares and splitting secrets")
+	.arg(Arg::with_name("listen")
+	     .long("listen")
+	     .value_name("ADDR:PORT")
+	     .default_value("127.0.0.1:7342")
+	     .help("Address to listen on"))
+	.arg(Arg::with_name("token-file")
+	     .long("token-file")
+	     .value_name("FILE")
+	     .required(true)
+	     .help("File holding the bearer token for submitting shares"))
+	.arg(Arg::with_name("admin-token-file")
+	     .long("admin-token-file")
+	     .value_name("FILE")
+	     .help("File holding the bearer token for /secret and /split \
+		    (without it, those endpoints are disabled)"))
+	.arg(Arg::with_name("cert")
+	     .long("cert")
+	     .value_name("PEM")
+	     .requires("key")
+	     .help("TLS certificate chain (needs the 'tls' feature)"))
+	.arg(Arg::with_name("key")
+	     .long("key")
+	     .value_name("PEM")
+	     .requires("cert")
+	     .help("TLS private key (needs the 'tls' feature)"))
+	.arg(Arg::with_name("hardened")
+	     .long("hardened")
+	     .help("Blind field inversions with random values \
+		    (side-channel mitigation)"))
+	.get_matches();
+
+    let submit_token = read_token(matches.value_of("token-file").unwrap());
+    let admin_token = matches.value_of("admin-token-file").map(read_token);
+    if admin_token.as_ref() == Some(&submit_token) {
+	return Err("The admin token must differ from the submit token".into())
+    }
+    let mut state = State {
+	decoder : Decoder::new(),
+	submit_token, admin_token,
+	hardened : matches.is_present("hardened"),
+    };
+
+    let addr = matches.value_of("listen").unwrap();
+    let server = new_server(addr, matches.value_of("cert"), matches.value_of("key"));
+    eprintln!("Listening on {}", addr);
+
+    // Requests are handled one at a time; this is not a high-volume
+    // service and it keeps the shared state trivially consistent.
+    for mut request in server.incoming_requests() {
+	let response = state.handle(&mut request);
+	eprintln!("{} {} -> {}", request.method(), request.url(),
+		  response.status_code().0);
+	let _ = request.respond(response);
+    }
+    Ok(())
+}
@@ -21,11 +21,1066 @@
 // n = number of shares
 
 extern crate clap;
-use clap::{Arg, App, SubCommand};
+use clap::{Arg, App};
+use guff_ssss::codec::{HashedArmorCodec, HashedV2Codec, Registry, ShareCodec, TemplateCodec};
+use guff_ssss::custodian::Custodian;
+use guff_ssss::estimate::{estimate, ShareSize};
+use guff_ssss::hash::Algorithm;
+use guff_ssss::manifest::{DealerKey, Manifest};
+use guff_ssss::participants;
+use guff_ssss::perl::{pad_words, to_perl_share};
+use rand::seq::SliceRandom;
+use std::convert::TryInto;
+use guff_ssss::binary::encode_binary_with;
+use guff_ssss::{archive, check_recoverable, Error, Params, Share, new_set_id, pad_secret, padded_len,
+		split_shares, split_shares_at, share_fingerprint_with, wrap_share};
+use std::collections::HashSet;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 
-fn main() {
+mod common;
+use common::{Failure, OrExit};
 
-    
+// Batch mode (--batch PATH)
+//
+// For provisioning fleets of devices. PATH is either a directory, in
+// which case every regular file in it is a secret, or a file (or `-`
+// for stdin) holding one secret per line. Each secret gets its own
+// share set with a fresh set ID so that the shares of different
+// secrets can't be accidentally mixed at combine time.
+//
+// Secrets that may hold newlines (keys, certificates, whole config
+// files) can be separated by some other --delimiter instead, or sent
+// as --records: each a 4-byte big-endian length, then the secret.
+fn batch_secrets(path : &Path, separator : &Separator) -> Vec<(String, Vec<u8>)> {
+    let mut secrets = Vec::new();
+    if path.is_dir() {
+	if !matches!(separator, Separator::Lines) {
+	    panic!("--delimiter and --records are for a --batch file or stdin, not a directory")
+	}
+	let mut entries : Vec<_> = fs::read_dir(path)
+	    .unwrap_or_else(|e| common::fail(e, format!("Can't read {}", path.display())))
+	    .filter_map(|e| e.ok()).map(|e| e.path())
+	    .filter(|p| p.is_file()).collect();
+	entries.sort();
+	for entry in entries {
+	    let name = entry.file_name().unwrap().to_string_lossy().into_owned();
+	    let secret = fs::read(&entry)
+		.unwrap_or_else(|e| common::fail(e, format!("Can't read {}", entry.display())));
+	    secrets.push((name, secret));
+	}
+	return secrets
+    }
+    let data = if path == Path::new("-") {
+	let mut data = Vec::new();
+	io::stdin().lock().read_to_end(&mut data).unwrap_or_else(|e| panic!("stdin: {}", e));
+	data
+    } else {
+	fs::read(path).unwrap_or_else(|e| common::fail(e, format!("Can't read {}", path.display())))
+    };
+    match separator {
+	Separator::Lines => {
+	    let list = String::from_utf8(data)
+		.unwrap_or_else(|_| panic!("{} isn't text: use --delimiter or --records", path.display()));
+	    for (i, line) in list.lines().enumerate() {
+		if line.is_empty() { continue }
+		secrets.push((format!("line-{}", i + 1), line.as_bytes().to_vec()));
+	    }
+	},
+	Separator::Delimiter(delimiter) => {
+	    let mut rest = &data[..];
+	    let mut i = 0;
+	    while !rest.is_empty() {
+		let end = rest.windows(delimiter.len()).position(|w| w == &delimiter[..])
+		    .unwrap_or(rest.len());
+		i += 1;
+		if end > 0 { secrets.push((format!("secret-{}", i), rest[..end].to_vec())) }
+		rest = &rest[(end + delimiter.len()).min(rest.len())..];
+	    }
+	},
+	Separator::Records => {
+	    let mut rest = &data[..];
+	    while !rest.is_empty() {
+		let name = format!("record-{}", secrets.len() + 1);
+		let len = match rest.get(..4) {
+		    Some(len) => u32::from_be_bytes(len.try_into().unwrap()) as usize,
+		    None => panic!("{}: cut short in its length", name),
+		};
+		let secret = rest.get(4..4 + len)
+		    .unwrap_or_else(|| panic!("{}: cut short ({} of {} bytes)", name, rest.len() - 4, len));
+		secrets.push((name, secret.to_vec()));
+		rest = &rest[4 + len..];
+	    }
+	},
+    }
+    secrets
+}
+
+// How the secrets in a --batch file are separated
+enum Separator {
+    Lines,
+    Delimiter(Vec<u8>),
+    Records,
+}
+
+// --delimiter TEXT, with \n, \r, \t, \0 and \\ for the bytes a
+// shell makes awkward to type
+fn delimiter(text : &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+	let c = match c {
+	    '\\' => match chars.next() {
+		Some('n') => '\n',
+		Some('r') => '\r',
+		Some('t') => '\t',
+		Some('0') => '\0',
+		Some('\\') => '\\',
+		Some(other) => panic!("--delimiter: unknown escape \\{}", other),
+		None => panic!("--delimiter ends in a lone \\"),
+	    },
+	    c => c,
+	};
+	let mut buf = [0; 4];
+	bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+    if bytes.is_empty() { panic!("--delimiter can't be empty") }
+    bytes
+}
+
+fn split_batch(secrets : Vec<(String, Vec<u8>)>, params : &Params,
+	       prepare : impl Fn(Vec<u8>) -> Result<Vec<u8>, Failure>,
+	       encode : impl Fn(&Share) -> Vec<u8>,
+	       outdir : Option<&Path>, shuffle : bool, checks : Option<usize>) {
+    let mut used = HashSet::new();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for (name, secret) in secrets {
+	if secret.is_empty() { common::fail(Error::FieldError("the secret is empty".into()), &name) }
+	let secret = prepare(secret).map_err(|e| e.within(&name)).or_exit();
+	// 32-bit IDs can collide across a big enough fleet
+	let id = loop {
+	    let id = new_set_id();
+	    if used.insert(id.clone()) { break id }
+	};
+	let params = params.clone().with_set_id(&id);
+	let mut shares = split_shares(&secret, &params, |_, _| {})
+	    .unwrap_or_else(|e| common::fail(e, &name));
+	if checks.is_some() { self_check(&secret, &shares, checks) }
+	if shuffle { shares.shuffle(&mut rand::thread_rng()) }
+	let mut text = format!("# secret: {}  set: {}\n", name, id).into_bytes();
+	for share in shares.iter() { text.extend(encode(share)) }
+	match outdir {
+	    Some(dir) => {
+		let file = dir.join(format!("{}.shares", name));
+		common::write_owned(&file, &text, 0o600, None)
+		    .unwrap_or_else(|e| common::fail(e, format!("Can't write {}", file.display())));
+		common::note!("{} -> {} (set {})", name, file.display(), id);
+	    },
+	    None => {
+		stdout.write_all(&text).unwrap();
+		writeln!(stdout).unwrap();
+	    },
+	}
+    }
+}
+
+// Watch mode (--watch FILE, --destination PATH ...)
+//
+// Shares of a service credential are no use once it's rotated. This
+// splits FILE, then waits, and each time its contents change splits
+// it again under a new set ID, replacing share i at the i'th
+// destination (and the manifest, if there is one). The old shares
+// don't combine with the new ones, so a custodian who misses an
+// update can't be mixed in by mistake. Only a hash of the last
+// contents is kept between changes, never the secret itself.
+fn watch_and_split(path : &Path, params : &Params, prepare : impl Fn(Vec<u8>) -> Result<Vec<u8>, Failure>,
+		   destinations : &[PathBuf], encode : impl Fn(&Share) -> Vec<u8>,
+		   manifest : Option<(&Path, Option<&Path>, &str, Algorithm)>, checks : Option<usize>) -> ! {
+    let hasher = RandomState::new();
+    let dir = match path.parent() {
+	Some(dir) if dir != Path::new("") => dir,
+	_ => Path::new("."),
+    };
+    let mut watcher = common::DirWatcher::new(dir);
+    let mut last = None;
+    loop {
+	// A file that's missing or empty is most likely being replaced
+	match fs::read(path) {
+	    Ok(secret) if !secret.is_empty() && last != Some(hasher.hash_one(&secret)) => {
+		last = Some(hasher.hash_one(&secret));
+		let secret = prepare(secret).map_err(|e| e.within(path.display())).or_exit();
+		let id = new_set_id();
+		let params = params.clone().with_set_id(&id);
+		let shares = split_shares(&secret, &params, |_, _| {})
+		    .unwrap_or_else(|e| common::fail(e, path.display()));
+		self_check(&secret, &shares, checks);
+		if let Some((file, key, format, hash)) = manifest {
+		    let text : Vec<String> = shares.iter().map(Share::to_string).collect();
+		    write_manifest(file, &params, &secret, &text, key, format, hash);
+		}
+		let files : Vec<(PathBuf, Vec<u8>)> = shares.iter().zip(destinations)
+		    .map(|(share, dest)| (dest.clone(), encode(share))).collect();
+		common::write_files(&files).or_exit();
+		common::note!("Split {} as set {} -> {}", path.display(), id,
+			      destinations.iter().map(|d| d.display().to_string())
+			      .collect::<Vec<_>>().join(", "));
+	    },
+	    _ => {},
+	}
+	watcher.wait();
+	// Let a burst of writes settle before reading it
+	thread::sleep(Duration::from_millis(250));
+    }
+}
+
+// Recombine subsets of the shares in memory (--self-check [N]) before
+// anything is written, so a set that can't give the secret back is
+// never handed out
+fn self_check(secret : &[u8], shares : &[Share], checks : Option<usize>) {
+    if let Some(budget) = checks {
+	let tried = check_recoverable(secret, shares, budget)
+	    .unwrap_or_else(|e| panic!("Self-check failed, so nothing was written: {}", e));
+	common::note!("Self-check: {} subset(s) of the shares give back the secret", tried);
+    }
+}
+
+// A secret typed at the terminal is read without echo, and twice;
+// piped data is taken as it is, since it may be binary
+fn read_stdin() -> Vec<u8> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+	return common::prompt_secret_twice("Enter the secret: ")
+    }
+    let mut secret = Vec::new();
+    stdin.lock().read_to_end(&mut secret)
+	.unwrap_or_else(|e| common::fail(e, "Can't read stdin"));
+    secret
+}
+
+// A descriptor inherited from the parent (eg, `--secret-fd 3
+// 3<secret.key`, or a pipe from a password manager) keeps the secret
+// out of the command line, the shell history and the terminal
+#[cfg(unix)]
+fn read_fd(fd : i32) -> Vec<u8> {
+    use std::os::unix::io::FromRawFd;
+    if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+	panic!("--secret-fd {}: not an open file descriptor", fd)
+    }
+    // We own the descriptor from here on, and it's closed once read
+    let mut file = unsafe { fs::File::from_raw_fd(fd) };
+    let mut secret = Vec::new();
+    file.read_to_end(&mut secret)
+	.unwrap_or_else(|e| common::fail(e, format!("Can't read file descriptor {}", fd)));
+    secret
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd : i32) -> Vec<u8> {
+    panic!("--secret-fd is only available on Unix")
+}
+
+// Turn the secret as given into the bytes to split: decode it if it's
+// hex (whitespace is ignored, so key material can be pasted as it's
+// usually printed), then pad it to the security level
+fn prepare(secret : Vec<u8>, is_hex : bool, security : Option<usize>) -> Result<Vec<u8>, Failure> {
+    let secret = if is_hex {
+	let digits : Vec<u8> = secret.into_iter().filter(|c| !c.is_ascii_whitespace()).collect();
+	hex::decode(&digits).map_err(|e| format!("secret is not valid hex: {}", e))?
+    } else {
+	secret
+    };
+    match security {
+	Some(bits) => Ok(pad_secret(&secret, bits)?),
+	None => Ok(secret),
+    }
+}
+
+// GF(2^16) and GF(2^32) split whole words, so a secret that doesn't
+// fill the last one is padded (see Params::with_word_padding), and
+// the shares say so. They have to go out in a format that can say
+// it too, or shamir-combine would give back the padding as part of
+// the secret.
+fn check_padding(share : &Share, output : &str, survives : impl FnOnce() -> bool) {
+    if share.padded && !survives() {
+	panic!("At {} bits this secret has to be padded to whole words, and {} shares can't \
+		say so: use --format v2 (or json or yaml), or -w 8", share.width, output)
+    }
+}
+
+// TLS private keys (--tls-key): split the key's DER and its kind,
+// rather than the PEM text, so shamir-combine can check it
+#[cfg(feature = "x509")]
+fn tls_secret(pem : &[u8]) -> Vec<u8> {
+    let key = guff_ssss::x509::TlsKey::from_pem(pem).unwrap_or_else(|e| panic!("{}", e));
+    common::note!("Splitting a TLS key ({}, {} bytes of DER)", key.kind.label(), key.der.len());
+    key.to_secret()
+}
+
+#[cfg(not(feature = "x509"))]
+fn tls_secret(_pem : &[u8]) -> Vec<u8> {
+    panic!("Built without the 'x509' feature; can't split TLS keys")
+}
+
+// Public verification record (--manifest FILE, --sign KEYFILE)
+//
+// The record commits to the secret as it was split, so after any
+// padding. It's given a set ID so that it can only ever match one
+// share set.
+fn write_manifest(path : &Path, params : &Params, secret : &[u8], shares : &[String],
+		  key : Option<&Path>, format : &str, hash : Algorithm) {
+    let mut manifest = Manifest::new_with_hash(params, secret, shares, hash)
+	.or_exit();
+    if let Some(key) = key {
+	let text = fs::read_to_string(key)
+	    .unwrap_or_else(|e| common::fail(e, format!("Can't read {}", key.display())));
+	let bytes : [u8; 32] = hex::decode(text.trim()).ok()
+	    .and_then(|b| b.try_into().ok())
+	    .unwrap_or_else(|| panic!("{}: expected a key of 64 hex digits", key.display()));
+	manifest.sign(&DealerKey::from_bytes(&bytes));
+    }
+    let text = match format {
+	#[cfg(feature = "yaml")]
+	"yaml" => manifest.to_yaml(),
+	_ => manifest.to_string(),
+    };
+    fs::write(path, text)
+	.unwrap_or_else(|e| common::fail(e, format!("Can't write {}", path.display())));
+    common::note!("Manifest written to {}", path.display());
+}
+
+// Binary share files (--binary DIR), one per share
+fn write_binary(dir : &Path, shares : &[Share], hash : Algorithm) -> Result<(), Failure> {
+    create_dir(dir)?;
+    let mut files = Vec::new();
+    for share in shares {
+	let name = match &share.set_id {
+	    Some(id) => format!("{}-share-{}.gssb", id, share.index),
+	    None => format!("share-{}.gssb", share.index),
+	};
+	files.push((share.index, dir.join(name), encode_binary_with(share, hash)));
+    }
+    write_share_files(files)
+}
+
+fn create_dir(dir : &Path) -> Result<(), Failure> {
+    fs::create_dir_all(dir).map_err(|e| Failure::about(e.into(), format!("Can't create {}", dir.display())))
+}
+
+// All at once, then say where each went
+fn write_share_files(files : Vec<(u64, PathBuf, Vec<u8>)>) -> Result<(), Failure> {
+    let (indices, files) : (Vec<u64>, Vec<(PathBuf, Vec<u8>)>) = files.into_iter()
+	.map(|(index, file, data)| (index, (file, data))).unzip();
+    common::write_files(&files)?;
+    for (index, (file, _)) in indices.iter().zip(&files) {
+	common::note!("Share {} -> {}", index, file.display());
+    }
+    Ok(())
+}
+
+// systemd credentials (--credstore NAME), one per share. Shares are
+// only kept together like this on their way to other machines'
+// stores, or where a machine is trusted with a quorum anyway.
+fn write_credentials(dir : &Path, name : &str, shares : &[Share], codec : &dyn ShareCodec) {
+    for share in shares {
+	let mut line = Vec::new();
+	codec.write_line(share, &mut line).unwrap_or_else(|e| panic!("Can't encode: {}", e));
+	let path = common::write_credential(dir, &format!("{}.{}", name, share.index), &line);
+	common::note!("Share {} -> {}", share.index, path.display());
+    }
+}
+
+// Password-manager imports (--vault FORMAT, --vault-dir DIR)
+//
+// One file per share, so that each custodian only ever gets (and
+// imports) their own.
+#[cfg(feature = "vault")]
+fn write_vault(dir : &Path, shares : &[Share], format : &str) -> Result<(), Failure> {
+    use guff_ssss::vault::{to_bitwarden_json, to_keepass_xml};
+    create_dir(dir)?;
+    let mut files = Vec::new();
+    for share in shares {
+	let (text, ext) = match format {
+	    "keepass" => (to_keepass_xml(std::slice::from_ref(share)), "xml"),
+	    _ => (to_bitwarden_json(std::slice::from_ref(share)), "json"),
+	};
+	let name = match &share.set_id {
+	    Some(id) => format!("{}-share-{}.{}", id, share.index, ext),
+	    None => format!("share-{}.{}", share.index, ext),
+	};
+	files.push((share.index, dir.join(name), text.into_bytes()));
+    }
+    write_share_files(files)
+}
+
+#[cfg(not(feature = "vault"))]
+fn write_vault(_dir : &Path, _shares : &[Share], _format : &str) -> Result<(), Failure> {
+    Err("Built without the 'vault' feature; can't write password-manager files".into())
+}
+
+// FROST key packages (--frost CURVE --frost-dir DIR)
+//
+// The secret must be a signing-key scalar for the curve. Key packages
+// are secret, one per participant; the public key package goes to
+// every participant (and anyone verifying signatures).
+#[cfg(feature = "frost")]
+fn write_frost(dir : &Path, curve : &str, scalar : &[u8], k : u16, n : u16) -> Result<(), Failure> {
+    use guff_ssss::frost::split_frost;
+    let split = split_frost(curve.parse()?, scalar, k, n)?;
+    create_dir(dir)?;
+    let public = dir.join("public-key-package.bin");
+    let files = split.key_packages.iter()
+	.map(|(index, bytes)| (dir.join(format!("key-package-{}.bin", index)), bytes))
+	.chain(std::iter::once((public, &split.public_key_package)));
+    for (file, bytes) in files {
+	common::write_owned(&file, bytes, 0o600, None)
+	    .map_err(|e| Failure::about(e.into(), format!("Can't write {}", file.display())))?;
+	common::note!("Wrote {}", file.display());
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "frost"))]
+fn write_frost(_dir : &Path, _curve : &str, _scalar : &[u8], _k : u16, _n : u16) -> Result<(), Failure> {
+    Err("Built without the 'frost' feature; can't write FROST key packages".into())
+}
+
+// Timelocked shares (--timelock WHEN, --drand URL)
+//
+// Every share is locked to the same drand round, so the secret can't
+// be recovered before then however many custodians get together.
+#[cfg(feature = "timelock")]
+fn write_timelocked(shares : &[String], when : &str, drand : Option<&str>) {
+    use guff_ssss::timelock::{Chain, LockedShare, QUICKNET};
+    let chain = Chain::fetch(drand.unwrap_or(QUICKNET)).unwrap_or_else(|e| panic!("{}", e));
+    let round = chain.parse_round(when).unwrap_or_else(|e| panic!("{}", e));
+    common::note!("Locking shares until round {} (unix time {})", round, chain.time_of(round));
+    for (i, share) in shares.iter().enumerate() {
+	let locked = LockedShare::lock(share, &chain, round).unwrap_or_else(|e| panic!("{}", e));
+	if i > 0 { println!() }
+	print!("{}", locked);
+    }
+}
+
+#[cfg(not(feature = "timelock"))]
+fn write_timelocked(_shares : &[String], _when : &str, _drand : Option<&str>) {
+    panic!("Built without the 'timelock' feature; can't timelock shares")
+}
+
+// A share sealed to this machine's TPM (--tpm FILE, --tpm-pcrs LIST)
+//
+// Share 1 stays with the machine; only the others are given out.
+#[cfg(feature = "tpm")]
+fn write_sealed(path : &Path, share : &str, pcrs : &str) {
+    use guff_ssss::tpm::{parse_pcrs, seal};
+    let pcrs = parse_pcrs(pcrs).unwrap_or_else(|e| panic!("{}", e));
+    let sealed = seal(share, &pcrs).unwrap_or_else(|e| panic!("{}", e));
+    common::write_owned(path, sealed.to_string().as_bytes(), 0o600, None)
+	.unwrap_or_else(|e| common::fail(e, format!("Can't write {}", path.display())));
+    common::note!("Sealed share 1 to this machine's TPM (PCRs {:?}) in {}", pcrs, path.display());
+}
+
+#[cfg(not(feature = "tpm"))]
+fn write_sealed(_path : &Path, _share : &str, _pcrs : &str) {
+    panic!("Built without the 'tpm' feature; can't seal a share to the TPM")
+}
+
+// A share wrapped under a YubiKey (--yubikey FILE, --yubikey-slot N)
+//
+// Taken after any --tpm share, so with both it's share 2.
+#[cfg(feature = "yubikey")]
+fn write_yubikey(path : &Path, share : &str, slot : &str) {
+    use guff_ssss::yubikey::{ykchalresp, WrappedShare};
+    let slot = slot.parse().unwrap_or_else(|_| panic!("YubiKey slot must be 1 or 2"));
+    common::note!("Asking the YubiKey (touch it if it flashes)");
+    let wrapped = WrappedShare::wrap(share, slot, ykchalresp).unwrap_or_else(|e| panic!("{}", e));
+    common::write_owned(path, wrapped.to_string().as_bytes(), 0o600, None)
+	.unwrap_or_else(|e| common::fail(e, format!("Can't write {}", path.display())));
+    let index = share.parse::<guff_ssss::Share>().map(|s| s.index).unwrap_or(0);
+    common::note!("Wrapped share {} under YubiKey slot {} in {}", index, slot, path.display());
+}
+
+#[cfg(not(feature = "yubikey"))]
+fn write_yubikey(_path : &Path, _share : &str, _slot : &str) {
+    panic!("Built without the 'yubikey' feature; can't wrap a share under a YubiKey")
+}
+
+// Shares for long secrets are easier to print or write down wrapped
+fn wrap_line(share : &str, wrap : Option<usize>) -> String {
+    match wrap {
+	Some(cols) => wrap_share(share, cols),
+	None => share.to_string(),
+    }
+}
+
+// What was written, for whoever is at the terminal to check the
+// shares against as they're handed out
+fn summary_rows(shares : &[Share], hash : Algorithm) -> Vec<common::ShareRow> {
+    shares.iter().map(|share| common::ShareRow {
+	index : share.index, set : share.set_id.clone(),
+	fingerprint : share_fingerprint_with(&share.to_string(), hash),
+	status : (common::Tone::Good, "written".to_string()),
+    }).collect()
+}
+
+// Sizes before splitting (--estimate)
+//
+// Shares numbered alike take the same space, so runs of them are
+// printed together:
+//
+//   Shares 1 to 9: 72 bytes each, QR version 5
+//   Shares 10 to 12: 73 bytes each, QR version 5
+//   Total: 867 bytes for 12 shares of a 32-byte secret
+fn print_estimate(params : &Params, size : usize, codec : &dyn ShareCodec) {
+    let estimate = estimate(params, size, codec).or_exit();
+    let most = if estimate.exact { "" } else { "at most " };
+    let mut runs : Vec<(u64, u64, &ShareSize)> = Vec::new();
+    for share in estimate.shares.iter() {
+	match runs.last_mut() {
+	    Some((_, last, size)) if (size.bytes, size.qr_version) == (share.bytes, share.qr_version) =>
+		*last = share.index,
+	    _ => runs.push((share.index, share.index, share)),
+	}
+    }
+    for (first, last, size) in runs {
+	let qr = match size.qr_version {
+	    Some(version) => format!("QR version {}", version),
+	    None => "too big for a QR code".to_string(),
+	};
+	if first == last {
+	    println!("Share {}: {}{} bytes, {}", first, most, size.bytes, qr);
+	} else {
+	    println!("Shares {} to {}: {}{} bytes each, {}", first, last, most, size.bytes, qr);
+	}
+    }
+    println!("Total: {}{} bytes for {} shares of a {}-byte secret",
+	     most, estimate.total(), estimate.shares.len(), size);
+}
+
+// Pair each custodian with their share, in the order the shares were
+// made, and with --name-others tell each of them who has the rest
+fn holders(shares : &[Share], custodians : Vec<Custodian>, name_others : bool)
+	   -> Vec<(u64, Custodian)> {
+    let mut holders : Vec<(u64, Custodian)> = shares.iter().map(|share| share.index)
+	.zip(custodians).collect();
+    if name_others {
+	let everyone : Vec<(u64, String)> = holders.iter()
+	    .map(|(index, custodian)| (*index, custodian.to_string())).collect();
+	for (index, custodian) in holders.iter_mut() {
+	    for (other, who) in everyone.iter().filter(|(other, _)| other != index) {
+		custodian.notes.push(format!("share {} is with {}", other, who));
+	    }
+	}
+    }
+    holders
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+
+    let registry = Registry::new();
+    let formats = registry.names();
+    let hashes : Vec<&str> = Algorithm::ALL.iter().map(|hash| hash.name()).collect();
+    let matches = App::new("shamir-split")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Shamir's Secret Sharing Scheme")
+	.usage("echo secret | shamir-split -t K -n N")
+	.arg(Arg::with_name("threshold")
+	     .short("t")
+	     .long("threshold")
+	     .value_name("K")
+	     .required(true)
+	     .help("Number of shares needed to recover the secret"))
+	.arg(Arg::with_name("shares")
+	     .short("n")
+	     .long("shares")
+	     .value_name("N")
+	     .required_unless("participants")
+	     .conflicts_with("participants")
+	     .help("Number of shares to create"))
+	.arg(Arg::with_name("participants")
+	     .long("participants")
+	     .value_name("NAMES")
+	     .conflicts_with_all(&["batch", "watch", "perl", "frost", "tpm", "yubikey"])
+	     .help("Make a share for each of these comma-separated names, numbered by a hash \
+		    of the name, so anyone with the list can tell whose a share is"))
+	.arg(Arg::with_name("custodian")
+	     .long("custodian")
+	     .value_name("WHO")
+	     .multiple(true)
+	     .number_of_values(1)
+	     .conflicts_with_all(&["batch", "watch", "binary", "vault", "credstore", "frost",
+				   "timelock", "tpm", "yubikey", "perl", "template"])
+	     .help("Who holds each share, as NAME or \"NAME <CONTACT>\": give one per share, \
+		    in share order (or --participants order), to write above the share as a \
+		    comment"))
+	.arg(Arg::with_name("note")
+	     .long("note")
+	     .value_name("TEXT")
+	     .multiple(true)
+	     .number_of_values(1)
+	     .requires("custodian")
+	     .help("Add a note to every share's custodian comment (eg, who to call to recover)"))
+	.arg(Arg::with_name("name-others")
+	     .long("name-others")
+	     .requires("custodian")
+	     .help("Note on every share who holds each of the others, so whoever finds one \
+		    knows who else to call"))
+	.arg(Arg::with_name("allow-trivial")
+	     .long("allow-trivial")
+	     .help("Allow a threshold of 1, where every share is a copy of the secret"))
+	.arg(Arg::with_name("width")
+	     .short("w")
+	     .long("width")
+	     .value_name("BITS")
+	     .default_value("8")
+	     .possible_values(&["4", "8", "16", "32"])
+	     .help("Field width: work in GF(2^BITS), which allows up to 2^(BITS-1) shares"))
+	.arg(Arg::with_name("input")
+	     .short("i")
+	     .long("input")
+	     .value_name("FILE")
+	     .conflicts_with_all(&["batch", "secret-fd"])
+	     .help("Read the secret from FILE instead of stdin"))
+	.arg(Arg::with_name("pack")
+	     .long("pack")
+	     .value_name("PATH")
+	     .multiple(true)
+	     .conflicts_with_all(&["batch", "input", "secret-fd", "hex"])
+	     .help("Split these files and directories (with names, permissions and \
+		    times) as one secret; restore with shamir-combine --unpack DIR"))
+	.arg(Arg::with_name("secret-fd")
+	     .long("secret-fd")
+	     .value_name("N")
+	     .conflicts_with("batch")
+	     .help("Read the secret from inherited file descriptor N instead of stdin"))
+	.arg(Arg::with_name("hex")
+	     .short("x")
+	     .long("hex")
+	     .help("The secret is given in hex, and the bytes it stands for are split"))
+	.arg(Arg::with_name("security")
+	     .short("s")
+	     .long("security")
+	     .value_name("BITS")
+	     .help("Pad the secret to BITS bits so the shares don't give away its length \
+		    (recover with shamir-combine --padded)"))
+	.arg(Arg::with_name("batch")
+	     .long("batch")
+	     .value_name("PATH")
+	     .help("Split every file in directory PATH, or every line of file PATH (- for stdin)"))
+	.arg(Arg::with_name("delimiter")
+	     .long("delimiter")
+	     .value_name("TEXT")
+	     .requires("batch")
+	     .conflicts_with("records")
+	     .help("Secrets in the --batch file are separated by TEXT rather than by newlines \
+		    (\\n, \\r, \\t, \\0 and \\\\ are escapes)"))
+	.arg(Arg::with_name("records")
+	     .long("records")
+	     .requires("batch")
+	     .help("Secrets in the --batch file are records: a 4-byte big-endian length, \
+		    then that many bytes"))
+	.arg(Arg::with_name("output-dir")
+	     .long("output-dir")
+	     .value_name("DIR")
+	     .requires("batch")
+	     .help("Write each batch share set to DIR/<name>.shares"))
+	.arg(Arg::with_name("watch")
+	     .long("watch")
+	     .value_name("FILE")
+	     .requires("destination")
+	     .conflicts_with_all(&["batch", "input", "secret-fd", "pack", "binary", "vault", "frost",
+				   "timelock", "tpm", "yubikey", "tls-key", "perl", "shuffle"])
+	     .help("Split FILE, then split it again under a new set ID whenever it changes \
+		    (runs until stopped)"))
+	.arg(Arg::with_name("destination")
+	     .long("destination")
+	     .value_name("PATH")
+	     .multiple(true)
+	     .number_of_values(1)
+	     .requires("watch")
+	     .help("Where --watch writes a share: give one per share, for shares 1 to N in turn"))
+	.arg(Arg::with_name("self-check")
+	     .long("self-check")
+	     .value_name("SUBSETS")
+	     .min_values(0)
+	     .max_values(1)
+	     .conflicts_with("frost")
+	     .help("Before writing anything, combine up to SUBSETS (default 20) different \
+		    quorums of the new shares and check each gives back the secret"))
+	.arg(Arg::with_name("estimate")
+	     .long("estimate")
+	     .value_name("BYTES")
+	     .min_values(0)
+	     .max_values(1)
+	     .conflicts_with_all(&["batch", "watch", "binary", "vault", "credstore", "frost",
+				   "timelock", "tpm", "yubikey", "perl", "wrap", "custodian",
+				   "participants", "manifest", "self-check"])
+	     .help("Don't split: print how big each share will be, in all and the QR code \
+		    version it needs. BYTES is the secret's size; without it, the secret is \
+		    read to find out"))
+	.arg(Arg::with_name("shuffle")
+	     .long("shuffle")
+	     .help("Print the shares in random order rather than by share number"))
+	.arg(Arg::with_name("manifest")
+	     .long("manifest")
+	     .value_name("FILE")
+	     .conflicts_with("batch")
+	     .help("Also write a record of the split to FILE, for shamir-combine --manifest \
+		    to check shares and the secret against. It holds no share data, but a \
+		    guessable secret (eg, a password) can be tested against it"))
+	.arg(Arg::with_name("hash")
+	     .long("hash")
+	     .value_name("ALGORITHM")
+	     .possible_values(&hashes)
+	     .help("Hash for armor and v2 checks, binary checksums, the manifest and fingerprints \
+		    (default: sha-256; blake3 needs the 'blake3' feature). It's recorded with \
+		    them, so shamir-combine needn't be told"))
+	.arg(Arg::with_name("sign")
+	     .long("sign")
+	     .value_name("KEYFILE")
+	     .requires("manifest")
+	     .help("Sign the manifest with the Ed25519 key in KEYFILE (64 hex digits)"))
+	.arg(Arg::with_name("format")
+	     .long("format")
+	     .value_name("NAME")
+	     .possible_values(&formats)
+	     .default_value("v2")
+	     .help("Share format (the manifest is written in the same format). v2 lines \
+		    carry a version, the field polynomial and a checksum; 'text' is the \
+		    bare K=W=S=Values= format of earlier versions"))
+	.arg(Arg::with_name("binary")
+	     .long("binary")
+	     .value_name("DIR")
+	     .conflicts_with_all(&["batch", "format", "template", "wrap"])
+	     .help("Write each share to its own compact binary file in DIR instead of printing it"))
+	.arg(Arg::with_name("vault")
+	     .long("vault")
+	     .value_name("FORMAT")
+	     .possible_values(&["keepass", "bitwarden"])
+	     .requires("vault-dir")
+	     .conflicts_with_all(&["batch", "binary", "format", "template", "wrap", "perl"])
+	     .help("Write each share as a KeePass XML or Bitwarden JSON file to import \
+		    (needs the 'vault' feature)"))
+	.arg(Arg::with_name("vault-dir")
+	     .long("vault-dir")
+	     .value_name("DIR")
+	     .requires("vault")
+	     .help("Where --vault writes its files"))
+	.arg(Arg::with_name("credstore")
+	     .long("credstore")
+	     .value_name("NAME")
+	     .conflicts_with_all(&["batch", "binary", "vault", "frost", "timelock", "watch", "perl"])
+	     .help("Store the shares as systemd credentials NAME.1, NAME.2, ... instead of \
+		    printing them, for a service's ImportCredential=NAME.* (and \
+		    shamir-combine --credentials NAME)"))
+	.arg(Arg::with_name("credstore-dir")
+	     .long("credstore-dir")
+	     .value_name("DIR")
+	     .requires("credstore")
+	     .help("Credential store for --credstore (default: /run/credstore)"))
+	.arg(Arg::with_name("frost")
+	     .long("frost")
+	     .value_name("CURVE")
+	     .possible_values(&["ed25519", "secp256k1"])
+	     .requires("frost-dir")
+	     .conflicts_with_all(&["batch", "binary", "vault", "format", "template", "wrap",
+				   "perl", "manifest", "security", "pack", "shuffle"])
+	     .help("The secret is a signing-key scalar for CURVE: split it into FROST key \
+		    packages instead of shares (needs the 'frost' feature)"))
+	.arg(Arg::with_name("frost-dir")
+	     .long("frost-dir")
+	     .value_name("DIR")
+	     .requires("frost")
+	     .help("Where --frost writes the key packages"))
+	.arg(Arg::with_name("timelock")
+	     .long("timelock")
+	     .value_name("WHEN")
+	     .conflicts_with_all(&["batch", "binary", "vault", "frost", "format", "template",
+				   "wrap", "perl"])
+	     .help("Lock every share until WHEN: a drand round, @SECONDS since the epoch or \
+		    +DURATION (eg, +30d) (needs the 'timelock' feature)"))
+	.arg(Arg::with_name("drand")
+	     .long("drand")
+	     .value_name("URL")
+	     .requires("timelock")
+	     .help("drand beacon for --timelock (default: quicknet at api.drand.sh)"))
+	.arg(Arg::with_name("tpm")
+	     .long("tpm")
+	     .value_name("FILE")
+	     .conflicts_with_all(&["batch", "frost", "perl"])
+	     .help("Seal share 1 to this machine's TPM, in FILE, and print only the others \
+		    (needs the 'tpm' feature)"))
+	.arg(Arg::with_name("tpm-pcrs")
+	     .long("tpm-pcrs")
+	     .value_name("LIST")
+	     .requires("tpm")
+	     .help("PCRs the sealed share depends on (default: 0,7)"))
+	.arg(Arg::with_name("yubikey")
+	     .long("yubikey")
+	     .value_name("FILE")
+	     .conflicts_with_all(&["batch", "frost", "perl"])
+	     .help("Wrap a share under a YubiKey's challenge-response, in FILE, and print \
+		    only the others (needs the 'yubikey' feature and ykchalresp)"))
+	.arg(Arg::with_name("yubikey-slot")
+	     .long("yubikey-slot")
+	     .value_name("N")
+	     .requires("yubikey")
+	     .help("YubiKey challenge-response slot (default: 2)"))
+	.arg(Arg::with_name("tls-key")
+	     .long("tls-key")
+	     .conflicts_with_all(&["batch", "pack", "hex", "frost", "perl"])
+	     .help("The secret is a PEM private key for TLS (PKCS#1, PKCS#8 or EC); \
+		    shamir-combine gives it back as PEM (needs the 'x509' feature)"))
+	.arg(Arg::with_name("template")
+	     .long("template")
+	     .value_name("TEMPLATE")
+	     .conflicts_with("format")
+	     .help("Print each share through TEMPLATE, eg \"{{label}}-{{index}}: {{data}}\". \
+		    Fields: label (set ID), quorum, width, index, data, share"))
+	.arg(Arg::with_name("perl")
+	     .long("perl")
+	     .conflicts_with_all(&["batch", "binary", "format", "manifest", "security", "template"])
+	     .help("Write shares the way the Perl shamir-split in Math::FastGF2 does"))
+	.arg(Arg::with_name("quiet")
+	     .short("q")
+	     .long("quiet")
+	     .help("Print only the shares, and errors as a single line"))
+	.arg(Arg::with_name("wrap")
+	     .long("wrap")
+	     .value_name("COLS")
+	     .help("Wrap shares at COLS columns, ending each broken line with '\\'"))
+	.arg(Arg::with_name("no-color")
+	     .long("no-color")
+	     .help("Don't colour the summaries on the terminal (also NO_COLOR=1)"))
+	.get_matches();
+
+    if matches.is_present("quiet") { common::set_quiet() }
+    common::set_color(matches.is_present("no-color"));
+    common::binary_stdio();
+    let k : u16 = matches.value_of("threshold").unwrap().parse()
+	.unwrap_or_else(|_| panic!("threshold must be a number"));
+    let names : Option<Vec<&str>> = matches.value_of("participants")
+	.map(|list| list.split(',').collect());
+    let n : u16 = match &names {
+	Some(names) => names.len().try_into().unwrap_or(u16::MAX),
+	None => matches.value_of("shares").unwrap().parse()
+	    .unwrap_or_else(|_| panic!("number of shares must be a number")),
+    };
+    let w : u16 = matches.value_of("width").unwrap().parse().unwrap();
+    // Catch bad parameters before waiting for the secret
+    let mut params = Params::new(k, n, w);
+    if matches.is_present("manifest") { params = params.with_set_id(&new_set_id()) }
+    // The Perl scripts pad with NULs of their own (see below)
+    if !matches.is_present("perl") { params = params.with_word_padding() }
+    params.check()?;
+    params.check_sane(matches.is_present("allow-trivial")).map_err(|e| {
+	Error::FieldError(format!("{} (or use --allow-trivial to split anyway)", e))
+    })?;
+    let indices = names.as_ref().map(|names| participants::indices(names, w)).transpose()?;
+    let security : Option<usize> = matches.value_of("security").map(|s| s.parse()
+	.unwrap_or_else(|_| panic!("security level must be a number of bits")));
+    let is_hex = matches.is_present("hex");
+    let shuffle = matches.is_present("shuffle");
+    let format = matches.value_of("format").unwrap();
+    let template = matches.value_of("template").map(|t| {
+	let template = TemplateCodec::new(t).or_exit();
+	if !template.can_decode() {
+	    common::note!("{} shamir-combine can't read shares back through this \
+			   template; it needs {{{{share}}}}, or {{{{quorum}}}}, {{{{width}}}}, \
+			   {{{{index}}}} and {{{{data}}}}", common::paint(common::Tone::Warn, "Warning:"));
+	}
+	template
+    });
+    let hash : Algorithm = matches.value_of("hash").map(|name| name.parse().unwrap())
+	.unwrap_or_default();
+    let (hashed_armor, hashed_v2) = (HashedArmorCodec(hash), HashedV2Codec(hash));
+    let codec : &dyn ShareCodec = match &template {
+	Some(template) => template,
+	None if format == "armor" => &hashed_armor,
+	None if format == "v2" => &hashed_v2,
+	None => registry.get(format).unwrap(),
+    };
+    let custodians : Option<Vec<Custodian>> = matches.values_of("custodian").map(|specs| {
+	let notes : Vec<&str> = matches.values_of("note").into_iter().flatten().collect();
+	specs.map(|spec| Custodian::parse(spec)
+		  .and_then(|c| notes.iter().try_fold(c, |c, note| c.with_note(note)))
+		  .or_exit()).collect()
+    });
+    if let Some(custodians) = &custodians {
+	if custodians.len() != n as usize {
+	    panic!("--custodian needs to be given once for each of the {} shares (got {})",
+		   n, custodians.len())
+	}
+	if codec.name() == "json" {
+	    panic!("JSON has no comments to name custodians in: choose another --format")
+	}
+    }
+    let wrap : Option<usize> = matches.value_of("wrap").map(|w| w.parse()
+	.unwrap_or_else(|_| panic!("wrap width must be a number")));
+    let checks : Option<usize> = matches.is_present("self-check").then(|| {
+	matches.value_of("self-check").unwrap_or("20").parse()
+	    .unwrap_or_else(|_| panic!("--self-check expects a number of subsets"))
+    });
+
+    if let Some(bytes) = matches.value_of("estimate") {
+	let bytes : usize = bytes.parse()
+	    .unwrap_or_else(|_| panic!("--estimate expects the secret's size in bytes"));
+	let size = match security {
+	    Some(bits) => padded_len(bytes, bits)?,
+	    None => bytes,
+	};
+	print_estimate(&params, size, codec);
+	return Ok(())
+    }
+
+    let survives = |share : &Share| codec.decode(&codec.encode(share)).ok().as_ref() == Some(share);
+    let encode = |share : &Share| -> Vec<u8> {
+	check_padding(share, codec.name(), || survives(share));
+	match wrap {
+	    Some(_) => format!("{}\n", wrap_line(codec.encode(share).trim_end(), wrap)).into_bytes(),
+	    None => {
+		let mut line = Vec::new();
+		codec.write_line(share, &mut line).unwrap_or_else(|e| panic!("Can't encode: {}", e));
+		line
+	    },
+	}
+    };
+
+    if let Some(path) = matches.value_of("batch") {
+	if codec.name() == "json" {
+	    panic!("JSON has no comments to name the batch secrets in: choose another --format")
+	}
+	let separator = match matches.value_of("delimiter") {
+	    Some(text) => Separator::Delimiter(delimiter(text)),
+	    None if matches.is_present("records") => Separator::Records,
+	    None => Separator::Lines,
+	};
+	split_batch(batch_secrets(Path::new(path), &separator), &params, |s| prepare(s, is_hex, security),
+		    encode, matches.value_of("output-dir").map(Path::new), shuffle, checks);
+	return Ok(())
+    }
+
+    if let Some(path) = matches.value_of("watch") {
+	let destinations : Vec<PathBuf> = matches.values_of("destination").unwrap()
+	    .map(PathBuf::from).collect();
+	if destinations.len() != n as usize {
+	    panic!("--watch needs a --destination for each of the {} shares (got {})",
+		   n, destinations.len())
+	}
+	let manifest = matches.value_of("manifest")
+	    .map(|file| (Path::new(file), matches.value_of("sign").map(Path::new), format, hash));
+	watch_and_split(Path::new(path), &params, |s| prepare(s, is_hex, security),
+			&destinations, encode, manifest, checks);
+    }
 
+    let secret = match (matches.value_of("input"), matches.value_of("secret-fd")) {
+	_ if matches.is_present("pack") => {
+	    let paths : Vec<&Path> = matches.values_of("pack").unwrap().map(Path::new).collect();
+	    archive::pack(&paths).unwrap_or_else(|e| panic!("{}", e))
+	},
+	(Some(file), _) => common::read_file(Path::new(file))
+	    .map_err(|e| Failure::about(e.into(), "Can't read the secret"))?,
+	(None, Some(fd)) => read_fd(fd.parse()
+				    .unwrap_or_else(|_| panic!("--secret-fd expects a number"))),
+	(None, None) => read_stdin(),
+    };
+    if secret.is_empty() {
+	return Err(Error::FieldError("The secret is empty: there's nothing to split".into()).into())
+    }
+    let secret = if matches.is_present("tls-key") { tls_secret(&secret) } else { secret };
+    let secret = prepare(secret, is_hex, security)?;
+    if matches.is_present("estimate") {
+	print_estimate(&params, secret.len(), codec);
+	return Ok(())
+    }
+    if let Some(curve) = matches.value_of("frost") {
+	return write_frost(Path::new(matches.value_of("frost-dir").unwrap()), curve, &secret, k, n)
+    }
+    let perl = matches.is_present("perl");
+    let secret = if perl { pad_words(&secret, w) } else { secret };
 
+    let bar = common::progress_bar(secret.len());
+    let mut shares = match &indices {
+	Some(indices) => split_shares_at(&secret, &params, indices, common::progress_callback(&bar)),
+	None => split_shares(&secret, &params, common::progress_callback(&bar)),
+    }?;
+    if let (Some(names), Some(indices)) = (&names, &indices) {
+	for (name, index) in names.iter().zip(indices) {
+	    common::note!("Share {} is for {}", index, name.trim());
+	}
+    }
+    if let Some(bar) = bar { bar.finish_and_clear() }
+    let other = ["binary", "vault", "timelock", "tpm", "yubikey"].iter().find(|o| matches.is_present(o));
+    match other {
+	Some(other) => check_padding(&shares[0], other, || false),
+	None => check_padding(&shares[0], codec.name(), || survives(&shares[0])),
+    }
+    let holders = custodians
+	.map(|custodians| holders(&shares, custodians, matches.is_present("name-others")))
+	.unwrap_or_default();
+    self_check(&secret, &shares, checks);
+    // Shares are only made into text where they have to be, since
+    // the text of each is twice the size of the secret
+    let text = |shares : &[Share]| -> Vec<String> { shares.iter().map(Share::to_string).collect() };
+    if let Some(path) = matches.value_of("manifest") {
+	write_manifest(Path::new(path), &params, &secret, &text(&shares),
+		       matches.value_of("sign").map(Path::new), format, hash);
+    }
+    if let Some(path) = matches.value_of("tpm") {
+	let share = shares.remove(0).to_string();
+	write_sealed(Path::new(path), &share, matches.value_of("tpm-pcrs").unwrap_or("0,7"));
+    }
+    if let Some(path) = matches.value_of("yubikey") {
+	let share = shares.remove(0).to_string();
+	write_yubikey(Path::new(path), &share, matches.value_of("yubikey-slot").unwrap_or("2"));
+    }
+    // Printed in order, the first share on a sheet is always number 1
+    if shuffle { shares.shuffle(&mut rand::thread_rng()) }
+    if let Some(dir) = matches.value_of("binary") {
+	return write_binary(Path::new(dir), &shares, hash)
+    }
+    if let Some(name) = matches.value_of("credstore") {
+	let dir = Path::new(matches.value_of("credstore-dir").unwrap_or(common::CREDSTORE));
+	write_credentials(dir, name, &shares, codec);
+	return Ok(())
+    }
+    if let Some(format) = matches.value_of("vault") {
+	return write_vault(Path::new(matches.value_of("vault-dir").unwrap()), &shares, format)
+    }
+    if let Some(when) = matches.value_of("timelock") {
+	write_timelocked(&text(&shares), when, matches.value_of("drand"));
+	return Ok(())
+    }
+    let summary = common::on_terminal().then(|| summary_rows(&shares, hash));
+    // Each share goes out as it's encoded, a piece at a time, unless
+    // it has to be wrapped
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for share in shares {
+	if let Some((_, custodian)) = holders.iter().find(|(index, _)| *index == share.index) {
+	    stdout.write_all(custodian.comments().as_bytes())
+		.unwrap_or_else(|e| panic!("Can't write the shares: {}", e));
+	}
+	let written = if perl || wrap.is_some() {
+	    let line = if perl { to_perl_share(&share) } else { codec.encode(&share) };
+	    writeln!(stdout, "{}", wrap_line(line.trim_end(), wrap))
+	} else {
+	    codec.write_line(&share, &mut stdout)
+	};
+	written.map_err(|e| Failure::about(e.into(), "Can't write the shares"))?;
+    }
+    drop(stdout);
+    if let Some(rows) = summary {
+	common::share_table(&rows);
+	common::note!("Any {} of these {} shares recover the secret", k, rows.len());
+    }
+    Ok(())
 }
@@ -0,0 +1,100 @@
+// Check one share against the dealer's record of the split
+//
+// A custodian handed a share, and the manifest written with
+// shamir-split --manifest, can check on their own, without the other
+// custodians and without a network connection, that the share is
+// the one the dealer recorded:
+//
+//   shamir-verify --manifest set.manifest [--dealer-key KEY] my-share.txt
+//
+// It prints whether the share is a valid member of the set and exits
+// with status 1 if not. With --dealer-key, the manifest must also
+// carry that key's signature; without it, the signing key (if any)
+// is printed for the custodian to compare against one they trust.
+
+extern crate clap;
+use clap::{App, Arg};
+use guff_ssss::binary::{decode_binary, is_binary_share};
+use guff_ssss::codec::Registry;
+use guff_ssss::{join_continuations, Share};
+use std::io::{self, Read};
+use std::path::Path;
+
+mod common;
+use common::{paint_out, Failure, Tone};
+
+// A share in any of the formats shamir-split writes: binary, a line
+// in one of the registered codecs (maybe wrapped), or a multi-line
+// encoding such as armor
+fn read_share(bytes : Vec<u8>) -> Result<Share, Failure> {
+    if is_binary_share(&bytes) {
+	return Ok(decode_binary(&bytes)?)
+    }
+    let text = String::from_utf8(bytes).map_err(|_| "not a text or binary share")?;
+    let lines : Vec<String> = join_continuations(text.lines()).into_iter()
+	.filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+	.collect();
+    let registry = Registry::new();
+    match lines.as_slice() {
+	[line] => registry.decode(line.trim()),
+	_ => registry.decode(&text),
+    }.map_err(Failure::from)
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+
+    let matches = App::new("shamir-verify")
+	.version("1.0")
+	.author("Declan Malone <idablack@users.sourceforge.net>")
+	.about("Check a share against the manifest of its set, offline")
+	.arg(Arg::with_name("manifest")
+	     .long("manifest")
+	     .value_name("FILE")
+	     .required(true)
+	     .help("The record written by shamir-split --manifest"))
+	.arg(Arg::with_name("dealer-key")
+	     .long("dealer-key")
+	     .value_name("KEY")
+	     .help("Require the manifest to be signed by this public key"))
+	.arg(Arg::with_name("quiet")
+	     .short("q")
+	     .long("quiet")
+	     .help("Say nothing; only the exit status tells"))
+	.arg(Arg::with_name("no-color")
+	     .long("no-color")
+	     .help("Don't colour the verdict on the terminal (also NO_COLOR=1)"))
+	.arg(Arg::with_name("SHARE")
+	     .help("File holding the share (default: stdin)"))
+	.get_matches();
+
+    if matches.is_present("quiet") { common::set_quiet() }
+    common::set_color(matches.is_present("no-color"));
+    let manifest = common::read_manifest(Path::new(matches.value_of("manifest").unwrap()),
+					 matches.value_of("dealer-key"))?;
+    let bytes = match matches.value_of("SHARE") {
+	Some(path) if path != "-" => common::read_file(Path::new(path))
+	    .map_err(|e| Failure::about(e.into(), "Can't read the share"))?,
+	_ => {
+	    let mut bytes = Vec::new();
+	    io::stdin().read_to_end(&mut bytes).map_err(|e| Failure::about(e.into(), "stdin"))?;
+	    bytes
+	},
+    };
+    let share = read_share(bytes).map_err(|e| e.within("Can't read the share"))?;
+
+    let set = manifest.set_id.as_deref().unwrap_or("(no set ID)");
+    let checked = manifest.check_share(&share);
+    if !common::quiet() {
+	match &checked {
+	    Ok(()) => println!("Share {} {} of set {} (one of {} shares, \
+				any {} of which recover the secret)", share.index,
+			       paint_out(Tone::Good, "is a valid member"), set, manifest.shares,
+			       manifest.quorum),
+	    Err(e) => println!("Share {} {} of set {}: {}", share.index,
+			       paint_out(Tone::Bad, "is not a valid member"), set, e),
+	}
+    }
+    Ok(checked?)
+}
@@ -0,0 +1,262 @@
+// Interactive terminal wizard for Shamir's Secret Sharing Scheme
+//
+// Walks a non-expert through either entering shares to recover a
+// secret, or splitting a new secret. While a share is being typed it
+// is checked on every keystroke (field count, hex digits, and
+// agreement with the shares already entered), so a transcription
+// mistake shows up straight away rather than as a failed combine.
+
+use guff_ssss::{Decoder, Params, combine_decoder, share_number, split};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+mod common;
+use common::Failure;
+
+enum Screen { Menu, Combine, Split, Done }
+
+// Which split input has the cursor
+#[derive(Clone, Copy, PartialEq)]
+enum SplitField { Secret, Quorum, Shares }
+
+struct Wizard {
+    screen   : Screen,
+    input    : String,		// share being typed / split secret
+    decoder  : Decoder,
+    accepted : Vec<String>,	// share numbers entered so far
+    message  : String,
+    secret   : Option<Vec<u8>>,
+    revealed : bool,
+    field    : SplitField,
+    quorum   : String,
+    shares   : String,
+    output   : Vec<String>,	// shares produced by split
+}
+
+impl Wizard {
+    fn new() -> Wizard {
+	Wizard {
+	    screen : Screen::Menu, input : String::new(),
+	    decoder : Decoder::new(), accepted : Vec::new(),
+	    message : String::new(), secret : None, revealed : false,
+	    field : SplitField::Secret,
+	    quorum : String::new(), shares : String::new(),
+	    output : Vec::new(),
+	}
+    }
+
+    // Live check of the share being typed, without committing it
+    fn check_input(&self) -> Result<(), String> {
+	if self.input.trim().is_empty() { return Ok(()) }
+	self.decoder.clone().add_share(&self.input).map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    fn enter_share(&mut self) {
+	let line = self.input.trim().to_string();
+	match self.decoder.add_share(&line) {
+	    Ok(true) => {
+		let s = share_number(&line).unwrap_or("?").to_string();
+		self.message = format!("Share {} accepted", s);
+		self.accepted.push(s);
+		self.input.clear();
+		if self.decoder.is_complete() {
+		    match combine_decoder(&mut self.decoder, false) {
+			Ok(secret) => {
+			    self.secret = Some(secret);
+			    self.screen = Screen::Done;
+			},
+			Err(e) => self.message = e.to_string(),
+		    }
+		}
+	    },
+	    Ok(false) => self.message = "Already have a share with that number".into(),
+	    Err(e) => self.message = e.to_string(),
+	}
+    }
+
+    fn do_split(&mut self) {
+	let k = self.quorum.parse::<u16>();
+	let n = self.shares.parse::<u16>();
+	let (k, n) = match (k, n) {
+	    (Ok(k), Ok(n)) => (k, n),
+	    _ => { self.message = "Quorum and share count must be numbers".into(); return }
+	};
+	if self.input.is_empty() {
+	    self.message = "Enter a secret first".into();
+	    return
+	}
+	if let Err(e) = Params::new(k, n, 8).check_sane(false) {
+	    self.message = e.to_string();
+	    return
+	}
+	match split(self.input.as_bytes(), k, n, 8) {
+	    Ok(shares) => {
+		self.output = shares.iter().map(|share| share.to_string()).collect();
+		self.input.clear();
+		self.message = "Write each share down and give it to a different custodian".into();
+		self.screen = Screen::Done;
+	    },
+	    Err(e) => self.message = e.to_string(),
+	}
+    }
+
+    // Returns false when the user wants out
+    fn key(&mut self, code : KeyCode) -> bool {
+	match self.screen {
+	    Screen::Menu => match code {
+		KeyCode::Char('c') => self.screen = Screen::Combine,
+		KeyCode::Char('s') => self.screen = Screen::Split,
+		KeyCode::Char('q') | KeyCode::Esc => return false,
+		_ => {},
+	    },
+	    Screen::Combine => match code {
+		KeyCode::Esc => return false,
+		KeyCode::Enter => self.enter_share(),
+		KeyCode::Backspace => { self.input.pop(); },
+		KeyCode::Char(c) => self.input.push(c),
+		_ => {},
+	    },
+	    Screen::Split => {
+		let target = match self.field {
+		    SplitField::Secret => &mut self.input,
+		    SplitField::Quorum => &mut self.quorum,
+		    SplitField::Shares => &mut self.shares,
+		};
+		match code {
+		    KeyCode::Esc => return false,
+		    KeyCode::Tab | KeyCode::Down => {
+			self.field = match self.field {
+			    SplitField::Secret => SplitField::Quorum,
+			    SplitField::Quorum => SplitField::Shares,
+			    SplitField::Shares => SplitField::Secret,
+			}
+		    },
+		    KeyCode::Enter => self.do_split(),
+		    KeyCode::Backspace => { target.pop(); },
+		    KeyCode::Char(c) => target.push(c),
+		    _ => {},
+		}
+	    },
+	    Screen::Done => match code {
+		KeyCode::Char('r') => self.revealed = !self.revealed,
+		KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => return false,
+		_ => {},
+	    },
+	}
+	true
+    }
+
+    fn draw(&self, frame : &mut Frame) {
+	let [title, body, status] = Layout::vertical([
+	    Constraint::Length(3), Constraint::Min(5), Constraint::Length(3),
+	]).areas(frame.area());
+	frame.render_widget(
+	    Paragraph::new("Shamir's Secret Sharing Scheme")
+		.block(Block::default().borders(Borders::ALL)), title);
+	frame.render_widget(
+	    Paragraph::new(self.message.as_str())
+		.block(Block::default().borders(Borders::ALL).title("Status")), status);
+
+	match self.screen {
+	    Screen::Menu => {
+		frame.render_widget(
+		    Paragraph::new("[c] Combine shares to recover a secret\n\
+				    [s] Split a secret into shares\n\
+				    [q] Quit")
+			.block(Block::default().borders(Borders::ALL).title("What would you like to do?")),
+		    body);
+	    },
+	    Screen::Combine => {
+		let [gauge, input, list] = Layout::vertical([
+		    Constraint::Length(3), Constraint::Length(3), Constraint::Min(3),
+		]).areas(body);
+		let (have, k) = (self.decoder.have(), self.decoder.quorum);
+		let (ratio, label) = if k == 0 {
+		    (0.0, "enter the first share to learn the quorum".to_string())
+		} else {
+		    (have as f64 / k as f64, format!("{} of {} shares", have, k))
+		};
+		frame.render_widget(
+		    Gauge::default().ratio(ratio).label(label)
+			.block(Block::default().borders(Borders::ALL).title("Progress")), gauge);
+		let (colour, verdict) = match self.check_input() {
+		    Ok(()) => (Color::Green, "looks valid".to_string()),
+		    Err(e) => (Color::Red, e),
+		};
+		frame.render_widget(
+		    Paragraph::new(self.input.as_str())
+			.style(Style::default().fg(colour))
+			.block(Block::default().borders(Borders::ALL)
+			       .title(format!("Type or paste a share, then Enter ({})", verdict))),
+		    input);
+		let items : Vec<ListItem> = self.accepted.iter()
+		    .map(|s| ListItem::new(format!("share {}", s))).collect();
+		frame.render_widget(
+		    List::new(items).block(Block::default().borders(Borders::ALL).title("Accepted")),
+		    list);
+	    },
+	    Screen::Split => {
+		let [secret, quorum, shares] = Layout::vertical([
+		    Constraint::Length(3), Constraint::Length(3), Constraint::Length(3),
+		]).areas(body);
+		let style = |f| if self.field == f {
+		    Style::default().fg(Color::Yellow)
+		} else {
+		    Style::default()
+		};
+		let masked = "*".repeat(self.input.chars().count());
+		frame.render_widget(
+		    Paragraph::new(masked).style(style(SplitField::Secret))
+			.block(Block::default().borders(Borders::ALL).title("Secret")), secret);
+		frame.render_widget(
+		    Paragraph::new(self.quorum.as_str()).style(style(SplitField::Quorum))
+			.block(Block::default().borders(Borders::ALL)
+			       .title("Shares needed to recover (k)")), quorum);
+		frame.render_widget(
+		    Paragraph::new(self.shares.as_str()).style(style(SplitField::Shares))
+			.block(Block::default().borders(Borders::ALL)
+			       .title("Shares to create (n) -- Tab moves, Enter splits")), shares);
+	    },
+	    Screen::Done => {
+		let text = match &self.secret {
+		    Some(secret) if self.revealed =>
+			format!("{}\n\n[r] hide  [q] quit", String::from_utf8_lossy(secret)),
+		    Some(_) => "Secret recovered.\n\n[r] reveal  [q] quit".to_string(),
+		    None => format!("{}\n\n[q] quit", self.output.join("\n")),
+		};
+		frame.render_widget(
+		    Paragraph::new(text).wrap(Wrap { trim : false })
+			.block(Block::default().borders(Borders::ALL).title("Done")), body);
+	    },
+	}
+    }
+}
+
+fn event_loop(terminal : &mut DefaultTerminal) -> std::io::Result<Wizard> {
+    let mut wizard = Wizard::new();
+    loop {
+	terminal.draw(|frame| wizard.draw(frame))?;
+	if let Event::Key(key) = event::read()? {
+	    if key.kind == KeyEventKind::Press && !wizard.key(key.code) {
+		return Ok(wizard)
+	    }
+	}
+    }
+}
+
+fn main() { common::main(run) }
+
+fn run() -> Result<(), Failure> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal);
+    ratatui::restore();
+    // Shares from a split are also printed once the screen is gone,
+    // so they can be copied from the scrollback.
+    for share in result.map_err(|e| Failure::about(e.into(), "Terminal"))?.output.iter() {
+	println!("{}", share)
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! git's credential helper protocol
+//!
+//! `git-credential-shamir` answers git's requests for a password by
+//! combining shares of it, so the whole token is only ever in memory.
+//! git talks to a helper in `key=value` lines, ending with a blank
+//! line (or end of input):
+//!
+//! ```text
+//! protocol=https
+//! host=github.com
+//! path=org/repo.git
+//! ```
+//!
+//! and the helper answers in the same form with the `username` and
+//! `password` to use, or with nothing at all to let git try its next
+//! helper. Values can't hold a newline or a NUL, so a secret that does
+//! is refused rather than sent on cut short.
+
+use std::io::{self, BufRead, Write};
+
+/// What git asked about, in the order it said it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Request {
+    pub fields : Vec<(String, String)>,
+}
+
+impl Request {
+    /// Read one request, up to a blank line or the end of `input`
+    pub fn read<R : BufRead>(input : R) -> io::Result<Request> {
+	let mut fields = Vec::new();
+	for line in input.lines() {
+	    let line = line?;
+	    let line = line.trim_end_matches('\r');
+	    if line.is_empty() { break }
+	    let (key, value) = line.split_once('=').ok_or_else(|| io::Error::new(
+		io::ErrorKind::InvalidData, format!("not a key=value line: {}", line)))?;
+	    fields.push((key.to_string(), value.to_string()));
+	}
+	Ok(Request { fields })
+    }
+
+    /// The last value given for `key` (git's rule for repeats)
+    pub fn get(&self, key : &str) -> Option<&str> {
+	self.fields.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Answer a `get` with `password`, and `username` if there is one
+pub fn write_reply<W : Write>(out : &mut W, username : Option<&str>, password : &[u8])
+			     -> io::Result<()> {
+    let password = std::str::from_utf8(password).ok()
+	.filter(|p| !p.contains(['\n', '\0']))
+	.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData,
+				      "the secret isn't a single line of text"))?;
+    if let Some(username) = username {
+	writeln!(out, "username={}", username)?;
+    }
+    writeln!(out, "password={}", password)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol() {
+	let input = b"protocol=https\r\nhost=example.com\nhost=git.example.com\n\nignored=1\n";
+	let request = Request::read(&input[..]).unwrap();
+	assert_eq!(request.fields.len(), 3);
+	assert_eq!(request.get("protocol"), Some("https"));
+	assert_eq!(request.get("host"), Some("git.example.com"));
+	assert_eq!(request.get("path"), None);
+	assert!(Request::read(&b"no equals\n"[..]).is_err());
+	assert_eq!(Request::read(&b""[..]).unwrap(), Request::default());
+
+	let mut out = Vec::new();
+	write_reply(&mut out, Some("ci"), b"tok=en").unwrap();
+	assert_eq!(out, b"username=ci\npassword=tok=en\n");
+	out.clear();
+	write_reply(&mut out, None, b"token").unwrap();
+	assert_eq!(out, b"password=token\n");
+	assert!(write_reply(&mut Vec::new(), None, b"two\nlines").is_err());
+	assert!(write_reply(&mut Vec::new(), None, b"\xff").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Envelope encryption: split a key-encryption key, not the data
+//!
+//! The usual escrow arrangement. The payload is encrypted under a
+//! random data-encryption key (DEK), the DEK is wrapped under a
+//! random key-encryption key (KEK), and only the KEK is split into
+//! shares. Both use ChaCha20-Poly1305. The payload can then be as big
+//! as it likes and stored wherever is convenient, while the shares
+//! stay 32 bytes long, and either key can be replaced without
+//! touching the other:
+//!
+//! * [rewrap] moves the DEK to a new KEK (and so a new share set)
+//!   by rewriting only the small key file, leaving the payload as it is
+//! * [rotate] re-encrypts the payload under a new DEK, wrapped under
+//!   the same KEK, so custodians keep the shares they have
+//!
+//! The key file is text:
+//!
+//! ```text
+//! guff-ssss envelope v1
+//! set: <set ID of the KEK shares>    (optional)
+//! wrapped-dek: <hex of nonce and sealed DEK>
+//! ```
+//!
+//! and the encrypted payload is the magic `GSSENV01`, a 12-byte nonce
+//! and the sealed data.
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use std::io;
+
+/// Length of both keys
+pub const KEY_BYTES : usize = 32;
+
+const HEADER : &str = "guff-ssss envelope v1";
+const MAGIC : &[u8; 8] = b"GSSENV01";
+const NONCE_BYTES : usize = 12;
+
+fn invalid(msg : &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// A new random key (for use as a KEK or a DEK)
+pub fn new_key() -> [u8; KEY_BYTES] {
+    let mut key = [0u8; KEY_BYTES];
+    rand::thread_rng().fill_bytes(&mut key);
+    key
+}
+
+// nonce || sealed data
+fn seal(key : &[u8], aad : &[u8], data : &[u8]) -> Vec<u8> {
+    let mut nonce = [0u8; NONCE_BYTES];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let sealed = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg : data, aad })
+	.expect("ChaCha20-Poly1305 only fails on absurdly long input");
+    [&nonce[..], &sealed].concat()
+}
+
+fn open(key : &[u8], aad : &[u8], data : &[u8], what : &str) -> io::Result<Vec<u8>> {
+    if key.len() != KEY_BYTES {
+	return Err(invalid(&format!("{} key must be {} bytes", what, KEY_BYTES)))
+    }
+    if data.len() < NONCE_BYTES { return Err(invalid(&format!("{} is truncated", what))) }
+    let (nonce, sealed) = data.split_at(NONCE_BYTES);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher.decrypt(Nonce::from_slice(nonce), Payload { msg : sealed, aad })
+	.map_err(|_| io::Error::new(io::ErrorKind::PermissionDenied,
+				    format!("{} doesn't decrypt: wrong key or tampered data", what)))
+}
+
+/// The DEK, wrapped under a KEK
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrappedKey {
+    /// Set ID of the KEK's shares, so the right ones can be found
+    pub set_id : Option<String>,
+    /// Nonce and sealed DEK
+    pub wrapped : Vec<u8>,
+}
+
+impl std::fmt::Display for WrappedKey {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	writeln!(f, "{}", HEADER)?;
+	if let Some(id) = &self.set_id { writeln!(f, "set: {}", id)? }
+	writeln!(f, "wrapped-dek: {}", hex::encode(&self.wrapped))
+    }
+}
+
+impl std::str::FromStr for WrappedKey {
+    type Err = io::Error;
+
+    fn from_str(text : &str) -> io::Result<WrappedKey> {
+	let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+	if lines.next() != Some(HEADER) { return Err(invalid("not an envelope key file")) }
+	let (mut set_id, mut wrapped) = (None, None);
+	for line in lines {
+	    match line.split_once(": ") {
+		Some(("set", id)) => set_id = Some(id.to_string()),
+		Some(("wrapped-dek", hex)) => wrapped = Some(hex::decode(hex)
+		    .map_err(|_| invalid("bad hex in wrapped-dek"))?),
+		_ => return Err(invalid(&format!("unexpected line in key file: {}", line))),
+	    }
+	}
+	let wrapped = wrapped.ok_or_else(|| invalid("key file has no wrapped-dek"))?;
+	Ok(WrappedKey { set_id, wrapped })
+    }
+}
+
+/// Wrap `dek` under `kek`
+pub fn wrap_dek(kek : &[u8], dek : &[u8], set_id : Option<&str>) -> WrappedKey {
+    WrappedKey {
+	set_id : set_id.map(|id| id.to_string()),
+	wrapped : seal(kek, HEADER.as_bytes(), dek),
+    }
+}
+
+/// Get the DEK back out with the (recombined) KEK
+pub fn unwrap_dek(kek : &[u8], key : &WrappedKey) -> io::Result<Vec<u8>> {
+    open(kek, HEADER.as_bytes(), &key.wrapped, "wrapped DEK")
+}
+
+/// Encrypt a payload under `dek`
+pub fn encrypt(dek : &[u8], payload : &[u8]) -> Vec<u8> {
+    [&MAGIC[..], &seal(dek, MAGIC, payload)].concat()
+}
+
+/// Decrypt a payload made by [encrypt]
+pub fn decrypt(dek : &[u8], data : &[u8]) -> io::Result<Vec<u8>> {
+    match data.strip_prefix(&MAGIC[..]) {
+	Some(data) => open(dek, MAGIC, data, "payload"),
+	None => Err(invalid("not an encrypted envelope payload")),
+    }
+}
+
+/// Encrypt `payload` under a new DEK wrapped under a new KEK. Gives
+/// back the KEK (to be split and then forgotten), the key file and
+/// the encrypted payload.
+pub fn seal_new(payload : &[u8], set_id : Option<&str>)
+		-> ([u8; KEY_BYTES], WrappedKey, Vec<u8>) {
+    let (kek, dek) = (new_key(), new_key());
+    (kek, wrap_dek(&kek, &dek, set_id), encrypt(&dek, payload))
+}
+
+/// Move the DEK from `old_kek` to `new_kek`, whose shares have set ID
+/// `set_id`. The payload doesn't change.
+pub fn rewrap(old_kek : &[u8], key : &WrappedKey, new_kek : &[u8], set_id : Option<&str>)
+	      -> io::Result<WrappedKey> {
+    let dek = unwrap_dek(old_kek, key)?;
+    Ok(wrap_dek(new_kek, &dek, set_id))
+}
+
+/// Re-encrypt the payload under a new DEK, wrapped under the same KEK
+pub fn rotate(kek : &[u8], key : &WrappedKey, data : &[u8]) -> io::Result<(WrappedKey, Vec<u8>)> {
+    let payload = decrypt(&unwrap_dek(kek, key)?, data)?;
+    let dek = new_key();
+    Ok((wrap_dek(kek, &dek, key.set_id.as_deref()), encrypt(&dek, &payload)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kek_dek() {
+	let (kek, key, data) = seal_new(b"quarterly figures", Some("esc1"));
+	let text = key.to_string();
+	assert!(text.starts_with("guff-ssss envelope v1\nset: esc1\nwrapped-dek: "));
+	let key : WrappedKey = text.parse().unwrap();
+	let dek = unwrap_dek(&kek, &key).unwrap();
+	assert_eq!(decrypt(&dek, &data).unwrap(), b"quarterly figures");
+
+	// New KEK: same payload, old KEK no longer opens the key file
+	let kek2 = new_key();
+	let key2 = rewrap(&kek, &key, &kek2, Some("esc2")).unwrap();
+	assert_eq!(decrypt(&unwrap_dek(&kek2, &key2).unwrap(), &data).unwrap(),
+		   b"quarterly figures");
+	assert!(unwrap_dek(&kek, &key2).is_err());
+
+	// New DEK: same KEK, old DEK no longer opens the payload
+	let (key3, data3) = rotate(&kek, &key, &data).unwrap();
+	assert_eq!(key3.set_id.as_deref(), Some("esc1"));
+	assert_eq!(decrypt(&unwrap_dek(&kek, &key3).unwrap(), &data3).unwrap(),
+		   b"quarterly figures");
+	assert!(decrypt(&dek, &data3).is_err());
+
+	let mut tampered = data.clone();
+	*tampered.last_mut().unwrap() ^= 1;
+	assert!(decrypt(&dek, &tampered).is_err());
+	assert!("guff-ssss envelope v1\nset: x\n".parse::<WrappedKey>().is_err());
+    }
+}
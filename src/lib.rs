@@ -1,7 +1,44 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
-}
+//! Shamir's Secret Sharing Scheme over Galois fields GF(2<sup>w</sup>)
+//!
+//! The splitting and combining code lives in [guff_ssss_core], which
+//! has no I/O of its own and is everything a library user needs; it's
+//! all re-exported here. This crate adds the pieces that the
+//! `shamir-*` programs use on top of it: sending shares over the
+//! network ([transfer]), reading and writing them as QR code images
+//! ([qr](crate::qr)), packing whole directories into one secret
+//! ([archive]), answering git's requests for credentials
+//! ([credential]), and reading and writing large files through
+//! io_uring on Linux ([uring](crate::uring)).
+
+pub use guff_ssss_core::*;
+
+pub mod archive;
+
+pub mod credential;
+
+#[cfg(feature = "net")]
+pub mod transfer;
+
+#[cfg(feature = "qr")]
+pub mod qr;
+
+#[cfg(feature = "age")]
+pub mod age;
+
+#[cfg(feature = "envelope")]
+pub mod envelope;
+
+#[cfg(feature = "timelock")]
+pub mod timelock;
+
+#[cfg(feature = "x509")]
+pub mod x509;
+
+#[cfg(feature = "tpm")]
+pub mod tpm;
+
+#[cfg(feature = "yubikey")]
+pub mod yubikey;
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub mod uring;
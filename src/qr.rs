@@ -0,0 +1,88 @@
+//! Shares as images of QR codes
+//!
+//! Shares printed as QR codes tend to come back as phone photos or
+//! scans. Every QR code found in the image is decoded, and every
+//! share-looking line in each code is returned. Going the other way,
+//! [encode_image] draws a share as a PNG ready for printing.
+
+use qrcode::{Color, QrCode};
+use std::path::Path;
+
+/// True if the file name suggests an image we can try to decode
+pub fn is_image(path : &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+	Some(ext) => matches!(ext.to_ascii_lowercase().as_str(), "png" | "jpg" | "jpeg"),
+	None => false,
+    }
+}
+
+/// Decode all QR codes in the image at `path`
+pub fn decode_image(path : &Path) -> Result<Vec<String>, String> {
+    let img = image::open(path)
+	.map_err(|e| format!("{}: {}", path.display(), e))?
+	.to_luma8();
+    let mut prepared = rqrr::PreparedImage::prepare(img);
+    let grids = prepared.detect_grids();
+    if grids.is_empty() {
+	return Err(format!("{}: no QR code found", path.display()))
+    }
+    let mut lines = Vec::new();
+    for grid in grids {
+	let (_meta, content) = grid.decode()
+	    .map_err(|e| format!("{}: can't decode QR code: {}", path.display(), e))?;
+	lines.extend(content.lines()
+		     .map(|l| l.trim().to_string())
+		     .filter(|l| !l.is_empty()));
+    }
+    Ok(lines)
+}
+
+/// Draw `text` as a QR code and save it at `path` (as a PNG, or
+/// whatever the extension says), with 4-pixel modules and a
+/// 4-module quiet zone. Fails if the text won't fit in a QR code.
+pub fn encode_image(text : &str, path : &Path) -> Result<(), String> {
+    let code = QrCode::new(text.as_bytes())
+	.map_err(|e| format!("{}: can't make a QR code: {}", path.display(), e))?;
+    let (width, scale, quiet) = (code.width() as u32, 4, 4);
+    let size = (width + 2 * quiet) * scale;
+    let colors = code.to_colors();
+    let img = image::GrayImage::from_fn(size, size, |x, y| {
+	let (mx, my) = (x / scale, y / scale);
+	let dark = mx >= quiet && my >= quiet && mx < width + quiet && my < width + quiet
+	    && colors[((my - quiet) * width + mx - quiet) as usize] == Color::Dark;
+	image::Luma([if dark { 0 } else { 255 }])
+    });
+    img.save(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_rendered_share() {
+	let path = std::env::temp_dir().join(format!("guff-ssss-qr-{}.png", std::process::id()));
+	encode_image("3=8=1=876ec0b40cf66736af2848=\n", &path).unwrap();
+	let lines = decode_image(&path);
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(lines.unwrap(), vec!["3=8=1=876ec0b40cf66736af2848="]);
+    }
+
+    #[test]
+    fn estimated_versions() {
+	use crate::estimate::qr_version;
+	use qrcode::Version;
+	for len in [1, 14, 15, 62, 63, 500, 1000, 2331] {
+	    let code = QrCode::new(vec![b'a'; len]).unwrap();
+	    assert_eq!(Some(code.version()), qr_version(len).map(|v| Version::Normal(v as i16)));
+	}
+	assert!(QrCode::new(vec![b'a'; 2332]).is_err());
+    }
+
+    #[test]
+    fn image_extensions() {
+	assert!(is_image(Path::new("share.PNG")));
+	assert!(is_image(Path::new("photo.jpeg")));
+	assert!(!is_image(Path::new("share.txt")));
+    }
+}
@@ -0,0 +1,311 @@
+//! Timelocked shares, with drand
+//!
+//! A timelocked share can't be read by anyone, its custodian
+//! included, until a given round of a [drand](https://drand.love)
+//! randomness beacon has been published. drand's signature for a
+//! round is the key that opens it (tlock, "timelock encryption made
+//! practical"), so nobody has that key before then, and everybody has
+//! it after. Splitting with every share locked to the same future
+//! round means the secret can't be recovered early even if a whole
+//! quorum of custodians colludes. That's what dead man's switches
+//! and estate planning need.
+//!
+//! Each share is sealed with ChaCha20-Poly1305 under a random key, and
+//! only that key is tlock-encrypted. The text form is a block of
+//! `key: value` lines:
+//!
+//! ```text
+//! guff-ssss timelock v1
+//! drand: <URL of the beacon's HTTP API>
+//! chain: <chain hash>
+//! round: <round number>
+//! tlock: <hex of the tlock ciphertext of the key>
+//! share: <hex of nonce and sealed share line>
+//! ```
+//!
+//! Fetching the round's signature checks it against the chain hash
+//! written in the block, so a different (or fake) beacon can't be
+//! substituted.
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use drand_core::chain::ChainVerification;
+use drand_core::{ChainOptions, HttpClient};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The drand "quicknet" beacon, which publishes a round every 3 seconds
+pub const QUICKNET : &str =
+    "https://api.drand.sh/52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971";
+
+const HEADER : &str = "guff-ssss timelock v1";
+const KEY_LABEL : &[u8] = b"guff-ssss/timelock/v1";
+const NONCE_BYTES : usize = 12;
+
+fn invalid(msg : &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn drand_error(e : impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("drand: {}", e))
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// What we need to know about a drand beacon
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chain {
+    pub url          : String,
+    pub hash         : Vec<u8>,
+    pub public_key   : Vec<u8>,
+    pub genesis_time : u64,
+    pub period       : u64,
+}
+
+impl Chain {
+    /// Fetch the beacon's details from its HTTP API
+    pub fn fetch(url : &str) -> io::Result<Chain> {
+	let info = HttpClient::new(url, None).and_then(|c| c.chain_info()).map_err(drand_error)?;
+	Ok(Chain {
+	    url : url.to_string(), hash : info.hash(), public_key : info.public_key(),
+	    genesis_time : info.genesis_time(), period : info.period(),
+	})
+    }
+
+    /// When `round` is published, in seconds since the epoch
+    pub fn time_of(&self, round : u64) -> u64 {
+	self.genesis_time + round.saturating_sub(1) * self.period
+    }
+
+    /// The first round published at or after `time`
+    pub fn round_at(&self, time : u64) -> u64 {
+	match time.checked_sub(self.genesis_time) {
+	    Some(since) => since.div_ceil(self.period) + 1,
+	    None => 1,
+	}
+    }
+
+    /// The round meant by `when`: a round number, `@SECONDS` since
+    /// the epoch, or a time from now such as `+90d` (with units s, m,
+    /// h, d or w)
+    pub fn parse_round(&self, when : &str) -> io::Result<u64> {
+	let bad = || invalid(&format!("can't tell when '{}' is: use a round number, \
+				       @SECONDS or +DURATION (eg, +30d)", when));
+	if let Some(secs) = when.strip_prefix('@') {
+	    return Ok(self.round_at(secs.parse().map_err(|_| bad())?))
+	}
+	if let Some(duration) = when.strip_prefix('+') {
+	    let (split, last) = duration.char_indices().last().ok_or_else(bad)?;
+	    let unit = match last {
+		's' => 1, 'm' => 60, 'h' => 3600, 'd' => 86400, 'w' => 7 * 86400,
+		_ => return Err(bad()),
+	    };
+	    let count : u64 = duration[..split].parse().map_err(|_| bad())?;
+	    let time = count.checked_mul(unit)
+		.and_then(|secs| now().checked_add(secs))
+		.ok_or_else(bad)?;
+	    return Ok(self.round_at(time))
+	}
+	when.parse().map_err(|_| bad())
+    }
+}
+
+// The ChaCha20-Poly1305 key from the 16 bytes that tlock encrypts
+fn share_key(tlock_key : &[u8]) -> [u8; 32] {
+    let mut hash = Sha256::new();
+    hash.update(KEY_LABEL);
+    hash.update(tlock_key);
+    hash.finalize().into()
+}
+
+/// A share that can't be read before `round`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockedShare {
+    pub url   : String,
+    pub chain : Vec<u8>,
+    pub round : u64,
+    pub tlock : Vec<u8>,
+    /// Nonce and sealed share line
+    pub share : Vec<u8>,
+}
+
+impl LockedShare {
+    fn aad(&self) -> Vec<u8> {
+	[&self.round.to_be_bytes()[..], &self.chain].concat()
+    }
+
+    /// Lock `share` (any share text) until `round` of `chain`
+    pub fn lock(share : &str, chain : &Chain, round : u64) -> io::Result<LockedShare> {
+	let mut key = [0u8; 16];
+	rand::thread_rng().fill_bytes(&mut key);
+	let mut tlock = Vec::new();
+	tlock::encrypt(&mut tlock, &key[..], &chain.public_key, round)
+	    .map_err(|e| invalid(&format!("tlock: {}", e)))?;
+	let mut locked = LockedShare {
+	    url : chain.url.clone(), chain : chain.hash.clone(), round, tlock, share : Vec::new(),
+	};
+	let mut nonce = [0u8; NONCE_BYTES];
+	rand::thread_rng().fill_bytes(&mut nonce);
+	let cipher = ChaCha20Poly1305::new(Key::from_slice(&share_key(&key)));
+	let sealed = cipher.encrypt(Nonce::from_slice(&nonce),
+				    Payload { msg : share.as_bytes(), aad : &locked.aad() })
+	    .map_err(|_| io::Error::other("encryption failed"))?;
+	locked.share = [&nonce[..], &sealed].concat();
+	Ok(locked)
+    }
+
+    /// Open with drand's signature for the round
+    pub fn unlock(&self, signature : &[u8]) -> io::Result<String> {
+	// tlock asserts (rather than returning an error) when the
+	// signature is for some other round
+	let wrong = || invalid(&format!("round {} signature doesn't open the timelock",
+					self.round));
+	let key = std::panic::catch_unwind(|| {
+	    let mut key = Vec::new();
+	    tlock::decrypt(&mut key, self.tlock.as_slice(), signature).map(|_| key)
+	});
+	let mut key = key.map_err(|_| wrong())?.map_err(|_| wrong())?;
+	// tlock drops trailing zero bytes from what it decrypts
+	key.resize(16, 0);
+	if self.share.len() < NONCE_BYTES { return Err(invalid("timelocked share is truncated")) }
+	let (nonce, sealed) = self.share.split_at(NONCE_BYTES);
+	let cipher = ChaCha20Poly1305::new(Key::from_slice(&share_key(&key)));
+	let share = cipher.decrypt(Nonce::from_slice(nonce), Payload { msg : sealed, aad : &self.aad() })
+	    .map_err(|_| invalid("timelocked share doesn't decrypt: tampered with?"))?;
+	String::from_utf8(share).map_err(|_| invalid("timelocked share isn't text"))
+    }
+
+    /// Fetch the round's signature from the beacon, checking that it
+    /// comes from the right chain. Fails if the round isn't out yet.
+    pub fn fetch_signature(&self) -> io::Result<Vec<u8>> {
+	let options = ChainOptions::new(true, true,
+					 Some(ChainVerification::new(Some(self.chain.clone()), None)));
+	let client = HttpClient::new(&self.url, Some(options)).map_err(drand_error)?;
+	let info = client.chain_info().map_err(drand_error)?;
+	let chain = Chain {
+	    url : self.url.clone(), hash : info.hash(), public_key : info.public_key(),
+	    genesis_time : info.genesis_time(), period : info.period(),
+	};
+	let due = chain.time_of(self.round);
+	if now() < due {
+	    return Err(io::Error::new(io::ErrorKind::WouldBlock,
+				      format!("share is locked until round {}, in {} seconds",
+					      self.round, due - now())))
+	}
+	client.get(self.round).map(|beacon| beacon.signature()).map_err(drand_error)
+    }
+}
+
+impl std::fmt::Display for LockedShare {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	writeln!(f, "{}", HEADER)?;
+	writeln!(f, "drand: {}", self.url)?;
+	writeln!(f, "chain: {}", hex::encode(&self.chain))?;
+	writeln!(f, "round: {}", self.round)?;
+	writeln!(f, "tlock: {}", hex::encode(&self.tlock))?;
+	writeln!(f, "share: {}", hex::encode(&self.share))
+    }
+}
+
+impl std::str::FromStr for LockedShare {
+    type Err = io::Error;
+
+    fn from_str(text : &str) -> io::Result<LockedShare> {
+	let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+	if lines.next() != Some(HEADER) { return Err(invalid("not a timelocked share")) }
+	let (mut url, mut chain, mut round, mut tlock, mut share) = (None, None, None, None, None);
+	let hex = |value : &str| hex::decode(value).map_err(|_| invalid("bad hex in timelocked share"));
+	for line in lines {
+	    match line.split_once(": ") {
+		Some(("drand", value)) => url = Some(value.to_string()),
+		Some(("chain", value)) => chain = Some(hex(value)?),
+		Some(("round", value)) => round = Some(value.parse()
+		    .map_err(|_| invalid("bad round in timelocked share"))?),
+		Some(("tlock", value)) => tlock = Some(hex(value)?),
+		Some(("share", value)) => share = Some(hex(value)?),
+		_ => return Err(invalid(&format!("unexpected line in timelocked share: {}", line))),
+	    }
+	}
+	let missing = |what| invalid(&format!("timelocked share has no {}", what));
+	Ok(LockedShare {
+	    url : url.ok_or_else(|| missing("drand"))?,
+	    chain : chain.ok_or_else(|| missing("chain"))?,
+	    round : round.ok_or_else(|| missing("round"))?,
+	    tlock : tlock.ok_or_else(|| missing("tlock"))?,
+	    share : share.ok_or_else(|| missing("share"))?,
+	})
+    }
+}
+
+/// True if `text` holds timelocked shares
+pub fn is_timelocked(text : &str) -> bool {
+    text.lines().any(|l| l.trim() == HEADER)
+}
+
+/// Every timelocked share block in `text`
+pub fn parse_locked_shares(text : &str) -> io::Result<Vec<LockedShare>> {
+    let mut blocks : Vec<String> = Vec::new();
+    for line in text.lines() {
+	if line.trim() == HEADER {
+	    blocks.push(String::new())
+	} else if line.trim().is_empty() {
+	    continue
+	}
+	if let Some(block) = blocks.last_mut() {
+	    block.push_str(line);
+	    block.push('\n');
+	}
+    }
+    blocks.iter().map(|b| b.parse()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // drand's "quicknet" beacon, and its signature for round 1000
+    fn quicknet() -> Chain {
+	Chain {
+	    url : QUICKNET.into(),
+	    hash : hex::decode("52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971").unwrap(),
+	    public_key : hex::decode("83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c\
+				      8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb\
+				      5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a")
+		.unwrap(),
+	    genesis_time : 1692803367,
+	    period : 3,
+	}
+    }
+    const ROUND_1000 : &str = "b44679b9a59af2ec876b1a6b1ad52ea9b1615fc3982b19576350f93447cb1125\
+			       e342b73a8dd2bacbe47e4b6b63ed5e39";
+
+    #[test]
+    fn timelocked_share() {
+	let chain = quicknet();
+	let locked = LockedShare::lock("3=8=1=876ec0b40cf66736af2848=", &chain, 1000).unwrap();
+	let text = locked.to_string();
+	assert!(is_timelocked(&text) && text.contains("\nround: 1000\n"));
+	let two = parse_locked_shares(&format!("{}\n{}", text, text)).unwrap();
+	assert_eq!(two, [locked.clone(), locked.clone()]);
+	let signature = hex::decode(ROUND_1000).unwrap();
+	assert_eq!(locked.unlock(&signature).unwrap(), "3=8=1=876ec0b40cf66736af2848=");
+
+	// Another round's key, or a share moved to another round
+	let later = LockedShare::lock("3=8=1=876ec0b40cf66736af2848=", &chain, 1001).unwrap();
+	assert!(later.unlock(&signature).is_err());
+	let moved = LockedShare { round : 1001, ..locked };
+	assert!(moved.unlock(&signature).is_err());
+
+	assert_eq!(chain.round_at(chain.time_of(1000)), 1000);
+	assert_eq!(chain.round_at(chain.time_of(1000) + 1), 1001);
+	assert_eq!(chain.parse_round("@1692806364").unwrap(), 1000);
+	assert!(chain.parse_round("+10y").is_err());
+	for when in ["+", "+3é", "+é", "+99999999999999999999s", "+18446744073709551615w"] {
+	    assert!(chain.parse_round(when).is_err(), "{}", when);
+	}
+    }
+}
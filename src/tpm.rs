@@ -0,0 +1,263 @@
+//! Shares sealed to this machine's TPM
+//!
+//! `shamir-split --tpm FILE` seals one of the shares to the local
+//! TPM, under a policy on some of its PCRs (by default 0 and 7: the
+//! firmware and the Secure Boot state). The sealed share is useless
+//! anywhere else, and on this machine too once it boots something
+//! different, so recovering the secret needs both the remote
+//! custodians' shares and the original machine as it was.
+//!
+//! The TPM is found the way the tpm2-tools find it: from the `TCTI`
+//! (or `TPM2TOOLS_TCTI`) environment variable, or else the kernel's
+//! resource manager at `/dev/tpmrm0`. The share is sealed under a
+//! primary key made from the standard storage key template in the
+//! owner hierarchy, so that nothing has to be left in the TPM's
+//! persistent memory; the same template makes the same key again
+//! when it's time to unseal.
+//!
+//! A sealed share is a text block:
+//!
+//! ```text
+//! guff-ssss tpm v1
+//! pcrs: sha256:0,7
+//! public: <hex of the sealed object's TPMT_PUBLIC>
+//! private: <hex of its TPM2B_PRIVATE contents>
+//! ```
+//!
+//! TPMs will only seal 128 bytes, so a share of a long secret can't
+//! be sealed. Split a key instead (eg, with `shamir-envelope`).
+
+use std::convert::TryFrom;
+use std::io;
+use tss_esapi::attributes::ObjectAttributesBuilder;
+use tss_esapi::constants::SessionType;
+use tss_esapi::handles::{KeyHandle, SessionHandle};
+use tss_esapi::interface_types::algorithm::{HashingAlgorithm, PublicAlgorithm};
+use tss_esapi::interface_types::key_bits::RsaKeyBits;
+use tss_esapi::interface_types::resource_handles::Hierarchy;
+use tss_esapi::interface_types::session_handles::{AuthSession, PolicySession};
+use tss_esapi::structures::{Digest, KeyedHashScheme, PcrSelectionList, PcrSelectionListBuilder,
+			    PcrSlot, Private, Public, PublicBuilder, PublicKeyedHashParameters,
+			    RsaExponent, SensitiveData, SymmetricDefinition,
+			    SymmetricDefinitionObject};
+use tss_esapi::tcti_ldr::DeviceConfig;
+use tss_esapi::traits::{Marshall, UnMarshall};
+use tss_esapi::utils::create_restricted_decryption_rsa_public;
+use tss_esapi::{Context, TctiNameConf};
+
+const HEADER : &str = "guff-ssss tpm v1";
+
+/// The most a TPM will seal
+pub const MAX_SEALED : usize = 128;
+
+/// PCRs sealed to when none are given: firmware and Secure Boot state
+pub const DEFAULT_PCRS : &str = "0,7";
+
+fn invalid(msg : &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn tpm_error(e : tss_esapi::Error) -> io::Error {
+    io::Error::other(format!("TPM: {}", e))
+}
+
+/// PCR numbers from a list like "0,7"
+pub fn parse_pcrs(list : &str) -> io::Result<Vec<u8>> {
+    let mut pcrs = Vec::new();
+    for pcr in list.split(',').map(|p| p.trim()) {
+	match pcr.parse::<u8>() {
+	    Ok(n) if n < 24 => if !pcrs.contains(&n) { pcrs.push(n) },
+	    _ => return Err(invalid(&format!("'{}' isn't a PCR (0 to 23)", pcr))),
+	}
+    }
+    pcrs.sort_unstable();
+    Ok(pcrs)
+}
+
+/// A share sealed to the local TPM
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SealedShare {
+    /// SHA-256 PCRs the share is sealed to
+    pub pcrs    : Vec<u8>,
+    /// The sealed object's public area, marshalled
+    pub public  : Vec<u8>,
+    /// Its private area, encrypted by the TPM
+    pub private : Vec<u8>,
+}
+
+impl std::fmt::Display for SealedShare {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	let pcrs : Vec<String> = self.pcrs.iter().map(|p| p.to_string()).collect();
+	writeln!(f, "{}", HEADER)?;
+	writeln!(f, "pcrs: sha256:{}", pcrs.join(","))?;
+	writeln!(f, "public: {}", hex::encode(&self.public))?;
+	writeln!(f, "private: {}", hex::encode(&self.private))
+    }
+}
+
+impl std::str::FromStr for SealedShare {
+    type Err = io::Error;
+
+    fn from_str(text : &str) -> io::Result<SealedShare> {
+	let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+	if lines.next() != Some(HEADER) { return Err(invalid("not a TPM-sealed share")) }
+	let (mut pcrs, mut public, mut private) = (None, None, None);
+	let hex = |value : &str| hex::decode(value).map_err(|_| invalid("bad hex in TPM-sealed share"));
+	for line in lines {
+	    match line.split_once(": ") {
+		Some(("pcrs", value)) => match value.strip_prefix("sha256:") {
+		    Some(list) => pcrs = Some(parse_pcrs(list)?),
+		    None => return Err(invalid("only sha256 PCRs are supported")),
+		},
+		Some(("public", value)) => public = Some(hex(value)?),
+		Some(("private", value)) => private = Some(hex(value)?),
+		_ => return Err(invalid(&format!("unexpected line in TPM-sealed share: {}", line))),
+	    }
+	}
+	match (pcrs, public, private) {
+	    (Some(pcrs), Some(public), Some(private)) => Ok(SealedShare { pcrs, public, private }),
+	    _ => Err(invalid("TPM-sealed share is incomplete")),
+	}
+    }
+}
+
+/// True if `text` holds a TPM-sealed share
+pub fn is_sealed(text : &str) -> bool {
+    text.lines().any(|l| l.trim() == HEADER)
+}
+
+fn context() -> io::Result<Context> {
+    let tcti = TctiNameConf::from_environment_variable()
+	.unwrap_or_else(|_| TctiNameConf::Device(DeviceConfig::default()));
+    Context::new(tcti).map_err(tpm_error)
+}
+
+fn pcr_selection(pcrs : &[u8]) -> io::Result<PcrSelectionList> {
+    let slots = pcrs.iter().map(|&n| PcrSlot::try_from(1u32 << n))
+	.collect::<Result<Vec<PcrSlot>, _>>().map_err(tpm_error)?;
+    PcrSelectionListBuilder::new()
+	.with_selection(HashingAlgorithm::Sha256, &slots)
+	.build().map_err(tpm_error)
+}
+
+// The storage key everything is sealed under. It's made afresh each
+// time from the same template, which gives the same key.
+fn primary(context : &mut Context) -> io::Result<KeyHandle> {
+    let template = create_restricted_decryption_rsa_public(
+	SymmetricDefinitionObject::AES_128_CFB, RsaKeyBits::Rsa2048, RsaExponent::default())
+	.map_err(tpm_error)?;
+    context.execute_with_nullauth_session(|ctx| {
+	ctx.create_primary(Hierarchy::Owner, template, None, None, None, None)
+    }).map(|result| result.key_handle).map_err(tpm_error)
+}
+
+// Start a policy session (or a trial one, to work out the digest)
+// that's satisfied by the PCRs having their current values
+fn pcr_policy(context : &mut Context, session_type : SessionType, pcrs : &PcrSelectionList)
+	      -> io::Result<AuthSession> {
+    let session = context.start_auth_session(None, None, None, session_type,
+					     SymmetricDefinition::AES_128_CFB,
+					     HashingAlgorithm::Sha256)
+	.map_err(tpm_error)?
+	.ok_or_else(|| io::Error::other("TPM: no session handle"))?;
+    let policy = PolicySession::try_from(session).map_err(tpm_error)?;
+    context.policy_pcr(policy, Digest::default(), pcrs.clone()).map_err(tpm_error)?;
+    Ok(session)
+}
+
+/// Seal a share (in the text format) to the local TPM and the current
+/// values of `pcrs`
+pub fn seal(share : &str, pcrs : &[u8]) -> io::Result<SealedShare> {
+    if share.len() > MAX_SEALED {
+	return Err(invalid(&format!("a share of {} bytes is too long to seal in a TPM \
+				     (the most is {}): split a key instead",
+				    share.len(), MAX_SEALED)))
+    }
+    let mut context = context()?;
+    let selection = pcr_selection(pcrs)?;
+
+    let trial = pcr_policy(&mut context, SessionType::Trial, &selection)?;
+    let digest = context.policy_get_digest(PolicySession::try_from(trial).map_err(tpm_error)?);
+    context.flush_context(SessionHandle::from(trial).into())
+	.map_err(tpm_error)?;
+    let digest = digest.map_err(tpm_error)?;
+
+    // Only the policy opens it: there's no password to fall back on
+    let attributes = ObjectAttributesBuilder::new()
+	.with_fixed_tpm(true)
+	.with_fixed_parent(true)
+	.with_no_da(true)
+	.with_user_with_auth(false)
+	.build().map_err(tpm_error)?;
+    let public = PublicBuilder::new()
+	.with_public_algorithm(PublicAlgorithm::KeyedHash)
+	.with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+	.with_object_attributes(attributes)
+	.with_auth_policy(digest)
+	.with_keyed_hash_parameters(PublicKeyedHashParameters::new(KeyedHashScheme::Null))
+	.with_keyed_hash_unique_identifier(Digest::default())
+	.build().map_err(tpm_error)?;
+    let data = SensitiveData::try_from(share.as_bytes()).map_err(tpm_error)?;
+
+    let parent = primary(&mut context)?;
+    let created = context.execute_with_nullauth_session(|ctx| {
+	ctx.create(parent, public, None, Some(data), None, None)
+    });
+    context.flush_context(parent.into()).map_err(tpm_error)?;
+    let created = created.map_err(tpm_error)?;
+    Ok(SealedShare {
+	pcrs : pcrs.to_vec(),
+	public : created.out_public.marshall().map_err(tpm_error)?,
+	private : created.out_private.value().to_vec(),
+    })
+}
+
+impl SealedShare {
+    /// Get the share back. This only works on the machine it was
+    /// sealed on, with the PCRs as they were then.
+    pub fn unseal(&self) -> io::Result<String> {
+	let public = Public::unmarshall(&self.public).map_err(tpm_error)?;
+	let private = Private::try_from(self.private.clone()).map_err(tpm_error)?;
+	let mut context = context()?;
+	let selection = pcr_selection(&self.pcrs)?;
+
+	let parent = primary(&mut context)?;
+	let loaded = context.execute_with_nullauth_session(|ctx| ctx.load(parent, private, public));
+	context.flush_context(parent.into()).map_err(tpm_error)?;
+	let loaded = loaded.map_err(|e| io::Error::other(format!(
+	    "TPM: can't load the sealed share ({}); was it sealed on another machine?", e)))?;
+
+	let session = pcr_policy(&mut context, SessionType::Policy, &selection);
+	let data = session.and_then(|session| {
+	    context.execute_with_session(Some(session), |ctx| ctx.unseal(loaded.into()))
+		.map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, format!(
+		    "TPM: won't unseal the share ({}); have PCRs {:?} changed since it was sealed?",
+		    e, self.pcrs)))
+	});
+	context.flush_context(loaded.into()).map_err(tpm_error)?;
+	String::from_utf8(data?.value().to_vec())
+	    .map_err(|_| invalid("the TPM-sealed share isn't text"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sealing itself needs a TPM, so this only checks the text format
+    #[test]
+    fn sealed_share_text() {
+	assert_eq!(parse_pcrs("7, 0,7").unwrap(), [0, 7]);
+	assert!(parse_pcrs("24").is_err() && parse_pcrs("").is_err());
+
+	let sealed = SealedShare { pcrs : vec![0, 7], public : vec![0, 8, 0, 11],
+				   private : vec![0xde, 0xad] };
+	let text = sealed.to_string();
+	assert_eq!(text, "guff-ssss tpm v1\npcrs: sha256:0,7\npublic: 0008000b\nprivate: dead\n");
+	assert!(is_sealed(&text));
+	assert_eq!(text.parse::<SealedShare>().unwrap(), sealed);
+	assert!("guff-ssss tpm v1\npcrs: sha1:0\npublic: 00\nprivate: 00\n"
+		.parse::<SealedShare>().is_err());
+	assert!("guff-ssss tpm v1\npublic: 00\n".parse::<SealedShare>().is_err());
+    }
+}
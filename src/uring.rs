@@ -0,0 +1,185 @@
+//! Whole-file I/O through io_uring (Linux)
+//!
+//! With the `uring` feature, the command-line tools read a secret or
+//! a set of share files, and write share files, through a single
+//! io_uring instead of a file (or a thread) at a time. Each file is
+//! cut into chunks of up to 1MiB, and up to [QUEUE_DEPTH] chunks from
+//! any of the files are in flight at once, so a multi-gigabyte image
+//! keeps the device busy without a thread pool.
+//!
+//! Writes go to `NAME.tmp`, which is synced once all its chunks are
+//! written and then renamed over `NAME`, as the plain versions of the
+//! tools do.
+
+use io_uring::{opcode, types, IoUring};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Most reads or writes in flight at once
+pub const QUEUE_DEPTH : usize = 32;
+
+const CHUNK : usize = 1 << 20;
+
+fn at(path : &Path, e : io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind { Read, Write, Fsync }
+
+#[derive(Clone, Copy)]
+struct Op { file : usize, kind : Kind, offset : usize, len : usize }
+
+// Ops covering `len` bytes of `file`
+fn chunks(file : usize, kind : Kind, len : usize) -> impl Iterator<Item = Op> {
+    (0..len).step_by(CHUNK).map(move |offset| Op { file, kind, offset, len : CHUNK.min(len - offset) })
+}
+
+// Run `ops` on `files` (with file f's data at `bufs[f]`), putting
+// short transfers back on the queue, and with `sync` an fsync after
+// the last write to each file. Gives each file's first error.
+//
+// Safety: every op must lie inside its file's buffer, which mustn't
+// move or be used elsewhere until this returns.
+unsafe fn run(files : &[File], bufs : &[*mut u8], ops : Vec<Op>, sync : bool)
+	      -> io::Result<Vec<io::Result<()>>> {
+    let mut ring = IoUring::new(QUEUE_DEPTH as u32)?;
+    let mut results : Vec<io::Result<()>> = files.iter().map(|_| Ok(())).collect();
+    let mut left = vec![0usize; files.len()];
+    for op in ops.iter() { left[op.file] += op.len }
+    let mut queue : VecDeque<Op> = ops.into();
+    if sync {
+	for (file, _) in left.iter().enumerate().filter(|(_, &n)| n == 0) {
+	    queue.push_back(Op { file, kind : Kind::Fsync, offset : 0, len : 0 })
+	}
+    }
+    let mut in_flight : Vec<Option<Op>> = vec![None; QUEUE_DEPTH];
+    loop {
+	while let Some(slot) = in_flight.iter().position(Option::is_none) {
+	    let op = match queue.pop_front() {
+		Some(op) => op,
+		None => break,
+	    };
+	    if results[op.file].is_err() { continue }
+	    let fd = types::Fd(files[op.file].as_raw_fd());
+	    let buf = bufs[op.file].add(op.offset);
+	    let entry = match op.kind {
+		Kind::Read => opcode::Read::new(fd, buf, op.len as u32).offset(op.offset as u64).build(),
+		Kind::Write => opcode::Write::new(fd, buf, op.len as u32).offset(op.offset as u64).build(),
+		Kind::Fsync => opcode::Fsync::new(fd).build(),
+	    };
+	    ring.submission().push(&entry.user_data(slot as u64))
+		.expect("no more ops in flight than the queue holds");
+	    in_flight[slot] = Some(op);
+	}
+	if in_flight.iter().all(Option::is_none) { break }
+	ring.submit_and_wait(1)?;
+	let done : Vec<(u64, i32)> = ring.completion().map(|c| (c.user_data(), c.result())).collect();
+	for (slot, res) in done {
+	    let op = in_flight[slot as usize].take().expect("a completion for an op in flight");
+	    match (res, op.kind) {
+		(res, _) if res < 0 => results[op.file] = Err(io::Error::from_raw_os_error(-res)),
+		(_, Kind::Fsync) => {},
+		// The file shrank under us, or the device is full
+		(0, Kind::Read) => results[op.file] = Err(io::ErrorKind::UnexpectedEof.into()),
+		(0, _) => results[op.file] = Err(io::ErrorKind::WriteZero.into()),
+		(n, _) => {
+		    let n = n as usize;
+		    left[op.file] -= n;
+		    if n < op.len {
+			queue.push_front(Op { offset : op.offset + n, len : op.len - n, ..op })
+		    } else if sync && left[op.file] == 0 {
+			queue.push_back(Op { kind : Kind::Fsync, offset : 0, len : 0, ..op })
+		    }
+		},
+	    }
+	}
+    }
+    Ok(results)
+}
+
+/// False if the kernel won't give us a ring (it's too old, or
+/// io_uring has been turned off, as containers often do)
+pub fn available() -> bool {
+    IoUring::new(1).is_ok()
+}
+
+/// Read each of `paths` whole
+pub fn read_files(paths : &[&Path]) -> io::Result<Vec<Vec<u8>>> {
+    let mut files = Vec::new();
+    let mut data = Vec::new();
+    for path in paths {
+	let file = File::open(path).map_err(|e| at(path, e))?;
+	let len = file.metadata().map_err(|e| at(path, e))?.len() as usize;
+	files.push(file);
+	data.push(vec![0u8; len]);
+    }
+    let ops = data.iter().enumerate().flat_map(|(f, d)| chunks(f, Kind::Read, d.len())).collect();
+    let bufs : Vec<*mut u8> = data.iter_mut().map(|d| d.as_mut_ptr()).collect();
+    // Safety: the ops are within `data`, which stays put until we're done
+    let results = unsafe { run(&files, &bufs, ops, false)? };
+    for (path, result) in paths.iter().zip(results) { result.map_err(|e| at(path, e))? }
+    Ok(data)
+}
+
+/// Write each `(path, data)`, synced, and renamed into place only if
+/// all of them were written. They're shares, so only the owner can
+/// read them.
+pub fn write_files(outputs : &[(PathBuf, Vec<u8>)]) -> io::Result<()> {
+    let temps : Vec<PathBuf> = outputs.iter().map(|(path, _)| {
+	let mut tmp = path.as_os_str().to_owned();
+	tmp.push(".tmp");
+	PathBuf::from(tmp)
+    }).collect();
+    let written = (|| {
+	let mut files = Vec::new();
+	let mut options = fs::OpenOptions::new();
+	options.write(true).create(true).truncate(true).mode(0o600);
+	for tmp in temps.iter() {
+	    // so a leftover from an earlier try doesn't keep its mode
+	    let _ = fs::remove_file(tmp);
+	    files.push(options.open(tmp).map_err(|e| at(tmp, e))?)
+	}
+	let ops = outputs.iter().enumerate()
+	    .flat_map(|(f, (_, data))| chunks(f, Kind::Write, data.len())).collect();
+	// Writes only read the buffers, whatever the pointer type says
+	let bufs : Vec<*mut u8> = outputs.iter().map(|(_, data)| data.as_ptr() as *mut u8).collect();
+	// Safety: the ops are within `outputs`, borrowed until we're done
+	let results = unsafe { run(&files, &bufs, ops, true)? };
+	for (tmp, result) in temps.iter().zip(results) { result.map_err(|e| at(tmp, e))? }
+	for (tmp, (path, _)) in temps.iter().zip(outputs) { fs::rename(tmp, path).map_err(|e| at(path, e))? }
+	Ok(())
+    })();
+    if written.is_err() {
+	for tmp in temps.iter() { let _ = fs::remove_file(tmp); }
+    }
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uring_files() {
+	if !available() { return }
+	let dir = std::env::temp_dir().join(format!("guff-ssss-uring-{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	let big : Vec<u8> = (0..3 * CHUNK + 12345).map(|i| (i % 251) as u8).collect();
+	let outputs = vec![(dir.join("big"), big), (dir.join("small"), b"share".to_vec()),
+			   (dir.join("empty"), Vec::new())];
+	write_files(&outputs).unwrap();
+	let paths : Vec<&Path> = outputs.iter().map(|(path, _)| path.as_path()).collect();
+	let read = read_files(&paths).unwrap();
+	for ((path, data), back) in outputs.iter().zip(&read) {
+	    assert!(data == back, "{}", path.display());
+	    assert!(!Path::new(&format!("{}.tmp", path.display())).exists());
+	}
+	assert!(read_files(&[&dir.join("missing")]).is_err());
+	fs::remove_dir_all(&dir).unwrap();
+    }
+}
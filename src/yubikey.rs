@@ -0,0 +1,168 @@
+//! Shares wrapped under a YubiKey
+//!
+//! `shamir-split --yubikey FILE` encrypts one of the shares under a
+//! key that only a particular YubiKey can give: the HMAC-SHA1
+//! response, from one of its challenge-response slots, to a random
+//! challenge. The challenge is kept with the wrapped share, but the
+//! HMAC secret never leaves the token, so whoever holds this share
+//! has to have the token with them (and touch it, if the slot was
+//! programmed to need that) to contribute it.
+//!
+//! The token is asked through `ykchalresp`, from the YubiKey
+//! personalization tools, which has to be on the PATH. The slot is
+//! programmed with, eg, `ykman otp chalresp --generate 2`; keep a
+//! copy of the HMAC secret (or program a second token with it) if
+//! losing the token shouldn't mean losing the share.
+//!
+//! The response is hashed with SHA-256 into a ChaCha20-Poly1305 key.
+//! A wrapped share is a text block:
+//!
+//! ```text
+//! guff-ssss yubikey v1
+//! slot: 2
+//! challenge: <hex>
+//! share: <hex of nonce and sealed share>
+//! ```
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::process::Command;
+
+const HEADER : &str = "guff-ssss yubikey v1";
+const CHALLENGE_BYTES : usize = 32;
+const NONCE_BYTES : usize = 12;
+
+fn invalid(msg : &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Ask the YubiKey for its HMAC-SHA1 response to `challenge` from
+/// `slot` (1 or 2), with `ykchalresp`
+pub fn ykchalresp(slot : u8, challenge : &[u8]) -> io::Result<Vec<u8>> {
+    let output = Command::new("ykchalresp")
+	.arg(format!("-{}", slot))
+	.arg("-x")
+	.arg(hex::encode(challenge))
+	.output()
+	.map_err(|e| io::Error::new(e.kind(), format!("can't run ykchalresp: {}", e)))?;
+    if !output.status.success() {
+	return Err(io::Error::other(format!(
+	    "ykchalresp failed (is the YubiKey plugged in, with slot {} set up for \
+	     challenge-response?): {}", slot, String::from_utf8_lossy(&output.stderr).trim())))
+    }
+    hex::decode(String::from_utf8_lossy(&output.stdout).trim())
+	.map_err(|_| invalid("ykchalresp didn't give a hex response"))
+}
+
+fn cipher(response : &[u8]) -> ChaCha20Poly1305 {
+    let key = Sha256::new().chain_update(HEADER).chain_update(response).finalize();
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// A share wrapped under a YubiKey's challenge-response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrappedShare {
+    pub slot      : u8,
+    pub challenge : Vec<u8>,
+    /// Nonce and sealed share
+    pub share     : Vec<u8>,
+}
+
+impl WrappedShare {
+    /// Wrap `share` under the response from `respond`, which is given
+    /// the slot and a new challenge (normally [ykchalresp])
+    pub fn wrap<F>(share : &str, slot : u8, respond : F) -> io::Result<WrappedShare>
+    where F : Fn(u8, &[u8]) -> io::Result<Vec<u8>> {
+	if slot != 1 && slot != 2 { return Err(invalid("YubiKey slot must be 1 or 2")) }
+	let mut challenge = vec![0u8; CHALLENGE_BYTES];
+	let mut nonce = [0u8; NONCE_BYTES];
+	rand::thread_rng().fill_bytes(&mut challenge);
+	rand::thread_rng().fill_bytes(&mut nonce);
+	let sealed = cipher(&respond(slot, &challenge)?)
+	    .encrypt(Nonce::from_slice(&nonce), share.as_bytes())
+	    .expect("ChaCha20-Poly1305 only fails on absurdly long input");
+	Ok(WrappedShare { slot, challenge, share : [&nonce[..], &sealed].concat() })
+    }
+
+    /// Get the share back, with the same token
+    pub fn unwrap<F>(&self, respond : F) -> io::Result<String>
+    where F : Fn(u8, &[u8]) -> io::Result<Vec<u8>> {
+	if self.share.len() < NONCE_BYTES { return Err(invalid("wrapped share is truncated")) }
+	let (nonce, sealed) = self.share.split_at(NONCE_BYTES);
+	let share = cipher(&respond(self.slot, &self.challenge)?)
+	    .decrypt(Nonce::from_slice(nonce), sealed)
+	    .map_err(|_| io::Error::new(io::ErrorKind::PermissionDenied,
+					"the share won't unwrap: wrong YubiKey, or wrong slot"))?;
+	String::from_utf8(share).map_err(|_| invalid("the wrapped share isn't text"))
+    }
+}
+
+impl std::fmt::Display for WrappedShare {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+	writeln!(f, "{}", HEADER)?;
+	writeln!(f, "slot: {}", self.slot)?;
+	writeln!(f, "challenge: {}", hex::encode(&self.challenge))?;
+	writeln!(f, "share: {}", hex::encode(&self.share))
+    }
+}
+
+impl std::str::FromStr for WrappedShare {
+    type Err = io::Error;
+
+    fn from_str(text : &str) -> io::Result<WrappedShare> {
+	let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+	if lines.next() != Some(HEADER) { return Err(invalid("not a YubiKey-wrapped share")) }
+	let (mut slot, mut challenge, mut share) = (None, None, None);
+	let hex = |value : &str| hex::decode(value).map_err(|_| invalid("bad hex in YubiKey-wrapped share"));
+	for line in lines {
+	    match line.split_once(": ") {
+		Some(("slot", value)) => slot = Some(value.parse()
+		    .map_err(|_| invalid("bad slot in YubiKey-wrapped share"))?),
+		Some(("challenge", value)) => challenge = Some(hex(value)?),
+		Some(("share", value)) => share = Some(hex(value)?),
+		_ => return Err(invalid(&format!("unexpected line in YubiKey-wrapped share: {}",
+						 line))),
+	    }
+	}
+	match (slot, challenge, share) {
+	    (Some(slot), Some(challenge), Some(share)) => Ok(WrappedShare { slot, challenge, share }),
+	    _ => Err(invalid("YubiKey-wrapped share is incomplete")),
+	}
+    }
+}
+
+/// True if `text` holds a YubiKey-wrapped share
+pub fn is_wrapped(text : &str) -> bool {
+    text.lines().any(|l| l.trim() == HEADER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stands in for a token: any function of the slot and challenge
+    // that only the token knows will do
+    fn token(secret : u8) -> impl Fn(u8, &[u8]) -> io::Result<Vec<u8>> {
+	move |slot, challenge| Ok(Sha256::new().chain_update([secret, slot])
+				  .chain_update(challenge).finalize()[..20].to_vec())
+    }
+
+    #[test]
+    fn yubikey_wrap() {
+	let wrapped = WrappedShare::wrap("3=8=1=876ec0b40cf66736af2848=", 2, token(7)).unwrap();
+	let text = wrapped.to_string();
+	assert!(is_wrapped(&text) && text.contains("\nslot: 2\n"));
+	let parsed : WrappedShare = text.parse().unwrap();
+	assert_eq!(parsed, wrapped);
+	assert_eq!(parsed.unwrap(token(7)).unwrap(), "3=8=1=876ec0b40cf66736af2848=");
+
+	// Another token, or the other slot on the same one
+	assert!(parsed.unwrap(token(8)).is_err());
+	let other_slot = WrappedShare { slot : 1, ..parsed };
+	assert!(other_slot.unwrap(token(7)).is_err());
+	assert!(WrappedShare::wrap("x", 3, token(7)).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+// Exit status of shamir-split and shamir-combine for each class of
+// failure (see "Exit status" in README.md)
+
+use assert_cmd::Command;
+use std::path::PathBuf;
+
+fn split(args : &[&str], secret : &str) -> Vec<String> {
+    let output = Command::cargo_bin("shamir-split").unwrap()
+	.args(args).arg("-q").write_stdin(secret)
+	.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().lines().map(String::from).collect()
+}
+
+fn combine(args : &[&str], lines : &[&str]) -> Option<i32> {
+    let mut input = lines.join("\n");
+    input.push('\n');
+    Command::cargo_bin("shamir-combine").unwrap()
+	.args(args).arg("-q").write_stdin(input)
+	.output().unwrap()
+	.status.code()
+}
+
+fn scratch(name : &str) -> PathBuf {
+    std::env::temp_dir().join(format!("guff-ssss-exit-{}-{}", name, std::process::id()))
+}
+
+#[test]
+fn good_shares() {
+    let shares = split(&["-t", "2", "-n", "3"], "attack at dawn");
+    assert_eq!(combine(&[], &[&shares[0], &shares[2]]), Some(0));
+}
+
+#[test]
+fn malformed_share() {
+    let shares = split(&["-t", "2", "-n", "3"], "attack at dawn");
+    assert_eq!(combine(&[], &["garbage", &shares[0], &shares[1]]), Some(2));
+    assert_eq!(combine(&["--strict"], &["garbage", &shares[0], &shares[1]]), Some(2));
+}
+
+#[test]
+fn bad_checksum() {
+    let shares = split(&["-t", "2", "-n", "3"], "attack at dawn");
+    // Change the last hex digit of the values, leaving the checksum
+    let mut altered = shares[0].clone();
+    let last = altered.pop().unwrap();
+    altered.push(if last == '0' { '1' } else { '0' });
+    assert_eq!(combine(&["--strict"], &[&altered, &shares[1]]), Some(2));
+    // Without --strict the share is skipped, leaving too few
+    assert_eq!(combine(&[], &[&altered, &shares[1]]), Some(4));
+}
+
+#[test]
+fn bad_parameters() {
+    let status = |args : &[&str], secret : &str| {
+	Command::cargo_bin("shamir-split").unwrap()
+	    .args(args).arg("-q").write_stdin(secret.to_string())
+	    .output().unwrap()
+	    .status.code()
+    };
+    assert_eq!(status(&["-t", "2", "-n", "3"], ""), Some(3));
+    assert_eq!(status(&["-t", "1", "-n", "3"], "attack at dawn"), Some(3));
+    assert_eq!(status(&["-t", "4", "-n", "3"], "attack at dawn"), Some(3));
+}
+
+#[test]
+fn too_few_shares() {
+    let shares = split(&["-t", "3", "-n", "5"], "attack at dawn");
+    assert_eq!(combine(&[], &[&shares[0], &shares[1]]), Some(4));
+}
+
+#[test]
+fn inconsistent_shares() {
+    let two = split(&["-t", "2", "-n", "3"], "attack at dawn");
+    let three = split(&["-t", "3", "-n", "3"], "attack at dawn");
+    assert_eq!(combine(&[], &[&two[0], &three[1]]), Some(5));
+}
+
+#[test]
+fn manifest_mismatch() {
+    let manifest = scratch("manifest");
+    let path = manifest.to_str().unwrap();
+    split(&["-t", "2", "-n", "3", "--manifest", path], "attack at dawn");
+    let others = split(&["-t", "2", "-n", "3"], "attack at dawn");
+    let status = combine(&["--strict", "--manifest", path], &[&others[0], &others[1]]);
+    std::fs::remove_file(&manifest).unwrap();
+    assert_eq!(status, Some(6));
+}
+
+#[test]
+fn unreadable_input() {
+    let status = Command::cargo_bin("shamir-split").unwrap()
+	.args(["-t", "2", "-n", "3", "-q", "--input"]).arg(scratch("missing"))
+	.output().unwrap()
+	.status.code();
+    assert_eq!(status, Some(7));
+}
+
+#[test]
+fn unwritable_output() {
+    let status = Command::cargo_bin("shamir-split").unwrap()
+	.args(["-t", "2", "-n", "3", "-q", "--binary", "/dev/null/shares"])
+	.write_stdin("attack at dawn")
+	.output().unwrap()
+	.status.code();
+    assert_eq!(status, Some(7));
+}
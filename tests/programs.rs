@@ -0,0 +1,154 @@
+// shamir-split and shamir-combine run the way a user would: secrets
+// round trip through them at every width, and the flags that change
+// what they print or accept do what they say
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn split(args : &[&str], secret : &[u8]) -> Vec<String> {
+    let output = Command::cargo_bin("shamir-split").unwrap()
+	.args(args).arg("-q").write_stdin(secret.to_vec())
+	.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().lines().map(String::from).collect()
+}
+
+fn combine(args : &[&str], lines : &[&String]) -> Vec<u8> {
+    let input : String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    let output = Command::cargo_bin("shamir-combine").unwrap()
+	.args(args).arg("-q").write_stdin(input)
+	.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+// A fresh directory to write into, gone when the test is
+fn scratch(name : &str) -> Scratch {
+    let dir = std::env::temp_dir().join(format!("guff-ssss-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    Scratch(dir)
+}
+
+struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) { let _ = fs::remove_dir_all(&self.0); }
+}
+
+#[cfg(unix)]
+fn assert_private(path : &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode, 0o600, "{} is mode {:o}", path.display(), mode);
+}
+
+#[test]
+fn round_trip_widths() {
+    // 3 and 14 bytes need padding to whole words at 16 and 32 bits
+    for secret in [&b"odd"[..], b"attack at dawn", b"sixteen bytes!!!"] {
+	for width in ["4", "8", "16", "32"] {
+	    let shares = split(&["-w", width, "-t", "2", "-n", "3"], secret);
+	    assert_eq!(shares.len(), 3);
+	    assert_eq!(combine(&[], &[&shares[0], &shares[2]]), secret, "width {}", width);
+	    assert_eq!(combine(&[], &[&shares[2], &shares[1]]), secret, "width {}", width);
+	}
+    }
+}
+
+#[test]
+fn round_trip_text_format() {
+    for width in ["4", "8", "16", "32"] {
+	let shares = split(&["--format", "text", "-w", width, "-t", "3", "-n", "5"], b"12345678");
+	assert!(shares[0].starts_with(&format!("3={}=1=", width)));
+	assert_eq!(combine(&[], &[&shares[4], &shares[1], &shares[3]]), b"12345678");
+    }
+}
+
+#[test]
+fn quiet() {
+    let output = Command::cargo_bin("shamir-split").unwrap()
+	.args(["-t", "2", "-n", "3", "-q"]).write_stdin("attack at dawn")
+	.output().unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    let input = String::from_utf8(output.stdout).unwrap();
+
+    // Quiet gives the bare secret; otherwise it's labelled
+    let output = Command::cargo_bin("shamir-combine").unwrap()
+	.arg("-q").write_stdin(input.clone())
+	.output().unwrap();
+    assert_eq!(output.stdout, b"attack at dawn");
+    assert!(output.stderr.is_empty());
+    let output = Command::cargo_bin("shamir-combine").unwrap()
+	.write_stdin(input)
+	.output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Answer: Ok(\"attack at dawn\")"));
+}
+
+#[test]
+fn strict() {
+    let shares = split(&["-t", "2", "-n", "3"], b"attack at dawn");
+    let status = |args : &[&str], lines : &[&String]| {
+	let input : String = lines.iter().map(|line| format!("{}\n", line)).collect();
+	Command::cargo_bin("shamir-combine").unwrap()
+	    .args(args).arg("-q").write_stdin(input)
+	    .output().unwrap()
+	    .status.code()
+    };
+    // A share more than the quorum isn't used
+    assert_eq!(status(&[], &[&shares[0], &shares[1], &shares[2]]), Some(0));
+    assert_eq!(status(&["--strict"], &[&shares[0], &shares[1], &shares[2]]), Some(5));
+
+    // Nor is a damaged one, though the others are enough without it
+    let damaged = shares[0].replacen("ck=", "ck=0", 1);
+    assert_eq!(status(&[], &[&damaged, &shares[1], &shares[2]]), Some(0));
+    assert_eq!(status(&["--strict"], &[&damaged, &shares[1], &shares[2]]), Some(2));
+    assert_eq!(status(&["--strict"], &[&shares[1], &shares[2]]), Some(0));
+}
+
+#[test]
+fn batch() {
+    let lines = split(&["--batch", "-", "-t", "2", "-n", "3"], b"one\ntwo\n");
+    let output = combine(&[], &lines.iter().collect::<Vec<_>>());
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("Ok(\"one\")"), "{}", output);
+    assert!(output.contains("Ok(\"two\")"), "{}", output);
+
+    let dir = scratch("batch");
+    let list = dir.0.join("secrets");
+    fs::write(&list, "one\ntwo\n").unwrap();
+    let out = dir.0.join("shares");
+    fs::create_dir(&out).unwrap();
+    split(&["--batch", list.to_str().unwrap(), "--output-dir", out.to_str().unwrap(),
+	    "-t", "2", "-n", "3"], b"");
+    for (name, secret) in [("line-1", "one"), ("line-2", "two")] {
+	let file = out.join(format!("{}.shares", name));
+	#[cfg(unix)]
+	assert_private(&file);
+	let output = Command::cargo_bin("shamir-combine").unwrap()
+	    .arg("-q").arg(&file)
+	    .output().unwrap();
+	assert_eq!(output.stdout, secret.as_bytes());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn binary_shares_are_private() {
+    let dir = scratch("binary");
+    let out = dir.0.join("shares");
+    split(&["--binary", out.to_str().unwrap(), "-w", "16", "-t", "2", "-n", "3"], b"attack at dawn");
+    let mut files : Vec<PathBuf> = fs::read_dir(&out).unwrap()
+	.map(|entry| entry.unwrap().path())
+	.collect();
+    files.sort();
+    assert_eq!(files.len(), 3);
+    for file in files.iter() { assert_private(file) }
+
+    let output = Command::cargo_bin("shamir-combine").unwrap()
+	.arg("-q").args(&files[1..])
+	.output().unwrap();
+    assert_eq!(output.stdout, b"attack at dawn");
+}